@@ -51,6 +51,54 @@ fn convert_to_value_list(c: &mut Criterion) {
     });
 }
 
+fn convert_wide_value_list(c: &mut Criterion) {
+    c.bench_function("convert_wide_value_list", |b| {
+        let mut metric: [c_char; ARR_LENGTH] = [0; ARR_LENGTH];
+        metric[0] = b'h' as c_char;
+        metric[1] = b'o' as c_char;
+
+        let mut v: Vec<data_source_t> = (0..16)
+            .map(|i| {
+                let mut name: [c_char; ARR_LENGTH] = [0; ARR_LENGTH];
+                name[0] = b'd' as c_char;
+                name[1] = (b'a' + i) as c_char;
+                data_source_t {
+                    name,
+                    type_: DS_TYPE_GAUGE as i32,
+                    min: 0.0,
+                    max: 100.0,
+                }
+            })
+            .collect();
+
+        let conv = data_set_t {
+            type_: metric,
+            ds_num: 16,
+            ds: v.as_mut_ptr(),
+        };
+
+        let mut vs: Vec<value_t> = (0..16)
+            .map(|i| value_t {
+                gauge: f64::from(i),
+            })
+            .collect();
+
+        let list_t = value_list_t {
+            values: vs.as_mut_ptr(),
+            values_len: 16,
+            time: 1_000_000_000,
+            interval: 1_000_000_000,
+            host: metric,
+            plugin: metric,
+            plugin_instance: metric,
+            type_: metric,
+            type_instance: metric,
+            meta: ptr::null_mut(),
+        };
+        b.iter(|| ValueList::from(&conv, &list_t))
+    });
+}
+
 fn submit_value(c: &mut Criterion) {
     c.bench_function("submit_value", |b| {
         let values = vec![Value::Gauge(15.0), Value::Gauge(10.0), Value::Gauge(12.0)];
@@ -62,6 +110,18 @@ fn submit_value(c: &mut Criterion) {
     });
 }
 
+fn submit_interned_value(c: &mut Criterion) {
+    c.bench_function("submit_interned_value", |b| {
+        let values = vec![Value::Gauge(15.0), Value::Gauge(10.0), Value::Gauge(12.0)];
+        let id = ValueListBuilder::new("my-plugin", "load").intern().unwrap();
+        b.iter(|| {
+            ValueListBuilder::with_identifier(&id)
+                .values(&values)
+                .submit()
+        })
+    });
+}
+
 fn gen_nul_string(c: &mut Criterion) {
     // While not behaviorally the same, both of these functions
     // will detect a null for the use case of preparing a
@@ -80,5 +140,12 @@ fn gen_nul_string(c: &mut Criterion) {
     );
 }
 
-criterion_group!(benches, convert_to_value_list, submit_value, gen_nul_string);
+criterion_group!(
+    benches,
+    convert_to_value_list,
+    convert_wide_value_list,
+    submit_value,
+    submit_interned_value,
+    gen_nul_string
+);
 criterion_main!(benches);
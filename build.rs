@@ -120,10 +120,13 @@ fn bindings(loc: PathBuf, version: CollectdVersion) {
         .whitelist_type("cdtime_t")
         .whitelist_type("data_set_t")
         .whitelist_function("plugin_.*")
-        .whitelist_function("uc_get_rate")
+        .whitelist_function("uc_get_rate(_by_name)?")
+        .whitelist_function("meta_data_.*")
         .whitelist_var("OCONFIG_TYPE_.*")
         .whitelist_var("LOG_.*")
         .whitelist_var("DS_TYPE_.*")
+        .whitelist_var("MD_TYPE_.*")
+        .whitelist_var("NOTIF_(FAILURE|WARNING|OKAY)")
         .whitelist_var("DATA_MAX_NAME_LEN")
         .generate()
         .expect("Unable to generate bindings")
@@ -15,6 +15,7 @@ fi
 cp target/debug/examples/libloadrust.so /usr/lib/collectd/loadrust.so
 cp target/debug/examples/libwrite_logrs.so /usr/lib/collectd/write_logrs.so
 cp target/debug/examples/libmyerror.so /usr/lib/collectd/myerror.so
+cp target/debug/examples/libkitchen_sink.so /usr/lib/collectd/kitchen_sink.so
 
 cat <<EOF | tee /etc/collectd/collectd.conf
 Hostname "localhost"
@@ -23,6 +24,7 @@ LoadPlugin write_logrs
 LoadPlugin csv
 LoadPlugin logfile
 LoadPlugin myerror
+LoadPlugin kitchen_sink
 
 <Plugin logfile>
     LogLevel info
@@ -37,6 +39,17 @@ LoadPlugin myerror
 </Plugin>
 <Plugin loadrust>
 </Plugin>
+<Plugin kitchen_sink>
+    <Instance>
+        Name "small"
+        Gauge 1.5
+    </Instance>
+    <Instance>
+        Name "large"
+        Gauge 150
+        Limit 100
+    </Instance>
+</Plugin>
 EOF
 
 service collectd start
@@ -71,5 +84,7 @@ grep_test 'myerror: collectd_plugin::api::logger: read error: plugin encountered
 grep_test 'read-function of plugin `myerror'"'"' failed.' /var/lib/collectd/log
 grep_test 'myerror: collectd_plugin::api::logger: read error: plugin panicked' /var/lib/collectd/log
 grep_test 'myerror: collectd_plugin::api::logger: panic hook error: plugin panicked: (examples/myerror.rs: 45): Oh dear what is wrong!?' /var/lib/collectd/log
+grep_test 'epoch,value' /var/lib/collectd/csv/localhost/kitchen_sink-small/gauge*
+grep_test 'Warning notification from kitchen_sink: gauge of 150 is over the limit of 100' /var/lib/collectd/log
 
 exit $?
@@ -0,0 +1,192 @@
+#![cfg(feature = "serde")]
+
+use collectd_plugin::{
+    collectd_plugin, impl_plugin, CollectdLoggerBuilder, ConfigItem, Duration, IdentifierPattern,
+    LogLevel, MetaValue, Notification, NotificationBuilder, NotificationLevel, Plugin,
+    PluginManager, PluginRegistration, Value, ValueList, ValueListBuilder,
+};
+use log::{info, warn, LevelFilter};
+use serde::Deserialize;
+use std::error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Exercises every kind of callback at once, so that it doubles as a reference for how the pieces
+/// fit together. Each instance reports a gauge (tagged with meta data), writes the values of every
+/// plugin into a buffer that is emptied on flush, counts the warnings logged by collectd, and
+/// logs the notifications it receives (including its own, which it raises when its gauge is over
+/// the limit).
+///
+/// ```
+/// LoadPlugin kitchen_sink
+/// <Plugin kitchen_sink>
+///     <Instance>
+///         Name "small"
+///         Gauge 1.5
+///     </Instance>
+///     <Instance>
+///         Name "large"
+///         Gauge 150
+///         Limit 100
+///     </Instance>
+/// </Plugin>
+/// ```
+#[derive(Deserialize, Debug, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+struct KitchenSinkConfig {
+    #[serde(rename = "Instance")]
+    instances: Vec<InstanceConfig>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+#[serde(deny_unknown_fields)]
+struct InstanceConfig {
+    name: String,
+    gauge: f64,
+    limit: Option<f64>,
+}
+
+struct KitchenSink {
+    name: String,
+    gauge: f64,
+    limit: Option<f64>,
+
+    // Callbacks are invoked concurrently, so the buffer needs a mutex
+    buffer: Mutex<Vec<String>>,
+    warnings: AtomicUsize,
+}
+
+struct KitchenSinkManager;
+
+impl PluginManager for KitchenSinkManager {
+    fn name() -> &'static str {
+        "kitchen_sink"
+    }
+
+    fn plugins(
+        config: Option<&[ConfigItem<'_>]>,
+    ) -> Result<PluginRegistration, Box<dyn error::Error>> {
+        // Forward the `log` crate to collectd, with each line prefixed by the instance that logged
+        // it when it is logged from within a callback
+        CollectdLoggerBuilder::new()
+            .prefix_plugin::<Self>()
+            .prefix_instance()
+            .filter_level(LevelFilter::Info)
+            .try_init()
+            .expect("really the only thing that should create a logger");
+
+        let config: KitchenSinkConfig =
+            collectd_plugin::de::from_collectd(config.unwrap_or_default())?;
+
+        if config.instances.is_empty() {
+            return Ok(PluginRegistration::Empty(String::from(
+                "no instances configured",
+            )));
+        }
+
+        let plugins = config
+            .instances
+            .into_iter()
+            .map(|x| {
+                let plugin = KitchenSink {
+                    name: x.name.clone(),
+                    gauge: x.gauge,
+                    limit: x.limit,
+                    buffer: Mutex::new(Vec::new()),
+                    warnings: AtomicUsize::new(0),
+                };
+                let bx: Box<dyn Plugin> = Box::new(plugin);
+                (x.name, bx)
+            })
+            .collect();
+
+        Ok(PluginRegistration::Multiple(plugins))
+    }
+}
+
+// The capabilities are inferred from the methods that are implemented
+impl_plugin! {
+    impl Plugin for KitchenSink {
+        fn read_values(&self) -> Result<(), Box<dyn error::Error>> {
+            let meta = [(String::from("source"), MetaValue::String(self.name.clone()))];
+            ValueListBuilder::new(KitchenSinkManager::name(), "gauge")
+                .plugin_instance(self.name.as_str())
+                .values(&[Value::Gauge(self.gauge)])
+                .meta(&meta)
+                .submit()?;
+
+            if let Some(limit) = self.limit.filter(|&x| self.gauge > x) {
+                let msg = format!("gauge of {} is over the limit of {}", self.gauge, limit);
+                let name = KitchenSinkManager::name();
+                NotificationBuilder::new(NotificationLevel::Warning, name, &msg)
+                    .plugin_instance(self.name.as_str())
+                    .type_("gauge")
+                    .dispatch()?;
+            }
+
+            Ok(())
+        }
+
+        fn write_values(&self, list: ValueList<'_>) -> Result<(), Box<dyn error::Error>> {
+            // Values reported by this plugin carry the instance that reported them
+            let source = match list.meta_value("source")? {
+                Some(MetaValue::String(x)) => x,
+                _ => String::from("<other plugin>"),
+            };
+
+            let values: Vec<_> = list.values.iter().map(|x| x.value.to_string()).collect();
+            let line = format!(
+                "{}/{}/{} ({}): {}",
+                list.host,
+                list.plugin,
+                list.type_,
+                source,
+                values.join(",")
+            );
+            self.buffer.lock().unwrap_or_else(|e| e.into_inner()).push(line);
+            Ok(())
+        }
+
+        fn flush_matching(
+            &self,
+            timeout: Option<Duration>,
+            identifier: Option<&IdentifierPattern>,
+        ) -> Result<(), Box<dyn error::Error>> {
+            let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+            let lines = buffer.len();
+            buffer.clear();
+            info!(
+                "flushed {} buffered lines (timeout: {:?}, identifier: {:?})",
+                lines,
+                timeout,
+                identifier.map(|x| x.to_string())
+            );
+            Ok(())
+        }
+
+        fn log(&self, lvl: LogLevel, _msg: &str) -> Result<(), Box<dyn error::Error>> {
+            // Don't log from here, as messages logged from a log callback are dropped
+            if matches!(lvl, LogLevel::Error | LogLevel::Warning) {
+                self.warnings.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(())
+        }
+
+        fn notification(
+            &self,
+            notification: Notification<'_>,
+        ) -> Result<(), Box<dyn error::Error>> {
+            warn!(
+                "{:?} notification from {}: {} (warnings logged so far: {})",
+                notification.severity,
+                notification.plugin,
+                notification.message,
+                self.warnings.load(Ordering::Relaxed)
+            );
+            Ok(())
+        }
+    }
+}
+
+collectd_plugin!(KitchenSinkManager);
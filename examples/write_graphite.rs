@@ -13,6 +13,7 @@ use std::io::Write;
 use std::net::TcpStream;
 use std::ops::Deref;
 use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
 
 /// Here is what our collectd config can look like:
 ///
@@ -162,7 +163,12 @@ impl<W: Write + Send> Plugin for GraphitePlugin<W> {
             line.push_str(graphitize(type_instance).deref());
         }
 
-        let dt = list.time.timestamp().to_string();
+        let dt = list
+            .time
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0)
+            .to_string();
 
         // If there is only one value in the list we don't have to clone our premade string,
         // instead we can write it directly
@@ -1,9 +1,9 @@
-#![cfg(feature = "serde")]
+#![cfg(all(feature = "serde", feature = "chrono"))]
 
-use chrono::Duration;
+use chrono::{DateTime, Utc};
 use collectd_plugin::{
-    collectd_log, collectd_log_raw, collectd_plugin, CollectdLoggerBuilder, ConfigItem, LogLevel,
-    Plugin, PluginCapabilities, PluginManager, PluginRegistration, ValueList,
+    collectd_log, collectd_log_raw, collectd_plugin, CollectdLoggerBuilder, ConfigItem, Duration,
+    LogLevel, Plugin, PluginCapabilities, PluginManager, PluginRegistration, ValueList,
 };
 use itertools::Itertools;
 use log::{info, LevelFilter};
@@ -76,8 +76,8 @@ impl Plugin for LogWritePlugin {
             list.type_,
             list.type_instance.unwrap_or("<none>"),
             list.host,
-            list.time,
-            list.interval.num_seconds(),
+            DateTime::<Utc>::from(list.time),
+            list.interval.as_secs(),
             values,
         );
 
@@ -92,7 +92,7 @@ impl Plugin for LogWritePlugin {
         info!(
             "flushing: timeout: {}, identifier: {}",
             timeout
-                .map(|x| format!("{}", x))
+                .map(|x| format!("{:?}", x))
                 .unwrap_or_else(|| String::from("no timeout")),
             identifier
                 .map(|x| x.to_string())
@@ -13,10 +13,13 @@ generate () {
             --whitelist-type cdtime_t \
             --whitelist-type data_set_t \
             --whitelist-function 'plugin_.*' \
-            --whitelist-function 'uc_get_rate' \
+            --whitelist-function 'uc_get_rate(_by_name)?' \
+            --whitelist-function 'meta_data_.*' \
             --whitelist-var 'OCONFIG_TYPE_.*' \
             --whitelist-var 'LOG_.*' \
             --whitelist-var 'DS_TYPE_.*' \
+            --whitelist-var 'MD_TYPE_.*' \
+            --whitelist-var 'NOTIF_(FAILURE|WARNING|OKAY)' \
             --whitelist-var DATA_MAX_NAME_LEN \
             wrapper.h -- -DHAVE_CONFIG_H -DCOLLECTD_$3" > src/bindings-$3.rs
 }
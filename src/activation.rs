@@ -0,0 +1,200 @@
+//! Sockets passed to collectd through systemd's socket activation, so that plugins that serve
+//! requests (eg: the control socket or a scrape endpoint) can listen on sockets declared in a
+//! `.socket` unit instead of on ports in their own configuration.
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::env;
+use std::io;
+use std::net::TcpListener;
+use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::net::UnixListener;
+use std::process;
+use std::sync::Mutex;
+
+/// The first file descriptor passed by systemd (`SD_LISTEN_FDS_START`)
+const LISTEN_FDS_START: RawFd = 3;
+
+lazy_static! {
+    // File descriptors that have been turned into listeners and are owned by them
+    static ref CLAIMED: Mutex<HashSet<RawFd>> = Mutex::new(HashSet::new());
+}
+
+/// A listening socket that systemd passed to collectd. The socket is owned by the process until
+/// it is turned into a listener, which then closes it when dropped. Each socket can only be
+/// turned into a listener once, even when the plugin managers of a shared object all look for
+/// their own sockets.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ListenFd {
+    fd: RawFd,
+    name: Option<String>,
+}
+
+impl ListenFd {
+    /// The raw file descriptor
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// The `FileDescriptorName` of the socket in the `.socket` unit, which systemd defaults to the
+    /// name of the unit. `None` if systemd is too old to pass names.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Takes ownership of the socket as a TCP listener. Fails if the socket isn't a TCP socket or
+    /// was already taken.
+    pub fn into_tcp_listener(self) -> io::Result<TcpListener> {
+        claim(self.fd)?;
+        let listener = unsafe { TcpListener::from_raw_fd(self.fd) };
+        match listener.local_addr() {
+            Ok(_) => Ok(listener),
+            Err(_) => Err(release(listener.into_raw_fd(), "TCP")),
+        }
+    }
+
+    /// Takes ownership of the socket as a Unix domain socket listener. Fails if the socket isn't
+    /// a Unix domain socket or was already taken.
+    pub fn into_unix_listener(self) -> io::Result<UnixListener> {
+        claim(self.fd)?;
+        let listener = unsafe { UnixListener::from_raw_fd(self.fd) };
+        match listener.local_addr() {
+            Ok(_) => Ok(listener),
+            Err(_) => Err(release(listener.into_raw_fd(), "Unix domain")),
+        }
+    }
+}
+
+/// The sockets that systemd passed to collectd (as described by the `LISTEN_PID`, `LISTEN_FDS`,
+/// and `LISTEN_FDNAMES` environment variables) that have yet to be turned into listeners. Empty
+/// when collectd wasn't socket activated.
+///
+/// ```
+/// use collectd_plugin::listen_fd;
+///
+/// // With `FileDescriptorName=metrics` in collectd.socket
+/// if let Some(fd) = listen_fd("metrics") {
+///     let listener = fd.into_tcp_listener();
+/// }
+/// ```
+pub fn listen_fds() -> Vec<ListenFd> {
+    let pid = env::var("LISTEN_PID").ok();
+    let fds = env::var("LISTEN_FDS").ok();
+    let names = env::var("LISTEN_FDNAMES").ok();
+    let claimed = CLAIMED.lock().unwrap_or_else(|e| e.into_inner());
+
+    parse_listen_fds(
+        pid.as_deref(),
+        fds.as_deref(),
+        names.as_deref(),
+        process::id(),
+    )
+    .into_iter()
+    .filter(|x| !claimed.contains(&x.fd))
+    .collect()
+}
+
+/// The first socket passed to collectd with the given name that has yet to be turned into a
+/// listener. See `listen_fds`.
+pub fn listen_fd(name: &str) -> Option<ListenFd> {
+    listen_fds()
+        .into_iter()
+        .find(|x| x.name.as_deref() == Some(name))
+}
+
+fn parse_listen_fds(
+    pid: Option<&str>,
+    fds: Option<&str>,
+    names: Option<&str>,
+    own_pid: u32,
+) -> Vec<ListenFd> {
+    // The variables are inherited by children that weren't activated, hence the pid check
+    if pid.and_then(|x| x.parse::<u32>().ok()) != Some(own_pid) {
+        return Vec::new();
+    }
+
+    let count = match fds.and_then(|x| x.parse::<RawFd>().ok()) {
+        Some(count) if count > 0 => count,
+        _ => return Vec::new(),
+    };
+
+    let mut names = names.map(|x| x.split(':'));
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .map(|fd| ListenFd {
+            fd,
+            name: names
+                .as_mut()
+                .and_then(|x| x.next())
+                .filter(|x| !x.is_empty())
+                .map(String::from),
+        })
+        .collect()
+}
+
+fn claim(fd: RawFd) -> io::Result<()> {
+    let mut claimed = CLAIMED.lock().unwrap_or_else(|e| e.into_inner());
+    if claimed.insert(fd) {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("file descriptor {} is already a listener", fd),
+        ))
+    }
+}
+
+/// Gives up ownership of a socket of the wrong kind without closing it
+fn release(fd: RawFd, kind: &str) -> io::Error {
+    CLAIMED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&fd);
+
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("file descriptor {} is not a {} socket", fd, kind),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listen_fd(fd: RawFd, name: Option<&str>) -> ListenFd {
+        ListenFd {
+            fd,
+            name: name.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_parse_listen_fds() {
+        assert_eq!(
+            vec![listen_fd(3, Some("control")), listen_fd(4, Some("metrics"))],
+            parse_listen_fds(Some("10"), Some("2"), Some("control:metrics"), 10)
+        );
+
+        assert_eq!(
+            vec![listen_fd(3, None), listen_fd(4, None)],
+            parse_listen_fds(Some("10"), Some("2"), None, 10)
+        );
+
+        // Meant for another process
+        assert!(parse_listen_fds(Some("11"), Some("2"), None, 10).is_empty());
+        assert!(parse_listen_fds(None, Some("2"), None, 10).is_empty());
+        assert!(parse_listen_fds(Some("10"), Some("0"), None, 10).is_empty());
+        assert!(parse_listen_fds(Some("10"), Some("x"), None, 10).is_empty());
+    }
+
+    #[test]
+    fn test_into_listener() {
+        let fd = TcpListener::bind("127.0.0.1:0").unwrap().into_raw_fd();
+
+        let err = listen_fd(fd, None).into_unix_listener().unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+
+        let listener = listen_fd(fd, None).into_tcp_listener().unwrap();
+        let err = listen_fd(fd, None).into_tcp_listener().unwrap_err();
+        assert_eq!(io::ErrorKind::AlreadyExists, err.kind());
+        assert!(listener.local_addr().is_ok());
+    }
+}
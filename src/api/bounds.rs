@@ -0,0 +1,132 @@
+use super::{from_array, length, Value, ValueType};
+use crate::bindings::plugin_get_ds;
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::slice;
+
+/// A data source of a type from collectd's types database, along with the bounds of its values. A
+/// bound of NaN (`U` in types.db) means that the values are unbounded in that direction.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DataSource {
+    pub name: String,
+    pub value_type: ValueType,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl DataSource {
+    /// Returns true if the value lies within the bounds. Only gauges are checked, as collectd
+    /// applies the bounds of the other types to their rates rather than to the values themselves.
+    pub fn in_bounds(&self, value: Value) -> bool {
+        in_bounds(value, self.min, self.max)
+    }
+
+    /// Clamps a gauge to the nearest bound. Other types of values are returned as is.
+    pub fn clamp(&self, value: Value) -> Value {
+        clamp_value(value, self.min, self.max)
+    }
+}
+
+/// What happens when a value submitted with `ValueListBuilder::bounds` lies outside of the bounds
+/// of its data source (eg: a negative percentage)
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum BoundsPolicy {
+    /// Submit the value as is, and leave it to collectd to discard it
+    #[default]
+    Ignore,
+
+    /// Submit the nearest bound instead
+    Clamp,
+
+    /// Fail the submission with `SubmitError::OutOfBounds`
+    Reject,
+}
+
+/// The data sources of a type (eg: `load` has `shortterm`, `midterm`, and `longterm`), or `None` if
+/// collectd doesn't know of the type.
+pub fn data_sources(type_: &str) -> Option<Vec<DataSource>> {
+    let type_ = CString::new(type_).ok()?;
+    let ds = unsafe { plugin_get_ds(type_.as_ptr()).as_ref() }?;
+    if ds.ds.is_null() {
+        return Some(Vec::new());
+    }
+
+    let sources = unsafe { slice::from_raw_parts(ds.ds, length(ds.ds_num)) };
+    sources
+        .iter()
+        .map(|x| {
+            Some(DataSource {
+                name: String::from(from_array(&x.name).ok()?),
+                value_type: ValueType::try_from(x.type_ as u32).ok()?,
+                min: x.min,
+                max: x.max,
+            })
+        })
+        .collect()
+}
+
+/// A gauge of NaN is unknown and so is within bounds
+pub(crate) fn in_bounds(value: Value, min: f64, max: f64) -> bool {
+    match value {
+        Value::Gauge(x) => x.is_nan() || ((min.is_nan() || x >= min) && (max.is_nan() || x <= max)),
+        _ => true,
+    }
+}
+
+pub(crate) fn clamp_value(value: Value, min: f64, max: f64) -> Value {
+    match value {
+        Value::Gauge(x) if !in_bounds(value, min, max) => {
+            if !min.is_nan() && x < min {
+                Value::Gauge(min)
+            } else {
+                Value::Gauge(max)
+            }
+        }
+        x => x,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_bounds() {
+        assert!(in_bounds(Value::Gauge(50.0), 0.0, 100.0));
+        assert!(in_bounds(Value::Gauge(100.0), 0.0, 100.0));
+        assert!(!in_bounds(Value::Gauge(-1.0), 0.0, 100.0));
+        assert!(!in_bounds(Value::Gauge(101.0), 0.0, 100.0));
+        assert!(in_bounds(Value::Gauge(f64::NAN), 0.0, 100.0));
+        assert!(in_bounds(Value::Gauge(1e12), 0.0, f64::NAN));
+        assert!(!in_bounds(Value::Gauge(-1.0), 0.0, f64::NAN));
+
+        // Bounds of derives and counters apply to their rates
+        assert!(in_bounds(Value::Derive(-5), 0.0, 100.0));
+        assert!(in_bounds(Value::Counter(500), 0.0, 100.0));
+    }
+
+    #[test]
+    fn test_clamp_value() {
+        assert_eq!(
+            Value::Gauge(0.0),
+            clamp_value(Value::Gauge(-3.0), 0.0, 100.0)
+        );
+        assert_eq!(
+            Value::Gauge(100.0),
+            clamp_value(Value::Gauge(130.0), 0.0, 100.0)
+        );
+        assert_eq!(
+            Value::Gauge(42.0),
+            clamp_value(Value::Gauge(42.0), 0.0, 100.0)
+        );
+        assert_eq!(
+            Value::Gauge(5.0),
+            clamp_value(Value::Gauge(5.0), f64::NAN, f64::NAN)
+        );
+        assert_eq!(
+            Value::Derive(-5),
+            clamp_value(Value::Derive(-5), 0.0, 100.0)
+        );
+        assert!(clamp_value(Value::Gauge(f64::NAN), 0.0, 100.0).is_nan());
+    }
+}
@@ -0,0 +1,217 @@
+use super::{Value, ValueListBuilder};
+use crate::errors::SubmitError;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// What happens when a reservation would exceed a `MemoryBudget`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetPolicy {
+    /// Admit the new entry and have the buffer evict its oldest entries to make room
+    DropOldest,
+
+    /// Reject the new entry
+    DropNewest,
+
+    /// Wait up to the duration for other buffers to release memory, else reject the new entry
+    Block(Duration),
+}
+
+/// The outcome of reserving memory from a `MemoryBudget`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// The memory was reserved
+    Admitted,
+
+    /// The memory was reserved, but the budget is now exceeded by the given number of bytes, which
+    /// the buffer should free by evicting its oldest entries (and releasing their memory)
+    Evict(usize),
+
+    /// The memory was not reserved and the entry should be dropped
+    Rejected,
+}
+
+/// A memory budget in bytes that is shared by all the buffers of a plugin, so that a dead backend
+/// can't balloon collectd's memory usage. Buffers reserve the approximate size of an entry before
+/// buffering it and release it once the entry is written or dropped.
+///
+/// ```
+/// use collectd_plugin::{Admission, BudgetPolicy, MemoryBudget};
+/// use std::collections::VecDeque;
+///
+/// let budget = MemoryBudget::new(1 << 20, BudgetPolicy::DropOldest);
+/// let mut buffer: VecDeque<String> = VecDeque::new();
+///
+/// let entry = String::from("localhost/load/load 1 2 3");
+/// match budget.reserve(entry.len()) {
+///     Admission::Admitted => buffer.push_back(entry),
+///     Admission::Evict(mut bytes) => {
+///         buffer.push_back(entry);
+///         while bytes > 0 {
+///             match buffer.pop_front() {
+///                 Some(old) => {
+///                     bytes = bytes.saturating_sub(old.len());
+///                     budget.release(old.len());
+///                     budget.record_dropped(1);
+///                 }
+///                 None => break,
+///             }
+///         }
+///     }
+///     Admission::Rejected => {}
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MemoryBudget {
+    limit: usize,
+    policy: BudgetPolicy,
+    used: Mutex<usize>,
+    released: Condvar,
+    dropped: AtomicUsize,
+}
+
+impl MemoryBudget {
+    /// Creates a budget of the given number of bytes
+    pub fn new(limit: usize, policy: BudgetPolicy) -> Self {
+        MemoryBudget {
+            limit,
+            policy,
+            used: Mutex::new(0),
+            released: Condvar::new(),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Reserves the bytes for a new entry according to the budget's policy. A rejected entry is
+    /// counted as dropped.
+    pub fn reserve(&self, bytes: usize) -> Admission {
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        if *used + bytes <= self.limit {
+            *used += bytes;
+            return Admission::Admitted;
+        }
+
+        match self.policy {
+            BudgetPolicy::DropOldest => {
+                *used += bytes;
+                Admission::Evict(*used - self.limit)
+            }
+            BudgetPolicy::Block(timeout) if bytes <= self.limit => {
+                let deadline = Instant::now() + timeout;
+                while *used + bytes > self.limit {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return self.reject();
+                    }
+
+                    used = self
+                        .released
+                        .wait_timeout(used, deadline - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0;
+                }
+
+                *used += bytes;
+                Admission::Admitted
+            }
+            BudgetPolicy::Block(_) | BudgetPolicy::DropNewest => self.reject(),
+        }
+    }
+
+    fn reject(&self) -> Admission {
+        self.record_dropped(1);
+        Admission::Rejected
+    }
+
+    /// Returns bytes to the budget once an entry has been written or evicted
+    pub fn release(&self, bytes: usize) {
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        *used = used.saturating_sub(bytes);
+        self.released.notify_all();
+    }
+
+    /// Counts entries that a buffer evicted to stay within the budget
+    pub fn record_dropped(&self, entries: usize) {
+        self.dropped.fetch_add(entries, Ordering::Relaxed);
+    }
+
+    /// The bytes currently reserved
+    pub fn used(&self) -> usize {
+        *self.used.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The number of entries dropped due to the budget
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Dispatches the bytes reserved (`bytes-budget_used`) and entries dropped
+    /// (`derive-budget_dropped`) as self-metrics of the plugin
+    pub fn submit(&self, plugin: &str) -> Result<(), SubmitError> {
+        ValueListBuilder::new(plugin, "bytes")
+            .type_instance("budget_used")
+            .values(&[Value::Gauge(self.used() as f64)])
+            .submit()?;
+
+        ValueListBuilder::new(plugin, "derive")
+            .type_instance("budget_dropped")
+            .values(&[Value::Derive(self.dropped() as i64)])
+            .submit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_budget_drop_newest() {
+        let budget = MemoryBudget::new(10, BudgetPolicy::DropNewest);
+        assert_eq!(Admission::Admitted, budget.reserve(6));
+        assert_eq!(Admission::Rejected, budget.reserve(6));
+        assert_eq!(6, budget.used());
+        assert_eq!(1, budget.dropped());
+
+        budget.release(6);
+        assert_eq!(Admission::Admitted, budget.reserve(6));
+        assert!(budget.submit("myplugin").is_ok());
+    }
+
+    #[test]
+    fn test_budget_drop_oldest() {
+        let budget = MemoryBudget::new(10, BudgetPolicy::DropOldest);
+        assert_eq!(Admission::Admitted, budget.reserve(6));
+        assert_eq!(Admission::Evict(2), budget.reserve(6));
+        assert_eq!(12, budget.used());
+
+        budget.release(6);
+        budget.record_dropped(1);
+        assert_eq!(6, budget.used());
+        assert_eq!(1, budget.dropped());
+    }
+
+    #[test]
+    fn test_budget_block() {
+        let budget = Arc::new(MemoryBudget::new(
+            10,
+            BudgetPolicy::Block(Duration::from_secs(5)),
+        ));
+        assert_eq!(Admission::Admitted, budget.reserve(6));
+        assert_eq!(Admission::Rejected, budget.reserve(11));
+
+        let releaser = Arc::clone(&budget);
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            releaser.release(6);
+        });
+
+        assert_eq!(Admission::Admitted, budget.reserve(6));
+        handle.join().unwrap();
+
+        let budget = MemoryBudget::new(10, BudgetPolicy::Block(Duration::from_millis(10)));
+        assert_eq!(Admission::Admitted, budget.reserve(6));
+        assert_eq!(Admission::Rejected, budget.reserve(6));
+    }
+}
@@ -0,0 +1,445 @@
+use super::{
+    collectd_log, CdTime, Duration, Identifier, IntoDuration, LogLevel, Timestamp, Value, ValueList,
+};
+use crate::errors::DurationError;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// The last values seen for an identifier
+#[derive(Debug, PartialEq, Clone)]
+pub struct CacheEntry {
+    /// The values reported
+    pub values: Vec<Value>,
+
+    /// The timestamp at which the values were collected
+    pub time: Timestamp,
+
+    /// The interval in which new values are to be expected
+    pub interval: Duration,
+}
+
+impl<'a, 'b> From<&'b ValueList<'a>> for CacheEntry {
+    fn from(list: &'b ValueList<'a>) -> Self {
+        CacheEntry {
+            values: list.values.iter().map(|x| x.value).collect(),
+            time: list.time,
+            interval: list.interval,
+        }
+    }
+}
+
+/// A cache local to the plugin that keeps track of the last values and timestamp seen per
+/// identifier. Write plugins can use this to compute deltas between values or detect series that
+/// have gone stale without reaching into collectd's global cache from the write thread.
+///
+/// Entries that haven't been updated within the time to live are evicted through `evict_expired`.
+#[derive(Debug)]
+pub struct LocalCache {
+    ttl: ::std::time::Duration,
+    entries: Mutex<HashMap<Identifier, (CacheEntry, Instant)>>,
+}
+
+impl LocalCache {
+    /// Creates an empty cache where entries live for `ttl` after they were last updated
+    pub fn new<T: IntoDuration>(ttl: T) -> Result<Self, DurationError> {
+        Ok(LocalCache {
+            ttl: ttl.into_duration()?,
+            entries: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Records the values of the list and returns the previously cached entry, if any.
+    pub fn update(&self, list: &ValueList<'_>) -> Option<CacheEntry> {
+        self.insert_at(
+            Identifier::from(list),
+            CacheEntry::from(list),
+            Instant::now(),
+        )
+    }
+
+    fn insert_at(&self, id: Identifier, entry: CacheEntry, now: Instant) -> Option<CacheEntry> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.insert(id, (entry, now)).map(|(old, _)| old)
+    }
+
+    /// Returns the last entry seen for the identifier
+    pub fn get(&self, id: &Identifier) -> Option<CacheEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.get(id).map(|(entry, _)| entry.clone())
+    }
+
+    /// Removes and returns the identifiers that have not been updated within the time to live.
+    /// These are series that have stopped reporting.
+    pub fn evict_expired(&self) -> Vec<Identifier> {
+        self.evict_at(Instant::now())
+    }
+
+    fn evict_at(&self, now: Instant) -> Vec<Identifier> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let expired: Vec<Identifier> = entries
+            .iter()
+            .filter(|(_, (_, updated))| now.duration_since(*updated) >= self.ttl)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for id in &expired {
+            entries.remove(id);
+        }
+
+        expired
+    }
+
+    /// Number of identifiers in the cache
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Returns true if the cache contains no identifiers
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The points of a series in a `SeriesWindow`, oldest first
+pub type SeriesPoints = VecDeque<(Timestamp, Vec<Value>)>;
+
+/// A sliding window of the most recent points of each identifier, for write plugins that compute
+/// moving averages, anomaly scores, and the like. Points older than the maximum age (relative to
+/// the newest point of the series) are evicted, as are the oldest points once a series exceeds
+/// the maximum number of points.
+///
+/// ```
+/// use collectd_plugin::{SeriesWindow, ValueList};
+///
+/// fn write(window: &SeriesWindow, list: &ValueList<'_>) {
+///     let id = window.push(list);
+///     let avg = window.mean(&id, 0);
+/// }
+///
+/// let window = SeriesWindow::new("5m", 30).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct SeriesWindow {
+    max_age: ::std::time::Duration,
+    max_points: usize,
+    series: Mutex<HashMap<Identifier, SeriesPoints>>,
+}
+
+impl SeriesWindow {
+    /// Creates an empty window that retains up to `max_points` points that are no older than
+    /// `max_age` per series
+    pub fn new<T: IntoDuration>(max_age: T, max_points: usize) -> Result<Self, DurationError> {
+        Ok(SeriesWindow {
+            max_age: max_age.into_duration()?,
+            max_points,
+            series: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Appends the values of the list to its series and returns the series' identifier
+    pub fn push(&self, list: &ValueList<'_>) -> Identifier {
+        let id = Identifier::from(list);
+        let values = list.values.iter().map(|x| x.value).collect();
+        self.push_values(id.clone(), list.time, values);
+        id
+    }
+
+    /// Appends values observed at the given time to the series of the identifier
+    pub fn push_values(&self, id: Identifier, time: Timestamp, values: Vec<Value>) {
+        let mut series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        let points = series.entry(id).or_default();
+        points.push_back((time, values));
+
+        let CdTime(newest) = CdTime::from(time);
+        let CdTime(max_age) = CdTime::from(self.max_age);
+        let cutoff = newest.saturating_sub(max_age);
+        while let Some(&(oldest, _)) = points.front() {
+            let CdTime(oldest) = CdTime::from(oldest);
+            if oldest < cutoff || points.len() > self.max_points {
+                points.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Runs the computation over the points of the identifier's series, if there is one
+    pub fn with_points<F, R>(&self, id: &Identifier, f: F) -> Option<R>
+    where
+        F: FnOnce(&SeriesPoints) -> R,
+    {
+        let series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        series.get(id).map(f)
+    }
+
+    /// The average of the gauges at the given position (eg: 0 for single value types) within
+    /// the window. Values that are not gauges or are NaN are ignored.
+    pub fn mean(&self, id: &Identifier, index: usize) -> Option<f64> {
+        self.with_points(id, |points| {
+            let (sum, count) = points
+                .iter()
+                .filter_map(|(_, values)| match values.get(index) {
+                    Some(Value::Gauge(x)) if !x.is_nan() => Some(*x),
+                    _ => None,
+                })
+                .fold((0.0, 0), |(sum, count), x| (sum + x, count + 1));
+
+            if count == 0 {
+                None
+            } else {
+                Some(sum / f64::from(count))
+            }
+        })
+        .and_then(|x| x)
+    }
+
+    /// Removes the series of the identifier (eg: when it has gone stale)
+    pub fn remove(&self, id: &Identifier) -> Option<SeriesPoints> {
+        let mut series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        series.remove(id)
+    }
+
+    /// Number of series in the window
+    pub fn len(&self) -> usize {
+        self.series.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Returns true if the window contains no series
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Memoizes the result of expensive discovery work (device lists, API catalogs, etc) across read
+/// intervals. The result is rediscovered once it is older than the time to live, when the
+/// configuration it was discovered with changes, or after it has been invalidated. Should
+/// rediscovery fail, the stale result continues to be served so that a transient failure doesn't
+/// cause a gap in the values dispatched.
+///
+/// ```
+/// use collectd_plugin::CachedRead;
+///
+/// fn block_devices(cache: &CachedRead<Vec<String>>, dir: &str) -> Result<(), std::io::Error> {
+///     let devices = cache.get(dir, || {
+///         let mut names = Vec::new();
+///         for entry in std::fs::read_dir(dir)? {
+///             names.push(entry?.file_name().to_string_lossy().into_owned());
+///         }
+///         Ok::<_, std::io::Error>(names)
+///     })?;
+///
+///     for device in devices.iter() {
+///         // read and submit the stats of each device
+///     }
+///     Ok(())
+/// }
+///
+/// let cache: CachedRead<Vec<String>> = CachedRead::new("5m").unwrap();
+/// ```
+#[derive(Debug)]
+pub struct CachedRead<T> {
+    ttl: ::std::time::Duration,
+    state: Mutex<Option<Discovered<T>>>,
+}
+
+#[derive(Debug)]
+struct Discovered<T> {
+    value: Arc<T>,
+    fingerprint: u64,
+    discovered: Instant,
+}
+
+impl<T> CachedRead<T> {
+    /// Creates an empty cache where a discovery is reused for `ttl`
+    pub fn new<D: IntoDuration>(ttl: D) -> Result<Self, DurationError> {
+        Ok(CachedRead {
+            ttl: ttl.into_duration()?,
+            state: Mutex::new(None),
+        })
+    }
+
+    /// Returns the cached discovery if it is fresh and was made with the same configuration,
+    /// otherwise `discover` is invoked. When `discover` fails, the failure is logged and the
+    /// previous discovery is returned. The error is returned only if there is nothing cached.
+    pub fn get<K, F, E>(&self, config: &K, discover: F) -> Result<Arc<T>, E>
+    where
+        K: Hash + ?Sized,
+        F: FnOnce() -> Result<T, E>,
+        E: fmt::Display,
+    {
+        self.get_at(fingerprint(config), discover, Instant::now())
+    }
+
+    fn get_at<F, E>(&self, fingerprint: u64, discover: F, now: Instant) -> Result<Arc<T>, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: fmt::Display,
+    {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(ref d) = *state {
+            if d.fingerprint == fingerprint && now.duration_since(d.discovered) < self.ttl {
+                return Ok(Arc::clone(&d.value));
+            }
+        }
+
+        match discover() {
+            Ok(value) => {
+                let value = Arc::new(value);
+                *state = Some(Discovered {
+                    value: Arc::clone(&value),
+                    fingerprint,
+                    discovered: now,
+                });
+                Ok(value)
+            }
+            Err(e) => match *state {
+                Some(ref d) => {
+                    collectd_log(
+                        LogLevel::Warning,
+                        &format!("discovery failed, reusing previous results: {}", e),
+                    );
+                    Ok(Arc::clone(&d.value))
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    /// Forces the next `get` to rediscover. The current discovery is kept around as a fallback
+    /// should the rediscovery fail.
+    pub fn invalidate(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(ref mut d) = *state {
+            d.fingerprint = d.fingerprint.wrapping_add(1);
+        }
+    }
+
+    /// Drops the cached discovery, including the fallback
+    pub fn clear(&self) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+fn fingerprint<K: Hash + ?Sized>(config: &K) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    config.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::CdTime;
+
+    fn ident(type_instance: &str) -> Identifier {
+        Identifier {
+            host: String::from("localhost"),
+            plugin: String::from("memory"),
+            plugin_instance: None,
+            type_: String::from("memory"),
+            type_instance: Some(String::from(type_instance)),
+        }
+    }
+
+    fn entry(val: f64) -> CacheEntry {
+        CacheEntry {
+            values: vec![Value::Gauge(val)],
+            time: CdTime(1_000_000_000).into(),
+            interval: CdTime(10_000_000_000).into(),
+        }
+    }
+
+    #[test]
+    fn test_cache_update_returns_previous() {
+        let cache = LocalCache::new("30s").unwrap();
+        let now = Instant::now();
+        assert_eq!(None, cache.insert_at(ident("used"), entry(1.0), now));
+        assert_eq!(
+            Some(entry(1.0)),
+            cache.insert_at(ident("used"), entry(2.0), now)
+        );
+        assert_eq!(Some(entry(2.0)), cache.get(&ident("used")));
+        assert_eq!(None, cache.get(&ident("free")));
+    }
+
+    #[test]
+    fn test_cache_evict_expired() {
+        let cache = LocalCache::new("30s").unwrap();
+        let now = Instant::now();
+        let later = now + ::std::time::Duration::from_secs(20);
+        cache.insert_at(ident("used"), entry(1.0), now);
+        cache.insert_at(ident("free"), entry(1.0), later);
+        assert_eq!(2, cache.len());
+
+        let expired = cache.evict_at(now + ::std::time::Duration::from_secs(40));
+        assert_eq!(vec![ident("used")], expired);
+        assert_eq!(1, cache.len());
+    }
+
+    fn at(secs: u64) -> Timestamp {
+        CdTime(secs * 1_000_000_000).into()
+    }
+
+    #[test]
+    fn test_series_window_evicts_by_age_and_count() {
+        let window = SeriesWindow::new("30s", 3).unwrap();
+        for secs in &[10, 20, 30, 40] {
+            window.push_values(ident("used"), at(*secs), vec![Value::Gauge(*secs as f64)]);
+        }
+
+        // At most three points are kept
+        assert_eq!(Some(30.0), window.mean(&ident("used"), 0));
+
+        // Points more than 30 seconds older than the newest are evicted
+        window.push_values(ident("used"), at(65), vec![Value::Gauge(f64::NAN)]);
+        let times = window.with_points(&ident("used"), |points| {
+            points.iter().map(|(time, _)| *time).collect::<Vec<_>>()
+        });
+        assert_eq!(Some(vec![at(40), at(65)]), times);
+        assert_eq!(Some(40.0), window.mean(&ident("used"), 0));
+        assert_eq!(None, window.mean(&ident("used"), 1));
+        assert_eq!(None, window.mean(&ident("free"), 0));
+
+        assert_eq!(1, window.len());
+        assert!(window.remove(&ident("used")).is_some());
+        assert!(window.is_empty());
+    }
+
+    #[test]
+    fn test_cached_read_reuses_until_stale() {
+        let cache: CachedRead<usize> = CachedRead::new("30s").unwrap();
+        let now = Instant::now();
+        let ok = |x| move || -> Result<usize, String> { Ok(x) };
+        assert_eq!(1, *cache.get_at(1, ok(1), now).unwrap());
+        assert_eq!(1, *cache.get_at(1, ok(2), now).unwrap());
+
+        let later = now + ::std::time::Duration::from_secs(30);
+        assert_eq!(3, *cache.get_at(1, ok(3), later).unwrap());
+
+        // A new config fingerprint or an invalidation rediscovers
+        assert_eq!(4, *cache.get_at(2, ok(4), later).unwrap());
+        cache.invalidate();
+        assert_eq!(5, *cache.get_at(2, ok(5), later).unwrap());
+    }
+
+    #[test]
+    fn test_cached_read_falls_back_on_failure() {
+        let cache: CachedRead<usize> = CachedRead::new("30s").unwrap();
+        let now = Instant::now();
+        let fail = || -> Result<usize, String> { Err(String::from("timeout")) };
+        assert_eq!(Err(String::from("timeout")), cache.get_at(1, fail, now));
+
+        cache
+            .get_at(1, || -> Result<usize, String> { Ok(1) }, now)
+            .unwrap();
+        cache.invalidate();
+        assert_eq!(1, *cache.get_at(1, fail, now).unwrap());
+
+        cache.clear();
+        assert!(cache.get_at(1, fail, now).is_err());
+    }
+}
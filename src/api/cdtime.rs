@@ -9,15 +9,27 @@
 //! times and calculating differences is as simple as it is with `time_t`, i.e. a simple integer
 //! comparison / subtraction works.
 
-use crate::bindings::cdtime_t;
+use crate::bindings::{cdtime_t, plugin_get_interval};
+#[cfg(feature = "chrono")]
 use chrono::prelude::*;
-use chrono::Duration;
+use std::time::{Duration as StdDuration, SystemTime, UNIX_EPOCH};
 
-/// `CdTime` allows for ergonomic interop between collectd's `cdtime_t` and chrono's `Duration` and
-/// `DateTime`. The single field represents epoch nanoseconds.
+/// The timestamp type that is exposed in the API: the standard library's `SystemTime`. With the
+/// `chrono` feature, `CdTime` converts to and from chrono's `DateTime` as well.
+pub type Timestamp = SystemTime;
+
+/// The duration type that is exposed in the API: the standard library's `Duration`. With the
+/// `chrono` feature, `CdTime` converts to and from chrono's `Duration` as well, and chrono's
+/// `Duration` is an `IntoDuration`.
+pub type Duration = StdDuration;
+
+/// `CdTime` allows for ergonomic interop between collectd's `cdtime_t` and the standard library's
+/// `Duration` and `SystemTime` (as well as chrono's `Duration` and `DateTime` when the `chrono`
+/// feature is enabled). The single field represents epoch nanoseconds.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct CdTime(pub u64);
 
+#[cfg(feature = "chrono")]
 impl<Tz: TimeZone> From<DateTime<Tz>> for CdTime {
     fn from(dt: DateTime<Tz>) -> Self {
         let sec_nanos = (dt.timestamp() as u64) * 1_000_000_000;
@@ -26,6 +38,7 @@ impl<Tz: TimeZone> From<DateTime<Tz>> for CdTime {
     }
 }
 
+#[cfg(feature = "chrono")]
 impl From<CdTime> for DateTime<Utc> {
     fn from(v: CdTime) -> DateTime<Utc> {
         let CdTime(ns) = v;
@@ -35,16 +48,45 @@ impl From<CdTime> for DateTime<Utc> {
     }
 }
 
-impl From<Duration> for CdTime {
-    fn from(d: Duration) -> Self {
+#[cfg(feature = "chrono")]
+impl From<chrono::Duration> for CdTime {
+    fn from(d: chrono::Duration) -> Self {
         CdTime(d.num_nanoseconds().unwrap() as u64)
     }
 }
 
-impl From<CdTime> for Duration {
+#[cfg(feature = "chrono")]
+impl From<CdTime> for chrono::Duration {
     fn from(v: CdTime) -> Self {
         let CdTime(ns) = v;
-        Duration::nanoseconds(ns as i64)
+        chrono::Duration::nanoseconds(ns as i64)
+    }
+}
+
+impl From<StdDuration> for CdTime {
+    fn from(d: StdDuration) -> Self {
+        CdTime(d.as_secs() * 1_000_000_000 + u64::from(d.subsec_nanos()))
+    }
+}
+
+impl From<CdTime> for StdDuration {
+    fn from(v: CdTime) -> Self {
+        let CdTime(ns) = v;
+        StdDuration::from_nanos(ns)
+    }
+}
+
+impl From<SystemTime> for CdTime {
+    fn from(t: SystemTime) -> Self {
+        // Times before the epoch can't be represented by collectd, so they are clamped to the
+        // epoch.
+        CdTime::from(t.duration_since(UNIX_EPOCH).unwrap_or_default())
+    }
+}
+
+impl From<CdTime> for SystemTime {
+    fn from(v: CdTime) -> Self {
+        UNIX_EPOCH + StdDuration::from(v)
     }
 }
 
@@ -61,6 +103,14 @@ impl Into<cdtime_t> for CdTime {
     }
 }
 
+/// The interval at which collectd reads values. During a callback, this is the interval of the
+/// plugin instance being called (which takes a `read_interval` or a plugin's `Interval` option
+/// into account), else it is collectd's global `Interval`. Read plugins can derive their
+/// sampling windows from it instead of assuming collectd's default of ten seconds.
+pub fn get_interval() -> Duration {
+    CdTime::from(unsafe { plugin_get_interval() }).into()
+}
+
 /// Convert epoch nanoseconds into collectd's 2<sup>-30</sup> second resolution
 pub fn nanos_to_collectd(nanos: u64) -> cdtime_t {
     ((nanos / 1_000_000_000) << 30)
@@ -76,6 +126,12 @@ fn collectd_to_nanos(cd: cdtime_t) -> u64 {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_get_interval() {
+        // The stub of collectd has no interval
+        assert_eq!(CdTime(0), CdTime::from(get_interval()));
+    }
+
     #[test]
     fn test_nanos_to_collectd() {
         // Taken from utils_time_test.c
@@ -92,13 +148,31 @@ mod tests {
         assert_eq!(collectd_to_nanos(1546168770415815077), 1439981880053705608);
     }
 
+    #[cfg(feature = "chrono")]
     #[test]
     fn test_collectd_to_duration() {
         let v: cdtime_t = nanos_to_collectd(1_000_000_000);
-        let dur = Duration::from(CdTime::from(v));
+        let dur = chrono::Duration::from(CdTime::from(v));
         assert_eq!(dur.num_seconds(), 1);
     }
 
+    #[test]
+    fn test_collectd_to_std_duration() {
+        let v: cdtime_t = nanos_to_collectd(1_500_000_000);
+        let dur = StdDuration::from(CdTime::from(v));
+        assert_eq!(dur, StdDuration::from_millis(1500));
+        assert_eq!(CdTime::from(dur).0, 1_500_000_000);
+    }
+
+    #[test]
+    fn test_collectd_to_system_time() {
+        let v: cdtime_t = nanos_to_collectd(1_000_000_000);
+        let t: SystemTime = CdTime::from(v).into();
+        assert_eq!(UNIX_EPOCH + StdDuration::from_secs(1), t);
+        assert_eq!(CdTime::from(t).0, 1_000_000_000);
+    }
+
+    #[cfg(feature = "chrono")]
     #[test]
     fn test_collectd_to_datetime() {
         let v: cdtime_t = nanos_to_collectd(1_000_000_000);
@@ -106,6 +180,7 @@ mod tests {
         assert_eq!(Utc.ymd(1970, 1, 1).and_hms(0, 0, 1), dt);
     }
 
+    #[cfg(feature = "chrono")]
     #[test]
     fn test_datetime_to_collectd() {
         let dt = Utc.ymd(1970, 1, 1).and_hms(0, 0, 1);
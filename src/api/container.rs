@@ -0,0 +1,204 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Number of characters of a container ID that are used for its short form (the same as docker)
+const SHORT_ID_LEN: usize = 12;
+
+/// How the host field of dispatched values is derived when collectd runs inside a container.
+/// With the `serde` feature, this can be deserialized from the plugin's configuration (eg:
+/// `HostNaming "auto"`).
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum HostNaming {
+    /// Use collectd's hostname
+    #[default]
+    Hostname,
+
+    /// Use the name of the Kubernetes pod
+    Pod,
+
+    /// Use the short form of the container ID
+    Container,
+
+    /// Use the pod name, else the container ID, else collectd's hostname
+    Auto,
+}
+
+impl HostNaming {
+    /// The host that values should be attributed to. `None` means that collectd's hostname should
+    /// be used, which is also the case when collectd is not running inside a container.
+    ///
+    /// ```
+    /// use collectd_plugin::{ContainerInfo, HostNaming};
+    ///
+    /// let host = HostNaming::Auto.host(ContainerInfo::detect().as_ref());
+    /// ```
+    pub fn host(self, info: Option<&ContainerInfo>) -> Option<String> {
+        let info = info?;
+        let pod = || info.pod_name.clone();
+        let container = || info.short_id().map(String::from);
+        match self {
+            HostNaming::Hostname => None,
+            HostNaming::Pod => pod(),
+            HostNaming::Container => container(),
+            HostNaming::Auto => pod().or_else(container),
+        }
+    }
+}
+
+/// What is known about the container that collectd is running inside of
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ContainerInfo {
+    /// The full container ID, found from the process's cgroup or mounts
+    pub container_id: Option<String>,
+
+    /// The name of the Kubernetes pod, found from the `POD_NAME` environment variable (as
+    /// commonly exposed through the downward API) or else the pod's hostname
+    pub pod_name: Option<String>,
+}
+
+impl ContainerInfo {
+    /// Detects if collectd is running inside a container, and if so, returns information about
+    /// the container. The result is stable for the lifetime of the container, so it is best
+    /// computed once (eg: when the plugin is configured).
+    pub fn detect() -> Option<ContainerInfo> {
+        let container_id = fs::read_to_string("/proc/self/cgroup")
+            .ok()
+            .and_then(|x| container_id_from_cgroup(&x))
+            .or_else(|| {
+                fs::read_to_string("/proc/self/mountinfo")
+                    .ok()
+                    .and_then(|x| container_id_from_mountinfo(&x))
+            });
+
+        let pod_name = if env::var_os("KUBERNETES_SERVICE_HOST").is_some() {
+            env::var("POD_NAME")
+                .or_else(|_| env::var("HOSTNAME"))
+                .ok()
+                .filter(|x| !x.is_empty())
+        } else {
+            None
+        };
+
+        let marker = Path::new("/.dockerenv").exists() || Path::new("/run/.containerenv").exists();
+        if container_id.is_none() && pod_name.is_none() && !marker {
+            return None;
+        }
+
+        Some(ContainerInfo {
+            container_id,
+            pod_name,
+        })
+    }
+
+    /// The container ID shortened to the first 12 characters
+    pub fn short_id(&self) -> Option<&str> {
+        self.container_id
+            .as_ref()
+            .map(|x| &x[..x.len().min(SHORT_ID_LEN)])
+    }
+}
+
+/// Container IDs are 64 hex characters, which may be wrapped by the runtime (eg:
+/// `docker-<id>.scope` or `cri-containerd-<id>.scope` under systemd)
+fn container_id(segment: &str) -> Option<&str> {
+    let segment = segment.strip_suffix(".scope").unwrap_or(segment);
+    let id = segment.rsplit('-').next().unwrap_or(segment);
+    if id.len() == 64 && id.bytes().all(|x| x.is_ascii_hexdigit()) {
+        Some(id)
+    } else {
+        None
+    }
+}
+
+/// Finds the container ID in the contents of `/proc/self/cgroup` (eg:
+/// `12:cpu:/docker/<id>` or `0::/kubepods/burstable/pod<uid>/<id>`)
+fn container_id_from_cgroup(contents: &str) -> Option<String> {
+    contents
+        .lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .flat_map(|path| path.rsplit('/'))
+        .filter_map(container_id)
+        .map(String::from)
+        .next()
+}
+
+/// With cgroup v2 and a private cgroup namespace, the cgroup path is only `/`, but the runtime
+/// still bind mounts files like `/etc/hostname` from `.../containers/<id>/`
+fn container_id_from_mountinfo(contents: &str) -> Option<String> {
+    contents
+        .lines()
+        .flat_map(|line| line.split_whitespace())
+        .filter_map(|field| {
+            let mut segments = field.split('/');
+            segments.find(|x| *x == "containers")?;
+            segments.next().and_then(container_id)
+        })
+        .map(String::from)
+        .next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "8c2f4d1b0e9a7c6d5f4e3d2c1b0a99887766554433221100ffeeddccbbaa9988";
+
+    #[test]
+    fn test_container_id_from_cgroup() {
+        let docker = format!("12:cpu,cpuacct:/docker/{}\n0::/system.slice", ID);
+        assert_eq!(Some(ID.to_string()), container_id_from_cgroup(&docker));
+
+        let kube = format!(
+            "0::/kubepods.slice/kubepods-burstable.slice/cri-containerd-{}.scope",
+            ID
+        );
+        assert_eq!(Some(ID.to_string()), container_id_from_cgroup(&kube));
+
+        let host = "0::/user.slice/user-1000.slice/session-2.scope";
+        assert_eq!(None, container_id_from_cgroup(host));
+        assert_eq!(None, container_id_from_cgroup("0::/"));
+    }
+
+    #[test]
+    fn test_container_id_from_mountinfo() {
+        let mounts = format!(
+            "619 601 259:2 /var/lib/docker/containers/{}/hostname /etc/hostname rw - ext4",
+            ID
+        );
+        assert_eq!(Some(ID.to_string()), container_id_from_mountinfo(&mounts));
+        assert_eq!(
+            None,
+            container_id_from_mountinfo("22 1 259:2 / / rw - ext4")
+        );
+    }
+
+    #[test]
+    fn test_host_naming() {
+        let info = ContainerInfo {
+            container_id: Some(ID.to_string()),
+            pod_name: Some(String::from("web-5d8f7")),
+        };
+        let bare = ContainerInfo {
+            pod_name: None,
+            ..info.clone()
+        };
+
+        assert_eq!(Some("8c2f4d1b0e9a"), info.short_id());
+        assert_eq!(None, HostNaming::Hostname.host(Some(&info)));
+        assert_eq!(
+            Some("web-5d8f7".to_string()),
+            HostNaming::Pod.host(Some(&info))
+        );
+        assert_eq!(
+            Some("web-5d8f7".to_string()),
+            HostNaming::Auto.host(Some(&info))
+        );
+        assert_eq!(
+            Some("8c2f4d1b0e9a".to_string()),
+            HostNaming::Auto.host(Some(&bare))
+        );
+        assert_eq!(None, HostNaming::Pod.host(Some(&bare)));
+        assert_eq!(None, HostNaming::Auto.host(None));
+    }
+}
@@ -0,0 +1,95 @@
+use super::{CdTime, Duration};
+use crate::bindings::{plugin_ctx_t, plugin_get_ctx, plugin_set_ctx};
+use std::marker::PhantomData;
+
+/// Collectd's plugin context: the interval (and on collectd 5.7 and later, the flush settings)
+/// of the plugin that a thread is working for. Collectd sets up the context before invoking a
+/// callback, but threads spawned by a plugin start out with an empty context, so values they
+/// dispatch without an explicit interval get collectd's global `Interval` instead of the
+/// plugin's. Capture the context within a callback and enter it on the spawned thread.
+///
+/// ```
+/// use collectd_plugin::PluginContext;
+/// use std::thread;
+///
+/// // Called from within a callback (eg: `Plugin::initialize`)
+/// fn spawn_reporter() -> thread::JoinHandle<()> {
+///     let ctx = PluginContext::current();
+///     thread::spawn(move || {
+///         let _guard = ctx.enter();
+///         // values dispatched here carry the plugin's interval
+///     })
+/// }
+/// ```
+#[derive(Clone, Copy)]
+pub struct PluginContext {
+    ctx: plugin_ctx_t,
+}
+
+impl PluginContext {
+    /// The context of the calling thread
+    pub fn current() -> PluginContext {
+        PluginContext {
+            ctx: unsafe { plugin_get_ctx() },
+        }
+    }
+
+    /// The interval of the plugin, which is zero when the context is empty
+    pub fn interval(&self) -> Duration {
+        CdTime::from(self.ctx.interval).into()
+    }
+
+    /// Replaces the interval of the context (eg: for a thread that reports at its own pace)
+    pub fn with_interval(mut self, interval: Duration) -> PluginContext {
+        self.ctx.interval = CdTime::from(interval).into();
+        self
+    }
+
+    /// Makes this the context of the calling thread until the guard is dropped, which restores the
+    /// previous context
+    pub fn enter(self) -> ContextGuard {
+        ContextGuard {
+            previous: unsafe { plugin_set_ctx(self.ctx) },
+            _thread: PhantomData,
+        }
+    }
+}
+
+/// Restores the previous plugin context of the thread when dropped. See `PluginContext::enter`.
+pub struct ContextGuard {
+    previous: plugin_ctx_t,
+
+    // The context belongs to the thread that entered it
+    _thread: PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        unsafe {
+            plugin_set_ctx(self.previous);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_enter_context() {
+        let interval: Duration = CdTime(5_000_000_000).into();
+        let ctx = PluginContext::current().with_interval(interval);
+        assert_eq!(0, PluginContext::current().ctx.interval);
+
+        thread::spawn(move || {
+            {
+                let _guard = ctx.enter();
+                assert_eq!(interval, PluginContext::current().interval());
+            }
+            assert_eq!(0, PluginContext::current().ctx.interval);
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,46 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// Bitflags of the collectd daemon features that a plugin can make use of
+    pub struct DaemonCapabilities: u32 {
+        /// Values and notifications can carry meta data
+        const METADATA =          0b0000_0001;
+
+        /// Timestamps and intervals have a resolution of 2<sup>-30</sup> seconds
+        const HIGH_RES_TIME =     0b0000_0010;
+
+        /// Notifications can carry meta data
+        const NOTIFICATION_META = 0b0000_0100;
+    }
+}
+
+/// Reports the features of the collectd daemon that the plugin is loaded into, so that a plugin
+/// can degrade gracefully instead of assuming that a feature is present.
+///
+/// The structures shared with collectd differ between collectd versions, so a plugin must be
+/// built against the version family of the daemon that loads it (see the `COLLECTD_VERSION`
+/// environment variable). The features are therefore those of the version family the plugin was
+/// built against. Every version family supported (5.4 onwards) has all of the above features.
+///
+/// ```
+/// use collectd_plugin::{capabilities, DaemonCapabilities};
+///
+/// if capabilities().contains(DaemonCapabilities::METADATA) {
+///     // attach meta data to the submitted values
+/// }
+/// ```
+pub fn capabilities() -> DaemonCapabilities {
+    DaemonCapabilities::METADATA
+        | DaemonCapabilities::HIGH_RES_TIME
+        | DaemonCapabilities::NOTIFICATION_META
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        assert_eq!(DaemonCapabilities::all(), capabilities());
+    }
+}
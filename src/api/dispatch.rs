@@ -0,0 +1,114 @@
+use crate::bindings::{gauge_t, uc_get_rate_by_name};
+use crate::errors::SubmitError;
+use std::ffi::CString;
+use std::os::raw::c_void;
+use std::ptr;
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+
+extern "C" {
+    // The values returned from collectd's cache are allocated with malloc and it is up to the
+    // caller to free them
+    fn free(p: *mut c_void);
+}
+
+static DISPATCHED: AtomicUsize = AtomicUsize::new(0);
+static OUT_OF_MEMORY: AtomicUsize = AtomicUsize::new(0);
+static FAILED: AtomicUsize = AtomicUsize::new(0);
+static LAST_CODE: AtomicI64 = AtomicI64::new(0);
+
+/// A snapshot of the outcome of the value lists that the plugin has submitted to collectd, so
+/// that read plugins can emit self-metrics about dropped values. See `write_queue_length` for
+/// whether the write plugins are keeping up.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct DispatchStats {
+    /// Value lists that collectd accepted
+    pub dispatched: usize,
+
+    /// Value lists that were dropped as collectd ran out of memory queueing them
+    pub out_of_memory: usize,
+
+    /// Value lists that collectd rejected for any other reason
+    pub failed: usize,
+
+    /// The return code of the most recent dispatch that collectd rejected
+    pub last_code: Option<i32>,
+}
+
+/// Returns the outcome of the value lists submitted by the plugin so far
+pub fn dispatch_stats() -> DispatchStats {
+    let code = LAST_CODE.load(Ordering::Relaxed);
+    DispatchStats {
+        dispatched: DISPATCHED.load(Ordering::Relaxed),
+        out_of_memory: OUT_OF_MEMORY.load(Ordering::Relaxed),
+        failed: FAILED.load(Ordering::Relaxed),
+        last_code: if code == 0 { None } else { Some(code as i32) },
+    }
+}
+
+/// Tallies the return code from `plugin_dispatch_values`
+pub fn record_dispatch(res: &Result<(), SubmitError>) {
+    match *res {
+        Ok(()) => {
+            DISPATCHED.fetch_add(1, Ordering::Relaxed);
+        }
+        Err(ref e) => {
+            if let Some(code) = e.dispatch_code() {
+                LAST_CODE.store(i64::from(code), Ordering::Relaxed);
+                let counter = if let SubmitError::OutOfMemory = *e {
+                    &OUT_OF_MEMORY
+                } else {
+                    &FAILED
+                };
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// The number of value lists waiting in collectd's write queue. This is only available when
+/// collectd is configured with `CollectInternalStats true`, in which case collectd reports the
+/// length under the given host (collectd's `Hostname`) as `collectd-write_queue/queue_length`.
+pub fn write_queue_length(host: &str) -> Option<f64> {
+    let name = CString::new(format!("{}/collectd-write_queue/queue_length", host)).ok()?;
+    let mut values: *mut gauge_t = ptr::null_mut();
+    let mut len = 0;
+    let res = unsafe { uc_get_rate_by_name(name.as_ptr(), &mut values, &mut len) };
+    if res != 0 || values.is_null() {
+        return None;
+    }
+
+    let result = if len > 0 {
+        Some(unsafe { *values })
+    } else {
+        None
+    };
+
+    unsafe { free(values as *mut c_void) };
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_dispatch() {
+        let before = dispatch_stats();
+        record_dispatch(&Ok(()));
+        record_dispatch(&Err(SubmitError::OutOfMemory));
+        record_dispatch(&Err(SubmitError::Dispatch(-1)));
+        record_dispatch(&Err(SubmitError::Meta(String::from("key"))));
+
+        // Tests submitting values concurrently can only add to the counts
+        let after = dispatch_stats();
+        assert!(after.dispatched > before.dispatched);
+        assert!(after.out_of_memory > before.out_of_memory);
+        assert!(after.failed > before.failed);
+        assert_eq!(Some(-1), after.last_code);
+    }
+
+    #[test]
+    fn test_write_queue_length_unavailable() {
+        assert_eq!(None, write_queue_length("localhost"));
+    }
+}
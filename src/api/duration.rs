@@ -0,0 +1,68 @@
+use crate::errors::DurationError;
+use std::time::Duration;
+
+/// Conversion into a standard library `Duration`. APIs that accept a duration are generic over
+/// this trait so that one can pass a chrono `Duration`, a standard library `Duration`, or a
+/// [humantime](https://docs.rs/humantime) string like `"10s"` or `"1m 30s"`.
+///
+/// ```
+/// use collectd_plugin::IntoDuration;
+/// use std::time::Duration;
+///
+/// assert_eq!(Ok(Duration::from_secs(90)), "1m 30s".into_duration());
+/// assert_eq!(Ok(Duration::from_secs(10)), Duration::from_secs(10).into_duration());
+/// ```
+pub trait IntoDuration {
+    /// Performs the conversion
+    fn into_duration(self) -> Result<Duration, DurationError>;
+}
+
+impl IntoDuration for Duration {
+    fn into_duration(self) -> Result<Duration, DurationError> {
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl IntoDuration for chrono::Duration {
+    fn into_duration(self) -> Result<Duration, DurationError> {
+        self.to_std().map_err(|_| DurationError::OutOfRange)
+    }
+}
+
+impl IntoDuration for &str {
+    fn into_duration(self) -> Result<Duration, DurationError> {
+        humantime::parse_duration(self).map_err(|e| DurationError::Parse(String::from(self), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_std_into_duration() {
+        let dur = Duration::from_millis(1500);
+        assert_eq!(Ok(dur), dur.into_duration());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono_into_duration() {
+        assert_eq!(
+            Ok(Duration::from_millis(1500)),
+            chrono::Duration::milliseconds(1500).into_duration()
+        );
+        assert_eq!(
+            Err(DurationError::OutOfRange),
+            chrono::Duration::milliseconds(-1).into_duration()
+        );
+    }
+
+    #[test]
+    fn test_str_into_duration() {
+        assert_eq!(Ok(Duration::from_secs(10)), "10s".into_duration());
+        assert_eq!(Ok(Duration::from_millis(250)), "250ms".into_duration());
+        assert!("ten seconds".into_duration().is_err());
+    }
+}
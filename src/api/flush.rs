@@ -0,0 +1,210 @@
+use super::{CdTime, Duration, Identifier, IdentifierPattern, Timestamp};
+use crate::bindings::plugin_flush;
+use std::ffi::CString;
+use std::ptr;
+use std::time::SystemTime;
+
+/// Interprets the arguments of a flush the way collectd intends them, so that a write plugin can
+/// determine which of its buffered entries are to be flushed:
+///
+/// - Without a timeout, everything is flushed. Collectd requests this on shutdown and when the
+///   `FLUSH` command is given without a timeout.
+/// - With a timeout, only entries older than the timeout are flushed.
+/// - With an identifier, only entries of that identifier (eg: `localhost/cpu-0/cpu-idle`) are
+///   flushed. Components of the identifier may be left empty to match any value (eg: `/cpu-/`
+///   flushes all the values of the cpu plugin), see `IdentifierPattern`. An identifier that can't
+///   be parsed doesn't match any entry.
+///
+/// ```
+/// use collectd_plugin::{FlushPlan, Identifier, Timestamp};
+///
+/// fn flush(buffer: &mut Vec<(Identifier, Timestamp, f64)>, plan: &FlushPlan<'_>) {
+///     for (id, _time, value) in plan.drain(buffer, |x| (&x.0, x.1)) {
+///         // write the value
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlushPlan<'a> {
+    cutoff: Option<CdTime>,
+    identifier: Option<&'a str>,
+    pattern: Option<IdentifierPattern>,
+}
+
+impl<'a> FlushPlan<'a> {
+    /// Creates a plan from the arguments given to `Plugin::flush`
+    pub fn new(timeout: Option<Duration>, identifier: Option<&'a str>) -> Self {
+        FlushPlan::at(timeout, identifier, SystemTime::now())
+    }
+
+    fn at(timeout: Option<Duration>, identifier: Option<&'a str>, now: SystemTime) -> Self {
+        let CdTime(now) = CdTime::from(now);
+        FlushPlan {
+            cutoff: timeout.map(|x| {
+                let CdTime(timeout) = CdTime::from(x);
+                CdTime(now.saturating_sub(timeout))
+            }),
+            identifier,
+            pattern: identifier.and_then(|x| x.parse().ok()),
+        }
+    }
+
+    /// The parsed identifier of the flush request, if one was given and it could be parsed
+    pub fn pattern(&self) -> Option<&IdentifierPattern> {
+        self.pattern.as_ref()
+    }
+
+    /// Returns true if all entries are to be flushed regardless of their age and identifier
+    pub fn flushes_everything(&self) -> bool {
+        self.cutoff.is_none() && self.identifier.is_none()
+    }
+
+    /// Returns true if an entry of the identifier collected at the given time is to be flushed
+    pub fn matches(&self, id: &Identifier, time: Timestamp) -> bool {
+        let old_enough = match self.cutoff {
+            Some(CdTime(cutoff)) => {
+                let CdTime(time) = CdTime::from(time);
+                time <= cutoff
+            }
+            None => true,
+        };
+
+        old_enough
+            && match self.identifier {
+                Some(_) => matches!(self.pattern, Some(ref x) if x.matches(id)),
+                None => true,
+            }
+    }
+
+    /// Removes and returns the entries of the buffer that are to be flushed. The entries that are
+    /// kept retain their order.
+    pub fn drain<T, F>(&self, buffer: &mut Vec<T>, key: F) -> Vec<T>
+    where
+        F: Fn(&T) -> (&Identifier, Timestamp),
+    {
+        if self.flushes_everything() {
+            return std::mem::take(buffer);
+        }
+
+        let (flushed, kept) = buffer.drain(..).partition(|x| {
+            let (id, time) = key(x);
+            self.matches(id, time)
+        });
+
+        *buffer = kept;
+        flushed
+    }
+}
+
+/// Asks collectd to flush the named plugin (eg: `write_graphite` before maintenance), or every
+/// plugin that can flush when no plugin is given. Only values older than the timeout are flushed,
+/// and only those of the identifier if one is given (eg: `localhost/cpu-0/cpu-idle`), so `None`
+/// for both flushes everything. The flush callbacks run before this returns, so avoid asking for
+/// a flush of every plugin from the plugin's own flush callback. Returns false if collectd
+/// reported a failure or if the plugin or identifier contains a null character.
+pub fn request_flush(
+    plugin: Option<&str>,
+    timeout: Option<Duration>,
+    identifier: Option<&str>,
+) -> bool {
+    let plugin = match plugin.map(CString::new).transpose() {
+        Ok(x) => x,
+        Err(_) => return false,
+    };
+
+    let identifier = match identifier.map(CString::new).transpose() {
+        Ok(x) => x,
+        Err(_) => return false,
+    };
+
+    let as_ptr = |x: &Option<CString>| x.as_ref().map_or(ptr::null(), |x| x.as_ptr());
+
+    // A zero timeout asks for everything to be flushed
+    let timeout = timeout.map_or(0, |x| CdTime::from(x).into());
+    unsafe { plugin_flush(as_ptr(&plugin), timeout, as_ptr(&identifier)) == 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration as StdDuration;
+
+    fn ident(type_instance: &str) -> Identifier {
+        Identifier {
+            host: String::from("localhost"),
+            plugin: String::from("cpu"),
+            plugin_instance: Some(String::from("0")),
+            type_: String::from("cpu"),
+            type_instance: Some(String::from(type_instance)),
+        }
+    }
+
+    fn time(secs: u64) -> Timestamp {
+        Timestamp::from(CdTime(secs * 1_000_000_000))
+    }
+
+    fn now() -> SystemTime {
+        SystemTime::from(CdTime(100 * 1_000_000_000))
+    }
+
+    #[test]
+    fn test_flush_everything() {
+        let plan = FlushPlan::at(None, None, now());
+        assert!(plan.flushes_everything());
+        assert!(plan.matches(&ident("idle"), time(99)));
+
+        let mut buffer = vec![(ident("idle"), time(99)), (ident("user"), time(10))];
+        assert_eq!(2, plan.drain(&mut buffer, |x| (&x.0, x.1)).len());
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_flush_timeout_and_identifier() {
+        let timeout = Duration::from(CdTime::from(StdDuration::from_secs(30)));
+        let plan = FlushPlan::at(Some(timeout), Some("localhost/cpu-0/cpu-idle"), now());
+        assert!(!plan.flushes_everything());
+        assert!(plan.matches(&ident("idle"), time(70)));
+        assert!(!plan.matches(&ident("idle"), time(71)));
+        assert!(!plan.matches(&ident("user"), time(10)));
+
+        let mut buffer = vec![
+            (ident("idle"), time(10)),
+            (ident("user"), time(10)),
+            (ident("idle"), time(90)),
+        ];
+        let flushed = plan.drain(&mut buffer, |x| (&x.0, x.1));
+        assert_eq!(vec![(ident("idle"), time(10))], flushed);
+        assert_eq!(
+            vec![(ident("user"), time(10)), (ident("idle"), time(90))],
+            buffer
+        );
+    }
+
+    #[test]
+    fn test_flush_wildcard_identifier() {
+        let plan = FlushPlan::at(None, Some("/cpu-/cpu-"), now());
+        assert!(!plan.flushes_everything());
+        assert!(plan.matches(&ident("idle"), time(99)));
+        assert!(plan.matches(&ident("user"), time(10)));
+
+        let load: Identifier = "localhost/load/load".parse().unwrap();
+        assert!(!plan.matches(&load, time(10)));
+
+        let invalid = FlushPlan::at(None, Some("cpu"), now());
+        assert_eq!(None, invalid.pattern());
+        assert!(!invalid.matches(&ident("idle"), time(10)));
+    }
+
+    #[test]
+    fn test_request_flush() {
+        assert!(request_flush(None, None, None));
+        let timeout = Duration::from(CdTime(10 * 1_000_000_000));
+        assert!(request_flush(
+            Some("write_graphite"),
+            Some(timeout),
+            Some("localhost/cpu-0/cpu-idle")
+        ));
+        assert!(!request_flush(Some("write\0graphite"), None, None));
+        assert!(!request_flush(None, None, Some("local\0host/cpu/cpu")));
+    }
+}
@@ -0,0 +1,81 @@
+use super::{default_host, from_array};
+use crate::bindings::{global_option_get, ARR_LENGTH};
+use crate::errors::HostError;
+use std::ffi::CStr;
+
+/// The hostname that collectd attributes values to when a plugin doesn't set the host.
+///
+/// Collectd 5.8 changed `hostname_g` from an array to a pointer, so when built for the 5.7 family
+/// only the `Hostname` from collectd's configuration is known and `None` is returned if collectd
+/// determined the hostname itself. Older versions always have the hostname.
+pub fn hostname() -> Option<String> {
+    // The default host is left empty from collectd 5.7 on
+    let host = String::from(from_array(&default_host()).ok()?);
+    if !host.is_empty() {
+        return Some(host);
+    }
+
+    let host = unsafe { global_option_get(b"Hostname\0".as_ptr() as *const _) };
+    if host.is_null() {
+        return None;
+    }
+
+    let host = unsafe { CStr::from_ptr(host) }.to_str().ok()?;
+    Some(String::from(host)).filter(|x| !x.is_empty())
+}
+
+/// Checks that values can be attributed to the host, for plugins that submit values on behalf of
+/// other machines (see `ValueListBuilder::host`). The host must fit into collectd's identifiers
+/// and can't contain a slash (which separates the components of an identifier), whitespace, or
+/// control characters.
+///
+/// ```
+/// use collectd_plugin::validate_host;
+///
+/// assert!(validate_host("db-01.example.com").is_ok());
+/// assert!(validate_host("db 01").is_err());
+/// ```
+pub fn validate_host(host: &str) -> Result<(), HostError> {
+    if host.is_empty() {
+        return Err(HostError::Empty);
+    }
+
+    // Collectd's fields have room for a trailing null
+    if host.len() >= ARR_LENGTH {
+        return Err(HostError::TooLong(host.len()));
+    }
+
+    match host
+        .chars()
+        .find(|&c| c == '/' || c.is_whitespace() || c.is_control())
+    {
+        Some(c) => Err(HostError::Character(c)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hostname() {
+        // The stub of collectd has neither a hostname nor a configured one
+        assert_eq!(None, hostname());
+    }
+
+    #[test]
+    fn test_validate_host() {
+        assert_eq!(Ok(()), validate_host("localhost"));
+        assert_eq!(Ok(()), validate_host("10.0.0.1"));
+        assert_eq!(Ok(()), validate_host("fe80::1"));
+        assert_eq!(Err(HostError::Empty), validate_host(""));
+        assert_eq!(Err(HostError::Character('/')), validate_host("a/b"));
+        assert_eq!(Err(HostError::Character(' ')), validate_host("a b"));
+        assert_eq!(Err(HostError::Character('\0')), validate_host("a\0"));
+
+        let long = "a".repeat(ARR_LENGTH);
+        assert_eq!(Err(HostError::TooLong(ARR_LENGTH)), validate_host(&long));
+        assert_eq!(Ok(()), validate_host(&long[1..]));
+    }
+}
@@ -0,0 +1,291 @@
+use super::ValueList;
+use crate::errors::ParseIdentifierError;
+use std::fmt;
+use std::str::FromStr;
+
+/// Uniquely identifies a series of values reported to collectd. An identifier is written out
+/// in collectd's format of `host/plugin[-plugin_instance]/type[-type_instance]`
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Identifier {
+    /// The hostname where the values were collectd
+    pub host: String,
+
+    /// The plugin that submitted the values
+    pub plugin: String,
+
+    /// Distinguishes entities that yield metrics
+    pub plugin_instance: Option<String>,
+
+    /// The type found in types.db
+    pub type_: String,
+
+    /// Separates values of identical type which nonetheless belong to one another
+    pub type_instance: Option<String>,
+}
+
+impl<'a, 'b> From<&'b ValueList<'a>> for Identifier {
+    fn from(list: &'b ValueList<'a>) -> Self {
+        Identifier {
+            host: String::from(list.host),
+            plugin: String::from(list.plugin),
+            plugin_instance: list.plugin_instance.map(String::from),
+            type_: String::from(list.type_),
+            type_instance: list.type_instance.map(String::from),
+        }
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.host, self.plugin)?;
+        if let Some(ref instance) = self.plugin_instance {
+            write!(f, "-{}", instance)?;
+        }
+
+        write!(f, "/{}", self.type_)?;
+        if let Some(ref instance) = self.type_instance {
+            write!(f, "-{}", instance)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits a component into its name and the optional instance that follows the first dash
+fn split_instance(s: &str) -> (&str, Option<&str>) {
+    match s.find('-') {
+        Some(ind) => (&s[..ind], Some(&s[ind + 1..])),
+        None => (s, None),
+    }
+}
+
+impl FromStr for Identifier {
+    type Err = ParseIdentifierError;
+
+    /// Parses an identifier in collectd's format of `host/plugin[-plugin_instance]/type[-type_instance]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, '/');
+        let (host, plugin, type_) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(host), Some(plugin), Some(type_))
+                if !host.is_empty() && !plugin.is_empty() && !type_.is_empty() =>
+            {
+                (host, plugin, type_)
+            }
+            _ => return Err(ParseIdentifierError(String::from(s))),
+        };
+
+        let (plugin, plugin_instance) = split_instance(plugin);
+        let (type_, type_instance) = split_instance(type_);
+        Ok(Identifier {
+            host: String::from(host),
+            plugin: String::from(plugin),
+            plugin_instance: plugin_instance.map(String::from),
+            type_: String::from(type_),
+            type_instance: type_instance.map(String::from),
+        })
+    }
+}
+
+/// An identifier where each component may be left empty to match any value, as found in
+/// collectd's flush requests (eg: `FLUSH identifier="/cpu-/"` from the unixsock plugin flushes all
+/// the values of the cpu plugin). A component without an instance only matches identifiers that
+/// also lack the instance, while a trailing dash (eg: `cpu-`) matches any instance.
+///
+/// ```
+/// use collectd_plugin::{Identifier, IdentifierPattern};
+///
+/// let pattern: IdentifierPattern = "/cpu-/".parse().unwrap();
+/// let id: Identifier = "localhost/cpu-0/cpu-idle".parse().unwrap();
+/// assert!(pattern.matches(&id));
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+pub struct IdentifierPattern {
+    /// The hostname to match, if any
+    pub host: Option<String>,
+
+    /// The plugin to match, if any
+    pub plugin: Option<String>,
+
+    /// The plugin instance to match. `Some(None)` only matches identifiers without a plugin
+    /// instance.
+    pub plugin_instance: Option<Option<String>>,
+
+    /// The type to match, if any
+    pub type_: Option<String>,
+
+    /// The type instance to match. `Some(None)` only matches identifiers without a type instance.
+    pub type_instance: Option<Option<String>>,
+}
+
+impl IdentifierPattern {
+    /// Returns true if the identifier matches all the components that are not wildcards
+    pub fn matches(&self, id: &Identifier) -> bool {
+        fn component(pattern: &Option<String>, value: &str) -> bool {
+            match *pattern {
+                Some(ref x) => x == value,
+                None => true,
+            }
+        }
+
+        fn instance(pattern: &Option<Option<String>>, value: &Option<String>) -> bool {
+            match *pattern {
+                Some(ref x) => x == value,
+                None => true,
+            }
+        }
+
+        component(&self.host, &id.host)
+            && component(&self.plugin, &id.plugin)
+            && instance(&self.plugin_instance, &id.plugin_instance)
+            && component(&self.type_, &id.type_)
+            && instance(&self.type_instance, &id.type_instance)
+    }
+
+    /// Returns true if the pattern matches all identifiers
+    pub fn is_wildcard(&self) -> bool {
+        *self == IdentifierPattern::default()
+    }
+}
+
+impl From<Identifier> for IdentifierPattern {
+    fn from(id: Identifier) -> Self {
+        IdentifierPattern {
+            host: Some(id.host),
+            plugin: Some(id.plugin),
+            plugin_instance: Some(id.plugin_instance),
+            type_: Some(id.type_),
+            type_instance: Some(id.type_instance),
+        }
+    }
+}
+
+impl fmt::Display for IdentifierPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn component(
+            f: &mut fmt::Formatter<'_>,
+            name: &Option<String>,
+            instance: &Option<Option<String>>,
+        ) -> fmt::Result {
+            f.write_str(name.as_deref().unwrap_or(""))?;
+            match *instance {
+                Some(Some(ref x)) => write!(f, "-{}", x),
+                None if name.is_some() => f.write_str("-"),
+                _ => Ok(()),
+            }
+        }
+
+        write!(f, "{}/", self.host.as_deref().unwrap_or(""))?;
+        component(f, &self.plugin, &self.plugin_instance)?;
+        f.write_str("/")?;
+        component(f, &self.type_, &self.type_instance)
+    }
+}
+
+impl FromStr for IdentifierPattern {
+    type Err = ParseIdentifierError;
+
+    /// Parses a pattern in collectd's identifier format where any component may be empty
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn non_empty(s: &str) -> Option<String> {
+            Some(s).filter(|x| !x.is_empty()).map(String::from)
+        }
+
+        let mut parts = s.splitn(3, '/');
+        let (host, plugin, type_) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(host), Some(plugin), Some(type_)) => (host, plugin, type_),
+            _ => return Err(ParseIdentifierError(String::from(s))),
+        };
+
+        // An empty component without an instance, like the type in `localhost/cpu-0/`, matches
+        // any instance as well
+        let instance = |name: &str, instance: Option<&str>| match instance {
+            _ if name.is_empty() && instance.is_none() => None,
+            Some(x) => non_empty(x).map(Some),
+            None => Some(None),
+        };
+
+        let (plugin, plugin_instance) = split_instance(plugin);
+        let (type_, type_instance) = split_instance(type_);
+        Ok(IdentifierPattern {
+            host: non_empty(host),
+            plugin: non_empty(plugin),
+            plugin_instance: instance(plugin, plugin_instance),
+            type_: non_empty(type_),
+            type_instance: instance(type_, type_instance),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identifier_display() {
+        let mut id = Identifier {
+            host: String::from("localhost"),
+            plugin: String::from("cpu"),
+            plugin_instance: Some(String::from("0")),
+            type_: String::from("cpu"),
+            type_instance: Some(String::from("idle")),
+        };
+        assert_eq!("localhost/cpu-0/cpu-idle", id.to_string());
+
+        id.plugin_instance = None;
+        id.type_instance = None;
+        assert_eq!("localhost/cpu/cpu", id.to_string());
+    }
+
+    #[test]
+    fn test_identifier_from_str() {
+        let id: Identifier = "localhost/cpu-0/cpu-idle-percent".parse().unwrap();
+        assert_eq!("cpu", id.plugin);
+        assert_eq!(Some(String::from("0")), id.plugin_instance);
+        assert_eq!(Some(String::from("idle-percent")), id.type_instance);
+        assert_eq!("localhost/cpu-0/cpu-idle-percent", id.to_string());
+
+        let id: Identifier = "localhost/load/load".parse().unwrap();
+        assert_eq!(None, id.plugin_instance);
+        assert_eq!(None, id.type_instance);
+
+        assert!("localhost/load".parse::<Identifier>().is_err());
+        assert!("localhost//load".parse::<Identifier>().is_err());
+    }
+
+    #[test]
+    fn test_identifier_pattern() {
+        let id: Identifier = "localhost/cpu-0/cpu-idle".parse().unwrap();
+        let bare: Identifier = "localhost/load/load".parse().unwrap();
+
+        let exact: IdentifierPattern = "localhost/cpu-0/cpu-idle".parse().unwrap();
+        assert_eq!(IdentifierPattern::from(id.clone()), exact);
+        assert!(exact.matches(&id));
+        assert!(!exact.matches(&bare));
+
+        let any: IdentifierPattern = "//".parse().unwrap();
+        assert!(any.is_wildcard());
+        assert!(any.matches(&id) && any.matches(&bare));
+
+        let plugin: IdentifierPattern = "/cpu-/".parse().unwrap();
+        assert_eq!(Some(String::from("cpu")), plugin.plugin);
+        assert_eq!(None, plugin.plugin_instance);
+        assert!(plugin.matches(&id));
+        assert!(!plugin.matches(&bare));
+
+        // Without a trailing dash, only identifiers without an instance match
+        let load: IdentifierPattern = "/load/load".parse().unwrap();
+        assert!(load.matches(&bare));
+        let cpu: IdentifierPattern = "/cpu/cpu-idle".parse().unwrap();
+        assert!(!cpu.matches(&id));
+
+        let instance: IdentifierPattern = "localhost/-0/-idle".parse().unwrap();
+        assert!(instance.matches(&id));
+        assert!(!instance.matches(&bare));
+
+        for s in &["//", "/cpu-/", "localhost/-0/-idle", "localhost/load/load"] {
+            assert_eq!(*s, s.parse::<IdentifierPattern>().unwrap().to_string());
+        }
+
+        assert!("localhost/cpu".parse::<IdentifierPattern>().is_err());
+    }
+}
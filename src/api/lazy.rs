@@ -0,0 +1,198 @@
+use super::{collectd_log, LogLevel};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The callback that performs the deferred initialization
+type Initializer<T> = dyn Fn() -> Result<T, Box<dyn Error>> + Send + Sync;
+
+struct LazyState<T> {
+    value: Option<Arc<T>>,
+    failures: u32,
+    next_attempt: Option<Instant>,
+}
+
+/// Defers expensive initialization (eg: connecting to a database or discovering devices) until
+/// the first read or write callback asks for it. `PluginManager::plugins` is invoked early in
+/// collectd's startup, when some resources (like the network) may not be ready yet.
+///
+/// A failed initialization is logged and retried on a later callback, backing off exponentially
+/// from one second up to five minutes between attempts. While the value is unavailable, `get`
+/// returns `None` and the callback can skip its work for the interval.
+///
+/// ```
+/// use collectd_plugin::LazyInit;
+/// use std::error::Error;
+///
+/// struct Connection;
+///
+/// fn connect() -> Result<Connection, Box<dyn Error>> {
+///     // connect to the database
+///     Ok(Connection)
+/// }
+///
+/// let conn = LazyInit::new("database connection", connect);
+///
+/// fn read(conn: &LazyInit<Connection>) -> Result<(), Box<dyn Error>> {
+///     let conn = match conn.get() {
+///         Some(conn) => conn,
+///         None => return Ok(()),
+///     };
+///
+///     // query the database
+///     Ok(())
+/// }
+/// ```
+pub struct LazyInit<T> {
+    name: String,
+    init: Box<Initializer<T>>,
+    min_retry: Duration,
+    max_retry: Duration,
+    state: Mutex<LazyState<T>>,
+}
+
+impl<T> LazyInit<T> {
+    /// Creates an uninitialized value. The name is used to identify the value in logs.
+    pub fn new<F>(name: &str, init: F) -> Self
+    where
+        F: Fn() -> Result<T, Box<dyn Error>> + Send + Sync + 'static,
+    {
+        LazyInit {
+            name: String::from(name),
+            init: Box::new(init),
+            min_retry: Duration::from_secs(1),
+            max_retry: Duration::from_secs(300),
+            state: Mutex::new(LazyState {
+                value: None,
+                failures: 0,
+                next_attempt: None,
+            }),
+        }
+    }
+
+    /// The delay after the first failed attempt, which doubles with each subsequent failure up
+    /// to the maximum
+    pub fn retry(mut self, min: Duration, max: Duration) -> Self {
+        self.min_retry = min;
+        self.max_retry = max.max(min);
+        self
+    }
+
+    /// Returns the value, initializing it if this is the first call or a retry is due. Returns
+    /// `None` if the value is unavailable.
+    pub fn get(&self) -> Option<Arc<T>> {
+        self.get_at(Instant::now())
+    }
+
+    fn get_at(&self, now: Instant) -> Option<Arc<T>> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(ref value) = state.value {
+            return Some(Arc::clone(value));
+        }
+
+        if matches!(state.next_attempt, Some(x) if now < x) {
+            return None;
+        }
+
+        match (self.init)() {
+            Ok(value) => {
+                if state.failures > 0 {
+                    let msg = format!(
+                        "{}: initialized after {} failed attempt(s)",
+                        self.name, state.failures
+                    );
+                    collectd_log(LogLevel::Info, &msg);
+                }
+
+                let value = Arc::new(value);
+                state.value = Some(Arc::clone(&value));
+                state.failures = 0;
+                state.next_attempt = None;
+                Some(value)
+            }
+            Err(e) => {
+                let delay = self.backoff(state.failures);
+                state.failures = state.failures.saturating_add(1);
+                state.next_attempt = Some(now + delay);
+                let msg = format!(
+                    "{}: unable to initialize (retrying in {}s): {}",
+                    self.name,
+                    delay.as_secs_f64(),
+                    e
+                );
+                collectd_log(LogLevel::Warning, &msg);
+                None
+            }
+        }
+    }
+
+    fn backoff(&self, failures: u32) -> Duration {
+        let factor = 1u32.checked_shl(failures.min(31)).unwrap_or(u32::MAX);
+        self.min_retry
+            .checked_mul(factor)
+            .map_or(self.max_retry, |x| x.min(self.max_retry))
+    }
+
+    /// Returns true if the value has been initialized
+    pub fn is_initialized(&self) -> bool {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.value.is_some()
+    }
+
+    /// Discards the value so that it is initialized again by the next call to `get` (eg: when a
+    /// connection has been lost). Callers still holding the previous value are unaffected.
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.value = None;
+        state.next_attempt = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_lazy_init_retries_with_backoff() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&attempts);
+        let lazy = LazyInit::new("test", move || {
+            match counter.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err("unavailable")?,
+                x => Ok(x),
+            }
+        });
+
+        let start = Instant::now();
+        assert_eq!(None, lazy.get_at(start));
+
+        // Not retried until the backoff of a second has elapsed
+        assert_eq!(None, lazy.get_at(start + Duration::from_millis(500)));
+        assert_eq!(1, attempts.load(Ordering::SeqCst));
+
+        // The second failure doubles the backoff
+        let second = start + Duration::from_secs(1);
+        assert_eq!(None, lazy.get_at(second));
+        assert_eq!(None, lazy.get_at(second + Duration::from_secs(1)));
+        assert!(!lazy.is_initialized());
+
+        let third = second + Duration::from_secs(2);
+        assert_eq!(Some(Arc::new(2)), lazy.get_at(third));
+        assert_eq!(Some(Arc::new(2)), lazy.get_at(third));
+        assert_eq!(3, attempts.load(Ordering::SeqCst));
+
+        lazy.reset();
+        assert_eq!(Some(Arc::new(3)), lazy.get_at(third));
+    }
+
+    #[test]
+    fn test_lazy_init_backoff_is_capped() {
+        let lazy =
+            LazyInit::new("test", || Ok(())).retry(Duration::from_secs(2), Duration::from_secs(60));
+        assert_eq!(Duration::from_secs(2), lazy.backoff(0));
+        assert_eq!(Duration::from_secs(16), lazy.backoff(3));
+        assert_eq!(Duration::from_secs(60), lazy.backoff(5));
+        assert_eq!(Duration::from_secs(60), lazy.backoff(u32::MAX));
+    }
+}
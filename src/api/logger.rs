@@ -1,18 +1,24 @@
 use crate::bindings::{plugin_log, LOG_DEBUG, LOG_ERR, LOG_INFO, LOG_NOTICE, LOG_WARNING};
-use crate::errors::FfiError;
+use crate::errors::{FfiError, UnknownConstant};
 use crate::plugins::PluginManager;
+#[cfg(feature = "logger-filters")]
 use env_logger::filter;
+use lazy_static::lazy_static;
 use log::{self, error, log_enabled, Level, LevelFilter, Metadata, Record, SetLoggerError};
 use std::cell::Cell;
+use std::convert::TryFrom;
+use std::env;
 use std::error::Error;
 use std::ffi::{CStr, CString};
 use std::fmt::Write as FmtWrite;
 use std::io::{self, Write};
 use std::mem;
+use std::sync::{Arc, Mutex, RwLock};
 use strum_macros::{AsRefStr, EnumIter};
 
 /// Bridges the gap between collectd and rust logging. Terminology and filters methods found here
-/// are from env_logger.
+/// are from env_logger. Without the `logger-filters` feature, only the level is filtered on and
+/// the module filters are unavailable.
 ///
 /// It is recommended to instantiate the logger in `PluginManager::plugins`.
 ///
@@ -50,9 +56,15 @@ use strum_macros::{AsRefStr, EnumIter};
 pub struct CollectdLoggerBuilder {
     filter: filter::Builder,
     plugin: Option<&'static str>,
+    instance: bool,
     format: Format,
 }
 
+lazy_static! {
+    // The filter of the installed logger, kept so that it can be swapped at runtime
+    static ref LOGGER_FILTER: Mutex<Option<Arc<RwLock<filter::Filter>>>> = Mutex::new(None);
+}
+
 type FormatFn = dyn Fn(&mut dyn Write, &Record<'_>) -> io::Result<()> + Sync + Send;
 
 impl CollectdLoggerBuilder {
@@ -68,14 +80,18 @@ impl CollectdLoggerBuilder {
     /// This function will fail if it is called more than once, or if another
     /// library has already initialized a global logger.
     pub fn try_init(&mut self) -> Result<(), SetLoggerError> {
+        let filter = Arc::new(RwLock::new(self.filter.build()));
         let logger = CollectdLogger {
-            filter: self.filter.build(),
+            filter: Arc::clone(&filter),
             plugin: self.plugin,
+            instance: self.instance,
             format: mem::replace(&mut self.format, Default::default()).into_boxed_fn(),
         };
 
         log::set_max_level(logger.filter());
-        log::set_boxed_logger(Box::new(logger))
+        log::set_boxed_logger(Box::new(logger))?;
+        *LOGGER_FILTER.lock().unwrap_or_else(|e| e.into_inner()) = Some(filter);
+        Ok(())
     }
 
     /// Prefixes all log messages with a plugin's name. This is recommended to aid debugging and
@@ -85,6 +101,15 @@ impl CollectdLoggerBuilder {
         self
     }
 
+    /// Prefixes the log messages emitted during a callback of a plugin instance with the name
+    /// of the instance (eg: `myplugin/east`) instead of the plugin's name, so that the messages of
+    /// plugins with multiple instances can be told apart. Messages emitted outside of a callback
+    /// keep the prefix from `prefix_plugin`.
+    pub fn prefix_instance(&mut self) -> &mut Self {
+        self.instance = true;
+        self
+    }
+
     /// See [`env_logger::filter::Builder::filter_level`](https://docs.rs/env_logger/0.7.1/env_logger/filter/struct.Builder.html#method.filter_level)
     pub fn filter_level(&mut self, level: LevelFilter) -> &mut Self {
         self.filter.filter_level(level);
@@ -92,23 +117,46 @@ impl CollectdLoggerBuilder {
     }
 
     /// See: [`env_logger::filter::Builder::filter_module`](https://docs.rs/env_logger/0.7.1/env_logger/filter/struct.Builder.html#method.filter_module)
+    #[cfg(feature = "logger-filters")]
     pub fn filter_module(&mut self, module: &str, level: LevelFilter) -> &mut Self {
         self.filter.filter_module(module, level);
         self
     }
 
     /// See: [`env_logger::filter::Builder::filter`](https://docs.rs/env_logger/0.7.1/env_logger/filter/struct.Builder.html#method.filter)
+    #[cfg(feature = "logger-filters")]
     pub fn filter(&mut self, module: Option<&str>, level: LevelFilter) -> &mut Self {
         self.filter.filter(module, level);
         self
     }
 
+    /// Parses filters in the same syntax as `RUST_LOG` (eg: `info,mymodule=debug/pattern`), so
+    /// existing logging configuration carries over unchanged. The optional `/pattern` suffix only
+    /// keeps messages that match the pattern: a regular expression when the `regex_log_filter`
+    /// feature is enabled, else a substring. Without the `logger-filters` feature, only the
+    /// directives that consist of a level are used (eg: `info` of `info,mymodule=debug`).
+    ///
     /// See: [`env_logger::filter::Builder::parse`](https://docs.rs/env_logger/0.7.1/env_logger/filter/struct.Builder.html#method.parse)
     pub fn parse(&mut self, filters: &str) -> &mut Self {
         self.filter.parse(filters);
         self
     }
 
+    /// Parses the filters found in the environment variable, if it is set, with the same syntax
+    /// as `parse`. Filters added afterwards take precedence.
+    pub fn parse_env(&mut self, env: &str) -> &mut Self {
+        if let Ok(filters) = env::var(env) {
+            self.filter.parse(&filters);
+        }
+        self
+    }
+
+    /// Parses the filters found in the `RUST_LOG` environment variable of the collectd process,
+    /// if it is set
+    pub fn parse_default_env(&mut self) -> &mut Self {
+        self.parse_env("RUST_LOG")
+    }
+
     /// Sets the format function for formatting the log output.
     pub fn format<F: 'static>(&mut self, format: F) -> &mut Self
     where
@@ -119,6 +167,65 @@ impl CollectdLoggerBuilder {
     }
 }
 
+/// A stand-in for env_logger's filter that only filters on the level, for builds without the
+/// `logger-filters` feature
+#[cfg(not(feature = "logger-filters"))]
+mod filter {
+    use log::{LevelFilter, Metadata, Record};
+
+    #[derive(Default)]
+    pub struct Builder {
+        level: Option<LevelFilter>,
+    }
+
+    impl Builder {
+        pub fn new() -> Self {
+            Default::default()
+        }
+
+        pub fn filter_level(&mut self, level: LevelFilter) -> &mut Self {
+            self.level = Some(level);
+            self
+        }
+
+        /// Keeps the last directive that is a level, ignoring module directives and patterns
+        pub fn parse(&mut self, filters: &str) -> &mut Self {
+            let directives = filters.split('/').next().unwrap_or("");
+            for directive in directives.split(',').map(str::trim) {
+                if let Ok(level) = directive.parse() {
+                    self.level = Some(level);
+                }
+            }
+            self
+        }
+
+        /// Like env_logger, only errors are logged if no level has been given
+        pub fn build(&mut self) -> Filter {
+            Filter {
+                level: self.level.unwrap_or(LevelFilter::Error),
+            }
+        }
+    }
+
+    pub struct Filter {
+        level: LevelFilter,
+    }
+
+    impl Filter {
+        pub fn filter(&self) -> LevelFilter {
+            self.level
+        }
+
+        pub fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.level() <= self.level
+        }
+
+        pub fn matches(&self, record: &Record<'_>) -> bool {
+            self.enabled(record.metadata())
+        }
+    }
+}
+
 #[derive(Default)]
 struct Format {
     custom_format: Option<Box<FormatFn>>,
@@ -140,15 +247,49 @@ impl Format {
     }
 }
 
+thread_local! {
+    // Name of the plugin instance whose callback is running on this thread. The name outlives the
+    // callback, which is the only time that it is set.
+    static INSTANCE: Cell<Option<*const str>> = const { Cell::new(None) };
+}
+
+/// Runs the callback of a plugin instance, so that the messages that it logs can be attributed to
+/// the instance
+pub(crate) fn with_instance<R, F: FnOnce() -> R>(name: &str, f: F) -> R {
+    struct Restore(Option<*const str>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            INSTANCE.with(|x| x.set(self.0));
+        }
+    }
+
+    let _restore = Restore(INSTANCE.with(|x| x.replace(Some(name as *const str))));
+    f()
+}
+
+fn write_instance(buf: &mut Vec<u8>) -> bool {
+    INSTANCE.with(|x| match x.get() {
+        Some(name) => {
+            let _ = write!(buf, "{}: ", unsafe { &*name });
+            true
+        }
+        None => false,
+    })
+}
+
 struct CollectdLogger {
-    filter: filter::Filter,
+    filter: Arc<RwLock<filter::Filter>>,
     plugin: Option<&'static str>,
+    instance: bool,
     format: Box<FormatFn>,
 }
 
 impl log::Log for CollectdLogger {
     fn enabled(&self, metadata: &Metadata<'_>) -> bool {
-        self.filter.enabled(metadata)
+        self.filter
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .enabled(metadata)
     }
 
     fn log(&self, record: &Record<'_>) {
@@ -160,10 +301,12 @@ impl log::Log for CollectdLogger {
                 // Replaces the cell's contents with the default value, which is an empty vector.
                 // Should be very cheap to move in and out of
                 let mut write_buffer = cell.take();
-                if let Some(plugin) = self.plugin {
-                    // writing the formatting to the vec shouldn't fail unless we ran out of
-                    // memory, but in that case, we have a host of other problems.
-                    let _ = write!(write_buffer, "{}: ", plugin);
+                if !(self.instance && write_instance(&mut write_buffer)) {
+                    if let Some(plugin) = self.plugin {
+                        // writing the formatting to the vec shouldn't fail unless we ran out of
+                        // memory, but in that case, we have a host of other problems.
+                        let _ = write!(write_buffer, "{}: ", plugin);
+                    }
                 }
 
                 if (self.format)(&mut write_buffer, record).is_ok() {
@@ -189,13 +332,37 @@ impl log::Log for CollectdLogger {
 impl CollectdLogger {
     /// Checks if this record matches the configured filter.
     pub fn matches(&self, record: &Record<'_>) -> bool {
-        self.filter.matches(record)
+        self.filter
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .matches(record)
     }
 
     /// Returns the maximum `LevelFilter` that this env logger instance is
     /// configured to output.
     pub fn filter(&self) -> LevelFilter {
-        self.filter.filter()
+        self.filter
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .filter()
+    }
+}
+
+/// Replaces the filter of the logger installed through [`CollectdLoggerBuilder`] with one parsed
+/// from the env_logger filter syntax (eg: `info,mymodule=debug`). Returns false if no logger has
+/// been installed.
+///
+/// [`CollectdLoggerBuilder`]: struct.CollectdLoggerBuilder.html
+pub fn reload_log_filter(filters: &str) -> bool {
+    let installed = LOGGER_FILTER.lock().unwrap_or_else(|e| e.into_inner());
+    match *installed {
+        Some(ref filter) => {
+            let new_filter = filter::Builder::new().parse(filters).build();
+            log::set_max_level(new_filter.filter());
+            *filter.write().unwrap_or_else(|e| e.into_inner()) = new_filter;
+            true
+        }
+        None => false,
     }
 }
 
@@ -292,6 +459,36 @@ macro_rules! collectd_log_raw {
     });
 }
 
+/// Logs a debug message through the logger installed by `CollectdLoggerBuilder`. The level is
+/// checked against an atomic before the format arguments are evaluated, so debug logging in a
+/// per-value loop costs next to nothing when debug messages are filtered out. When no logger has
+/// been installed, the message is discarded. Plugins need not depend on the `log` crate.
+///
+/// ```
+/// use collectd_plugin::collectd_debug;
+///
+/// for value in &[1.0, 2.5] {
+///     collectd_debug!("submitting value: {}", value);
+/// }
+/// ```
+#[macro_export]
+macro_rules! collectd_debug {
+    (target: $target:expr, $($arg:tt)+) => (
+        $crate::__log::log!(target: $target, $crate::__log::Level::Debug, $($arg)+)
+    );
+    ($($arg:tt)+) => ($crate::__log::log!($crate::__log::Level::Debug, $($arg)+));
+}
+
+/// Logs a trace message like `collectd_debug!`. Collectd has no trace level, so trace messages
+/// that pass the logger's filter are written to collectd as debug messages.
+#[macro_export]
+macro_rules! collectd_trace {
+    (target: $target:expr, $($arg:tt)+) => (
+        $crate::__log::log!(target: $target, $crate::__log::Level::Trace, $($arg)+)
+    );
+    ($($arg:tt)+) => ($crate::__log::log!($crate::__log::Level::Trace, $($arg)+));
+}
+
 /// The available levels that collectd exposes to log messages.
 #[derive(AsRefStr, EnumIter, Debug, PartialEq, Eq, Clone, Copy, Hash)]
 #[strum(serialize_all = "lowercase")]
@@ -304,16 +501,50 @@ pub enum LogLevel {
     Debug = LOG_DEBUG,
 }
 
+impl LogLevel {
+    /// The syslog severity of the level. Collectd's levels share their numbers with syslog.
+    pub fn syslog_severity(self) -> u8 {
+        self as u8
+    }
+
+    /// The level of a syslog severity, where the severities that are more severe than an error
+    /// (emergency, alert, and critical) are logged as errors. Returns `None` for a number that
+    /// isn't a syslog severity.
+    pub fn from_syslog_severity(severity: u8) -> Option<LogLevel> {
+        match severity {
+            0..=3 => Some(LogLevel::Error),
+            4 => Some(LogLevel::Warning),
+            5 => Some(LogLevel::Notice),
+            6 => Some(LogLevel::Info),
+            7 => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+}
+
 impl LogLevel {
     /// Attempts to convert a u32 representing a collectd logging level into a Rust enum
+    #[deprecated(since = "0.14.0", note = "use the TryFrom<u32> implementation")]
     pub fn try_from(s: u32) -> Option<LogLevel> {
+        <LogLevel as TryFrom<u32>>::try_from(s).ok()
+    }
+}
+
+impl TryFrom<u32> for LogLevel {
+    type Error = UnknownConstant;
+
+    /// Attempts to convert a u32 representing a collectd logging level into a Rust enum
+    fn try_from(s: u32) -> Result<Self, UnknownConstant> {
         match s {
-            LOG_ERR => Some(LogLevel::Error),
-            LOG_WARNING => Some(LogLevel::Warning),
-            LOG_NOTICE => Some(LogLevel::Notice),
-            LOG_INFO => Some(LogLevel::Info),
-            LOG_DEBUG => Some(LogLevel::Debug),
-            _ => None,
+            LOG_ERR => Ok(LogLevel::Error),
+            LOG_WARNING => Ok(LogLevel::Warning),
+            LOG_NOTICE => Ok(LogLevel::Notice),
+            LOG_INFO => Ok(LogLevel::Info),
+            LOG_DEBUG => Ok(LogLevel::Debug),
+            value => Err(UnknownConstant {
+                kind: "log level",
+                value,
+            }),
         }
     }
 }
@@ -328,3 +559,86 @@ impl From<Level> for LogLevel {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn test_syslog_severity() {
+        assert_eq!(3, LogLevel::Error.syslog_severity());
+        assert_eq!(7, LogLevel::Debug.syslog_severity());
+        assert_eq!(Some(LogLevel::Error), LogLevel::from_syslog_severity(0));
+        assert_eq!(Some(LogLevel::Notice), LogLevel::from_syslog_severity(5));
+        assert_eq!(None, LogLevel::from_syslog_severity(8));
+
+        for lvl in LogLevel::iter() {
+            assert_eq!(
+                Some(lvl),
+                LogLevel::from_syslog_severity(lvl.syslog_severity())
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "logger-filters")]
+    fn test_parse_env() {
+        env::set_var("COLLECTD_PLUGIN_TEST_LOG", "warn,mymodule=debug/timeout");
+        let mut builder = CollectdLoggerBuilder::new();
+        builder
+            .parse_env("COLLECTD_PLUGIN_TEST_LOG")
+            .parse_env("COLLECTD_PLUGIN_TEST_LOG_UNSET");
+        assert_eq!(LevelFilter::Debug, builder.filter.build().filter());
+
+        let mut builder = CollectdLoggerBuilder::new();
+        builder.parse_env("COLLECTD_PLUGIN_TEST_LOG_UNSET");
+        assert_eq!(LevelFilter::Error, builder.filter.build().filter());
+    }
+
+    #[test]
+    #[cfg(not(feature = "logger-filters"))]
+    fn test_parse_level_only() {
+        let mut builder = CollectdLoggerBuilder::new();
+        builder.parse("warn,mymodule=debug/timeout");
+        assert_eq!(LevelFilter::Warn, builder.filter.build().filter());
+
+        builder
+            .parse("mymodule=trace")
+            .filter_level(LevelFilter::Info);
+        assert_eq!(LevelFilter::Info, builder.filter.build().filter());
+
+        let mut builder = CollectdLoggerBuilder::new();
+        builder.parse("mymodule");
+        assert_eq!(LevelFilter::Error, builder.filter.build().filter());
+    }
+
+    #[test]
+    fn test_with_instance() {
+        let mut buf = Vec::new();
+        assert!(!write_instance(&mut buf));
+
+        with_instance("myplugin/east", || {
+            with_instance("myplugin/west", || assert!(write_instance(&mut buf)));
+            assert!(write_instance(&mut buf));
+        });
+
+        assert!(!write_instance(&mut buf));
+        assert_eq!(&b"myplugin/west: myplugin/east: "[..], &buf[..]);
+    }
+
+    #[test]
+    fn test_disabled_macros_skip_formatting() {
+        // No logger is installed in tests, so everything is filtered
+        let mut evaluated = 0;
+        collectd_debug!("{}", {
+            evaluated += 1;
+            evaluated
+        });
+        collectd_trace!(target: "mymodule", "{}", {
+            evaluated += 1;
+            evaluated
+        });
+        assert_eq!(0, evaluated);
+    }
+}
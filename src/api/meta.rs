@@ -0,0 +1,245 @@
+use crate::bindings::{
+    meta_data_add_boolean, meta_data_add_double, meta_data_add_signed_int, meta_data_add_string,
+    meta_data_add_unsigned_int, meta_data_create, meta_data_destroy, meta_data_get_boolean,
+    meta_data_get_double, meta_data_get_signed_int, meta_data_get_string,
+    meta_data_get_unsigned_int, meta_data_t, meta_data_toc, meta_data_type, MD_TYPE_BOOLEAN,
+    MD_TYPE_DOUBLE, MD_TYPE_SIGNED_INT, MD_TYPE_STRING, MD_TYPE_UNSIGNED_INT,
+};
+use crate::errors::{ArrayError, SubmitError};
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::str::Utf8Error;
+
+extern "C" {
+    // Strings handed out by collectd's meta data API are allocated with malloc and it is up to the
+    // caller to free them
+    fn free(p: *mut c_void);
+}
+
+/// A value of the meta data that is attached to a value list. Meta data carries context about
+/// the values that isn't part of their identifier.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetaValue {
+    String(String),
+    SignedInt(i64),
+    UnsignedInt(u64),
+    Double(f64),
+    Boolean(bool),
+}
+
+impl MetaValue {
+    /// The string, if the value is one
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            MetaValue::String(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The value as a signed integer, if it is an integer that fits
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            MetaValue::SignedInt(x) => Some(x),
+            MetaValue::UnsignedInt(x) => i64::try_from(x).ok(),
+            _ => None,
+        }
+    }
+
+    /// The value as an unsigned integer, if it is an integer that fits
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            MetaValue::SignedInt(x) => u64::try_from(x).ok(),
+            MetaValue::UnsignedInt(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// The value as a double, if it is numeric
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            MetaValue::SignedInt(x) => Some(x as f64),
+            MetaValue::UnsignedInt(x) => Some(x as f64),
+            MetaValue::Double(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// The boolean, if the value is one
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            MetaValue::Boolean(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+/// Meta data that has been converted for submission and is destroyed when dropped
+pub struct MetaData(*mut meta_data_t);
+
+impl MetaData {
+    /// Converts the key value pairs into collectd's meta data
+    pub fn new(entries: &[(String, MetaValue)]) -> Result<MetaData, SubmitError> {
+        let md = MetaData(unsafe { meta_data_create() });
+        if md.0.is_null() {
+            return Err(SubmitError::Meta(String::new()));
+        }
+
+        for (key, value) in entries {
+            let k = to_cstring(key)?;
+            let res = unsafe {
+                match *value {
+                    MetaValue::String(ref s) => {
+                        meta_data_add_string(md.0, k.as_ptr(), to_cstring(s)?.as_ptr())
+                    }
+                    MetaValue::SignedInt(x) => meta_data_add_signed_int(md.0, k.as_ptr(), x),
+                    MetaValue::UnsignedInt(x) => meta_data_add_unsigned_int(md.0, k.as_ptr(), x),
+                    MetaValue::Double(x) => meta_data_add_double(md.0, k.as_ptr(), x),
+                    MetaValue::Boolean(x) => meta_data_add_boolean(md.0, k.as_ptr(), x),
+                }
+            };
+
+            if res != 0 {
+                return Err(SubmitError::Meta(key.clone()));
+            }
+        }
+
+        Ok(md)
+    }
+
+    pub fn as_mut_ptr(&self) -> *mut meta_data_t {
+        self.0
+    }
+}
+
+impl Drop for MetaData {
+    fn drop(&mut self) {
+        unsafe { meta_data_destroy(self.0) }
+    }
+}
+
+pub fn to_cstring(s: &str) -> Result<CString, SubmitError> {
+    CString::new(s).map_err(|e| {
+        SubmitError::Field(
+            "meta",
+            ArrayError::NullPresent(e.nul_position(), String::from(s)),
+        )
+    })
+}
+
+/// Takes ownership of a string allocated by collectd
+unsafe fn take_string(s: *mut c_char) -> Result<String, Utf8Error> {
+    let res = CStr::from_ptr(s).to_str().map(String::from);
+    free(s as *mut c_void);
+    res
+}
+
+/// Reads the entries of the meta data. Entries of an unknown type are skipped.
+pub fn read_meta(md: *mut meta_data_t) -> Result<Vec<(String, MetaValue)>, Utf8Error> {
+    if md.is_null() {
+        return Ok(Vec::new());
+    }
+
+    let mut toc: *mut *mut c_char = ptr::null_mut();
+    let len = unsafe { meta_data_toc(md, &mut toc) };
+    if len <= 0 || toc.is_null() {
+        return Ok(Vec::new());
+    }
+
+    // Every key needs to be freed, so the first error is held onto until all have been visited
+    let mut result = Ok(Vec::with_capacity(len as usize));
+    for i in 0..len as usize {
+        let key = unsafe { *toc.add(i) };
+        let entry = unsafe { read_entry(md, key) };
+        let name = unsafe { take_string(key) };
+        if let Ok(ref mut entries) = result {
+            match (name, entry) {
+                (Ok(name), Ok(Some(value))) => entries.push((name, value)),
+                (Ok(_), Ok(None)) => {}
+                (Err(e), _) | (_, Err(e)) => result = Err(e),
+            }
+        }
+    }
+
+    unsafe { free(toc as *mut c_void) };
+    result
+}
+
+/// Reads the entry of the key from the meta data, or `None` if there is no such entry or it is of
+/// an unknown type
+pub fn read_meta_entry(md: *mut meta_data_t, key: &str) -> Result<Option<MetaValue>, Utf8Error> {
+    let key = match CString::new(key) {
+        Ok(key) if !md.is_null() => key,
+        _ => return Ok(None),
+    };
+
+    unsafe { read_entry(md, key.as_ptr()) }
+}
+
+unsafe fn read_entry(
+    md: *mut meta_data_t,
+    key: *const c_char,
+) -> Result<Option<MetaValue>, Utf8Error> {
+    let value = match meta_data_type(md, key) as u32 {
+        MD_TYPE_STRING => {
+            let mut s: *mut c_char = ptr::null_mut();
+            if meta_data_get_string(md, key, &mut s) != 0 || s.is_null() {
+                return Ok(None);
+            }
+            MetaValue::String(take_string(s)?)
+        }
+        MD_TYPE_SIGNED_INT => {
+            let mut x = 0;
+            if meta_data_get_signed_int(md, key, &mut x) != 0 {
+                return Ok(None);
+            }
+            MetaValue::SignedInt(x)
+        }
+        MD_TYPE_UNSIGNED_INT => {
+            let mut x = 0;
+            if meta_data_get_unsigned_int(md, key, &mut x) != 0 {
+                return Ok(None);
+            }
+            MetaValue::UnsignedInt(x)
+        }
+        MD_TYPE_DOUBLE => {
+            let mut x = 0.0;
+            if meta_data_get_double(md, key, &mut x) != 0 {
+                return Ok(None);
+            }
+            MetaValue::Double(x)
+        }
+        MD_TYPE_BOOLEAN => {
+            let mut x = false;
+            if meta_data_get_boolean(md, key, &mut x) != 0 {
+                return Ok(None);
+            }
+            MetaValue::Boolean(x)
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meta_value_accessors() {
+        let unit = MetaValue::String(String::from("bytes"));
+        assert_eq!(Some("bytes"), unit.as_str());
+        assert_eq!(None, unit.as_f64());
+
+        assert_eq!(Some(5), MetaValue::UnsignedInt(5).as_i64());
+        assert_eq!(None, MetaValue::UnsignedInt(u64::MAX).as_i64());
+        assert_eq!(None, MetaValue::SignedInt(-1).as_u64());
+        assert_eq!(Some(-1.0), MetaValue::SignedInt(-1).as_f64());
+        assert_eq!(Some(0.5), MetaValue::Double(0.5).as_f64());
+        assert_eq!(None, MetaValue::Double(0.5).as_i64());
+        assert_eq!(Some(true), MetaValue::Boolean(true).as_bool());
+        assert_eq!(None, MetaValue::Boolean(true).as_str());
+    }
+}
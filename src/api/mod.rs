@@ -2,36 +2,113 @@ use crate::bindings::{
     data_set_t, hostname_g, plugin_dispatch_values, uc_get_rate, value_list_t, value_t, ARR_LENGTH,
     DS_TYPE_ABSOLUTE, DS_TYPE_COUNTER, DS_TYPE_DERIVE, DS_TYPE_GAUGE,
 };
-use crate::errors::{ArrayError, CacheRateError, ReceiveError, SubmitError};
-use chrono::prelude::*;
-use chrono::Duration;
+use crate::errors::{
+    ArrayError, CacheRateError, DurationError, ReceiveError, SubmitError, UnknownConstant, ENOMEM,
+};
 use memchr::memchr;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::ffi::CStr;
 use std::fmt;
 use std::os::raw::c_char;
 use std::ptr;
 use std::slice;
 use std::str::Utf8Error;
-
-pub use self::cdtime::{nanos_to_collectd, CdTime};
-pub use self::logger::{collectd_log, log_err, CollectdLoggerBuilder, LogLevel};
-pub use self::oconfig::{ConfigItem, ConfigValue};
-
+use std::sync::atomic::{AtomicU32, Ordering};
+use strum_macros::EnumIter;
+
+pub use self::bounds::{data_sources, BoundsPolicy, DataSource};
+pub use self::budget::{Admission, BudgetPolicy, MemoryBudget};
+pub use self::cache::{CacheEntry, CachedRead, LocalCache, SeriesPoints, SeriesWindow};
+pub use self::cdtime::{get_interval, nanos_to_collectd, CdTime, Duration, Timestamp};
+pub use self::container::{ContainerInfo, HostNaming};
+pub use self::context::{ContextGuard, PluginContext};
+pub use self::daemon::{capabilities, DaemonCapabilities};
+use self::dispatch::record_dispatch;
+pub use self::dispatch::{dispatch_stats, write_queue_length, DispatchStats};
+pub use self::duration::IntoDuration;
+pub use self::flush::{request_flush, FlushPlan};
+pub use self::host::{hostname, validate_host};
+pub use self::identifier::{Identifier, IdentifierPattern};
+pub use self::lazy::LazyInit;
+pub(crate) use self::logger::with_instance;
+pub use self::logger::{collectd_log, log_err, reload_log_filter, CollectdLoggerBuilder, LogLevel};
+pub use self::meta::MetaValue;
+use self::meta::{read_meta, read_meta_entry, MetaData};
+pub use self::notification::{
+    dispatch_notification, Escalator, Notification, NotificationBuilder, NotificationDeduper,
+    NotificationLevel,
+};
+pub use self::oconfig::{ConfigItem, ConfigValue, FromConfigValue, OwnedConfigItem};
+pub use self::rate::RateState;
+pub use self::read_interval::ReadInterval;
+pub use self::sanitize::SanitizePreset;
+pub use self::schedule::Schedule;
+pub use self::schema::SchemaRegistry;
+pub use self::shared::SharedConfig;
+pub use self::shutdown::{on_shutdown, take_shutdown_hooks, ShutdownHook};
+pub use self::syslog::SyslogFormat;
+pub use self::targets::TargetReport;
+pub use self::tls::{TlsConfig, TlsMaterial};
+pub use self::unit::Unit;
+
+mod bounds;
+mod budget;
+mod cache;
 mod cdtime;
+mod container;
+mod context;
+mod daemon;
+mod dispatch;
+mod duration;
+mod flush;
+mod host;
+mod identifier;
+mod lazy;
 mod logger;
+mod meta;
+mod notification;
 mod oconfig;
-
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+mod rate;
+mod sanitize;
+mod schedule;
+mod schema;
+mod shared;
+mod shutdown;
+mod syslog;
+mod targets;
+mod tls;
+mod unit;
+
+/// The types of data sources that collectd defines in types.db
+#[derive(EnumIter, Debug, PartialEq, Eq, Clone, Copy, Hash)]
 #[repr(u32)]
-#[allow(dead_code)]
-enum ValueType {
+pub enum ValueType {
     Counter = DS_TYPE_COUNTER,
     Gauge = DS_TYPE_GAUGE,
     Derive = DS_TYPE_DERIVE,
     Absolute = DS_TYPE_ABSOLUTE,
 }
 
+impl TryFrom<u32> for ValueType {
+    type Error = UnknownConstant;
+
+    /// Attempts to convert a u32 representing a collectd data source type into a Rust enum
+    fn try_from(s: u32) -> Result<Self, Self::Error> {
+        match s {
+            DS_TYPE_COUNTER => Ok(ValueType::Counter),
+            DS_TYPE_GAUGE => Ok(ValueType::Gauge),
+            DS_TYPE_DERIVE => Ok(ValueType::Derive),
+            DS_TYPE_ABSOLUTE => Ok(ValueType::Absolute),
+            value => Err(UnknownConstant {
+                kind: "data source type",
+                value,
+            }),
+        }
+    }
+}
+
 /// The value that a plugin reports can be any one of the following types
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Value {
@@ -77,6 +154,44 @@ impl Value {
             false
         }
     }
+
+    /// The type of data source that the value belongs to
+    pub fn value_type(&self) -> ValueType {
+        match *self {
+            Value::Counter(_) => ValueType::Counter,
+            Value::Gauge(_) => ValueType::Gauge,
+            Value::Derive(_) => ValueType::Derive,
+            Value::Absolute(_) => ValueType::Absolute,
+        }
+    }
+
+    /// Reads the field of collectd's value union that corresponds to the data source's type. As
+    /// collectd doesn't tag the union, the type must come from the data set.
+    pub fn from_raw(ds_type: ValueType, value: value_t) -> Value {
+        match ds_type {
+            ValueType::Counter => Value::Counter(raw_counter(value)),
+            ValueType::Gauge => Value::Gauge(raw_gauge(value)),
+            ValueType::Derive => Value::Derive(raw_derive(value)),
+            ValueType::Absolute => Value::Absolute(raw_absolute(value)),
+        }
+    }
+}
+
+// Every bit pattern is a valid u64, f64, and i64, so reading any field of the union is sound
+fn raw_counter(value: value_t) -> u64 {
+    unsafe { value.counter }
+}
+
+fn raw_gauge(value: value_t) -> f64 {
+    unsafe { value.gauge }
+}
+
+fn raw_derive(value: value_t) -> i64 {
+    unsafe { value.derive }
+}
+
+fn raw_absolute(value: value_t) -> u64 {
+    unsafe { value.absolute }
 }
 
 impl fmt::Display for Value {
@@ -111,13 +226,26 @@ pub struct ValueReport<'a> {
     /// The value reported
     pub value: Value,
 
-    /// Minimum value seen in an interval
+    /// The lower bound of the value's data source, or NaN if unbounded
     pub min: f64,
 
-    /// Maximum value seen in an interval
+    /// The upper bound of the value's data source, or NaN if unbounded
     pub max: f64,
 }
 
+impl<'a> ValueReport<'a> {
+    /// Returns true if the value lies within the bounds of its data source. Only gauges are
+    /// checked, as collectd applies the bounds of the other types to their rates.
+    pub fn in_bounds(&self) -> bool {
+        bounds::in_bounds(self.value, self.min, self.max)
+    }
+
+    /// The value clamped to the bounds of its data source (eg: a utilization of 104% as 100%)
+    pub fn clamped(&self) -> Value {
+        bounds::clamp_value(self.value, self.min, self.max)
+    }
+}
+
 /// Contains values and metadata that collectd has collected from plugins
 #[derive(Debug, PartialEq, Clone)]
 pub struct ValueList<'a> {
@@ -144,7 +272,7 @@ pub struct ValueList<'a> {
     pub host: &'a str,
 
     /// The timestamp at which the value was collected
-    pub time: DateTime<Utc>,
+    pub time: Timestamp,
 
     /// The interval in which new values are to be expected
     pub interval: Duration,
@@ -192,6 +320,79 @@ impl<'a> ValueList<'a> {
         }
     }
 
+    fn original_list(&self) -> &'a value_list_t {
+        // The list outlives 'a as it's the list this value list was created from
+        unsafe { &*self.original_list }
+    }
+
+    /// The plugin as the original C string given by collectd. Write plugins that hand the string
+    /// over to a C library can use this to skip converting to and from UTF-8.
+    pub fn plugin_cstr(&self) -> &'a CStr {
+        cstr_from_array(&self.original_list().plugin)
+    }
+
+    /// The plugin instance as the original C string given by collectd
+    pub fn plugin_instance_cstr(&self) -> Option<&'a CStr> {
+        cstr_empty_to_none(cstr_from_array(&self.original_list().plugin_instance))
+    }
+
+    /// The type as the original C string given by collectd
+    pub fn type_cstr(&self) -> &'a CStr {
+        cstr_from_array(&self.original_list().type_)
+    }
+
+    /// The type instance as the original C string given by collectd
+    pub fn type_instance_cstr(&self) -> Option<&'a CStr> {
+        cstr_empty_to_none(cstr_from_array(&self.original_list().type_instance))
+    }
+
+    /// The host as the original C string given by collectd
+    pub fn host_cstr(&self) -> &'a CStr {
+        cstr_from_array(&self.original_list().host)
+    }
+
+    /// The meta data attached to the values. With the `serde` feature, the entries can be
+    /// converted back into a struct with `de::from_meta`.
+    pub fn meta(&self) -> Result<Vec<(String, MetaValue)>, ReceiveError> {
+        read_meta(self.original_list().meta)
+            .map_err(|e| ReceiveError::Utf8(String::from(self.plugin), "meta", e))
+    }
+
+    /// The meta data attached to the values keyed by name, so that a write plugin can look up the
+    /// entries it forwards (eg: tags or units set by another plugin)
+    pub fn meta_map(&self) -> Result<HashMap<String, MetaValue>, ReceiveError> {
+        self.meta().map(|x| x.into_iter().collect())
+    }
+
+    /// A single entry of the meta data, without reading the others. Returns `None` if the entry
+    /// is missing.
+    pub fn meta_value(&self, key: &str) -> Result<Option<MetaValue>, ReceiveError> {
+        read_meta_entry(self.original_list().meta, key)
+            .map_err(|e| ReceiveError::Utf8(String::from(self.plugin), "meta", e))
+    }
+
+    /// The data set and value list that collectd handed over, for interop with C libraries that
+    /// work with collectd's structures (eg: libcollectdclient). The pointers are valid for as long
+    /// as the value list is, and the structures must not be modified, as other write plugins
+    /// receive the same structures.
+    pub fn as_raw(&self) -> (*const data_set_t, *const value_list_t) {
+        (self.original_set, self.original_list)
+    }
+
+    /// Converts a data set and value list given as raw pointers (eg: by a C library). See `from`.
+    ///
+    /// # Safety
+    ///
+    /// Both pointers must be non-null and point to initialized structures that outlive `'b`, where
+    /// the data sources of the set and the values of the list hold `ds_num` and `values_len`
+    /// elements (the values may be null for a list without values).
+    pub unsafe fn from_raw<'b>(
+        set: *const data_set_t,
+        list: *const value_list_t,
+    ) -> Result<ValueList<'b>, ReceiveError> {
+        ValueList::from(&*set, &*list)
+    }
+
     pub fn from<'b>(
         set: &'b data_set_t,
         list: &'b value_list_t,
@@ -201,29 +402,31 @@ impl<'a> ValueList<'a> {
         let ds_len = length(set.ds_num);
         let list_len = length(list.values_len);
 
-        let values: Result<Vec<ValueReport<'_>>, ReceiveError> =
+        // The value list of a missing callback has no values
+        let raw_values = if list.values.is_null() {
+            &[]
+        } else {
             unsafe { slice::from_raw_parts(list.values, list_len) }
-                .iter()
-                .zip(unsafe { slice::from_raw_parts(set.ds, ds_len) })
-                .map(|(val, source)| unsafe {
-                    let v = match ::std::mem::transmute(source.type_) {
-                        ValueType::Gauge => Value::Gauge(val.gauge),
-                        ValueType::Counter => Value::Counter(val.counter),
-                        ValueType::Derive => Value::Derive(val.derive),
-                        ValueType::Absolute => Value::Absolute(val.absolute),
-                    };
-
-                    let name = from_array(&source.name)
-                        .map_err(|e| ReceiveError::Utf8(String::from(p), "data source name", e))?;
-
-                    Ok(ValueReport {
-                        name,
-                        value: v,
-                        min: source.min,
-                        max: source.max,
-                    })
-                })
-                .collect();
+        };
+
+        // Converted in a single pass into a vector sized up front, as write plugins may receive
+        // tens of thousands of value lists per interval
+        let sources = unsafe { slice::from_raw_parts(set.ds, ds_len) };
+        let mut values = Vec::with_capacity(raw_values.len().min(sources.len()));
+        for (val, source) in raw_values.iter().zip(sources) {
+            let ds_type = ValueType::try_from(source.type_ as u32)
+                .map_err(|e| ReceiveError::DataSourceType(String::from(p), e))?;
+
+            let name = from_array(&source.name)
+                .map_err(|e| ReceiveError::Utf8(String::from(p), "data source name", e))?;
+
+            values.push(ValueReport {
+                name,
+                value: Value::from_raw(ds_type, *val),
+                min: source.min,
+                max: source.max,
+            });
+        }
 
         assert!(list.time > 0);
         assert!(list.interval > 0);
@@ -243,7 +446,7 @@ impl<'a> ValueList<'a> {
             from_array(&list.host).map_err(|e| ReceiveError::Utf8(String::from(p), "host", e))?;
 
         Ok(ValueList {
-            values: values?,
+            values,
             plugin_instance,
             plugin: p,
             type_,
@@ -265,8 +468,61 @@ struct SubmitValueList<'a> {
     type_: &'a str,
     type_instance: Option<&'a str>,
     host: Option<&'a str>,
-    time: Option<DateTime<Utc>>,
-    interval: Option<Duration>,
+    time: Option<Timestamp>,
+    interval: Option<Result<::std::time::Duration, DurationError>>,
+    interned: Option<&'a InternedIdentifier>,
+    meta: &'a [(String, MetaValue)],
+    significant_digits: Option<u32>,
+    bounds: BoundsPolicy,
+}
+
+/// The identifying fields of a value list (host, plugin, type, and instances) already converted
+/// into the character arrays collectd expects. Read plugins that dispatch the same metrics every
+/// interval can intern the identifiers once and skip the validation and conversion of each field
+/// on every submission.
+///
+/// ```
+/// use collectd_plugin::{InternedIdentifier, SubmitError, Value, ValueListBuilder};
+///
+/// let id = ValueListBuilder::new("myplugin", "load")
+///     .plugin_instance("0")
+///     .intern()
+///     .unwrap();
+///
+/// // Called every interval
+/// fn report(id: &InternedIdentifier, values: &[Value]) -> Result<(), SubmitError> {
+///     ValueListBuilder::with_identifier(id).values(values).submit()
+/// }
+/// ```
+#[derive(Clone, Copy)]
+pub struct InternedIdentifier {
+    plugin: [c_char; ARR_LENGTH],
+    plugin_instance: [c_char; ARR_LENGTH],
+    type_: [c_char; ARR_LENGTH],
+    type_instance: [c_char; ARR_LENGTH],
+    host: [c_char; ARR_LENGTH],
+}
+
+impl fmt::Debug for InternedIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InternedIdentifier")
+            .field("plugin", &cstr_from_array(&self.plugin))
+            .field("plugin_instance", &cstr_from_array(&self.plugin_instance))
+            .field("type_", &cstr_from_array(&self.type_))
+            .field("type_instance", &cstr_from_array(&self.type_instance))
+            .field("host", &cstr_from_array(&self.host))
+            .finish()
+    }
+}
+
+impl PartialEq for InternedIdentifier {
+    fn eq(&self, other: &InternedIdentifier) -> bool {
+        self.plugin[..] == other.plugin[..]
+            && self.plugin_instance[..] == other.plugin_instance[..]
+            && self.type_[..] == other.type_[..]
+            && self.type_instance[..] == other.type_instance[..]
+            && self.host[..] == other.host[..]
+    }
 }
 
 /// Creates a value list to report values to collectd.
@@ -289,10 +545,23 @@ impl<'a> ValueListBuilder<'a> {
                 host: None,
                 time: None,
                 interval: None,
+                interned: None,
+                meta: &[],
+                significant_digits: None,
+                bounds: BoundsPolicy::Ignore,
             },
         }
     }
 
+    /// Primes a value list for submission with an identifier that has already been interned.
+    /// The plugin, type, instances, and host of the interned identifier are used, so those fields
+    /// should not be set on the returned builder.
+    pub fn with_identifier(id: &'a InternedIdentifier) -> ValueListBuilder<'a> {
+        let mut builder = ValueListBuilder::new("", "");
+        builder.list.interned = Some(id);
+        builder
+    }
+
     /// A set of observed values that belong to the same plugin and type instance
     pub fn values(mut self, values: &'a [Value]) -> ValueListBuilder<'a> {
         self.list.values = values;
@@ -315,30 +584,62 @@ impl<'a> ValueListBuilder<'a> {
     }
 
     /// Override the machine's hostname that the observed values will be attributed to. Best to
-    /// override when observing values from another machine
+    /// override when observing values from another machine. The host is checked with
+    /// `validate_host` on `submit`.
     pub fn host<T: Into<&'a str>>(mut self, host: T) -> ValueListBuilder<'a> {
         self.list.host = Some(host.into());
         self
     }
 
+    /// Meta data to attach to the values, which gives write plugins context about the values
+    /// that isn't part of their identifier. With the `serde` feature, a struct can be converted
+    /// into meta data with `ser::to_meta`.
+    pub fn meta(mut self, meta: &'a [(String, MetaValue)]) -> ValueListBuilder<'a> {
+        self.list.meta = meta;
+        self
+    }
+
+    /// Rounds gauges to the number of significant digits before they are dispatched, which
+    /// reduces noise and storage in downstream systems. Overrides the default set with
+    /// `set_significant_digits`. Zero disables rounding.
+    pub fn significant_digits(mut self, digits: u32) -> ValueListBuilder<'a> {
+        self.list.significant_digits = Some(digits);
+        self
+    }
+
+    /// Checks gauges against the min and max of their data source in types.db before they are
+    /// dispatched, so that out of range values (eg: a utilization above 100%) are clamped or
+    /// rejected instead of being silently discarded by collectd. Values of a type that collectd
+    /// doesn't know are submitted as is.
+    pub fn bounds(mut self, policy: BoundsPolicy) -> ValueListBuilder<'a> {
+        self.list.bounds = policy;
+        self
+    }
+
     /// The timestamp at which the value was collected. Overrides the default time, which is when
     /// collectd receives the values from `submit`. Use only if there is a significant delay is
     /// metrics gathering or if submitting values from the past.
-    pub fn time(mut self, dt: DateTime<Utc>) -> ValueListBuilder<'a> {
+    pub fn time(mut self, dt: Timestamp) -> ValueListBuilder<'a> {
         self.list.time = Some(dt);
         self
     }
 
     /// The interval in which new values are to be expected. This is typically handled at a global
-    /// or plugin level. Use at your own discretion.
-    pub fn interval(mut self, interval: Duration) -> ValueListBuilder<'a> {
-        self.list.interval = Some(interval);
+    /// or plugin level. Use at your own discretion. Accepts anything that implements
+    /// [`IntoDuration`](trait.IntoDuration.html), so `"10s"` works as well. A string that can't be
+    /// parsed is reported as an error on `submit`.
+    pub fn interval<T: IntoDuration>(mut self, interval: T) -> ValueListBuilder<'a> {
+        self.list.interval = Some(interval.into_duration());
         self
     }
 
-    /// Submits the observed values to collectd and returns errors if encountered
-    pub fn submit(self) -> Result<(), SubmitError> {
-        let mut v: Vec<value_t> = self.list.values.iter().map(|&x| x.into()).collect();
+    /// Converts the identifying fields of the value list (plugin, type, instances, and host) so
+    /// that they can be reused across submissions with `with_identifier`
+    pub fn intern(&self) -> Result<InternedIdentifier, SubmitError> {
+        if let Some(id) = self.list.interned {
+            return Ok(*id);
+        }
+
         let plugin_instance = self
             .list
             .plugin_instance
@@ -354,21 +655,55 @@ impl<'a> ValueListBuilder<'a> {
         let host = self
             .list
             .host
-            .map(|x| to_array_res(x).map_err(|e| SubmitError::Field("host", e)))
-            .unwrap_or_else(|| {
-                // If a custom host is not provided by the plugin, we default to the global
-                // hostname. In versions prior to collectd 5.7, it was required to propagate the
-                // global hostname (hostname_g) in the submission. In collectd 5.7, one could
-                // submit an empty array or hostname_g and they would equate to the same thing. In
-                // collectd 5.8, hostname_g had the type signature changed so it could no longer be
-                // submitted and would cause garbage to be read (and thus could have very much
-                // unintended side effects)
-                if cfg!(collectd57) {
-                    Ok([0 as c_char; ARR_LENGTH])
-                } else {
-                    unsafe { Ok(hostname_g) }
-                }
-            })?;
+            .map(|x| {
+                validate_host(x).map_err(SubmitError::Host)?;
+                to_array_res(x).map_err(|e| SubmitError::Field("host", e))
+            })
+            .unwrap_or_else(|| Ok(default_host()))?;
+
+        let plugin = to_array_res(self.list.plugin).map_err(|e| SubmitError::Field("plugin", e))?;
+
+        let type_ = to_array_res(self.list.type_).map_err(|e| SubmitError::Field("type", e))?;
+
+        Ok(InternedIdentifier {
+            plugin,
+            plugin_instance,
+            type_,
+            type_instance,
+            host,
+        })
+    }
+
+    /// Submits the observed values to collectd and returns errors if encountered
+    pub fn submit(self) -> Result<(), SubmitError> {
+        let digits = self
+            .list
+            .significant_digits
+            .unwrap_or_else(|| SIGNIFICANT_DIGITS.load(Ordering::Relaxed));
+        let id = self.intern()?;
+        let sources = match self.list.bounds {
+            BoundsPolicy::Ignore => None,
+            _ => from_array(&id.type_).ok().and_then(data_sources),
+        };
+
+        let mut v: Vec<value_t> = Vec::with_capacity(self.list.values.len());
+        for (i, &x) in self.list.values.iter().enumerate() {
+            let x = match sources.as_ref().and_then(|s| s.get(i)) {
+                Some(source) if !source.in_bounds(x) => match self.list.bounds {
+                    BoundsPolicy::Reject => {
+                        return Err(SubmitError::OutOfBounds(source.name.clone(), x));
+                    }
+                    _ => source.clamp(x),
+                },
+                _ => x,
+            };
+
+            let x = match x {
+                Value::Gauge(g) if digits > 0 => Value::Gauge(round_significant(g, digits)),
+                x => x,
+            };
+            v.push(x.into());
+        }
 
         #[cfg(collectd57)]
         let len = v.len() as u64;
@@ -376,39 +711,97 @@ impl<'a> ValueListBuilder<'a> {
         #[cfg(not(collectd57))]
         let len = v.len() as i32;
 
-        let plugin = to_array_res(self.list.plugin).map_err(|e| SubmitError::Field("plugin", e))?;
+        let interval = self
+            .list
+            .interval
+            .map(|x| x.map(CdTime::from).map_err(SubmitError::Interval))
+            .unwrap_or_else(|| Ok(CdTime(0)))?;
 
-        let type_ = to_array_res(self.list.type_).map_err(|e| SubmitError::Field("type", e))?;
+        // Collectd copies the meta data on dispatch, so ours is destroyed once submitted
+        let meta = if self.list.meta.is_empty() {
+            None
+        } else {
+            Some(MetaData::new(self.list.meta)?)
+        };
 
         let list = value_list_t {
             values: v.as_mut_ptr(),
             values_len: len,
-            plugin_instance,
-            plugin,
-            type_,
-            type_instance,
-            host,
+            plugin_instance: id.plugin_instance,
+            plugin: id.plugin,
+            type_: id.type_,
+            type_instance: id.type_instance,
+            host: id.host,
             time: self.list.time.map(CdTime::from).unwrap_or(CdTime(0)).into(),
-            interval: self
-                .list
-                .interval
-                .map(CdTime::from)
-                .unwrap_or(CdTime(0))
-                .into(),
-            meta: ptr::null_mut(),
+            interval: interval.into(),
+            meta: meta
+                .as_ref()
+                .map(|x| x.as_mut_ptr())
+                .unwrap_or_else(ptr::null_mut),
         };
 
-        match unsafe { plugin_dispatch_values(&list) } {
+        let res = match unsafe { plugin_dispatch_values(&list) } {
             0 => Ok(()),
+            ENOMEM => Err(SubmitError::OutOfMemory),
             i => Err(SubmitError::Dispatch(i)),
-        }
+        };
+
+        record_dispatch(&res);
+        res
+    }
+}
+
+/// Default number of significant digits that gauges are rounded to on submission (zero disables
+/// rounding)
+static SIGNIFICANT_DIGITS: AtomicU32 = AtomicU32::new(0);
+
+/// Sets the number of significant digits that gauges are rounded to when submitted through a
+/// `ValueListBuilder` (which includes values dispatched on behalf of sidecars). Individual
+/// submissions can override this with `ValueListBuilder::significant_digits`. `None` disables
+/// rounding, which is the default.
+pub fn set_significant_digits(digits: Option<u32>) {
+    SIGNIFICANT_DIGITS.store(digits.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Rounds the number to the given number of significant digits. NaN and infinities are left
+/// as is.
+fn round_significant(x: f64, digits: u32) -> f64 {
+    if x == 0.0 || !x.is_finite() {
+        return x;
+    }
+
+    let magnitude = x.abs().log10().floor() as i32;
+    let shift = digits as i32 - 1 - magnitude;
+
+    // Dividing by an exact power of ten keeps numbers like 12300 from becoming 12299.999..
+    if shift >= 0 {
+        let factor = 10f64.powi(shift);
+        (x * factor).round() / factor
+    } else {
+        let factor = 10f64.powi(-shift);
+        (x / factor).round() * factor
+    }
+}
+
+/// The host to submit when a custom host is not provided by the plugin.
+///
+/// In versions prior to collectd 5.7, it was required to propagate the global hostname
+/// (hostname_g) in the submission. In collectd 5.7, one could submit an empty array or hostname_g
+/// and they would equate to the same thing. In collectd 5.8, hostname_g had the type signature
+/// changed so it could no longer be submitted and would cause garbage to be read (and thus could
+/// have very much unintended side effects)
+fn default_host() -> [c_char; ARR_LENGTH] {
+    if cfg!(collectd57) {
+        [0 as c_char; ARR_LENGTH]
+    } else {
+        unsafe { hostname_g }
     }
 }
 
 /// Collectd stores textual data in fixed sized arrays, so this function will convert a string
 /// slice into array compatible with collectd's text fields. Be aware that `ARR_LENGTH` is 64
 /// before collectd 5.7
-fn to_array_res(s: &str) -> Result<[c_char; ARR_LENGTH], ArrayError> {
+pub(crate) fn to_array_res(s: &str) -> Result<[c_char; ARR_LENGTH], ArrayError> {
     // By checking if the length is greater than or *equal* to, we guarantee a trailing null
     if s.len() >= ARR_LENGTH {
         return Err(ArrayError::TooLong(s.len()));
@@ -430,9 +823,14 @@ fn to_array_res(s: &str) -> Result<[c_char; ARR_LENGTH], ArrayError> {
 
 /// Turns a fixed size character array into string slice, if possible
 pub fn from_array(s: &[c_char; ARR_LENGTH]) -> Result<&str, Utf8Error> {
+    cstr_from_array(s).to_str()
+}
+
+/// Borrows a fixed size, null terminated, character array as a `CStr`
+pub fn cstr_from_array(s: &[c_char; ARR_LENGTH]) -> &CStr {
     unsafe {
         let a = s as *const [c_char; ARR_LENGTH] as *const c_char;
-        CStr::from_ptr(a).to_str()
+        CStr::from_ptr(a)
     }
 }
 
@@ -445,6 +843,14 @@ pub fn empty_to_none(s: &str) -> Option<&str> {
     }
 }
 
+fn cstr_empty_to_none(s: &CStr) -> Option<&CStr> {
+    if s.to_bytes().is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
 #[cfg(collectd57)]
 pub fn length(len: u64) -> usize {
     len as usize
@@ -465,12 +871,66 @@ pub fn get_default_interval<T>() -> *const T {
     ptr::null()
 }
 
+/// A read interval that differs from the default, in the representation that the version of
+/// collectd expects when registering a read callback: a `cdtime_t` from collectd 5.7 on, else a
+/// pointer to a `timespec`
+mod read_interval {
+    use super::CdTime;
+    use std::os::raw::c_long;
+
+    /// The layout of the C `timespec`
+    #[repr(C)]
+    struct Timespec {
+        tv_sec: c_long,
+        tv_nsec: c_long,
+    }
+
+    pub struct ReadInterval {
+        cdtime: u64,
+        timespec: Timespec,
+    }
+
+    impl ReadInterval {
+        pub fn new(interval: ::std::time::Duration) -> Self {
+            ReadInterval {
+                cdtime: CdTime::from(interval).into(),
+                timespec: Timespec {
+                    tv_sec: interval.as_secs() as c_long,
+                    tv_nsec: interval.subsec_nanos() as c_long,
+                },
+            }
+        }
+
+        pub fn as_arg<T: IntervalArg>(&self) -> T {
+            T::from_interval(self)
+        }
+    }
+
+    /// The types that collectd takes a read interval as
+    pub trait IntervalArg {
+        fn from_interval(interval: &ReadInterval) -> Self;
+    }
+
+    impl IntervalArg for u64 {
+        fn from_interval(interval: &ReadInterval) -> Self {
+            interval.cdtime
+        }
+    }
+
+    impl<T> IntervalArg for *const T {
+        fn from_interval(interval: &ReadInterval) -> Self {
+            &interval.timespec as *const Timespec as *const T
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use self::cdtime::nanos_to_collectd;
     use super::*;
-    use crate::bindings::data_source_t;
+    use crate::bindings::{data_source_t, LOG_INFO};
     use std::os::raw::c_char;
+    use strum::IntoEnumIterator;
 
     #[test]
     fn test_empty_to_none() {
@@ -509,6 +969,109 @@ mod tests {
         assert!(actual.is_err());
     }
 
+    fn raw_bytes(value: value_t) -> [u8; 8] {
+        unsafe { ::std::mem::transmute(value) }
+    }
+
+    fn from_bytes(bytes: [u8; 8]) -> value_t {
+        unsafe { ::std::mem::transmute(bytes) }
+    }
+
+    // The byte patterns of each type of value as laid out in memory by collectd on a little endian
+    // machine
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn test_value_raw_golden() {
+        let golden = [
+            (Value::Gauge(1.5), [0, 0, 0, 0, 0, 0, 0xf8, 0x3f]),
+            (Value::Gauge(-0.0), [0, 0, 0, 0, 0, 0, 0, 0x80]),
+            (
+                Value::Derive(-2),
+                [0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            ),
+            (
+                Value::Derive(i64::MAX),
+                [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f],
+            ),
+            (Value::Counter(1), [1, 0, 0, 0, 0, 0, 0, 0]),
+            (Value::Counter(0x0102_0304), [4, 3, 2, 1, 0, 0, 0, 0]),
+            (Value::Absolute(u64::MAX), [0xff; 8]),
+        ];
+
+        for (value, bytes) in golden.iter() {
+            assert_eq!(*bytes, raw_bytes((*value).into()), "{:?}", value);
+            assert_eq!(
+                *value,
+                Value::from_raw(value.value_type(), from_bytes(*bytes))
+            );
+        }
+
+        // collectd's NAN
+        let nan = Value::from_raw(ValueType::Gauge, from_bytes([0, 0, 0, 0, 0, 0, 0xf8, 0x7f]));
+        assert!(nan.is_nan());
+        assert_eq!([0, 0, 0, 0, 0, 0, 0xf8, 0x7f], raw_bytes(nan.into()));
+    }
+
+    #[test]
+    fn test_value_raw_roundtrip() {
+        let values = [
+            Value::Gauge(f64::MIN_POSITIVE),
+            Value::Gauge(f64::INFINITY),
+            Value::Derive(i64::MIN),
+            Value::Counter(0),
+            Value::Absolute(1 << 63),
+        ];
+
+        for value in values.iter() {
+            let raw: value_t = (*value).into();
+            assert_eq!(*value, Value::from_raw(value.value_type(), raw));
+        }
+    }
+
+    #[test]
+    fn test_value_type_try_from() {
+        for ds_type in ValueType::iter() {
+            assert_eq!(Ok(ds_type), ValueType::try_from(ds_type as u32));
+        }
+
+        assert_eq!(
+            Err(UnknownConstant {
+                kind: "data source type",
+                value: 4
+            }),
+            ValueType::try_from(4)
+        );
+    }
+
+    #[test]
+    fn test_read_interval_args() {
+        let interval = ReadInterval::new(Duration::from_millis(1500));
+        let cdtime: u64 = interval.as_arg();
+        assert_eq!(1_610_612_736, cdtime);
+
+        let timespec: *const [std::os::raw::c_long; 2] = interval.as_arg();
+        assert_eq!([1, 500_000_000], unsafe { *timespec });
+    }
+
+    #[test]
+    fn test_log_level_try_from() {
+        for level in LogLevel::iter() {
+            assert_eq!(
+                Ok(level),
+                <LogLevel as TryFrom<u32>>::try_from(level as u32)
+            );
+        }
+
+        assert!(<LogLevel as TryFrom<u32>>::try_from(0).is_err());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_log_level_inherent_try_from() {
+        assert_eq!(Some(LogLevel::Info), LogLevel::try_from(LOG_INFO));
+        assert_eq!(None, LogLevel::try_from(0));
+    }
+
     #[test]
     fn test_submit() {
         let values = vec![Value::Gauge(15.0), Value::Gauge(10.0), Value::Gauge(12.0)];
@@ -518,6 +1081,64 @@ mod tests {
         assert_eq!(result.unwrap(), ());
     }
 
+    #[test]
+    fn test_submit_interned() {
+        let id = ValueListBuilder::new("my-plugin", "load")
+            .plugin_instance("0")
+            .intern()
+            .unwrap();
+        let builder = ValueListBuilder::with_identifier(&id);
+        assert_eq!(builder.intern().unwrap(), id);
+        assert_eq!(cstr_from_array(&id.plugin).to_bytes(), b"my-plugin");
+        assert_eq!(cstr_from_array(&id.plugin_instance).to_bytes(), b"0");
+        assert_eq!(cstr_from_array(&id.type_instance).to_bytes(), b"");
+
+        let values = vec![Value::Gauge(15.0)];
+        assert_eq!(builder.values(&values).submit().unwrap(), ());
+
+        let res = ValueListBuilder::new("my-plugin", "load")
+            .type_instance("a\0b")
+            .intern();
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_round_significant() {
+        assert_eq!(1.23, round_significant(1.23456, 3));
+        assert_eq!(-0.00123, round_significant(-0.00123456, 3));
+        assert_eq!(12300.0, round_significant(12345.6, 3));
+        assert_eq!(100.0, round_significant(99.96, 3));
+        assert_eq!(0.0, round_significant(0.0, 3));
+        assert!(round_significant(f64::NAN, 3).is_nan());
+        assert_eq!(f64::INFINITY, round_significant(f64::INFINITY, 3));
+    }
+
+    #[test]
+    fn test_submit_significant_digits() {
+        let values = vec![Value::Gauge(15.1234), Value::Counter(10)];
+        let result = ValueListBuilder::new("my-plugin", "load")
+            .values(&values)
+            .significant_digits(2)
+            .submit();
+        assert_eq!(result.unwrap(), ());
+    }
+
+    #[test]
+    fn test_submit_interval() {
+        let values = vec![Value::Gauge(15.0)];
+        let result = ValueListBuilder::new("my-plugin", "load")
+            .values(&values)
+            .interval("10s")
+            .submit();
+        assert_eq!(result.unwrap(), ());
+
+        let result = ValueListBuilder::new("my-plugin", "load")
+            .values(&values)
+            .interval("ten seconds")
+            .submit();
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_recv_value_list_conversion() {
         let empty: [c_char; ARR_LENGTH] = [0; ARR_LENGTH];
@@ -574,11 +1195,32 @@ mod tests {
                 type_: "ho",
                 type_instance: None,
                 host: "ho",
-                time: Utc.ymd(1970, 1, 1).and_hms(0, 0, 1),
-                interval: Duration::seconds(1),
+                time: CdTime(1_000_000_000).into(),
+                interval: CdTime(1_000_000_000).into(),
                 original_list: &list_t,
                 original_set: &conv,
             }
         );
+
+        assert_eq!(actual.plugin_cstr().to_bytes(), b"hi");
+        assert_eq!(actual.plugin_instance_cstr().unwrap().to_bytes(), b"ho");
+        assert_eq!(actual.type_cstr().to_bytes(), b"ho");
+        assert_eq!(actual.type_instance_cstr(), None);
+        assert_eq!(actual.host_cstr().to_bytes(), b"ho");
+
+        let (set, list) = actual.as_raw();
+        assert!(ptr::eq(set, &conv));
+        assert!(ptr::eq(list, &list_t));
+        assert_eq!(actual, unsafe { ValueList::from_raw(set, list) }.unwrap());
+
+        // Missing values are delivered without any values
+        let missing = value_list_t {
+            values: ptr::null_mut(),
+            values_len: 0,
+            ..list_t
+        };
+        let actual = ValueList::from(&conv, &missing).unwrap();
+        assert!(actual.values.is_empty());
+        assert_eq!(actual.type_, "ho");
     }
 }
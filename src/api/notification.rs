@@ -0,0 +1,773 @@
+use super::meta::to_cstring;
+use super::{
+    collectd_log, default_host, empty_to_none, from_array, to_array_res, CdTime, IntoDuration,
+    LogLevel, MetaValue, Timestamp,
+};
+use crate::bindings::{
+    notification_meta_s, notification_meta_type_e_NM_TYPE_BOOLEAN as NM_TYPE_BOOLEAN,
+    notification_meta_type_e_NM_TYPE_DOUBLE as NM_TYPE_DOUBLE,
+    notification_meta_type_e_NM_TYPE_SIGNED_INT as NM_TYPE_SIGNED_INT,
+    notification_meta_type_e_NM_TYPE_STRING as NM_TYPE_STRING,
+    notification_meta_type_e_NM_TYPE_UNSIGNED_INT as NM_TYPE_UNSIGNED_INT, notification_t,
+    plugin_dispatch_notification, plugin_notification_meta_add_boolean,
+    plugin_notification_meta_add_double, plugin_notification_meta_add_signed_int,
+    plugin_notification_meta_add_string, plugin_notification_meta_add_unsigned_int,
+    plugin_notification_meta_free, ARR_LENGTH, NOTIF_FAILURE, NOTIF_OKAY, NOTIF_WARNING,
+};
+use crate::errors::{DurationError, ReceiveError, SubmitError, UnknownConstant};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+use std::str::Utf8Error;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use strum_macros::{AsRefStr, EnumIter};
+
+/// The severity levels that collectd attaches to notifications
+#[derive(AsRefStr, EnumIter, Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[strum(serialize_all = "lowercase")]
+#[repr(u32)]
+pub enum NotificationLevel {
+    Failure = NOTIF_FAILURE,
+    Warning = NOTIF_WARNING,
+    Okay = NOTIF_OKAY,
+}
+
+impl TryFrom<u32> for NotificationLevel {
+    type Error = UnknownConstant;
+
+    /// Attempts to convert a u32 representing a collectd notification severity into a Rust enum
+    fn try_from(s: u32) -> Result<Self, Self::Error> {
+        match s {
+            NOTIF_FAILURE => Ok(NotificationLevel::Failure),
+            NOTIF_WARNING => Ok(NotificationLevel::Warning),
+            NOTIF_OKAY => Ok(NotificationLevel::Okay),
+            value => Err(UnknownConstant {
+                kind: "notification severity",
+                value,
+            }),
+        }
+    }
+}
+
+/// A notification that collectd delivered to a plugin with the `NOTIFICATION` capability (eg: a
+/// threshold that was crossed, as reported by the threshold plugin)
+#[derive(Debug, PartialEq, Clone)]
+pub struct Notification<'a> {
+    pub severity: NotificationLevel,
+
+    /// When the notification was raised
+    pub time: Timestamp,
+
+    pub message: &'a str,
+
+    /// The host, plugin, and type that the notification is about. Fields that don't apply to the
+    /// notification are empty.
+    pub host: &'a str,
+    pub plugin: &'a str,
+    pub plugin_instance: Option<&'a str>,
+    pub type_: Option<&'a str>,
+    pub type_instance: Option<&'a str>,
+
+    /// Additional details attached to the notification (eg: the threshold plugin attaches the
+    /// `DataSource`, `CurrentValue`, `WarningMin`, and so on)
+    pub meta: Vec<(String, MetaValue)>,
+}
+
+impl<'a> Notification<'a> {
+    pub fn from(n: &'a notification_t) -> Result<Notification<'a>, ReceiveError> {
+        let plugin = from_array(&n.plugin)
+            .map_err(|e| ReceiveError::Utf8(String::from(""), "plugin name", e))?;
+        let field = |name, res: Result<&'a str, Utf8Error>| {
+            res.map_err(|e| ReceiveError::Utf8(String::from(plugin), name, e))
+        };
+
+        let severity = NotificationLevel::try_from(n.severity as u32)
+            .map_err(|e| ReceiveError::Severity(String::from(plugin), e))?;
+        let message = unsafe { CStr::from_ptr(n.message.as_ptr()) }.to_str();
+
+        Ok(Notification {
+            severity,
+            time: CdTime::from(n.time).into(),
+            message: field("message", message)?,
+            host: field("host", from_array(&n.host))?,
+            plugin,
+            plugin_instance: field("plugin_instance", from_array(&n.plugin_instance))
+                .map(empty_to_none)?,
+            type_: field("type", from_array(&n.type_)).map(empty_to_none)?,
+            type_instance: field("type instance", from_array(&n.type_instance))
+                .map(empty_to_none)?,
+            meta: read_notification_meta(n.meta)
+                .map_err(|e| ReceiveError::Utf8(String::from(plugin), "meta", e))?,
+        })
+    }
+}
+
+/// Collects the linked list of a notification's meta data
+fn read_notification_meta(
+    mut meta: *mut notification_meta_s,
+) -> Result<Vec<(String, MetaValue)>, Utf8Error> {
+    let mut res = Vec::new();
+    while !meta.is_null() {
+        let m = unsafe { &*meta };
+        let name = unsafe { CStr::from_ptr(m.name.as_ptr()) }.to_str()?;
+        let value = unsafe {
+            match m.type_ {
+                NM_TYPE_STRING if !m.nm_value.nm_string.is_null() => {
+                    let s = CStr::from_ptr(m.nm_value.nm_string).to_str()?;
+                    Some(MetaValue::String(String::from(s)))
+                }
+                NM_TYPE_SIGNED_INT => Some(MetaValue::SignedInt(m.nm_value.nm_signed_int)),
+                NM_TYPE_UNSIGNED_INT => Some(MetaValue::UnsignedInt(m.nm_value.nm_unsigned_int)),
+                NM_TYPE_DOUBLE => Some(MetaValue::Double(m.nm_value.nm_double)),
+                NM_TYPE_BOOLEAN => Some(MetaValue::Boolean(m.nm_value.nm_boolean)),
+                _ => None,
+            }
+        };
+
+        if let Some(value) = value {
+            res.push((String::from(name), value));
+        }
+        meta = m.next;
+    }
+
+    Ok(res)
+}
+
+/// Dispatches a notification, stamped with the current time, about a plugin to collectd. Messages
+/// that are too long are truncated. See `NotificationBuilder` to set the other fields.
+pub fn dispatch_notification(
+    level: NotificationLevel,
+    plugin: &str,
+    plugin_instance: Option<&str>,
+    message: &str,
+) -> Result<(), SubmitError> {
+    let mut builder = NotificationBuilder::new(level, plugin, message);
+    builder.plugin_instance = plugin_instance;
+    builder.dispatch()
+}
+
+/// Creates a notification to raise with collectd (eg: a read plugin that detects a fault).
+/// Collectd delivers the notification to plugins that registered to receive notifications.
+///
+/// ```
+/// use collectd_plugin::{NotificationBuilder, NotificationLevel};
+/// use std::error::Error;
+///
+/// fn raise() -> Result<(), Box<dyn Error>> {
+///     NotificationBuilder::new(NotificationLevel::Failure, "myplugin", "disk is failing")
+///         .plugin_instance("sda")
+///         .type_("smart")
+///         .dispatch()?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct NotificationBuilder<'a> {
+    severity: NotificationLevel,
+    message: &'a str,
+    plugin: &'a str,
+    plugin_instance: Option<&'a str>,
+    type_: Option<&'a str>,
+    type_instance: Option<&'a str>,
+    host: Option<&'a str>,
+    time: Option<Timestamp>,
+    meta: &'a [(String, MetaValue)],
+}
+
+impl<'a> NotificationBuilder<'a> {
+    /// Primes a notification about the plugin. Messages that are too long are truncated.
+    pub fn new(severity: NotificationLevel, plugin: &'a str, message: &'a str) -> Self {
+        NotificationBuilder {
+            severity,
+            message,
+            plugin,
+            plugin_instance: None,
+            type_: None,
+            type_instance: None,
+            host: None,
+            time: None,
+            meta: &[],
+        }
+    }
+
+    pub fn plugin_instance(mut self, plugin_instance: &'a str) -> Self {
+        self.plugin_instance = Some(plugin_instance);
+        self
+    }
+
+    pub fn type_(mut self, type_: &'a str) -> Self {
+        self.type_ = Some(type_);
+        self
+    }
+
+    pub fn type_instance(mut self, type_instance: &'a str) -> Self {
+        self.type_instance = Some(type_instance);
+        self
+    }
+
+    /// Override the machine that the notification is about. By default, the notification is
+    /// about collectd's hostname.
+    pub fn host(mut self, host: &'a str) -> Self {
+        self.host = Some(host);
+        self
+    }
+
+    /// When the notification was raised. Defaults to the time of dispatch.
+    pub fn time(mut self, time: Timestamp) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// Additional details to attach to the notification
+    pub fn meta(mut self, meta: &'a [(String, MetaValue)]) -> Self {
+        self.meta = meta;
+        self
+    }
+
+    /// Dispatches the notification to collectd
+    pub fn dispatch(self) -> Result<(), SubmitError> {
+        let field = |name, value: Option<&str>| match value {
+            Some(x) => to_array_res(x).map_err(|e| SubmitError::Field(name, e)),
+            None => Ok([0 as c_char; ARR_LENGTH]),
+        };
+
+        let host = match self.host {
+            Some(host) => field("host", Some(host))?,
+            None => default_host(),
+        };
+
+        let time = self
+            .time
+            .map(CdTime::from)
+            .unwrap_or_else(|| CdTime::from(SystemTime::now()));
+
+        let mut notif = notification_t {
+            severity: self.severity as i32,
+            time: time.into(),
+            message: [0; 256],
+            host,
+            plugin: field("plugin", Some(self.plugin))?,
+            plugin_instance: field("plugin_instance", self.plugin_instance)?,
+            type_: field("type", self.type_)?,
+            type_instance: field("type_instance", self.type_instance)?,
+            meta: ptr::null_mut(),
+        };
+
+        copy_truncated(&mut notif.message, self.message);
+        let notif = NotificationMeta::new(notif, self.meta)?;
+        match unsafe { plugin_dispatch_notification(&notif.0) } {
+            0 => Ok(()),
+            i => Err(SubmitError::Dispatch(i)),
+        }
+    }
+}
+
+/// A notification whose meta data is freed when dropped
+struct NotificationMeta(notification_t);
+
+impl NotificationMeta {
+    fn new(
+        notif: notification_t,
+        entries: &[(String, MetaValue)],
+    ) -> Result<NotificationMeta, SubmitError> {
+        let mut res = NotificationMeta(notif);
+        for (key, value) in entries {
+            let n = &mut res.0;
+            let k = to_cstring(key)?;
+            let code = unsafe {
+                match *value {
+                    MetaValue::String(ref s) => {
+                        plugin_notification_meta_add_string(n, k.as_ptr(), to_cstring(s)?.as_ptr())
+                    }
+                    MetaValue::SignedInt(x) => {
+                        plugin_notification_meta_add_signed_int(n, k.as_ptr(), x)
+                    }
+                    MetaValue::UnsignedInt(x) => {
+                        plugin_notification_meta_add_unsigned_int(n, k.as_ptr(), x)
+                    }
+                    MetaValue::Double(x) => plugin_notification_meta_add_double(n, k.as_ptr(), x),
+                    MetaValue::Boolean(x) => plugin_notification_meta_add_boolean(n, k.as_ptr(), x),
+                }
+            };
+
+            if code != 0 {
+                return Err(SubmitError::Meta(key.clone()));
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+impl Drop for NotificationMeta {
+    fn drop(&mut self) {
+        if !self.0.meta.is_null() {
+            unsafe { plugin_notification_meta_free(self.0.meta) };
+        }
+    }
+}
+
+/// Copies as much of the string into the null terminated character array as fits without
+/// splitting a character
+fn copy_truncated(dst: &mut [c_char], src: &str) {
+    let mut end = src.len().min(dst.len() - 1);
+    while !src.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    for (d, &b) in dst.iter_mut().zip(&src.as_bytes()[..end]) {
+        *d = b as c_char;
+    }
+    dst[end] = 0;
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DedupEntry {
+    dispatched: Instant,
+    suppressed: u64,
+}
+
+/// Suppresses repeated notifications that share an identifier and severity within a window of
+/// time. Check-style plugins that run every interval can otherwise flood collectd with the same
+/// notification over and over. Notifications that were suppressed and never followed by another
+/// dispatch after the window has passed are summarized in collectd's log instead.
+///
+/// ```
+/// use collectd_plugin::{NotificationDeduper, NotificationLevel};
+///
+/// fn check(deduper: &NotificationDeduper) {
+///     if let Some(suppressed) = deduper.check("myhost/myplugin", NotificationLevel::Failure) {
+///         // dispatch the notification, mentioning the suppressed count
+///     }
+/// }
+///
+/// let deduper = NotificationDeduper::new("60s").unwrap();
+/// ```
+#[derive(Debug)]
+pub struct NotificationDeduper {
+    window: Duration,
+    seen: Mutex<HashMap<(String, NotificationLevel), DedupEntry>>,
+}
+
+impl NotificationDeduper {
+    /// Creates a deduper where identical notifications are dispatched at most once per `window`
+    pub fn new<T: IntoDuration>(window: T) -> Result<Self, DurationError> {
+        Ok(NotificationDeduper {
+            window: window.into_duration()?,
+            seen: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Returns `Some` if the notification should be dispatched, else `None` when it should be
+    /// dropped. The number contained is how many identical notifications were suppressed since
+    /// the last dispatch, which is useful to append to the notification's message.
+    pub fn check(&self, identifier: &str, severity: NotificationLevel) -> Option<u64> {
+        self.check_at(identifier, severity, Instant::now())
+    }
+
+    fn check_at(&self, identifier: &str, severity: NotificationLevel, now: Instant) -> Option<u64> {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let window = self.window;
+        let key = (String::from(identifier), severity);
+        let result = match seen.get_mut(&key) {
+            Some(ref mut entry) if now.duration_since(entry.dispatched) < window => {
+                entry.suppressed += 1;
+                None
+            }
+            Some(entry) => {
+                let suppressed = entry.suppressed;
+                entry.dispatched = now;
+                entry.suppressed = 0;
+                Some(suppressed)
+            }
+            None => {
+                seen.insert(
+                    key,
+                    DedupEntry {
+                        dispatched: now,
+                        suppressed: 0,
+                    },
+                );
+                Some(0)
+            }
+        };
+
+        // Drop expired entries so that the map doesn't grow unbounded with identifiers that are
+        // no longer seen. Suppressed notifications would otherwise go unreported, so their count
+        // is logged before the entry is dropped.
+        seen.retain(|(identifier, severity), entry| {
+            if now.duration_since(entry.dispatched) < window {
+                return true;
+            }
+
+            if entry.suppressed != 0 {
+                let msg = format!(
+                    "{}: suppressed {} {} notifications",
+                    identifier,
+                    entry.suppressed,
+                    severity.as_ref()
+                );
+                collectd_log(LogLevel::Info, &msg);
+            }
+            false
+        });
+
+        result
+    }
+
+    /// Returns the number of notifications suppressed for the identifier and severity in the
+    /// current window
+    pub fn suppressed(&self, identifier: &str, severity: NotificationLevel) -> u64 {
+        let seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.get(&(String::from(identifier), severity))
+            .map(|x| x.suppressed)
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EscalationState {
+    level: NotificationLevel,
+    warnings: u32,
+    okays: u32,
+}
+
+/// Tracks the results of checks per identifier and only raises notifications when the severity
+/// changes, which is the pattern that check-style plugins follow. A warning that persists for a
+/// number of consecutive checks is escalated to a failure, and a problem is only considered
+/// resolved once a number of consecutive checks are okay.
+///
+/// ```
+/// use collectd_plugin::{Escalator, NotificationLevel};
+///
+/// let escalator = Escalator::new().escalate_after(2);
+/// assert_eq!(None, escalator.record("db", NotificationLevel::Okay));
+/// assert_eq!(Some(NotificationLevel::Warning), escalator.record("db", NotificationLevel::Warning));
+/// assert_eq!(Some(NotificationLevel::Failure), escalator.record("db", NotificationLevel::Warning));
+/// assert_eq!(None, escalator.record("db", NotificationLevel::Warning));
+/// assert_eq!(Some(NotificationLevel::Okay), escalator.record("db", NotificationLevel::Okay));
+/// ```
+#[derive(Debug)]
+pub struct Escalator {
+    escalate_after: u32,
+    recover_after: u32,
+    states: Mutex<HashMap<String, EscalationState>>,
+}
+
+impl Default for Escalator {
+    fn default() -> Self {
+        Escalator::new()
+    }
+}
+
+impl Escalator {
+    /// Creates an escalator that escalates after three consecutive warnings and recovers after
+    /// one okay check
+    pub fn new() -> Self {
+        Escalator {
+            escalate_after: 3,
+            recover_after: 1,
+            states: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The number of consecutive warnings after which a warning is escalated to a failure. Zero
+    /// means that warnings are never escalated.
+    pub fn escalate_after(mut self, checks: u32) -> Self {
+        self.escalate_after = checks;
+        self
+    }
+
+    /// The number of consecutive okay checks after which a warning or failure is resolved
+    pub fn recover_after(mut self, checks: u32) -> Self {
+        self.recover_after = checks.max(1);
+        self
+    }
+
+    /// Records the result of a check and returns the new severity if it changed. Identifiers
+    /// start out okay, so only problems (and their resolution) cause a transition.
+    pub fn record(&self, identifier: &str, result: NotificationLevel) -> Option<NotificationLevel> {
+        let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        let state = states
+            .entry(String::from(identifier))
+            .or_insert(EscalationState {
+                level: NotificationLevel::Okay,
+                warnings: 0,
+                okays: 0,
+            });
+
+        let next = match result {
+            NotificationLevel::Okay => {
+                state.warnings = 0;
+                state.okays += 1;
+                if state.okays >= self.recover_after {
+                    NotificationLevel::Okay
+                } else {
+                    state.level
+                }
+            }
+            NotificationLevel::Warning => {
+                state.okays = 0;
+                state.warnings += 1;
+                let escalate = self.escalate_after != 0 && state.warnings >= self.escalate_after;
+                if escalate || state.level == NotificationLevel::Failure {
+                    NotificationLevel::Failure
+                } else {
+                    NotificationLevel::Warning
+                }
+            }
+            NotificationLevel::Failure => {
+                state.okays = 0;
+                NotificationLevel::Failure
+            }
+        };
+
+        if next == state.level {
+            None
+        } else {
+            state.level = next;
+            Some(next)
+        }
+    }
+
+    /// Records the result of a check and dispatches the notification with the new severity if
+    /// the severity changed. Returns if a notification was dispatched.
+    pub fn notify(
+        &self,
+        identifier: &str,
+        result: NotificationLevel,
+        notification: NotificationBuilder<'_>,
+    ) -> Result<bool, SubmitError> {
+        match self.record(identifier, result) {
+            Some(severity) => {
+                let mut notification = notification;
+                notification.severity = severity;
+                notification.dispatch().map(|_| true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// The current severity of the identifier
+    pub fn level(&self, identifier: &str) -> NotificationLevel {
+        let states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        states
+            .get(identifier)
+            .map(|x| x.level)
+            .unwrap_or(NotificationLevel::Okay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::notification_meta_s__bindgen_ty_1;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn test_copy_truncated() {
+        let mut dst = [1 as c_char; 4];
+        copy_truncated(&mut dst, "ab");
+        assert_eq!(dst, [b'a' as c_char, b'b' as c_char, 0, 1]);
+
+        copy_truncated(&mut dst, "abcdef");
+        assert_eq!(dst, [b'a' as c_char, b'b' as c_char, b'c' as c_char, 0]);
+
+        // "é" is two bytes and shouldn't be split
+        copy_truncated(&mut dst, "abé");
+        assert_eq!(dst, [b'a' as c_char, b'b' as c_char, 0, 0]);
+    }
+
+    #[test]
+    fn test_dispatch_notification() {
+        let res = dispatch_notification(
+            NotificationLevel::Failure,
+            "myplugin",
+            Some("a"),
+            "instance quarantined",
+        );
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_notification_from() {
+        let mut threshold = notification_meta_s {
+            name: [0; ARR_LENGTH],
+            type_: NM_TYPE_DOUBLE,
+            nm_value: notification_meta_s__bindgen_ty_1 { nm_double: 90.5 },
+            next: ptr::null_mut(),
+        };
+        copy_truncated(&mut threshold.name, "CurrentValue");
+
+        let mut notif = notification_t {
+            severity: NOTIF_WARNING as i32,
+            time: CdTime::from(Duration::from_secs(1)).into(),
+            message: [0; 256],
+            host: [0; ARR_LENGTH],
+            plugin: [0; ARR_LENGTH],
+            plugin_instance: [0; ARR_LENGTH],
+            type_: [0; ARR_LENGTH],
+            type_instance: [0; ARR_LENGTH],
+            meta: &mut threshold,
+        };
+        copy_truncated(&mut notif.message, "Host localhost, plugin load");
+        copy_truncated(&mut notif.host, "localhost");
+        copy_truncated(&mut notif.plugin, "load");
+        copy_truncated(&mut notif.type_, "load");
+
+        let actual = Notification::from(&notif).unwrap();
+        assert_eq!(NotificationLevel::Warning, actual.severity);
+        assert_eq!("Host localhost, plugin load", actual.message);
+        assert_eq!("localhost", actual.host);
+        assert_eq!("load", actual.plugin);
+        assert_eq!(None, actual.plugin_instance);
+        assert_eq!(Some("load"), actual.type_);
+        assert_eq!(
+            vec![(String::from("CurrentValue"), MetaValue::Double(90.5))],
+            actual.meta
+        );
+
+        notif.severity = 3;
+        assert!(Notification::from(&notif).is_err());
+    }
+
+    #[test]
+    fn test_notification_builder() {
+        let meta = [(String::from("CurrentValue"), MetaValue::Double(90.5))];
+        let res = NotificationBuilder::new(NotificationLevel::Warning, "myplugin", "too hot")
+            .plugin_instance("a")
+            .type_("temperature")
+            .host("remote")
+            .meta(&meta)
+            .dispatch();
+        assert!(res.is_ok());
+
+        let long = "a".repeat(ARR_LENGTH);
+        let res = NotificationBuilder::new(NotificationLevel::Okay, "myplugin", "recovered")
+            .type_instance(&long)
+            .dispatch();
+        match res {
+            Err(SubmitError::Field("type_instance", _)) => {}
+            x => panic!("unexpected result: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_notification_level_try_from() {
+        assert_eq!(
+            Ok(NotificationLevel::Failure),
+            NotificationLevel::try_from(NOTIF_FAILURE)
+        );
+        assert_eq!(
+            Ok(NotificationLevel::Okay),
+            NotificationLevel::try_from(NOTIF_OKAY)
+        );
+        assert!(NotificationLevel::try_from(3).is_err());
+
+        for level in NotificationLevel::iter() {
+            assert_eq!(Ok(level), NotificationLevel::try_from(level as u32));
+        }
+    }
+
+    #[test]
+    fn test_dedup_within_window() {
+        let deduper = NotificationDeduper::new(Duration::from_secs(10)).unwrap();
+        let now = Instant::now();
+        assert_eq!(
+            Some(0),
+            deduper.check_at("a", NotificationLevel::Warning, now)
+        );
+        assert_eq!(None, deduper.check_at("a", NotificationLevel::Warning, now));
+        assert_eq!(None, deduper.check_at("a", NotificationLevel::Warning, now));
+        assert_eq!(2, deduper.suppressed("a", NotificationLevel::Warning));
+        assert_eq!(
+            Some(0),
+            deduper.check_at("b", NotificationLevel::Warning, now)
+        );
+        assert_eq!(
+            Some(0),
+            deduper.check_at("a", NotificationLevel::Failure, now)
+        );
+    }
+
+    #[test]
+    fn test_dedup_after_window() {
+        let deduper = NotificationDeduper::new(Duration::from_secs(10)).unwrap();
+        let now = Instant::now();
+        let later = now + Duration::from_secs(11);
+        assert_eq!(
+            Some(0),
+            deduper.check_at("a", NotificationLevel::Warning, now)
+        );
+        assert_eq!(None, deduper.check_at("a", NotificationLevel::Warning, now));
+        assert_eq!(
+            Some(1),
+            deduper.check_at("a", NotificationLevel::Warning, later)
+        );
+        assert_eq!(0, deduper.suppressed("a", NotificationLevel::Warning));
+    }
+
+    #[test]
+    fn test_dedup_expires_suppressed() {
+        let deduper = NotificationDeduper::new(Duration::from_secs(10)).unwrap();
+        let now = Instant::now();
+        let later = now + Duration::from_secs(11);
+        assert_eq!(
+            Some(0),
+            deduper.check_at("a", NotificationLevel::Warning, now)
+        );
+        assert_eq!(None, deduper.check_at("a", NotificationLevel::Warning, now));
+        assert_eq!(
+            Some(0),
+            deduper.check_at("b", NotificationLevel::Warning, later)
+        );
+        assert_eq!(0, deduper.suppressed("a", NotificationLevel::Warning));
+        assert_eq!(1, deduper.seen.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_escalator_escalates_and_recovers() {
+        let escalator = Escalator::new().escalate_after(3).recover_after(2);
+        let warn = NotificationLevel::Warning;
+        let okay = NotificationLevel::Okay;
+        assert_eq!(Some(warn), escalator.record("a", warn));
+        assert_eq!(None, escalator.record("a", warn));
+
+        // A single okay check isn't enough to recover and resets the count of warnings
+        assert_eq!(None, escalator.record("a", okay));
+        assert_eq!(None, escalator.record("a", warn));
+        assert_eq!(None, escalator.record("a", warn));
+        assert_eq!(
+            Some(NotificationLevel::Failure),
+            escalator.record("a", warn)
+        );
+
+        // A warning doesn't de-escalate a failure
+        assert_eq!(None, escalator.record("a", warn));
+        assert_eq!(NotificationLevel::Failure, escalator.level("a"));
+        assert_eq!(okay, escalator.level("b"));
+
+        assert_eq!(None, escalator.record("a", okay));
+        assert_eq!(Some(okay), escalator.record("a", okay));
+    }
+
+    #[test]
+    fn test_escalator_never_escalates() {
+        let escalator = Escalator::new().escalate_after(0);
+        let warn = NotificationLevel::Warning;
+        assert_eq!(Some(warn), escalator.record("a", warn));
+        for _ in 0..10 {
+            assert_eq!(None, escalator.record("a", warn));
+        }
+
+        let notification = NotificationBuilder::new(warn, "myplugin", "slow");
+        let failure = NotificationLevel::Failure;
+        assert!(escalator
+            .notify("a", failure, notification.clone())
+            .unwrap());
+        assert!(!escalator.notify("a", failure, notification).unwrap());
+    }
+}
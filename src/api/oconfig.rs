@@ -2,8 +2,9 @@ use crate::bindings::{
     oconfig_item_t, oconfig_value_s__bindgen_ty_1, oconfig_value_t, OCONFIG_TYPE_BOOLEAN,
     OCONFIG_TYPE_NUMBER, OCONFIG_TYPE_STRING,
 };
-use crate::errors::ConfigError;
+use crate::errors::{ConfigError, ParseConfigError};
 use std::ffi::CStr;
+use std::os::raw::c_int;
 use std::slice;
 
 /// A parsed value from the Collectd config
@@ -32,6 +33,309 @@ pub struct ConfigItem<'a> {
     pub children: Vec<ConfigItem<'a>>,
 }
 
+/// Conversion from a single config value into a Rust type. Numbers are only converted into
+/// integers when they are whole and within range.
+pub trait FromConfigValue<'a>: Sized {
+    /// Performs the conversion, returning `None` if the value is of the wrong type
+    fn from_config_value(value: &ConfigValue<'a>) -> Option<Self>;
+}
+
+impl<'a> FromConfigValue<'a> for bool {
+    fn from_config_value(value: &ConfigValue<'a>) -> Option<Self> {
+        match *value {
+            ConfigValue::Boolean(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> FromConfigValue<'a> for f64 {
+    fn from_config_value(value: &ConfigValue<'a>) -> Option<Self> {
+        match *value {
+            ConfigValue::Number(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> FromConfigValue<'a> for &'a str {
+    fn from_config_value(value: &ConfigValue<'a>) -> Option<Self> {
+        match *value {
+            ConfigValue::String(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> FromConfigValue<'a> for String {
+    fn from_config_value(value: &ConfigValue<'a>) -> Option<Self> {
+        <&str>::from_config_value(value).map(String::from)
+    }
+}
+
+macro_rules! from_config_integer {
+    ($($t:ty),*) => {
+        $(
+            impl<'a> FromConfigValue<'a> for $t {
+                fn from_config_value(value: &ConfigValue<'a>) -> Option<Self> {
+                    match *value {
+                        ConfigValue::Number(x)
+                            if x.trunc() == x
+                                && x >= <$t>::min_value() as f64
+                                && x <= <$t>::max_value() as f64 =>
+                        {
+                            Some(x as $t)
+                        }
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+from_config_integer!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+/// An owned copy of a `ConfigItem` so that config can outlive collectd's config callback
+#[derive(Debug, PartialEq, Clone)]
+pub struct OwnedConfigItem {
+    key: String,
+    values: Vec<OwnedConfigValue>,
+    children: Vec<OwnedConfigItem>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum OwnedConfigValue {
+    Number(f64),
+    Boolean(bool),
+    String(String),
+}
+
+impl<'a, 'b> From<&'b ConfigItem<'a>> for OwnedConfigItem {
+    fn from(item: &'b ConfigItem<'a>) -> Self {
+        let values = item
+            .values
+            .iter()
+            .map(|x| match *x {
+                ConfigValue::Number(x) => OwnedConfigValue::Number(x),
+                ConfigValue::Boolean(x) => OwnedConfigValue::Boolean(x),
+                ConfigValue::String(x) => OwnedConfigValue::String(String::from(x)),
+            })
+            .collect();
+
+        OwnedConfigItem {
+            key: String::from(item.key),
+            values,
+            children: item.children.iter().map(OwnedConfigItem::from).collect(),
+        }
+    }
+}
+
+impl OwnedConfigItem {
+    /// Borrows the owned config as a `ConfigItem`
+    pub fn as_item(&self) -> ConfigItem<'_> {
+        let values = self
+            .values
+            .iter()
+            .map(|x| match *x {
+                OwnedConfigValue::Number(x) => ConfigValue::Number(x),
+                OwnedConfigValue::Boolean(x) => ConfigValue::Boolean(x),
+                OwnedConfigValue::String(ref x) => ConfigValue::String(x.as_str()),
+            })
+            .collect();
+
+        ConfigItem {
+            key: self.key.as_str(),
+            values,
+            children: self.children.iter().map(|x| x.as_item()).collect(),
+        }
+    }
+}
+
+impl OwnedConfigItem {
+    /// Parses text in collectd's config syntax (eg: the contents of a file that is shared by
+    /// several plugins) into the top level items. Like collectd, keys and `true` / `false` (as
+    /// well as `yes` / `no` and `on` / `off`) are unquoted, numbers are unquoted, and any other
+    /// unquoted word is a string.
+    ///
+    /// ```
+    /// use collectd_plugin::{ConfigValue, OwnedConfigItem};
+    ///
+    /// let items = OwnedConfigItem::parse("<Proxy \"corp\">\n  Port 3128\n</Proxy>").unwrap();
+    /// let proxy = items[0].as_item();
+    /// assert_eq!(vec![ConfigValue::String("corp")], proxy.values);
+    /// assert_eq!("Port", proxy.children[0].key);
+    /// ```
+    pub fn parse(s: &str) -> Result<Vec<OwnedConfigItem>, ParseConfigError> {
+        // The blocks that are open, starting with a root that holds the top level items
+        let mut stack = vec![(
+            0,
+            OwnedConfigItem {
+                key: String::new(),
+                values: Vec::new(),
+                children: Vec::new(),
+            },
+        )];
+
+        let mut logical = String::new();
+        for (ind, line) in s.lines().enumerate() {
+            let lineno = ind + 1;
+
+            // A trailing backslash continues the line
+            if let Some(x) = line.strip_suffix('\\') {
+                logical.push_str(x);
+                continue;
+            }
+
+            logical.push_str(line);
+            let tokens = tokenize(&logical).map_err(|e| ParseConfigError(lineno, e))?;
+            logical.clear();
+
+            match tokens.as_slice() {
+                [] => {}
+                [Token::Open, Token::Bare(key), Token::Close] if key.starts_with('/') => {
+                    let (_, block) = stack
+                        .pop()
+                        .filter(|_| !stack.is_empty())
+                        .ok_or_else(|| ParseConfigError(lineno, format!("unexpected <{}>", key)))?;
+
+                    if !block.key.eq_ignore_ascii_case(&key[1..]) {
+                        let msg = format!("expected </{}> but found <{}>", block.key, key);
+                        return Err(ParseConfigError(lineno, msg));
+                    }
+
+                    if let Some((_, parent)) = stack.last_mut() {
+                        parent.children.push(block);
+                    }
+                }
+                [Token::Open, Token::Bare(key), values @ .., Token::Close]
+                    if are_values(values) =>
+                {
+                    let item = OwnedConfigItem {
+                        key: key.clone(),
+                        values: config_values(values),
+                        children: Vec::new(),
+                    };
+                    stack.push((lineno, item));
+                }
+                [Token::Bare(key), values @ ..] if are_values(values) => {
+                    let item = OwnedConfigItem {
+                        key: key.clone(),
+                        values: config_values(values),
+                        children: Vec::new(),
+                    };
+
+                    if let Some((_, parent)) = stack.last_mut() {
+                        parent.children.push(item);
+                    }
+                }
+                _ => return Err(ParseConfigError(lineno, String::from("unexpected syntax"))),
+            }
+        }
+
+        match stack.pop() {
+            Some((_, root)) if stack.is_empty() => Ok(root.children),
+            Some((lineno, block)) => {
+                let msg = format!("<{}> is never closed", block.key);
+                Err(ParseConfigError(lineno, msg))
+            }
+            None => unreachable!("root of the config to be on the stack"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Quoted(String),
+    Bare(String),
+}
+
+/// Splits a line of config into its tokens, stopping at a comment
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '#' => break,
+            '<' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            '>' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(x) => s.push(x),
+                            None => return Err(String::from("unterminated string")),
+                        },
+                        Some(x) => s.push(x),
+                        None => return Err(String::from("unterminated string")),
+                    }
+                }
+                tokens.push(Token::Quoted(s));
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            _ => {
+                let mut s = String::new();
+                while let Some(&x) = chars.peek() {
+                    if x.is_whitespace() || x == '"' || x == '#' || x == '<' || x == '>' {
+                        break;
+                    }
+                    s.push(x);
+                    chars.next();
+                }
+                tokens.push(Token::Bare(s));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn are_values(tokens: &[Token]) -> bool {
+    tokens
+        .iter()
+        .all(|x| matches!(*x, Token::Quoted(_) | Token::Bare(_)))
+}
+
+fn config_values(tokens: &[Token]) -> Vec<OwnedConfigValue> {
+    tokens
+        .iter()
+        .filter_map(|x| match *x {
+            Token::Quoted(ref s) => Some(OwnedConfigValue::String(s.clone())),
+            Token::Bare(ref s) => Some(bare_value(s)),
+            Token::Open | Token::Close => None,
+        })
+        .collect()
+}
+
+fn bare_value(s: &str) -> OwnedConfigValue {
+    let lower = s.to_ascii_lowercase();
+    match lower.as_str() {
+        "true" | "yes" | "on" => return OwnedConfigValue::Boolean(true),
+        "false" | "no" | "off" => return OwnedConfigValue::Boolean(false),
+        _ => {}
+    }
+
+    // Only words that look like numbers are numbers, so that words like "inf" stay strings
+    let numeric = s.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+' || c == '.');
+    match s.parse::<f64>() {
+        Ok(x) if numeric => OwnedConfigValue::Number(x),
+        _ => OwnedConfigValue::String(String::from(s)),
+    }
+}
+
 impl<'a> ConfigValue<'a> {
     pub unsafe fn from(value: &oconfig_value_t) -> Result<ConfigValue<'_>, ConfigError> {
         match value.value {
@@ -60,19 +364,87 @@ impl<'a> ConfigValue<'a> {
 }
 
 impl<'a> ConfigItem<'a> {
+    /// Converts the item's only value. Errors if the item does not have exactly one value or if
+    /// the value is of the wrong type.
+    pub fn value<T: FromConfigValue<'a>>(&self) -> Result<T, ConfigError> {
+        match self.values.as_slice() {
+            [value] => T::from_config_value(value)
+                .ok_or_else(|| ConfigError::ValueType(String::from(self.key))),
+            values => Err(ConfigError::ValueCount(
+                String::from(self.key),
+                values.len(),
+            )),
+        }
+    }
+
+    /// Returns the items with the given key in declaration order. Keys are compared case
+    /// insensitively, like collectd's C plugins do.
+    pub fn find_all<'b>(
+        items: &'b [ConfigItem<'a>],
+        key: &'b str,
+    ) -> impl Iterator<Item = &'b ConfigItem<'a>> + 'b {
+        items
+            .iter()
+            .filter(move |x| x.key.eq_ignore_ascii_case(key))
+    }
+
+    /// Collects the value of every item with the given key in declaration order, so that
+    /// `Server "a"` and `Server "b"` become `vec!["a", "b"]`. Each item must have exactly one
+    /// value.
+    ///
+    /// ```
+    /// use collectd_plugin::{ConfigItem, ConfigValue};
+    ///
+    /// let server = |name| ConfigItem {
+    ///     key: "Server",
+    ///     values: vec![ConfigValue::String(name)],
+    ///     children: vec![],
+    /// };
+    ///
+    /// let config = vec![server("a"), server("b")];
+    /// let servers: Vec<&str> = ConfigItem::collect(&config, "server").unwrap();
+    /// assert_eq!(servers, vec!["a", "b"]);
+    /// ```
+    pub fn collect<T: FromConfigValue<'a>>(
+        items: &[ConfigItem<'a>],
+        key: &str,
+    ) -> Result<Vec<T>, ConfigError> {
+        ConfigItem::find_all(items, key)
+            .map(|x| x.value())
+            .collect()
+    }
+
+    /// Groups the items by key, with the keys ordered by their first appearance and each key's
+    /// items kept in declaration order. Keys are compared case insensitively and the first
+    /// spelling of a key is the one returned.
+    pub fn multimap<'b>(items: &'b [ConfigItem<'a>]) -> Vec<(&'a str, Vec<&'b ConfigItem<'a>>)> {
+        let mut result: Vec<(&'a str, Vec<&'b ConfigItem<'a>>)> = Vec::new();
+        for item in items {
+            match result
+                .iter_mut()
+                .find(|(key, _)| key.eq_ignore_ascii_case(item.key))
+            {
+                Some((_, group)) => group.push(item),
+                None => result.push((item.key, vec![item])),
+            }
+        }
+
+        result
+    }
+
     pub unsafe fn from<'b>(item: &'b oconfig_item_t) -> Result<ConfigItem<'b>, ConfigError> {
         let key = CStr::from_ptr(item.key)
             .to_str()
             .map_err(ConfigError::StringDecode)?;
 
         let values: Result<Vec<ConfigValue<'b>>, ConfigError> =
-            slice::from_raw_parts(item.values, item.values_num as usize)
+            raw_slice(item.values, item.values_num)
                 .iter()
                 .map(|x| ConfigValue::from(x))
                 .collect();
 
         let children: Result<Vec<ConfigItem<'b>>, ConfigError> =
-            slice::from_raw_parts(item.children, item.children_num as usize)
+            raw_slice(item.children, item.children_num)
                 .iter()
                 .map(|x| ConfigItem::from(x))
                 .collect();
@@ -84,3 +456,183 @@ impl<'a> ConfigItem<'a> {
         })
     }
 }
+
+/// Collectd leaves the array of an item without values or children as null
+unsafe fn raw_slice<'b, T>(ptr: *const T, len: c_int) -> &'b [T] {
+    if ptr.is_null() || len <= 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, len as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item<'a>(key: &'a str, values: Vec<ConfigValue<'a>>) -> ConfigItem<'a> {
+        ConfigItem {
+            key,
+            values,
+            children: vec![],
+        }
+    }
+
+    #[test]
+    fn test_config_value_conversions() {
+        assert_eq!(
+            Some(80u16),
+            u16::from_config_value(&ConfigValue::Number(80.0))
+        );
+        assert_eq!(None, u16::from_config_value(&ConfigValue::Number(80.5)));
+        assert_eq!(None, u8::from_config_value(&ConfigValue::Number(300.0)));
+        assert_eq!(None, u32::from_config_value(&ConfigValue::Number(-1.0)));
+        assert_eq!(
+            Some(-1i32),
+            i32::from_config_value(&ConfigValue::Number(-1.0))
+        );
+        assert_eq!(
+            Some(true),
+            bool::from_config_value(&ConfigValue::Boolean(true))
+        );
+        assert_eq!(None, bool::from_config_value(&ConfigValue::String("true")));
+        assert_eq!(
+            Some(String::from("a")),
+            String::from_config_value(&ConfigValue::String("a"))
+        );
+    }
+
+    #[test]
+    fn test_config_collect() {
+        let items = vec![
+            item("Server", vec![ConfigValue::String("a")]),
+            item("Port", vec![ConfigValue::Number(80.0)]),
+            item("server", vec![ConfigValue::String("b")]),
+        ];
+
+        let servers: Vec<String> = ConfigItem::collect(&items, "Server").unwrap();
+        assert_eq!(servers, vec![String::from("a"), String::from("b")]);
+
+        let ports: Vec<u16> = ConfigItem::collect(&items, "port").unwrap();
+        assert_eq!(ports, vec![80]);
+
+        let missing: Vec<bool> = ConfigItem::collect(&items, "Missing").unwrap();
+        assert!(missing.is_empty());
+
+        let res: Result<Vec<bool>, _> = ConfigItem::collect(&items, "Server");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "unexpected value type for config key: Server"
+        );
+    }
+
+    #[test]
+    fn test_config_value_count() {
+        let multi = item(
+            "Server",
+            vec![ConfigValue::String("a"), ConfigValue::Number(80.0)],
+        );
+        let res: Result<&str, _> = multi.value();
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "expected a single value for config key Server but found 2"
+        );
+    }
+
+    #[test]
+    fn test_owned_config_roundtrip() {
+        let config = ConfigItem {
+            key: "Node",
+            values: vec![ConfigValue::String("a"), ConfigValue::Boolean(true)],
+            children: vec![item("Port", vec![ConfigValue::Number(80.0)])],
+        };
+
+        let owned = OwnedConfigItem::from(&config);
+        assert_eq!(config, owned.as_item());
+    }
+
+    #[test]
+    fn test_config_multimap() {
+        let items = vec![
+            item("Server", vec![ConfigValue::String("a")]),
+            item("Port", vec![ConfigValue::Number(80.0)]),
+            item("server", vec![ConfigValue::String("b")]),
+        ];
+
+        let map = ConfigItem::multimap(&items);
+        assert_eq!(2, map.len());
+        assert_eq!("Server", map[0].0);
+        assert_eq!(vec![&items[0], &items[2]], map[0].1);
+        assert_eq!("Port", map[1].0);
+        assert_eq!(vec![&items[1]], map[1].1);
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let text = r#"
+# Settings shared by the plugins
+Proxy "http://proxy:3128" # trailing comment
+Timeout 2.5
+Verify off
+LogLevel info
+<Credentials "api">
+  User "a \"quoted\" name"
+  Tags "a" \
+       "b"
+</credentials>
+"#;
+
+        let items = OwnedConfigItem::parse(text).unwrap();
+        let items: Vec<ConfigItem<'_>> = items.iter().map(|x| x.as_item()).collect();
+        assert_eq!(5, items.len());
+        assert_eq!(
+            item("Proxy", vec![ConfigValue::String("http://proxy:3128")]),
+            items[0]
+        );
+        assert_eq!(item("Timeout", vec![ConfigValue::Number(2.5)]), items[1]);
+        assert_eq!(item("Verify", vec![ConfigValue::Boolean(false)]), items[2]);
+        assert_eq!(
+            item("LogLevel", vec![ConfigValue::String("info")]),
+            items[3]
+        );
+        assert_eq!(
+            ConfigItem {
+                key: "Credentials",
+                values: vec![ConfigValue::String("api")],
+                children: vec![
+                    item("User", vec![ConfigValue::String("a \"quoted\" name")]),
+                    item(
+                        "Tags",
+                        vec![ConfigValue::String("a"), ConfigValue::String("b")]
+                    ),
+                ],
+            },
+            items[4]
+        );
+    }
+
+    #[test]
+    fn test_parse_config_errors() {
+        let err = |s| OwnedConfigItem::parse(s).unwrap_err().to_string();
+        assert_eq!(
+            "invalid config on line 2: <Node> is never closed",
+            err("Key 1\n<Node>\n")
+        );
+        assert_eq!(
+            "invalid config on line 1: unexpected </Node>",
+            err("</Node>")
+        );
+        assert_eq!(
+            "invalid config on line 2: expected </A> but found </B>",
+            err("<A>\n</B>")
+        );
+        assert_eq!(
+            "invalid config on line 1: unterminated string",
+            err("Key \"a")
+        );
+        assert_eq!(
+            "invalid config on line 1: unexpected syntax",
+            err("\"Key\" 1")
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use super::{CdTime, Timestamp, Value, ValueReport};
+use std::time::Duration;
+
+/// The previous observation of a value, which is needed to turn counters, derives, and absolutes
+/// into rates the same way collectd does.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RateState {
+    /// The value previously observed
+    pub previous: Value,
+
+    /// Time elapsed since the previous observation
+    pub elapsed: Duration,
+}
+
+impl RateState {
+    /// Creates the state from the previous value and the timestamps of both observations. A
+    /// current timestamp that precedes the previous one results in no time having elapsed, and no
+    /// rate can be computed.
+    pub fn new(previous: Value, previous_time: Timestamp, time: Timestamp) -> Self {
+        let CdTime(prev) = CdTime::from(previous_time);
+        let CdTime(now) = CdTime::from(time);
+        RateState {
+            previous,
+            elapsed: Duration::from_nanos(now.saturating_sub(prev)),
+        }
+    }
+}
+
+/// Difference between two counter observations. A smaller new value means the counter wrapped
+/// around, and it is assumed to be a 32 bit counter unless the old value didn't fit in 32 bits.
+/// This mirrors collectd's `counter_diff`.
+fn counter_diff(old: u64, new: u64) -> u64 {
+    if old > new {
+        if old <= u64::from(u32::MAX) {
+            (u64::from(u32::MAX) - old) + new + 1
+        } else {
+            (u64::MAX - old) + new + 1
+        }
+    } else {
+        new - old
+    }
+}
+
+fn seconds(dur: Duration) -> f64 {
+    dur.as_secs() as f64 + f64::from(dur.subsec_nanos()) / 1e9
+}
+
+impl<'a> ValueReport<'a> {
+    /// Returns the value if it is a gauge. Counters, derives, and absolutes are not
+    /// meaningful on their own and need to be converted with `as_rate`.
+    ///
+    /// ```
+    /// # use collectd_plugin::{Value, ValueReport};
+    /// let report = ValueReport { name: "value", value: Value::Gauge(2.5), min: 0.0, max: 10.0 };
+    /// assert_eq!(Some(2.5), report.as_gauge());
+    /// ```
+    pub fn as_gauge(&self) -> Option<f64> {
+        match self.value {
+            Value::Gauge(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// The amount a monotonic value has increased since the previous observation:
+    ///
+    /// - Counters take wrap around into account
+    /// - Derives that decreased have been reset and yield `None`
+    /// - Absolutes are reset on every read, so the value is the delta
+    ///
+    /// Gauges and a previous value of a different type yield `None`.
+    pub fn as_counter_delta(&self, state: &RateState) -> Option<u64> {
+        match (self.value, state.previous) {
+            (Value::Counter(new), Value::Counter(old)) => Some(counter_diff(old, new)),
+            (Value::Derive(new), Value::Derive(old)) if new >= old => {
+                Some(new.wrapping_sub(old) as u64)
+            }
+            (Value::Absolute(new), Value::Absolute(_)) => Some(new),
+            _ => None,
+        }
+    }
+
+    /// Converts the value into a per second rate with the semantics of its data source type:
+    ///
+    /// - Gauges are returned as is
+    /// - Counters take wrap around into account. A rate above the data source's maximum is
+    ///   indicative of a counter reset and is discarded.
+    /// - Derives may be negative, unless the data source's minimum rules it out, in which case the
+    ///   rate is discarded
+    /// - Absolutes are divided by the elapsed time
+    ///
+    /// `None` is returned when no time has elapsed, when the rate is discarded, or the previous
+    /// value is of a different type.
+    ///
+    /// ```
+    /// # use collectd_plugin::{RateState, Value, ValueReport};
+    /// # use std::time::Duration;
+    /// let report = ValueReport { name: "value", value: Value::Derive(40), min: 0.0, max: f64::NAN };
+    /// let state = RateState { previous: Value::Derive(20), elapsed: Duration::from_secs(10) };
+    /// assert_eq!(Some(2.0), report.as_rate(&state));
+    /// ```
+    pub fn as_rate(&self, state: &RateState) -> Option<f64> {
+        if let Value::Gauge(x) = self.value {
+            return Some(x);
+        }
+
+        let elapsed = seconds(state.elapsed);
+        if elapsed <= 0.0 {
+            return None;
+        }
+
+        let rate = match (self.value, state.previous) {
+            (Value::Counter(new), Value::Counter(old)) => counter_diff(old, new) as f64 / elapsed,
+            (Value::Derive(new), Value::Derive(old)) => new.wrapping_sub(old) as f64 / elapsed,
+            (Value::Absolute(new), Value::Absolute(_)) => new as f64 / elapsed,
+            _ => return None,
+        };
+
+        if (!self.min.is_nan() && rate < self.min) || (!self.max.is_nan() && rate > self.max) {
+            None
+        } else {
+            Some(rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(value: Value, min: f64, max: f64) -> ValueReport<'static> {
+        ValueReport {
+            name: "value",
+            value,
+            min,
+            max,
+        }
+    }
+
+    fn state(previous: Value, secs: u64) -> RateState {
+        RateState {
+            previous,
+            elapsed: Duration::from_secs(secs),
+        }
+    }
+
+    #[test]
+    fn test_counter_wraps() {
+        assert_eq!(10, counter_diff(5, 15));
+        assert_eq!(11, counter_diff(u64::from(u32::MAX) - 5, 5));
+        assert_eq!(11, counter_diff(u64::MAX - 5, 5));
+
+        let r = report(Value::Counter(5), 0.0, f64::NAN);
+        let s = state(Value::Counter(u64::from(u32::MAX) - 5), 11);
+        assert_eq!(Some(11), r.as_counter_delta(&s));
+        assert_eq!(Some(1.0), r.as_rate(&s));
+    }
+
+    #[test]
+    fn test_counter_reset_above_max() {
+        let r = report(Value::Counter(0), 0.0, 1000.0);
+        let s = state(Value::Counter(u64::from(u32::MAX) + 10), 10);
+        assert_eq!(None, r.as_rate(&s));
+    }
+
+    #[test]
+    fn test_derive_sign() {
+        let s = state(Value::Derive(100), 10);
+        assert_eq!(
+            Some(-5.0),
+            report(Value::Derive(50), f64::NAN, f64::NAN).as_rate(&s)
+        );
+        assert_eq!(None, report(Value::Derive(50), 0.0, f64::NAN).as_rate(&s));
+        assert_eq!(
+            None,
+            report(Value::Derive(50), 0.0, f64::NAN).as_counter_delta(&s)
+        );
+        assert_eq!(
+            Some(5.0),
+            report(Value::Derive(150), 0.0, f64::NAN).as_rate(&s)
+        );
+    }
+
+    #[test]
+    fn test_absolute_and_gauge() {
+        let s = state(Value::Absolute(1000), 4);
+        assert_eq!(
+            Some(5.0),
+            report(Value::Absolute(20), 0.0, f64::NAN).as_rate(&s)
+        );
+        assert_eq!(
+            Some(20),
+            report(Value::Absolute(20), 0.0, f64::NAN).as_counter_delta(&s)
+        );
+
+        let gauge = report(Value::Gauge(1.5), f64::NAN, f64::NAN);
+        assert_eq!(Some(1.5), gauge.as_gauge());
+        assert_eq!(Some(1.5), gauge.as_rate(&s));
+        assert_eq!(None, gauge.as_counter_delta(&s));
+        assert_eq!(
+            None,
+            report(Value::Derive(1), f64::NAN, f64::NAN).as_gauge()
+        );
+    }
+
+    #[test]
+    fn test_no_rate_without_elapsed_time_or_matching_type() {
+        let r = report(Value::Counter(10), f64::NAN, f64::NAN);
+        assert_eq!(None, r.as_rate(&state(Value::Counter(5), 0)));
+        assert_eq!(None, r.as_rate(&state(Value::Derive(5), 10)));
+    }
+
+    #[test]
+    fn test_rate_state_new() {
+        let prev = Timestamp::from(CdTime(1_000_000_000));
+        let now = Timestamp::from(CdTime(11_000_000_000));
+        let s = RateState::new(Value::Counter(0), prev, now);
+        assert_eq!(Duration::from_secs(10), s.elapsed);
+        assert_eq!(
+            Duration::from_secs(0),
+            RateState::new(Value::Counter(0), now, prev).elapsed
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use super::Identifier;
+use std::borrow::Cow;
+
+/// Named rules for rewriting the components of an identifier (host, plugin, type, and instances)
+/// into a form that a backend can store, so that writers of the same backend agree on how a
+/// series is named. With the `serde` feature, a preset can be deserialized from the plugin's
+/// configuration (eg: `Sanitize "graphite"`).
+///
+/// | Preset            | Rewritten                                           | Into            |
+/// |-------------------|-----------------------------------------------------|-----------------|
+/// | `Graphite`        | `.` `/` `\` `"`, whitespace, and control characters | `_`             |
+/// | `PrometheusLabel` | anything other than `[a-zA-Z0-9_]`                  | `_`             |
+/// |                   | a leading digit                                     | prefixed by `_` |
+/// | `Influx`          | `,` `=` and space                                   | prefixed by `\` |
+/// |                   | other whitespace and control characters             | `_`             |
+///
+/// The rewrites that replace a character with `_` are lossy (`cpu.0` and `cpu_0` both become
+/// `cpu_0`), while the escapes of `Influx` are undone by the backend when it parses a line.
+///
+/// ```
+/// use collectd_plugin::SanitizePreset;
+///
+/// assert_eq!("db_example_com", SanitizePreset::Graphite.sanitize("db.example.com"));
+/// assert_eq!("_0_idle", SanitizePreset::PrometheusLabel.sanitize("0-idle"));
+/// assert_eq!("disk\\ io", SanitizePreset::Influx.sanitize("disk io"));
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SanitizePreset {
+    /// A component of a dotted graphite path
+    Graphite,
+
+    /// A prometheus label name or a part of a metric name
+    PrometheusLabel,
+
+    /// A measurement, tag key, or tag value of influx's line protocol
+    Influx,
+}
+
+impl SanitizePreset {
+    /// Rewrites the characters of the string that the backend can't store. The string is
+    /// borrowed when there is nothing to rewrite.
+    pub fn sanitize(self, s: &str) -> Cow<'_, str> {
+        let leading_digit = self == SanitizePreset::PrometheusLabel
+            && matches!(s.chars().next(), Some(c) if c.is_ascii_digit());
+
+        let valid = !leading_digit
+            && s.chars().all(|c| match self {
+                SanitizePreset::Graphite => graphite(c).is_none(),
+                SanitizePreset::PrometheusLabel => prometheus_label(c).is_none(),
+                SanitizePreset::Influx => influx(c).is_none(),
+            });
+
+        if valid {
+            return Cow::Borrowed(s);
+        }
+
+        let mut res = String::with_capacity(s.len() + 1);
+        if leading_digit {
+            res.push('_');
+        }
+
+        for c in s.chars() {
+            match self {
+                SanitizePreset::Graphite => res.push(graphite(c).unwrap_or(c)),
+                SanitizePreset::PrometheusLabel => res.push(prometheus_label(c).unwrap_or(c)),
+                SanitizePreset::Influx => match influx(c) {
+                    Some('\\') => {
+                        res.push('\\');
+                        res.push(c);
+                    }
+                    x => res.push(x.unwrap_or(c)),
+                },
+            }
+        }
+
+        Cow::Owned(res)
+    }
+
+    /// Rewrites every component of the identifier
+    pub fn sanitize_identifier(self, id: &Identifier) -> Identifier {
+        let sanitize = |x: &str| String::from(self.sanitize(x));
+        Identifier {
+            host: sanitize(&id.host),
+            plugin: sanitize(&id.plugin),
+            plugin_instance: id.plugin_instance.as_ref().map(|x| sanitize(x)),
+            type_: sanitize(&id.type_),
+            type_instance: id.type_instance.as_ref().map(|x| sanitize(x)),
+        }
+    }
+}
+
+// Each rule returns what the character is replaced with, or `None` if it is kept
+
+fn graphite(c: char) -> Option<char> {
+    match c {
+        '.' | '/' | '\\' | '"' => Some('_'),
+        c if c.is_whitespace() || c.is_control() => Some('_'),
+        _ => None,
+    }
+}
+
+fn prometheus_label(c: char) -> Option<char> {
+    if c.is_ascii_alphanumeric() || c == '_' {
+        None
+    } else {
+        Some('_')
+    }
+}
+
+/// An escaped character is signaled by returning the escape character
+fn influx(c: char) -> Option<char> {
+    match c {
+        ',' | '=' | ' ' => Some('\\'),
+        c if c.is_whitespace() || c.is_control() => Some('_'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_borrows_valid() {
+        for preset in &[
+            SanitizePreset::Graphite,
+            SanitizePreset::PrometheusLabel,
+            SanitizePreset::Influx,
+        ] {
+            assert!(matches!(preset.sanitize("cpu_idle"), Cow::Borrowed(_)));
+        }
+    }
+
+    #[test]
+    fn test_sanitize_graphite() {
+        let preset = SanitizePreset::Graphite;
+        assert_eq!("a_b_c_d_e", preset.sanitize("a.b/c d\te"));
+        assert_eq!("cpu-0:idle#1", preset.sanitize("cpu-0:idle#1"));
+    }
+
+    #[test]
+    fn test_sanitize_prometheus_label() {
+        let preset = SanitizePreset::PrometheusLabel;
+        assert_eq!("df_root", preset.sanitize("df-root"));
+        assert_eq!("_9p", preset.sanitize("9p"));
+        assert_eq!("caf_", preset.sanitize("café"));
+        assert_eq!("", preset.sanitize(""));
+    }
+
+    #[test]
+    fn test_sanitize_influx() {
+        let preset = SanitizePreset::Influx;
+        assert_eq!("a\\,b\\=c\\ d", preset.sanitize("a,b=c d"));
+        assert_eq!("a_b", preset.sanitize("a\nb"));
+        assert_eq!("10.0.0.1", preset.sanitize("10.0.0.1"));
+    }
+
+    #[test]
+    fn test_sanitize_identifier() {
+        let id: Identifier = "db.example.com/df-root/df_complex-used".parse().unwrap();
+        let actual = SanitizePreset::Graphite.sanitize_identifier(&id);
+        assert_eq!("db_example_com", actual.host);
+        assert_eq!(Some(String::from("root")), actual.plugin_instance);
+        assert_eq!("db_example_com/df-root/df_complex-used", actual.to_string());
+    }
+}
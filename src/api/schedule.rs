@@ -0,0 +1,261 @@
+use crate::errors::ParseScheduleError;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Schedules are evaluated at minute granularity, so finding the next run is bounded to a scan of
+/// the minutes in five years (which accommodates schedules that only match on leap days)
+const MAX_MINUTES_AHEAD: u64 = 5 * 366 * 24 * 60;
+
+/// A cron-like schedule of when a plugin should read values (eg: `5 * * * *` to read hourly at
+/// five minutes past the hour). Collectd only supports reading at fixed intervals, so a plugin
+/// that returns a schedule from `Plugin::schedule` has its read callback invoked every second and
+/// `read_values` is only called when the schedule is due.
+///
+/// The five fields are minute (0-59), hour (0-23), day of month (1-31), month (1-12), and day of
+/// week (0-6 where 0 is Sunday). A field may be `*`, a value, a range (`1-5`), a step (`*/15` or
+/// `0-30/10`), or a comma separated list of the aforementioned. Like cron, when both the day of
+/// month and day of week are restricted, a day matching either is due. Schedules are evaluated in
+/// UTC.
+///
+/// With the `serde` feature, a schedule can be deserialized from the plugin's configuration.
+///
+/// ```
+/// use collectd_plugin::Schedule;
+///
+/// let schedule: Schedule = "5 * * * *".parse().unwrap();
+/// assert!("5 * * *".parse::<Schedule>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl Schedule {
+    /// Returns true if values should be read in the minute of the given time
+    pub fn matches(&self, time: SystemTime) -> bool {
+        let fields = match time.duration_since(UNIX_EPOCH) {
+            Ok(x) => Fields::from_secs(x.as_secs()),
+            Err(_) => return false,
+        };
+
+        let day_of_month = bit(self.days_of_month, fields.day_of_month);
+        let day_of_week = bit(self.days_of_week, fields.day_of_week);
+        let day = match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+
+        day && bit(self.minutes, fields.minute)
+            && bit(self.hours, fields.hour)
+            && bit(self.months, fields.month)
+    }
+
+    /// The start of the first minute after the given time in which the schedule is due, if there
+    /// is one (eg: February 30th never occurs)
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let start = secs / 60 + 1;
+        (start..start + MAX_MINUTES_AHEAD)
+            .map(|minute| UNIX_EPOCH + Duration::from_secs(minute * 60))
+            .find(|x| self.matches(*x))
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = ParseScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(ParseScheduleError(format!(
+                "expected 5 fields but found {} in: {}",
+                fields.len(),
+                s
+            )));
+        }
+
+        let days_of_week = parse_field(fields[4], 0, 7)?;
+        Ok(Schedule {
+            expression: fields.join(" "),
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days_of_month: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+
+            // Like cron, 7 is an alias for Sunday
+            days_of_week: (days_of_week | (days_of_week >> 7)) & 0x7f,
+            any_day_of_month: fields[2] == "*",
+            any_day_of_week: fields[4] == "*",
+        })
+    }
+}
+
+fn bit(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Parses a field of the schedule into a bit set of the values that it matches
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, ParseScheduleError> {
+    let err = || ParseScheduleError(format!("invalid field: {}", field));
+    let parse = |x: &str| {
+        x.parse::<u32>()
+            .ok()
+            .filter(|x| *x >= min && *x <= max)
+            .ok_or_else(err)
+    };
+
+    let mut set = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(ind) => (&part[..ind], parse_step(&part[ind + 1..]).ok_or_else(err)?),
+            None => (part, 1),
+        };
+
+        let (lo, hi) = match range.find('-') {
+            _ if range == "*" => (min, max),
+            Some(ind) => (parse(&range[..ind])?, parse(&range[ind + 1..])?),
+            None if step > 1 => (parse(range)?, max),
+            None => {
+                let x = parse(range)?;
+                (x, x)
+            }
+        };
+
+        if lo > hi {
+            return Err(err());
+        }
+
+        for x in (lo..=hi).step_by(step) {
+            set |= 1 << x;
+        }
+    }
+
+    Ok(set)
+}
+
+fn parse_step(s: &str) -> Option<usize> {
+    s.parse().ok().filter(|x| *x > 0)
+}
+
+/// The calendar fields of a time in UTC
+#[derive(Debug, PartialEq)]
+struct Fields {
+    minute: u32,
+    hour: u32,
+    day_of_month: u32,
+    month: u32,
+    day_of_week: u32,
+}
+
+impl Fields {
+    fn from_secs(secs: u64) -> Fields {
+        let days = secs / 86_400;
+        let rem = secs % 86_400;
+
+        // Converts days since the epoch to a civil date (Howard Hinnant's days_from_civil inverse)
+        let z = days + 719_468;
+        let era = z / 146_097;
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day_of_month = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+
+        Fields {
+            minute: (rem / 60 % 60) as u32,
+            hour: (rem / 3600) as u32,
+            day_of_month: day_of_month as u32,
+            month: month as u32,
+
+            // The epoch was on a Thursday
+            day_of_week: ((days + 4) % 7) as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    // 2024-02-29T13:05:00Z, a Thursday
+    const LEAP_DAY: u64 = 1_709_211_900;
+
+    #[test]
+    fn test_fields_from_secs() {
+        assert_eq!(
+            Fields {
+                minute: 5,
+                hour: 13,
+                day_of_month: 29,
+                month: 2,
+                day_of_week: 4,
+            },
+            Fields::from_secs(LEAP_DAY + 30)
+        );
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let schedule: Schedule = "*/15  0-6/3 1,15 * 7".parse().unwrap();
+        assert_eq!("*/15 0-6/3 1,15 * 7", schedule.to_string());
+        assert_eq!(1 | 1 << 15 | 1 << 30 | 1 << 45, schedule.minutes);
+        assert_eq!(0b100_1001, schedule.hours);
+        assert_eq!(0b1, schedule.days_of_week);
+
+        assert!("* * * *".parse::<Schedule>().is_err());
+        assert!("60 * * * *".parse::<Schedule>().is_err());
+        assert!("* * 0 * *".parse::<Schedule>().is_err());
+        assert!("5-1 * * * *".parse::<Schedule>().is_err());
+        assert!("*/0 * * * *".parse::<Schedule>().is_err());
+        assert!("a * * * *".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn test_schedule_matches() {
+        let hourly: Schedule = "5 * * * *".parse().unwrap();
+        assert!(hourly.matches(at(LEAP_DAY)));
+        assert!(hourly.matches(at(LEAP_DAY + 59)));
+        assert!(!hourly.matches(at(LEAP_DAY + 60)));
+
+        // Either the day of month or day of week has to match when both are restricted
+        let either: Schedule = "* * 1 * 4".parse().unwrap();
+        assert!(either.matches(at(LEAP_DAY)));
+        let both: Schedule = "* * 1 * *".parse().unwrap();
+        assert!(!both.matches(at(LEAP_DAY)));
+    }
+
+    #[test]
+    fn test_schedule_next_after() {
+        let hourly: Schedule = "5 * * * *".parse().unwrap();
+        assert_eq!(Some(at(LEAP_DAY + 3600)), hourly.next_after(at(LEAP_DAY)));
+        assert_eq!(Some(at(LEAP_DAY)), hourly.next_after(at(LEAP_DAY - 1)));
+
+        let leap: Schedule = "0 0 29 2 *".parse().unwrap();
+        assert_eq!(
+            Some(at(1_709_164_800)),
+            leap.next_after(at(1_709_164_800 - 86_400 * 365))
+        );
+
+        let never: Schedule = "0 0 30 2 *".parse().unwrap();
+        assert_eq!(None, never.next_after(at(LEAP_DAY)));
+    }
+}
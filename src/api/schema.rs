@@ -0,0 +1,143 @@
+use super::ValueList;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+
+/// The callback that derives a backend schema for a type that the registry hasn't seen before
+type SchemaFactory<S> = dyn Fn(&ValueList<'_>) -> Result<S, Box<dyn Error>> + Send + Sync;
+
+/// Maps the types seen on the write path to the schemas of a backend (eg: column sets or
+/// measurement names), so that a write plugin can create the backend schema when a type first
+/// appears instead of erroring on novel types. The factory is invoked once per type with the first
+/// value list of that type. If the factory fails, nothing is registered and the factory is
+/// invoked again with the next value list of the type.
+///
+/// ```
+/// use collectd_plugin::{SchemaRegistry, ValueList};
+/// use std::error::Error;
+///
+/// // The columns of a table for a type
+/// let registry = SchemaRegistry::new(|list: &ValueList<'_>| {
+///     let columns: Vec<String> = list.values.iter().map(|x| String::from(x.name)).collect();
+///     // create the table in the backend
+///     Ok(columns)
+/// });
+///
+/// fn write(registry: &SchemaRegistry<Vec<String>>, list: &ValueList<'_>) -> Result<(), Box<dyn Error>> {
+///     let columns = registry.schema(list)?;
+///     // insert the values into the columns
+///     Ok(())
+/// }
+/// ```
+pub struct SchemaRegistry<S> {
+    factory: Box<SchemaFactory<S>>,
+    schemas: RwLock<HashMap<String, Arc<S>>>,
+}
+
+impl<S> SchemaRegistry<S> {
+    /// Creates an empty registry that derives schemas of unknown types with the factory
+    pub fn new<F>(factory: F) -> Self
+    where
+        F: Fn(&ValueList<'_>) -> Result<S, Box<dyn Error>> + Send + Sync + 'static,
+    {
+        SchemaRegistry {
+            factory: Box::new(factory),
+            schemas: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the schema for the type of the value list, invoking the factory if the type is new
+    pub fn schema(&self, list: &ValueList<'_>) -> Result<Arc<S>, Box<dyn Error>> {
+        self.schema_with(list.type_, || (self.factory)(list))
+    }
+
+    fn schema_with<F>(&self, type_: &str, create: F) -> Result<Arc<S>, Box<dyn Error>>
+    where
+        F: FnOnce() -> Result<S, Box<dyn Error>>,
+    {
+        if let Some(schema) = self.get(type_) {
+            return Ok(schema);
+        }
+
+        // Check again under the write lock so that concurrent writes of a new type create the
+        // schema only once
+        let mut schemas = self.schemas.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(schema) = schemas.get(type_) {
+            return Ok(Arc::clone(schema));
+        }
+
+        let schema = Arc::new(create()?);
+        schemas.insert(String::from(type_), Arc::clone(&schema));
+        Ok(schema)
+    }
+
+    /// The schema of a type, if the type has been seen
+    pub fn get(&self, type_: &str) -> Option<Arc<S>> {
+        let schemas = self.schemas.read().unwrap_or_else(|e| e.into_inner());
+        schemas.get(type_).cloned()
+    }
+
+    /// Registers the schema of a type ahead of time (eg: schemas that already exist in the
+    /// backend), replacing any previous schema of the type
+    pub fn register(&self, type_: &str, schema: S) {
+        let mut schemas = self.schemas.write().unwrap_or_else(|e| e.into_inner());
+        schemas.insert(String::from(type_), Arc::new(schema));
+    }
+
+    /// Forgets the schema of a type (eg: it was dropped from the backend), so that the factory is
+    /// invoked when the type is next seen
+    pub fn remove(&self, type_: &str) -> Option<Arc<S>> {
+        let mut schemas = self.schemas.write().unwrap_or_else(|e| e.into_inner());
+        schemas.remove(type_)
+    }
+
+    /// The types that have a schema
+    pub fn types(&self) -> Vec<String> {
+        let schemas = self.schemas.read().unwrap_or_else(|e| e.into_inner());
+        schemas.keys().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn registry() -> SchemaRegistry<String> {
+        SchemaRegistry::new(|list: &ValueList<'_>| Ok(String::from(list.type_)))
+    }
+
+    #[test]
+    fn test_schema_created_once() {
+        let registry = registry();
+        let created = Cell::new(0);
+        let create = || {
+            created.set(created.get() + 1);
+            Ok(String::from("load(short, mid, long)"))
+        };
+
+        let schema = registry.schema_with("load", create).unwrap();
+        assert_eq!("load(short, mid, long)", *schema);
+        let schema = registry.schema_with("load", create).unwrap();
+        assert_eq!("load(short, mid, long)", *schema);
+        assert_eq!(1, created.get());
+        assert_eq!(vec![String::from("load")], registry.types());
+    }
+
+    #[test]
+    fn test_schema_failure_not_registered() {
+        let registry = registry();
+        assert!(registry
+            .schema_with("load", || Err("backend unavailable".into()))
+            .is_err());
+        assert_eq!(None, registry.get("load"));
+
+        registry.register("load", String::from("load(value)"));
+        assert_eq!(
+            Some(Arc::new(String::from("load(value)"))),
+            registry.get("load")
+        );
+        assert!(registry.remove("load").is_some());
+        assert!(registry.types().is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+use super::{ConfigItem, OwnedConfigItem};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+lazy_static! {
+    // Shared configs that have been read, so that each file is only read once
+    static ref SHARED_CONFIGS: Mutex<HashMap<PathBuf, Arc<SharedConfig>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Settings shared by several plugins (eg: a proxy, credentials, or runtime tuning), so that they
+/// don't have to be repeated in each plugin's block of collectd.conf. Collectd only hands a
+/// `<Plugin>` block to the first plugin that registered the block's name, so the shared settings
+/// are kept in a file of collectd's config syntax that each plugin's block references.
+///
+/// A plugin inherits the shared settings that it doesn't set itself, and the result can be
+/// deserialized like any other config.
+///
+/// ```
+/// use collectd_plugin::{ConfigItem, ConfigValue, SharedConfig};
+///
+/// let shared: SharedConfig = "Proxy \"http://proxy:3128\"\nTimeout 5".parse().unwrap();
+/// let own = vec![ConfigItem {
+///     key: "timeout",
+///     values: vec![ConfigValue::Number(10.0)],
+///     children: vec![],
+/// }];
+///
+/// let config = shared.inherit(&own);
+/// assert_eq!(2, config.len());
+/// assert_eq!("Proxy", config[0].key);
+/// assert_eq!(vec![ConfigValue::Number(10.0)], config[1].values);
+/// ```
+///
+/// Where `myplugin.conf` has the shared settings:
+///
+/// ```
+/// use collectd_plugin::{ConfigItem, PluginManager, PluginRegistration, SharedConfig};
+/// use std::error;
+///
+/// struct MyPlugin;
+/// impl PluginManager for MyPlugin {
+///     fn name() -> &'static str {
+///         "myplugin"
+///     }
+///
+///     fn plugins(
+///         config: Option<&[ConfigItem<'_>]>,
+///     ) -> Result<PluginRegistration, Box<dyn error::Error>> {
+///         let config = config.unwrap_or_default();
+///         let path: Option<&str> = ConfigItem::collect(config, "SharedConfig")?.pop();
+///         let shared = path.map(SharedConfig::load).transpose()?;
+///         let config = match shared {
+///             Some(ref shared) => shared.inherit(config),
+///             None => config.to_vec(),
+///         };
+///
+///         // deserialize the config and register the plugin
+///         unimplemented!()
+///     }
+/// }
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct SharedConfig {
+    items: Vec<OwnedConfigItem>,
+}
+
+impl SharedConfig {
+    /// Reads the shared config at the path. The file is only read the first time it is loaded,
+    /// and later loads of the same path return the same config.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Arc<SharedConfig>> {
+        let path = path.as_ref();
+        let mut configs = SHARED_CONFIGS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(config) = configs.get(path) {
+            return Ok(Arc::clone(config));
+        }
+
+        let contents = fs::read_to_string(path)?;
+        let items = OwnedConfigItem::parse(&contents)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let config = Arc::new(SharedConfig { items });
+        configs.insert(path.to_path_buf(), Arc::clone(&config));
+        Ok(config)
+    }
+
+    /// The items of the shared config
+    pub fn items(&self) -> Vec<ConfigItem<'_>> {
+        self.items.iter().map(|x| x.as_item()).collect()
+    }
+
+    /// Combines a plugin's own config with the shared items whose keys the plugin doesn't set.
+    /// Keys are compared case insensitively and a block is inherited as a whole, so a plugin that
+    /// sets a key replaces every shared item of the key. The inherited items come first.
+    pub fn inherit<'a>(&'a self, own: &[ConfigItem<'a>]) -> Vec<ConfigItem<'a>> {
+        self.items
+            .iter()
+            .map(|x| x.as_item())
+            .filter(|x| !own.iter().any(|y| y.key.eq_ignore_ascii_case(x.key)))
+            .chain(own.iter().cloned())
+            .collect()
+    }
+}
+
+impl std::str::FromStr for SharedConfig {
+    type Err = crate::errors::ParseConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OwnedConfigItem::parse(s).map(|items| SharedConfig { items })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ConfigValue;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn test_shared_config_load_once() {
+        let path = env::temp_dir().join(format!("collectd-shared-{}.conf", process::id()));
+        fs::write(&path, "Proxy \"a\"\n").unwrap();
+        let first = SharedConfig::load(&path).unwrap();
+
+        // The file is not read again
+        fs::write(&path, "Proxy \"b\"\n").unwrap();
+        let second = SharedConfig::load(&path).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(vec![ConfigValue::String("a")], second.items()[0].values);
+        fs::remove_file(&path).unwrap();
+
+        let missing = env::temp_dir().join(format!("collectd-missing-{}.conf", process::id()));
+        assert!(SharedConfig::load(&missing).is_err());
+    }
+
+    #[test]
+    fn test_shared_config_inherit() {
+        let shared: SharedConfig = "Server \"a\"\nServer \"b\"\n<Proxy>\nPort 1\n</Proxy>"
+            .parse()
+            .unwrap();
+
+        let own = vec![ConfigItem {
+            key: "server",
+            values: vec![ConfigValue::String("c")],
+            children: vec![],
+        }];
+
+        let config = shared.inherit(&own);
+        assert_eq!(2, config.len());
+        assert_eq!("Proxy", config[0].key);
+        assert_eq!(1, config[0].children.len());
+        assert_eq!(own[0], config[1]);
+    }
+}
@@ -0,0 +1,66 @@
+use lazy_static::lazy_static;
+use std::error::Error;
+use std::sync::Mutex;
+
+/// A teardown registered with `on_shutdown`
+pub type ShutdownHook = Box<dyn FnOnce() -> Result<(), Box<dyn Error>> + Send>;
+
+lazy_static! {
+    static ref SHUTDOWN_HOOKS: Mutex<Vec<ShutdownHook>> = Mutex::new(Vec::new());
+}
+
+/// Registers a hook that is executed when collectd shuts down the plugin, so that background
+/// runtimes, buffers, and the like can manage their own teardown instead of relying on
+/// `PluginManager::shutdown`. Hooks are executed in the reverse order of registration (the last
+/// subsystem started is the first stopped) and before `PluginManager::shutdown`. An error or
+/// panic from a hook is logged and does not prevent the remaining hooks from executing.
+///
+/// ```
+/// use collectd_plugin::on_shutdown;
+///
+/// on_shutdown(|| {
+///     // flush buffers, join threads, etc
+///     Ok(())
+/// });
+/// ```
+pub fn on_shutdown<F>(hook: F)
+where
+    F: FnOnce() -> Result<(), Box<dyn Error>> + Send + 'static,
+{
+    SHUTDOWN_HOOKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Box::new(hook));
+}
+
+/// Removes the registered hooks in the order that they should be executed
+pub fn take_shutdown_hooks() -> Vec<ShutdownHook> {
+    let mut hooks = std::mem::take(&mut *SHUTDOWN_HOOKS.lock().unwrap_or_else(|e| e.into_inner()));
+    hooks.reverse();
+    hooks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_shutdown_hooks_reverse_order() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..3 {
+            let order = Arc::clone(&order);
+            on_shutdown(move || {
+                order.lock().unwrap().push(i);
+                Ok(())
+            });
+        }
+
+        for hook in take_shutdown_hooks() {
+            hook().unwrap();
+        }
+
+        assert_eq!(vec![2, 1, 0], *order.lock().unwrap());
+        assert!(take_shutdown_hooks().is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+use super::{CdTime, LogLevel, Timestamp};
+use std::fmt::Write;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The facility collectd logs under when it logs to syslog
+const FACILITY_DAEMON: u8 = 3;
+
+/// Formats messages with RFC5424 headers (eg: `<27>1 2024-02-29T13:05:00.000Z myhost collectd
+/// 1234 - - disk full`) so that log plugins can forward collectd's log messages to a remote
+/// syslog server. The message ID and structured data are left empty.
+///
+/// ```
+/// use collectd_plugin::{LogLevel, SyslogFormat};
+/// use std::time::SystemTime;
+///
+/// let format = SyslogFormat::new("collectd").hostname("myhost").facility(16);
+/// let line = format.format(LogLevel::Warning, "disk full", SystemTime::now().into());
+/// assert!(line.starts_with("<132>1 "));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyslogFormat {
+    facility: u8,
+    hostname: Option<String>,
+    app_name: String,
+    procid: String,
+}
+
+impl SyslogFormat {
+    /// Creates a format for messages from the application under the daemon facility
+    pub fn new(app_name: &str) -> Self {
+        SyslogFormat {
+            facility: FACILITY_DAEMON,
+            hostname: None,
+            app_name: header_field(app_name, 48),
+            procid: process::id().to_string(),
+        }
+    }
+
+    /// The syslog facility (0 to 23, eg: 16 for local0). Facilities out of range are clamped.
+    pub fn facility(mut self, facility: u8) -> Self {
+        self.facility = facility.min(23);
+        self
+    }
+
+    /// The host that the messages originate from. Left empty if not given.
+    pub fn hostname(mut self, hostname: &str) -> Self {
+        self.hostname = Some(header_field(hostname, 255));
+        self
+    }
+
+    /// The priority that prefixes the message, which combines the facility and the severity
+    pub fn priority(&self, lvl: LogLevel) -> u8 {
+        self.facility * 8 + lvl.syslog_severity()
+    }
+
+    /// The header of a message logged at the given time, including the trailing space
+    pub fn prefix(&self, lvl: LogLevel, time: Timestamp) -> String {
+        let mut prefix = format!("<{}>1 ", self.priority(lvl));
+        write_timestamp(&mut prefix, CdTime::from(time).into());
+        let hostname = self.hostname.as_deref().unwrap_or("-");
+        let _ = write!(
+            prefix,
+            " {} {} {} - - ",
+            hostname, self.app_name, self.procid
+        );
+        prefix
+    }
+
+    /// The message with its header
+    pub fn format(&self, lvl: LogLevel, msg: &str, time: Timestamp) -> String {
+        let mut line = self.prefix(lvl, time);
+        line.push_str(msg);
+        line
+    }
+}
+
+/// Header fields are limited to printable ASCII without spaces, and are replaced with the nil
+/// value when empty
+fn header_field(s: &str, max: usize) -> String {
+    let field: String = s
+        .chars()
+        .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+        .take(max)
+        .collect();
+
+    if field.is_empty() {
+        String::from("-")
+    } else {
+        field
+    }
+}
+
+/// Writes the time in UTC with millisecond precision (eg: `2024-02-29T13:05:00.000Z`)
+fn write_timestamp(out: &mut String, time: SystemTime) {
+    let since = match time.duration_since(UNIX_EPOCH) {
+        Ok(x) => x,
+        Err(_) => {
+            out.push('-');
+            return;
+        }
+    };
+
+    let secs = since.as_secs();
+    let days = secs / 86_400;
+    let rem = secs % 86_400;
+
+    // Converts days since the epoch to a civil date (Howard Hinnant's civil_from_days)
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let _ = write!(
+        out,
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60,
+        since.subsec_millis()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // 2024-02-29T13:05:00.250Z
+    fn leap_day() -> Timestamp {
+        UNIX_EPOCH + Duration::from_millis(1_709_211_900_250)
+    }
+
+    #[test]
+    fn test_syslog_format() {
+        let format = SyslogFormat::new("collectd").hostname("my host");
+        let line = format.format(LogLevel::Error, "disk full", leap_day());
+        let expected = format!(
+            "<27>1 2024-02-29T13:05:00.250Z my_host collectd {} - - disk full",
+            process::id()
+        );
+        assert_eq!(expected, line);
+
+        let format = SyslogFormat::new("").facility(200);
+        assert_eq!(191, format.priority(LogLevel::Debug));
+        let prefix = format.prefix(LogLevel::Debug, leap_day());
+        assert!(prefix.ends_with(&format!(" - - {} - - ", process::id())));
+    }
+
+    #[test]
+    fn test_write_timestamp() {
+        let mut s = String::new();
+        write_timestamp(&mut s, UNIX_EPOCH);
+        assert_eq!("1970-01-01T00:00:00.000Z", s);
+
+        let mut s = String::new();
+        write_timestamp(&mut s, UNIX_EPOCH + Duration::from_secs(951_782_400));
+        assert_eq!("2000-02-29T00:00:00.000Z", s);
+    }
+}
@@ -0,0 +1,129 @@
+use super::{collectd_log, LogLevel, Value, ValueListBuilder};
+use crate::errors::{SubmitError, TargetsError};
+use std::error::Error;
+
+/// Collects the outcome of reading each target of a plugin instance that handles several targets
+/// (eg: databases or hosts), so that one unreachable target doesn't fail the entire read (and
+/// cause collectd to backoff from reading all of the targets). Each recorded target has an `up`
+/// gauge dispatched (1 when read successfully, else 0) under the target's plugin instance.
+///
+/// ```
+/// use collectd_plugin::TargetReport;
+/// use std::error::Error;
+///
+/// fn read_target(target: &str) -> Result<(), Box<dyn Error>> {
+///     Ok(())
+/// }
+///
+/// fn read_values(targets: &[&str]) -> Result<(), Box<dyn Error>> {
+///     let mut report = TargetReport::new("myplugin");
+///     for target in targets {
+///         report.record(target, read_target(target));
+///     }
+///     report.finish()
+/// }
+/// ```
+#[derive(Debug)]
+pub struct TargetReport<'a> {
+    plugin: &'a str,
+    succeeded: usize,
+    failures: Vec<(String, String)>,
+}
+
+impl<'a> TargetReport<'a> {
+    /// Creates a report for targets whose values are dispatched under the plugin
+    pub fn new(plugin: &'a str) -> Self {
+        TargetReport {
+            plugin,
+            succeeded: 0,
+            failures: Vec::new(),
+        }
+    }
+
+    /// Records the outcome of reading the target and dispatches its `up` gauge
+    pub fn record<E: Into<Box<dyn Error>>>(&mut self, target: &str, result: Result<(), E>) {
+        let up = match result {
+            Ok(()) => {
+                self.succeeded += 1;
+                1.0
+            }
+            Err(e) => {
+                self.failures
+                    .push((String::from(target), e.into().to_string()));
+                0.0
+            }
+        };
+
+        if let Err(e) = self.dispatch_up(target, up) {
+            let msg = format!(
+                "{}: unable to dispatch up for {}: {}",
+                self.plugin, target, e
+            );
+            collectd_log(LogLevel::Warning, &msg);
+        }
+    }
+
+    fn dispatch_up(&self, target: &str, up: f64) -> Result<(), SubmitError> {
+        ValueListBuilder::new(self.plugin, "gauge")
+            .plugin_instance(target)
+            .type_instance("up")
+            .values(&[Value::Gauge(up)])
+            .submit()
+    }
+
+    /// The targets that failed to be read along with their errors
+    pub fn failures(&self) -> &[(String, String)] {
+        &self.failures
+    }
+
+    /// Concludes the read. The read is only considered failed when every recorded target failed,
+    /// else failures are logged as warnings and the read succeeds.
+    pub fn finish(self) -> Result<(), Box<dyn Error>> {
+        if self.failures.is_empty() {
+            return Ok(());
+        }
+
+        let err = TargetsError {
+            total: self.succeeded + self.failures.len(),
+            failures: self.failures,
+        };
+
+        if self.succeeded == 0 {
+            return Err(Box::new(err));
+        }
+
+        collectd_log(LogLevel::Warning, &format!("{}: {}", self.plugin, err));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_report_partial_failure() {
+        let mut report = TargetReport::new("myplugin");
+        report.record::<Box<dyn Error>>("a", Ok(()));
+        report.record("b", Err("connection refused"));
+        assert_eq!(
+            &[(String::from("b"), String::from("connection refused"))],
+            report.failures()
+        );
+        assert!(report.finish().is_ok());
+    }
+
+    #[test]
+    fn test_target_report_total_failure() {
+        let mut report = TargetReport::new("myplugin");
+        report.record("a", Err("timed out"));
+        report.record("b", Err("connection refused"));
+        let err = report.finish().unwrap_err();
+        assert_eq!(
+            "2 of 2 targets failed: a: timed out; b: connection refused",
+            err.to_string()
+        );
+
+        assert!(TargetReport::new("myplugin").finish().is_ok());
+    }
+}
@@ -0,0 +1,173 @@
+use crate::errors::TlsConfigError;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// TLS settings shared by plugins that write to a remote endpoint, so that each parses and
+/// validates them identically. With the `serde` feature, the settings are deserialized with the
+/// same option names as collectd's `write_http` plugin:
+///
+/// ```plain
+/// <Plugin myplugin>
+///     CACert "/etc/ssl/certs/ca.pem"
+///     ClientCert "/etc/collectd/client.pem"
+///     ClientKey "/etc/collectd/client.key"
+///     VerifyPeer true
+///     VerifyHost true
+/// </Plugin>
+/// ```
+///
+/// Since the options sit alongside those of the plugin, the TLS settings are deserialized from
+/// the same config items as the plugin's own config struct (eg: `from_collectd::<TlsConfig>(items)`).
+///
+/// The crate doesn't depend on a TLS implementation. Instead, `TlsConfig::load` returns the PEM
+/// encoded contents of the files, which can be passed to rustls or native-tls (eg:
+/// `native_tls::Identity::from_pkcs8(&cert, &key)`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TlsConfig {
+    /// File of certificate authorities to verify the server with instead of the system's roots
+    #[cfg_attr(feature = "serde", serde(rename = "CACert"))]
+    pub ca_cert: Option<PathBuf>,
+
+    /// File of the certificate (chain) presented to the server for client authentication
+    #[cfg_attr(feature = "serde", serde(rename = "ClientCert"))]
+    pub client_cert: Option<PathBuf>,
+
+    /// File of the private key of the client certificate
+    #[cfg_attr(feature = "serde", serde(rename = "ClientKey"))]
+    pub client_key: Option<PathBuf>,
+
+    /// Verify that the server's certificate is signed by a trusted authority
+    #[cfg_attr(feature = "serde", serde(rename = "VerifyPeer"))]
+    pub verify_peer: bool,
+
+    /// Verify that the server's certificate matches the host being connected to
+    #[cfg_attr(feature = "serde", serde(rename = "VerifyHost"))]
+    pub verify_host: bool,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        TlsConfig {
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            verify_peer: true,
+            verify_host: true,
+        }
+    }
+}
+
+/// The PEM encoded contents of the files referenced by a `TlsConfig`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TlsMaterial {
+    pub ca_cert: Option<Vec<u8>>,
+
+    /// The client certificate and private key
+    pub identity: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl TlsConfig {
+    /// Checks that the settings are consistent and that the referenced files exist. Best called
+    /// when the plugin is configured so that mistakes are reported before values are written.
+    pub fn validate(&self) -> Result<(), TlsConfigError> {
+        match (&self.client_cert, &self.client_key) {
+            (Some(_), None) => return Err(missing("ClientCert", "ClientKey")),
+            (None, Some(_)) => return Err(missing("ClientKey", "ClientCert")),
+            _ => {}
+        }
+
+        let files = [&self.ca_cert, &self.client_cert, &self.client_key];
+        for path in files.iter().filter_map(|x| x.as_ref()) {
+            if !path.is_file() {
+                return Err(TlsConfigError(format!("{} is not a file", path.display())));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates the settings and reads the referenced files
+    pub fn load(&self) -> Result<TlsMaterial, TlsConfigError> {
+        self.validate()?;
+        let ca_cert = self.ca_cert.as_deref().map(read_pem).transpose()?;
+        let identity = match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => Some((read_pem(cert)?, read_pem(key)?)),
+            _ => None,
+        };
+
+        Ok(TlsMaterial { ca_cert, identity })
+    }
+}
+
+fn missing(given: &str, needed: &str) -> TlsConfigError {
+    TlsConfigError(format!("{} is given without {}", given, needed))
+}
+
+fn read_pem(path: &Path) -> Result<Vec<u8>, TlsConfigError> {
+    let contents = fs::read(path)
+        .map_err(|e| TlsConfigError(format!("unable to read {}: {}", path.display(), e)))?;
+
+    if !contents.windows(11).any(|x| x == b"-----BEGIN ") {
+        return Err(TlsConfigError(format!(
+            "{} is not PEM encoded",
+            path.display()
+        )));
+    }
+
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn test_tls_config_validate() {
+        assert!(TlsConfig::default().validate().is_ok());
+
+        let no_key = TlsConfig {
+            client_cert: Some(PathBuf::from("/etc/hostname")),
+            ..Default::default()
+        };
+        assert_eq!(Err(missing("ClientCert", "ClientKey")), no_key.validate());
+
+        let absent = TlsConfig {
+            ca_cert: Some(PathBuf::from("/does/not/exist.pem")),
+            ..Default::default()
+        };
+        assert!(absent.validate().is_err());
+    }
+
+    #[test]
+    fn test_tls_config_load() {
+        let dir = env::temp_dir().join(format!("collectd-tls-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let ca = dir.join("ca.pem");
+        let junk = dir.join("junk.pem");
+        let pem = b"-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n";
+        fs::write(&ca, &pem[..]).unwrap();
+        fs::write(&junk, b"MIIB").unwrap();
+
+        let config = TlsConfig {
+            ca_cert: Some(ca),
+            ..Default::default()
+        };
+        let material = config.load().unwrap();
+        assert_eq!(Some(pem.to_vec()), material.ca_cert);
+        assert_eq!(None, material.identity);
+
+        let config = TlsConfig {
+            ca_cert: Some(junk),
+            ..Default::default()
+        };
+        assert!(config.load().is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+use super::MetaValue;
+use strum_macros::{AsRefStr, EnumIter, EnumString};
+
+/// The unit of a value, which collectd's types.db doesn't capture. A plugin attaches the unit to
+/// the values it submits as meta data under the `unit` key so that write plugins can convert
+/// values to a common base unit and name them accordingly (eg: a Prometheus exporter emitting
+/// `_seconds` and `_bytes` metrics).
+///
+/// ```
+/// use collectd_plugin::{Unit, ValueListBuilder, Value};
+///
+/// let meta = [Unit::Milliseconds.to_meta()];
+/// let values = [Value::Gauge(250.0)];
+/// let builder = ValueListBuilder::new("myplugin", "latency")
+///     .values(&values)
+///     .meta(&meta);
+///
+/// assert_eq!(Some(Unit::Milliseconds), Unit::from_meta(&meta));
+/// assert_eq!((Unit::Seconds, 0.25), Unit::Milliseconds.to_base(250.0));
+/// ```
+#[derive(AsRefStr, EnumIter, EnumString, Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[strum(serialize_all = "snake_case")]
+pub enum Unit {
+    Bytes,
+    Bits,
+    Seconds,
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+
+    /// A proportion from 0 to 100
+    Percent,
+
+    /// A proportion from 0 to 1
+    Ratio,
+    Celsius,
+    Hertz,
+}
+
+impl Unit {
+    /// The meta data key that the unit is attached under
+    pub const META_KEY: &'static str = "unit";
+
+    /// The meta data entry that attaches the unit to values
+    pub fn to_meta(self) -> (String, MetaValue) {
+        (
+            String::from(Unit::META_KEY),
+            MetaValue::String(String::from(self.as_ref())),
+        )
+    }
+
+    /// Finds the unit in the meta data of values. Unknown units are ignored.
+    pub fn from_meta(meta: &[(String, MetaValue)]) -> Option<Unit> {
+        meta.iter().find_map(|(key, value)| match value {
+            MetaValue::String(ref unit) if key == Unit::META_KEY => unit.parse().ok(),
+            _ => None,
+        })
+    }
+
+    /// The unit that values of this unit are normalized to. Bits are normalized to bytes, durations
+    /// to seconds, and percentages to ratios, following Prometheus's naming conventions.
+    pub fn base(self) -> Unit {
+        match self {
+            Unit::Bytes | Unit::Bits => Unit::Bytes,
+            Unit::Seconds | Unit::Milliseconds | Unit::Microseconds | Unit::Nanoseconds => {
+                Unit::Seconds
+            }
+            Unit::Percent | Unit::Ratio => Unit::Ratio,
+            Unit::Celsius => Unit::Celsius,
+            Unit::Hertz => Unit::Hertz,
+        }
+    }
+
+    /// How many of the base unit are in one of this unit
+    fn scale(self) -> f64 {
+        match self {
+            Unit::Bits => 0.125,
+            Unit::Milliseconds => 1e-3,
+            Unit::Microseconds => 1e-6,
+            Unit::Nanoseconds => 1e-9,
+            Unit::Percent => 0.01,
+            _ => 1.0,
+        }
+    }
+
+    /// Converts a value to another unit, if the units measure the same quantity
+    pub fn convert(self, value: f64, to: Unit) -> Option<f64> {
+        if self.base() != to.base() {
+            return None;
+        }
+
+        if self == to {
+            Some(value)
+        } else {
+            Some(value * self.scale() / to.scale())
+        }
+    }
+
+    /// Converts a value to the base unit
+    pub fn to_base(self, value: f64) -> (Unit, f64) {
+        (self.base(), value * self.scale())
+    }
+
+    /// The suffix for a metric name measured in the unit (eg: `request_duration_seconds`)
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Unit::Bytes => "bytes",
+            Unit::Bits => "bits",
+            Unit::Seconds => "seconds",
+            Unit::Milliseconds => "milliseconds",
+            Unit::Microseconds => "microseconds",
+            Unit::Nanoseconds => "nanoseconds",
+            Unit::Percent => "percent",
+            Unit::Ratio => "ratio",
+            Unit::Celsius => "celsius",
+            Unit::Hertz => "hertz",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn test_unit_meta_roundtrip() {
+        for unit in Unit::iter() {
+            assert_eq!(Some(unit), Unit::from_meta(&[unit.to_meta()]));
+        }
+
+        let unknown = [(
+            String::from("unit"),
+            MetaValue::String(String::from("furlongs")),
+        )];
+        assert_eq!(None, Unit::from_meta(&unknown));
+        assert_eq!(None, Unit::from_meta(&[]));
+    }
+
+    #[test]
+    fn test_unit_convert() {
+        assert_eq!(Some(8.0), Unit::Bytes.convert(1.0, Unit::Bits));
+        assert_eq!(Some(1.5), Unit::Milliseconds.convert(1500.0, Unit::Seconds));
+        assert_eq!(Some(250.0), Unit::Seconds.convert(0.25, Unit::Milliseconds));
+        assert_eq!(Some(0.5), Unit::Percent.convert(50.0, Unit::Ratio));
+        assert_eq!(None, Unit::Bytes.convert(1.0, Unit::Seconds));
+
+        assert_eq!((Unit::Bytes, 2.0), Unit::Bits.to_base(16.0));
+        assert_eq!((Unit::Celsius, 21.5), Unit::Celsius.to_base(21.5));
+        for unit in Unit::iter() {
+            assert_eq!(unit.base(), unit.base().base());
+        }
+    }
+}
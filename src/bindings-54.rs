@@ -8,11 +8,19 @@ pub const DS_TYPE_COUNTER: u32 = 0;
 pub const DS_TYPE_GAUGE: u32 = 1;
 pub const DS_TYPE_DERIVE: u32 = 2;
 pub const DS_TYPE_ABSOLUTE: u32 = 3;
+pub const MD_TYPE_STRING: u32 = 1;
+pub const MD_TYPE_SIGNED_INT: u32 = 2;
+pub const MD_TYPE_UNSIGNED_INT: u32 = 3;
+pub const MD_TYPE_DOUBLE: u32 = 4;
+pub const MD_TYPE_BOOLEAN: u32 = 5;
 pub const LOG_ERR: u32 = 3;
 pub const LOG_WARNING: u32 = 4;
 pub const LOG_NOTICE: u32 = 5;
 pub const LOG_INFO: u32 = 6;
 pub const LOG_DEBUG: u32 = 7;
+pub const NOTIF_FAILURE: u32 = 1;
+pub const NOTIF_WARNING: u32 = 2;
+pub const NOTIF_OKAY: u32 = 4;
 pub type __time_t = ::std::os::raw::c_long;
 pub type __syscall_slong_t = ::std::os::raw::c_long;
 #[repr(C)]
@@ -1220,3 +1228,98 @@ extern "C" {
 extern "C" {
     pub fn uc_get_rate(ds: *const data_set_t, vl: *const value_list_t) -> *mut gauge_t;
 }
+extern "C" {
+    pub fn uc_get_rate_by_name(
+        name: *const ::std::os::raw::c_char,
+        ret_values: *mut *mut gauge_t,
+        ret_values_num: *mut ::std::os::raw::c_ulong,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn meta_data_create() -> *mut meta_data_t;
+}
+extern "C" {
+    pub fn meta_data_destroy(md: *mut meta_data_t);
+}
+extern "C" {
+    pub fn meta_data_toc(
+        md: *mut meta_data_t,
+        toc: *mut *mut *mut ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn meta_data_type(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn meta_data_add_string(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn meta_data_add_signed_int(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: i64,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn meta_data_add_unsigned_int(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: u64,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn meta_data_add_double(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: f64,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn meta_data_add_boolean(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: bool,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn meta_data_get_string(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: *mut *mut ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn meta_data_get_signed_int(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: *mut i64,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn meta_data_get_unsigned_int(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: *mut u64,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn meta_data_get_double(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: *mut f64,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn meta_data_get_boolean(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: *mut bool,
+    ) -> ::std::os::raw::c_int;
+}
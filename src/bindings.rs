@@ -17,6 +17,77 @@ extern "C" {
     pub static mut hostname_g: [::std::os::raw::c_char; ARR_LENGTH];
 }
 
+// The filter chain header is not part of the generated bindings, but its match and target
+// interfaces have been the same since collectd 5.0
+pub const FC_MATCH_NO_MATCH: ::std::os::raw::c_int = 0;
+pub const FC_MATCH_MATCHES: ::std::os::raw::c_int = 1;
+pub const FC_TARGET_CONTINUE: ::std::os::raw::c_int = 0;
+pub const FC_TARGET_STOP: ::std::os::raw::c_int = 1;
+pub const FC_TARGET_RETURN: ::std::os::raw::c_int = 2;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct match_proc_t {
+    pub create: ::std::option::Option<
+        unsafe extern "C" fn(
+            ci: *const oconfig_item_t,
+            user_data: *mut *mut ::std::os::raw::c_void,
+        ) -> ::std::os::raw::c_int,
+    >,
+    pub destroy: ::std::option::Option<
+        unsafe extern "C" fn(user_data: *mut *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int,
+    >,
+    pub match_: ::std::option::Option<
+        unsafe extern "C" fn(
+            ds: *const data_set_t,
+            vl: *const value_list_t,
+            meta: *mut *mut notification_meta_t,
+            user_data: *mut *mut ::std::os::raw::c_void,
+        ) -> ::std::os::raw::c_int,
+    >,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct target_proc_t {
+    pub create: ::std::option::Option<
+        unsafe extern "C" fn(
+            ci: *const oconfig_item_t,
+            user_data: *mut *mut ::std::os::raw::c_void,
+        ) -> ::std::os::raw::c_int,
+    >,
+    pub destroy: ::std::option::Option<
+        unsafe extern "C" fn(user_data: *mut *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int,
+    >,
+    pub invoke: ::std::option::Option<
+        unsafe extern "C" fn(
+            ds: *const data_set_t,
+            vl: *mut value_list_t,
+            meta: *mut *mut notification_meta_t,
+            user_data: *mut *mut ::std::os::raw::c_void,
+        ) -> ::std::os::raw::c_int,
+    >,
+}
+
+extern "C" {
+    pub fn fc_register_match(
+        name: *const ::std::os::raw::c_char,
+        proc_: match_proc_t,
+    ) -> ::std::os::raw::c_int;
+
+    pub fn fc_register_target(
+        name: *const ::std::os::raw::c_char,
+        proc_: target_proc_t,
+    ) -> ::std::os::raw::c_int;
+}
+
+// Not part of the plugin header, but collectd has exported its global options since 5.0
+extern "C" {
+    pub fn global_option_get(
+        option: *const ::std::os::raw::c_char,
+    ) -> *const ::std::os::raw::c_char;
+}
+
 #[cfg(any(test, feature = "stub"))]
 #[doc(hidden)]
 #[allow(unused_variables)]
@@ -28,6 +99,447 @@ pub mod overrides {
         0
     }
 
+    #[no_mangle]
+    pub extern "C" fn plugin_dispatch_notification(
+        notif: *const notification_t,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    lazy_static::lazy_static! {
+        // Like collectd, a read callback can't be registered under a name that is already taken
+        static ref READ_CALLBACKS: ::std::sync::Mutex<Vec<String>> =
+            ::std::sync::Mutex::new(Vec::new());
+    }
+
+    unsafe fn callback_name(name: *const ::std::os::raw::c_char) -> String {
+        ::std::ffi::CStr::from_ptr(name)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    lazy_static::lazy_static! {
+        // Collectd only rejects write, flush, log, notification, and missing callbacks when it
+        // runs out of memory, so tests pick the names of the callbacks that are rejected
+        static ref REJECTED_CALLBACKS: ::std::sync::Mutex<Vec<String>> =
+            ::std::sync::Mutex::new(Vec::new());
+    }
+
+    /// Rejects any write, flush, log, notification, or missing callback registered under the name
+    pub fn reject_callback(name: &str) {
+        REJECTED_CALLBACKS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(String::from(name));
+    }
+
+    // Like collectd, the user data of a rejected callback is freed (unlike a rejected read
+    // callback, whose user data is left with the caller)
+    unsafe fn register_callback(
+        name: *const ::std::os::raw::c_char,
+        user_data: *const user_data_t,
+    ) -> ::std::os::raw::c_int {
+        let name = callback_name(name);
+        let rejected = REJECTED_CALLBACKS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(&name);
+
+        if !rejected {
+            return 0;
+        }
+
+        if let Some(free_func) = user_data.as_ref().and_then(|x| x.free_func) {
+            free_func((*user_data).data);
+        }
+        -1
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn plugin_unregister_read(
+        name: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int {
+        let name = callback_name(name);
+        let mut callbacks = READ_CALLBACKS.lock().unwrap_or_else(|e| e.into_inner());
+        match callbacks.iter().position(|x| *x == name) {
+            Some(ind) => {
+                callbacks.remove(ind);
+                0
+            }
+            None => -1,
+        }
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_unregister_write(
+        name: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_unregister_flush(
+        name: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_unregister_missing(
+        name: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn plugin_unregister_log(
+        name: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int {
+        let name = callback_name(name);
+        let removed = {
+            let mut callbacks = LOG_CALLBACKS.lock().unwrap_or_else(|e| e.into_inner());
+            match callbacks.iter().position(|x| x.0 == name) {
+                Some(ind) => callbacks.remove(ind),
+                None => return -1,
+            }
+        };
+
+        // Like collectd, the user data is freed once the callback is unregistered
+        let LogCallback(_, _, user_data) = removed;
+        if let Some(free_func) = user_data.free_func {
+            free_func(user_data.data);
+        }
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_unregister_notification(
+        name: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    // The real function is variadic, but since the arguments are ignored the stub does not need
+    // to be
+    #[no_mangle]
+    pub extern "C" fn plugin_unregister_read_group(
+        group: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn fc_register_match(
+        name: *const ::std::os::raw::c_char,
+        proc_: match_proc_t,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn fc_register_target(
+        name: *const ::std::os::raw::c_char,
+        proc_: target_proc_t,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_flush(
+        plugin: *const ::std::os::raw::c_char,
+        timeout: cdtime_t,
+        identifier: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn global_option_get(
+        option: *const ::std::os::raw::c_char,
+    ) -> *const ::std::os::raw::c_char {
+        ::std::ptr::null()
+    }
+
+    // Log callbacks are recorded so that, like in collectd, a message logged while testing a
+    // plugin is delivered to the plugins that registered to receive log messages
+    #[derive(Clone)]
+    struct LogCallback(String, plugin_log_cb, user_data_t);
+
+    unsafe impl Send for LogCallback {}
+
+    lazy_static::lazy_static! {
+        static ref LOG_CALLBACKS: ::std::sync::Mutex<Vec<LogCallback>> =
+            ::std::sync::Mutex::new(Vec::new());
+    }
+
+    thread_local! {
+        // Guards against a log plugin that logs while receiving a message
+        static LOGGING: ::std::cell::Cell<bool> = const { ::std::cell::Cell::new(false) };
+    }
+
+    // Collectd formats the message before handing it to log callbacks, but the crate always logs
+    // preformatted messages, so the format is the message
+    #[no_mangle]
+    pub unsafe extern "C" fn plugin_log(
+        level: ::std::os::raw::c_int,
+        format: *const ::std::os::raw::c_char,
+    ) {
+        if format.is_null() || LOGGING.with(|x| x.replace(true)) {
+            return;
+        }
+
+        let callbacks = LOG_CALLBACKS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+
+        for LogCallback(_, callback, mut user_data) in callbacks {
+            if let Some(callback) = callback {
+                callback(level, format, &mut user_data);
+            }
+        }
+
+        LOGGING.with(|x| x.set(false));
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_get_ds(name: *const ::std::os::raw::c_char) -> *const data_set_t {
+        ::std::ptr::null()
+    }
+
+    #[no_mangle]
+    pub extern "C" fn meta_data_create() -> *mut meta_data_t {
+        ::std::ptr::null_mut()
+    }
+
+    #[no_mangle]
+    pub extern "C" fn meta_data_destroy(md: *mut meta_data_t) {}
+
+    #[no_mangle]
+    pub extern "C" fn meta_data_add_string(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn meta_data_add_signed_int(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: i64,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn meta_data_add_unsigned_int(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: u64,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn meta_data_add_double(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: f64,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn meta_data_add_boolean(
+        md: *mut meta_data_t,
+        key: *const ::std::os::raw::c_char,
+        value: bool,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn uc_get_rate_by_name(
+        name: *const ::std::os::raw::c_char,
+        ret_values: *mut *mut gauge_t,
+        ret_values_num: *mut ::std::os::raw::c_ulong,
+    ) -> ::std::os::raw::c_int {
+        -1
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_notification_meta_add_string(
+        n: *mut notification_t,
+        name: *const ::std::os::raw::c_char,
+        value: *const ::std::os::raw::c_char,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_notification_meta_add_signed_int(
+        n: *mut notification_t,
+        name: *const ::std::os::raw::c_char,
+        value: i64,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_notification_meta_add_unsigned_int(
+        n: *mut notification_t,
+        name: *const ::std::os::raw::c_char,
+        value: u64,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_notification_meta_add_double(
+        n: *mut notification_t,
+        name: *const ::std::os::raw::c_char,
+        value: f64,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_notification_meta_add_boolean(
+        n: *mut notification_t,
+        name: *const ::std::os::raw::c_char,
+        value: bool,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_notification_meta_free(
+        n: *mut notification_meta_t,
+    ) -> ::std::os::raw::c_int {
+        0
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_get_interval() -> cdtime_t {
+        0
+    }
+
+    thread_local! {
+        // Collectd's plugin context is thread local and zeroed until a context is set
+        static PLUGIN_CTX: ::std::cell::Cell<plugin_ctx_t> =
+            const { ::std::cell::Cell::new(unsafe { ::std::mem::zeroed() }) };
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_get_ctx() -> plugin_ctx_t {
+        PLUGIN_CTX.with(|x| x.get())
+    }
+
+    #[no_mangle]
+    pub extern "C" fn plugin_set_ctx(ctx: plugin_ctx_t) -> plugin_ctx_t {
+        PLUGIN_CTX.with(|x| x.replace(ctx))
+    }
+
+    #[cfg(collectd57)]
+    #[no_mangle]
+    pub unsafe extern "C" fn plugin_register_complex_read(
+        group: *const ::std::os::raw::c_char,
+        name: *const ::std::os::raw::c_char,
+        callback: plugin_read_cb,
+        interval: cdtime_t,
+        user_data: *const user_data_t,
+    ) -> ::std::os::raw::c_int {
+        register_read(name)
+    }
+
+    #[cfg(not(collectd57))]
+    #[no_mangle]
+    pub unsafe extern "C" fn plugin_register_complex_read(
+        group: *const ::std::os::raw::c_char,
+        name: *const ::std::os::raw::c_char,
+        callback: plugin_read_cb,
+        interval: *const timespec,
+        user_data: *mut user_data_t,
+    ) -> ::std::os::raw::c_int {
+        register_read(name)
+    }
+
+    unsafe fn register_read(name: *const ::std::os::raw::c_char) -> ::std::os::raw::c_int {
+        let name = callback_name(name);
+        let mut callbacks = READ_CALLBACKS.lock().unwrap_or_else(|e| e.into_inner());
+        if callbacks.contains(&name) {
+            return -1;
+        }
+
+        callbacks.push(name);
+        0
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn plugin_register_write(
+        name: *const ::std::os::raw::c_char,
+        callback: plugin_write_cb,
+        user_data: *const user_data_t,
+    ) -> ::std::os::raw::c_int {
+        register_callback(name, user_data)
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn plugin_register_flush(
+        name: *const ::std::os::raw::c_char,
+        callback: plugin_flush_cb,
+        user_data: *const user_data_t,
+    ) -> ::std::os::raw::c_int {
+        register_callback(name, user_data)
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn plugin_register_missing(
+        name: *const ::std::os::raw::c_char,
+        callback: plugin_missing_cb,
+        user_data: *const user_data_t,
+    ) -> ::std::os::raw::c_int {
+        register_callback(name, user_data)
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn plugin_register_log(
+        name: *const ::std::os::raw::c_char,
+        callback: plugin_log_cb,
+        user_data: *const user_data_t,
+    ) -> ::std::os::raw::c_int {
+        let status = register_callback(name, user_data);
+        if status != 0 {
+            return status;
+        }
+
+        let user_data = if user_data.is_null() {
+            user_data_t {
+                data: ::std::ptr::null_mut(),
+                free_func: None,
+            }
+        } else {
+            *user_data
+        };
+
+        LOG_CALLBACKS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(LogCallback(callback_name(name), callback, user_data));
+        0
+    }
+
+    #[no_mangle]
+    pub unsafe extern "C" fn plugin_register_notification(
+        name: *const ::std::os::raw::c_char,
+        callback: plugin_notification_cb,
+        user_data: *const user_data_t,
+    ) -> ::std::os::raw::c_int {
+        register_callback(name, user_data)
+    }
+
     #[no_mangle]
     pub static mut hostname_g: [::std::os::raw::c_char; ARR_LENGTH] = [0; ARR_LENGTH];
 }
@@ -0,0 +1,553 @@
+use crate::api::{collectd_log, reload_log_filter, CdTime, IntoDuration, LogLevel};
+use crate::errors::FfiError;
+use crate::plugins::PluginCapabilities;
+use crate::registry::{self, InstanceStats, PluginInstance};
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::panic::catch_unwind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How long a connection waits for a command before checking whether the socket is shutting down
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A Unix domain socket that gives operators runtime introspection into the plugins built with
+/// this crate. The protocol mirrors collectd's unixsock plugin: one command per line, and each
+/// response starts with a status line of `<status> <message>`. A negative status denotes an error,
+/// while a positive status is the number of lines that follow.
+///
+/// The supported commands are:
+///
+/// - `LIST`: the registered plugin instances and their capabilities
+/// - `STATS`: per instance counts of the callbacks collectd has invoked and how many failed
+/// - `DIAG`: the crate's diagnostics (instances, counters, and last errors), which are also
+///   written to the collectd log
+/// - `FLUSH [timeout=<duration>] [plugin=<name>] [identifier=<id>]`: flushes the instances
+///   capable of flushing. The timeout is a humantime duration (eg: `10s`) and the plugin is either
+///   a plugin manager's name or an instance name.
+/// - `DISABLE <plugin>`: pauses the instances of the plugin (either a plugin manager's name or an
+///   instance name) without unregistering them. Their callbacks return immediately, so a disabled
+///   instance is neither read from nor written to (eg: while its target is under maintenance).
+/// - `ENABLE <plugin>`: resumes the instances paused with `DISABLE`
+/// - `LOGFILTER <filters>`: replaces the log filter of the logger installed with
+///   `CollectdLoggerBuilder` using the env_logger syntax (eg: `info,mymodule=debug`)
+///
+/// The socket is removed when the `ControlSocket` is dropped, so it is recommended to store it
+/// alongside the plugin.
+///
+/// # Example
+///
+/// ```
+/// # fn main() {
+/// use collectd_plugin::{ConfigItem, ConfigValue, ControlSocket, PluginManager, PluginRegistration};
+/// use std::error;
+///
+/// #[derive(Default)]
+/// struct MyPlugin;
+/// impl PluginManager for MyPlugin {
+///     fn name() -> &'static str {
+///         "myplugin"
+///     }
+///
+///     fn plugins(config: Option<&[ConfigItem]>) -> Result<PluginRegistration, Box<error::Error>> {
+///         let path = config
+///             .unwrap_or_default()
+///             .iter()
+///             .find(|x| x.key == "ControlSocket")
+///             .and_then(|x| match x.values.first() {
+///                 Some(ConfigValue::String(path)) => Some(*path),
+///                 _ => None,
+///             });
+///
+///         if let Some(path) = path {
+///             let socket = ControlSocket::bind(path)?;
+///             // store the socket with the plugin
+///         }
+///         unimplemented!()
+///     }
+/// }
+/// # }
+/// ```
+pub struct ControlSocket {
+    path: PathBuf,
+
+    /// Sockets passed by systemd belong to the socket unit and are left in place
+    owned: bool,
+    shutdown: Arc<AtomicBool>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ControlSocket {
+    /// Listens for commands at the given path. A stale socket at the path (eg: left behind by an
+    /// unclean shutdown) is replaced, but any other kind of file is left untouched and causes an
+    /// error.
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<ControlSocket> {
+        let path = path.as_ref().to_path_buf();
+        if let Ok(meta) = fs::symlink_metadata(&path) {
+            if meta.file_type().is_socket() {
+                fs::remove_file(&path)?;
+            }
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        ControlSocket::listen_on(listener, path, true)
+    }
+
+    /// Listens for commands on a socket that is already bound, such as one passed by systemd's
+    /// socket activation (see `listen_fd`). The socket must be bound to a path, which is not
+    /// removed when the `ControlSocket` is dropped.
+    pub fn from_listener(listener: UnixListener) -> io::Result<ControlSocket> {
+        let path = listener
+            .local_addr()?
+            .as_pathname()
+            .map(Path::to_path_buf)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "control socket has no path")
+            })?;
+
+        ControlSocket::listen_on(listener, path, false)
+    }
+
+    fn listen_on(listener: UnixListener, path: PathBuf, owned: bool) -> io::Result<ControlSocket> {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let stop = Arc::clone(&shutdown);
+        let handle = thread::Builder::new()
+            .name(String::from("collectd-control"))
+            .spawn(move || listen(&listener, &stop))?;
+
+        Ok(ControlSocket {
+            path,
+            owned,
+            shutdown,
+            handle: Mutex::new(Some(handle)),
+        })
+    }
+
+    /// The path the socket is listening at
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+
+        // Wake up the listener so that it notices the shutdown. Connections are served on their
+        // own threads, so a woken listener exits right away. A listener that couldn't be woken up
+        // is left waiting rather than holding up collectd's shutdown.
+        let woken = UnixStream::connect(&self.path).is_ok();
+        let handle = self
+            .handle
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let (true, Some(handle)) = (woken, handle) {
+            let _ = handle.join();
+        }
+
+        if self.owned {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn listen(listener: &UnixListener, shutdown: &Arc<AtomicBool>) {
+    for stream in listener.incoming() {
+        if shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+
+        // Each connection is served on its own thread so that a client that keeps its connection
+        // open doesn't hold up the others
+        let stop = Arc::clone(shutdown);
+        let res = stream.and_then(|stream| {
+            stream.set_read_timeout(Some(READ_TIMEOUT))?;
+            thread::Builder::new()
+                .name(String::from("collectd-control"))
+                .spawn(move || {
+                    let res = stream
+                        .try_clone()
+                        .and_then(|reader| serve(BufReader::new(reader), stream, &stop));
+                    log_connection_error(&res);
+                })
+                .map(|_| ())
+        });
+
+        log_connection_error(&res);
+    }
+}
+
+fn log_connection_error(res: &io::Result<()>) {
+    if let Err(ref e) = *res {
+        collectd_log(LogLevel::Warning, &format!("control socket error: {}", e));
+    }
+}
+
+fn serve<R: BufRead, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    shutdown: &AtomicBool,
+) -> io::Result<()> {
+    let mut buf = Vec::new();
+    while !shutdown.load(Ordering::SeqCst) {
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) => break,
+            Ok(_) => {}
+
+            // No command arrived before the read timed out. Whatever was read of the line is kept
+            // in the buffer while the shutdown is checked.
+            Err(ref e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                continue
+            }
+            Err(e) => return Err(e),
+        }
+
+        let line = std::str::from_utf8(&buf)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+            .trim();
+        if !line.is_empty() {
+            let response = execute(line, &registry::instances());
+            writer.write_all(response.as_bytes())?;
+            writer.flush()?;
+        }
+        buf.clear();
+    }
+
+    Ok(())
+}
+
+/// Executes a single command line and returns the newline terminated response
+fn execute(line: &str, instances: &[Arc<PluginInstance>]) -> String {
+    let line = line.trim();
+    let (command, args) = match line.find(char::is_whitespace) {
+        Some(idx) => (&line[..idx], line[idx..].trim()),
+        None => (line, ""),
+    };
+
+    match command.to_uppercase().as_str() {
+        "LIST" => listing(instances, |x| format!("{:?}", x.capabilities)),
+        "STATS" => listing(instances, |x| x.stats.to_string()),
+        "DIAG" => diagnostics(instances),
+        "FLUSH" => flush(args, instances),
+        "DISABLE" => set_disabled(args, true, instances),
+        "ENABLE" => set_disabled(args, false, instances),
+        "LOGFILTER" => log_filter(args),
+        _ => format!("-1 Unknown command: {}\n", command),
+    }
+}
+
+fn listing<F>(instances: &[Arc<PluginInstance>], describe: F) -> String
+where
+    F: Fn(&PluginInstance) -> String,
+{
+    let mut msg = format!("{} Instances found\n", instances.len());
+    for instance in instances {
+        let _ = writeln!(msg, "{} {}", instance.name, describe(instance));
+    }
+    msg
+}
+
+fn diagnostics(instances: &[Arc<PluginInstance>]) -> String {
+    let lines = registry::diagnostics(instances);
+    let mut msg = format!("{} Lines follow\n", lines.len());
+    for line in lines {
+        collectd_log(LogLevel::Info, &line);
+        msg.push_str(&line);
+        msg.push('\n');
+    }
+    msg
+}
+
+fn flush(args: &str, instances: &[Arc<PluginInstance>]) -> String {
+    let mut timeout = None;
+    let mut plugin = None;
+    let mut identifier = None;
+
+    for arg in args.split_whitespace() {
+        let mut split = arg.splitn(2, '=');
+        match (split.next(), split.next()) {
+            (Some("timeout"), Some(val)) => match val.into_duration() {
+                Ok(dur) => timeout = Some(CdTime::from(dur).into()),
+                Err(e) => return format!("-1 Invalid timeout: {}\n", e),
+            },
+            (Some("plugin"), Some(val)) => plugin = Some(val),
+            (Some("identifier"), Some(val)) => identifier = Some(val),
+            _ => return format!("-1 Invalid option: {}\n", arg),
+        }
+    }
+
+    let mut successes = 0;
+    let mut failures = Vec::new();
+    let targets = instances
+        .iter()
+        .filter(|x| x.capabilities.intersects(PluginCapabilities::FLUSH))
+        .filter(|x| x.is_active())
+        .filter(|x| match plugin {
+            Some(p) => x.name == p || x.manager == p,
+            None => true,
+        });
+
+    for instance in targets {
+        let stats = &instance.stats;
+        InstanceStats::incr(&stats.flushes);
+        let res = catch_unwind(|| instance.plugin.flush(timeout, identifier))
+            .map_err(|_| FfiError::Panic)
+            .and_then(|x| x.map_err(FfiError::Plugin));
+
+        instance.record_result(&stats.flush_errors, &res);
+        if res.is_ok() {
+            successes += 1;
+        } else {
+            failures.push(instance.name.as_str());
+        }
+    }
+
+    if failures.is_empty() {
+        format!("0 Done: {} successful, 0 errors\n", successes)
+    } else {
+        format!(
+            "-1 Done: {} successful, {} errors: {}\n",
+            successes,
+            failures.len(),
+            failures.join(", ")
+        )
+    }
+}
+
+fn set_disabled(plugin: &str, disabled: bool, instances: &[Arc<PluginInstance>]) -> String {
+    if plugin.is_empty() {
+        return String::from("-1 Missing plugin\n");
+    }
+
+    let targets: Vec<_> = instances
+        .iter()
+        .filter(|x| x.name == plugin || x.manager == plugin)
+        .collect();
+
+    if targets.is_empty() {
+        return format!("-1 No such plugin: {}\n", plugin);
+    }
+
+    let action = if disabled { "disabled" } else { "enabled" };
+    let mut changed = 0;
+    for instance in targets {
+        if instance.set_disabled(disabled) {
+            changed += 1;
+            let msg = format!("{}: {} from the control socket", instance.name, action);
+            collectd_log(LogLevel::Info, &msg);
+        }
+    }
+
+    format!("0 Done: {} instance(s) {}\n", changed, action)
+}
+
+fn log_filter(args: &str) -> String {
+    if args.is_empty() {
+        String::from("-1 Missing log filter\n")
+    } else if reload_log_filter(args) {
+        format!("0 Log filter set to: {}\n", args)
+    } else {
+        String::from("-1 No logger has been installed\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Duration;
+    use crate::errors::Unregister;
+    use crate::plugins::Plugin;
+    use std::error;
+
+    struct FlushPlugin {
+        fail: bool,
+    }
+
+    impl Plugin for FlushPlugin {
+        fn capabilities(&self) -> PluginCapabilities {
+            PluginCapabilities::READ | PluginCapabilities::FLUSH
+        }
+
+        fn flush(
+            &self,
+            _timeout: Option<Duration>,
+            _identifier: Option<&str>,
+        ) -> Result<(), Box<dyn error::Error>> {
+            if self.fail {
+                Err("flush failed")?
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    struct ReadPlugin;
+    impl Plugin for ReadPlugin {
+        fn capabilities(&self) -> PluginCapabilities {
+            PluginCapabilities::READ
+        }
+    }
+
+    fn instances() -> Vec<Arc<PluginInstance>> {
+        vec![
+            Arc::new(PluginInstance::new(
+                "myplugin",
+                "myplugin/a",
+                Box::new(FlushPlugin { fail: false }),
+            )),
+            Arc::new(PluginInstance::new(
+                "myplugin",
+                "myplugin/b",
+                Box::new(FlushPlugin { fail: true }),
+            )),
+            Arc::new(PluginInstance::new("other", "other", Box::new(ReadPlugin))),
+        ]
+    }
+
+    #[test]
+    fn test_list() {
+        assert_eq!(
+            execute("LIST", &instances()),
+            "3 Instances found\n\
+             myplugin/a READ | FLUSH\n\
+             myplugin/b READ | FLUSH\n\
+             other READ\n"
+        );
+    }
+
+    #[test]
+    fn test_flush() {
+        let instances = instances();
+        assert_eq!(
+            execute("flush plugin=myplugin/a timeout=10s", &instances),
+            "0 Done: 1 successful, 0 errors\n"
+        );
+        assert_eq!(
+            execute("FLUSH", &instances),
+            "-1 Done: 1 successful, 1 errors: myplugin/b\n"
+        );
+        assert_eq!(
+            execute("STATS", &instances),
+            "3 Instances found\n\
+             myplugin/a reads=0 read_errors=0 writes=0 write_errors=0 logs=0 log_errors=0 \
+             flushes=2 flush_errors=0 notifications=0 notification_errors=0 missing=0 missing_errors=0 panics=0\n\
+             myplugin/b reads=0 read_errors=0 writes=0 write_errors=0 logs=0 log_errors=0 \
+             flushes=1 flush_errors=1 notifications=0 notification_errors=0 missing=0 missing_errors=0 panics=0\n\
+             other reads=0 read_errors=0 writes=0 write_errors=0 logs=0 log_errors=0 \
+             flushes=0 flush_errors=0 notifications=0 notification_errors=0 missing=0 missing_errors=0 panics=0\n"
+        );
+    }
+
+    #[test]
+    fn test_diag() {
+        let response = execute("DIAG", &instances());
+        assert!(response.starts_with("4 Lines follow\n"));
+        assert!(response
+            .contains("\nother (capabilities: READ, quarantined: false, disabled: false, reads=0"));
+    }
+
+    #[test]
+    fn test_disable_and_enable() {
+        let instances = instances();
+        assert_eq!(
+            execute("DISABLE myplugin", &instances),
+            "0 Done: 2 instance(s) disabled\n"
+        );
+        assert!(instances[0].is_disabled() && instances[1].is_disabled());
+        assert!(!instances[2].is_disabled());
+
+        // Disabled instances are not flushed
+        assert_eq!(
+            execute("FLUSH", &instances),
+            "0 Done: 0 successful, 0 errors\n"
+        );
+
+        assert_eq!(
+            execute("ENABLE myplugin/b", &instances),
+            "0 Done: 1 instance(s) enabled\n"
+        );
+        assert!(instances[0].is_disabled() && !instances[1].is_disabled());
+        assert_eq!(
+            execute("ENABLE myplugin/b", &instances),
+            "0 Done: 0 instance(s) enabled\n"
+        );
+
+        assert_eq!(execute("DISABLE", &instances), "-1 Missing plugin\n");
+        assert_eq!(
+            execute("DISABLE nope", &instances),
+            "-1 No such plugin: nope\n"
+        );
+    }
+
+    #[test]
+    fn test_flush_skips_unregistered() {
+        let instances = instances();
+        let err = Unregister(String::from("device was removed"));
+        let res: Result<(), FfiError<'_>> = Err(FfiError::Plugin(Box::new(err)));
+        instances[1].record_result(&instances[1].stats.flush_errors, &res);
+        assert_eq!(
+            execute("FLUSH", &instances),
+            "0 Done: 1 successful, 0 errors\n"
+        );
+    }
+
+    #[test]
+    fn test_flush_invalid_options() {
+        assert_eq!(
+            execute("FLUSH timeout=soon", &instances()),
+            "-1 Invalid timeout: unable to parse duration: soon\n"
+        );
+        assert_eq!(
+            execute("FLUSH instance=a", &instances()),
+            "-1 Invalid option: instance=a\n"
+        );
+    }
+
+    #[test]
+    fn test_unknown_and_log_filter() {
+        assert_eq!(
+            execute("PUTVAL a/b/c", &instances()),
+            "-1 Unknown command: PUTVAL\n"
+        );
+        assert_eq!(
+            execute("LOGFILTER", &instances()),
+            "-1 Missing log filter\n"
+        );
+    }
+
+    #[test]
+    fn test_serve_lines() {
+        let input = b"LIST\n\nUNKNOWN\n";
+        let mut output = Vec::new();
+        serve(&input[..], &mut output, &AtomicBool::new(false)).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.ends_with("-1 Unknown command: UNKNOWN\n"));
+    }
+
+    #[test]
+    fn test_idle_connection() {
+        let path = std::env::temp_dir().join(format!("collectd-control-{}", std::process::id()));
+        let socket = ControlSocket::bind(&path).unwrap();
+
+        // A client that sends nothing doesn't keep others from being served
+        let _idle = UnixStream::connect(&path).unwrap();
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(b"UNKNOWN\n").unwrap();
+        let mut response = String::new();
+        BufReader::new(&client).read_line(&mut response).unwrap();
+        assert_eq!(response, "-1 Unknown command: UNKNOWN\n");
+
+        // Nor does it hold up the shutdown
+        drop(socket);
+        assert!(!path.exists());
+    }
+}
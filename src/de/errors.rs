@@ -14,6 +14,8 @@ pub enum DeError {
     ExpectStruct,
     ExpectObject,
     DataTypeNotSupported,
+    UnknownKey(String),
+    InexactNumber(f64),
 }
 
 // Since the failure crate can't automatically implement serde::de::Error (see issue
@@ -51,6 +53,8 @@ impl Display for Error {
             DeError::DataTypeNotSupported => {
                 write!(f, "could not deserialize as datatype not supported")
             }
+            DeError::UnknownKey(ref s) => write!(f, "unknown config key `{}`", s),
+            DeError::InexactNumber(x) => write!(f, "number {} is not a representable integer", x),
         }
     }
 }
@@ -0,0 +1,36 @@
+use crate::api::HostNaming;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use std::fmt;
+
+struct HostNamingVisitor;
+
+impl<'de> Visitor<'de> for HostNamingVisitor {
+    type Value = HostNaming;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("HOSTNAME | POD | CONTAINER | AUTO")
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<HostNaming, E>
+    where
+        E: de::Error,
+    {
+        let upper = s.to_ascii_uppercase();
+        match upper.as_str() {
+            "HOSTNAME" => Ok(HostNaming::Hostname),
+            "POD" => Ok(HostNaming::Pod),
+            "CONTAINER" => Ok(HostNaming::Container),
+            "AUTO" => Ok(HostNaming::Auto),
+            x => Err(E::custom(format!("Did not expect host naming of: {}", x))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for HostNaming {
+    fn deserialize<D>(deserializer: D) -> Result<HostNaming, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(HostNamingVisitor)
+    }
+}
@@ -0,0 +1,433 @@
+use super::errors::Error;
+use super::DeResult;
+use crate::api::MetaValue;
+use serde::de::{
+    self, Deserialize, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+/// Meta data entries arranged by their period separated keys
+#[derive(Debug)]
+enum Node<'a> {
+    Leaf(&'a MetaValue),
+    Branch(Vec<(&'a str, Node<'a>)>),
+}
+
+impl<'a> Node<'a> {
+    fn insert(&mut self, key: &'a str, value: &'a MetaValue) -> DeResult<()> {
+        let mut node = self;
+        let mut segments = key.split('.').peekable();
+        while let Some(segment) = segments.next() {
+            let children = match *node {
+                Node::Branch(ref mut children) => children,
+                Node::Leaf(_) => {
+                    return Err(de::Error::custom(format!("conflicting key: {}", key)))
+                }
+            };
+
+            let pos = match children.iter().position(|(name, _)| *name == segment) {
+                Some(_) if segments.peek().is_none() => {
+                    return Err(de::Error::custom(format!("duplicate key: {}", key)))
+                }
+                Some(pos) => pos,
+                None => {
+                    let child = if segments.peek().is_none() {
+                        Node::Leaf(value)
+                    } else {
+                        Node::Branch(Vec::new())
+                    };
+                    children.push((segment, child));
+                    children.len() - 1
+                }
+            };
+
+            node = &mut children[pos].1;
+        }
+
+        Ok(())
+    }
+}
+
+/// Deserializes meta data entries that were flattened with `ser::to_meta` back into a struct.
+/// Entries that the struct doesn't know about are ignored, unless the struct denies unknown
+/// fields.
+///
+/// ```
+/// use collectd_plugin::MetaValue;
+/// use collectd_plugin::de::from_meta;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct Disk<'a> {
+///     model: &'a str,
+///     rotational: bool,
+/// }
+///
+/// #[derive(Deserialize, Debug, PartialEq)]
+/// struct Context<'a> {
+///     #[serde(borrow)]
+///     disk: Disk<'a>,
+///     queue_depth: Option<u32>,
+/// }
+///
+/// let entries = vec![
+///     (String::from("disk.model"), MetaValue::String(String::from("ssd-1"))),
+///     (String::from("disk.rotational"), MetaValue::Boolean(false)),
+/// ];
+///
+/// let context: Context<'_> = from_meta(&entries).unwrap();
+/// assert_eq!(context.disk, Disk { model: "ssd-1", rotational: false });
+/// assert_eq!(context.queue_depth, None);
+/// ```
+pub fn from_meta<'a, T>(entries: &'a [(String, MetaValue)]) -> DeResult<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut root = Node::Branch(Vec::new());
+    for (key, value) in entries {
+        root.insert(key, value)?;
+    }
+
+    T::deserialize(&root)
+}
+
+impl<'de> de::Deserializer<'de> for &Node<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> DeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match *self {
+            Node::Leaf(MetaValue::String(ref s)) => visitor.visit_borrowed_str(s),
+            Node::Leaf(MetaValue::SignedInt(x)) => visitor.visit_i64(*x),
+            Node::Leaf(MetaValue::UnsignedInt(x)) => visitor.visit_u64(*x),
+            Node::Leaf(MetaValue::Double(x)) => visitor.visit_f64(*x),
+            Node::Leaf(MetaValue::Boolean(x)) => visitor.visit_bool(*x),
+            Node::Branch(ref children) => visitor.visit_map(BranchAccess {
+                children: children.iter(),
+                value: None,
+            }),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> DeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> DeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> DeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> DeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> DeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let children = match *self {
+            Node::Branch(ref children) => children,
+            Node::Leaf(_) => return Err(de::Error::custom("expecting a sequence")),
+        };
+
+        let mut elements = Vec::with_capacity(children.len());
+        for (key, node) in children {
+            let ind = key
+                .parse::<usize>()
+                .map_err(|_| de::Error::custom(format!("expecting sequence index: {}", key)))?;
+            elements.push((ind, node));
+        }
+
+        elements.sort_by_key(|&(ind, _)| ind);
+        visitor.visit_seq(ElementsAccess {
+            elements: elements.into_iter().map(|(_, node)| node),
+        })
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> DeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> DeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> DeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match *self {
+            Node::Leaf(MetaValue::String(ref s)) => {
+                visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(s.as_str()))
+            }
+            Node::Branch(ref children) if children.len() == 1 => {
+                let (variant, ref node) = children[0];
+                visitor.visit_enum(VariantNode { variant, node })
+            }
+            _ => Err(de::Error::custom("expecting an enum variant")),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf map struct identifier ignored_any
+    }
+}
+
+struct BranchAccess<'de, 'b> {
+    children: ::std::slice::Iter<'b, (&'de str, Node<'de>)>,
+    value: Option<&'b Node<'de>>,
+}
+
+impl<'de, 'b> MapAccess<'de> for BranchAccess<'de, 'b> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> DeResult<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.children.next() {
+            Some((key, node)) => {
+                self.value = Some(node);
+                seed.deserialize(KeyDeserializer(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> DeResult<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let node = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("value requested before key"))?;
+        seed.deserialize(node)
+    }
+}
+
+struct ElementsAccess<I> {
+    elements: I,
+}
+
+impl<'de, 'b, I> SeqAccess<'de> for ElementsAccess<I>
+where
+    I: Iterator<Item = &'b Node<'de>>,
+    'de: 'b,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> DeResult<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.elements.next() {
+            Some(node) => seed.deserialize(node).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+struct VariantNode<'de, 'b> {
+    variant: &'de str,
+    node: &'b Node<'de>,
+}
+
+impl<'de, 'b> EnumAccess<'de> for VariantNode<'de, 'b> {
+    type Error = Error;
+    type Variant = &'b Node<'de>;
+
+    fn variant_seed<V>(self, seed: V) -> DeResult<(V::Value, Self::Variant)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(KeyDeserializer(self.variant))?;
+        Ok((variant, self.node))
+    }
+}
+
+impl<'de> VariantAccess<'de> for &Node<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> DeResult<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> DeResult<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> DeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> DeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_any(self, visitor)
+    }
+}
+
+/// Keys are always strings, but maps may be keyed by numbers or booleans, so those are parsed
+/// from the key when requested
+struct KeyDeserializer<'de>(&'de str);
+
+macro_rules! deserialize_parsed_key {
+    ($($method:ident => $visit:ident),*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> DeResult<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                match self.0.parse() {
+                    Ok(x) => visitor.$visit(x),
+                    Err(_) => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> DeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    deserialize_parsed_key! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 f32 f64 char str string bytes byte_buf option unit unit_struct
+        newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ser::to_meta;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Media {
+        Ssd,
+        Hdd { rpm: u32 },
+        Tape(u8, u8),
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Disk {
+        media: Vec<Media>,
+        serial: Option<String>,
+        partitions: Vec<i8>,
+        labels: BTreeMap<u8, char>,
+        ratio: (f32, bool),
+    }
+
+    #[test]
+    fn test_meta_round_trip() {
+        let mut labels = BTreeMap::new();
+        labels.insert(1, 'a');
+        labels.insert(12, 'b');
+
+        let disk = Disk {
+            media: vec![Media::Ssd, Media::Hdd { rpm: 7200 }, Media::Tape(1, 2)],
+            serial: None,
+            partitions: (0..12).collect(),
+            labels,
+            ratio: (0.5, true),
+        };
+
+        let entries = to_meta(&disk).unwrap();
+        let actual: Disk = from_meta(&entries).unwrap();
+        assert_eq!(disk, actual);
+    }
+
+    #[test]
+    fn test_from_meta_conflicting_keys() {
+        let entries = vec![
+            (String::from("disk"), MetaValue::Boolean(true)),
+            (String::from("disk.model"), MetaValue::Boolean(true)),
+        ];
+        let res: DeResult<BTreeMap<String, bool>> = from_meta(&entries);
+        assert!(res.is_err());
+
+        let entries = vec![
+            (String::from("disk"), MetaValue::Boolean(true)),
+            (String::from("disk"), MetaValue::Boolean(false)),
+        ];
+        let res: DeResult<BTreeMap<String, bool>> = from_meta(&entries);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_from_meta_type_mismatch() {
+        #[derive(Deserialize, Debug)]
+        struct Queue {
+            #[allow(dead_code)]
+            depth: u8,
+        }
+
+        let entries = vec![(String::from("depth"), MetaValue::SignedInt(-1))];
+        let res: DeResult<Queue> = from_meta(&entries);
+        assert!(res.is_err());
+    }
+}
@@ -1,8 +1,13 @@
 mod deconfig;
 mod errors;
+mod host;
 mod level;
+mod meta;
+mod sanitize;
+mod schedule;
 pub use self::errors::*;
 pub use self::level::*;
+pub use self::meta::from_meta;
 
 use self::deconfig::*;
 use self::errors::Error;
@@ -29,12 +34,22 @@ enum DeType<'a> {
 
 pub struct Deserializer<'a> {
     depth: Vec<DeType<'a>>,
+
+    /// Number of additional sequence values consumed by the last element (eg: a tuple that
+    /// spans multiple values)
+    consumed: usize,
+
+    /// Reject unknown keys and numbers that can't be represented exactly instead of ignoring or
+    /// truncating them
+    strict: bool,
 }
 
 impl<'a> Deserializer<'a> {
     fn from_collectd(input: Vec<(&'a str, Vec<DeConfig<'a>>)>) -> Self {
         Deserializer {
             depth: vec![DeType::Struct(input, 0)],
+            consumed: 0,
+            strict: false,
         }
     }
 
@@ -84,6 +99,17 @@ impl<'a> Deserializer<'a> {
         }
     }
 
+    /// Grabs a number that is to be deserialized as an integer within the given bounds. In strict
+    /// mode, a number with a fractional part or outside of the bounds is an error.
+    fn grab_integer(&self, min: f64, max: f64) -> DeResult<f64> {
+        let x = self.grab_number()?;
+        if self.strict && (x.fract() != 0.0 || x < min || x > max) {
+            return Err(Error(DeError::InexactNumber(x)));
+        }
+
+        Ok(x)
+    }
+
     fn pop(&mut self) {
         self.depth.pop();
     }
@@ -138,6 +164,42 @@ where
     T::deserialize(&mut deserializer)
 }
 
+/// Like `from_collectd`, but for those who want config drift caught when collectd starts instead
+/// of silently ignored: a key that the struct doesn't have a field for, or a number that would be
+/// truncated or wrapped to fit into an integer field, is an error. Returning the error from
+/// `PluginManager::plugins` fails the plugin's registration.
+///
+/// ```
+/// use collectd_plugin::{ConfigItem, ConfigValue};
+/// use collectd_plugin::de::{from_collectd, from_collectd_strict};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// #[serde(rename_all = "PascalCase")]
+/// struct MyConfig {
+///     #[serde(default)]
+///     port: u16,
+/// }
+///
+/// let items = vec![ConfigItem {
+///     key: "Prot",
+///     values: vec![ConfigValue::Number(8080.0)],
+///     children: vec![],
+/// }];
+///
+/// assert!(from_collectd::<MyConfig>(&items).is_ok());
+/// assert!(from_collectd_strict::<MyConfig>(&items).is_err());
+/// ```
+pub fn from_collectd_strict<'a, T>(s: &'a [ConfigItem<'a>]) -> DeResult<T>
+where
+    T: Deserialize<'a>,
+{
+    let props = from_config(s);
+    let mut deserializer = Deserializer::from_collectd(props);
+    deserializer.strict = true;
+    T::deserialize(&mut deserializer)
+}
+
 impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     type Error = Error;
 
@@ -168,56 +230,64 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        self.grab_number().and_then(|x| visitor.visit_i8(x as i8))
+        self.grab_integer(i8::MIN as f64, i8::MAX as f64)
+            .and_then(|x| visitor.visit_i8(x as i8))
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> DeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.grab_number().and_then(|x| visitor.visit_i16(x as i16))
+        self.grab_integer(i16::MIN as f64, i16::MAX as f64)
+            .and_then(|x| visitor.visit_i16(x as i16))
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> DeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.grab_number().and_then(|x| visitor.visit_i32(x as i32))
+        self.grab_integer(i32::MIN as f64, i32::MAX as f64)
+            .and_then(|x| visitor.visit_i32(x as i32))
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> DeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.grab_number().and_then(|x| visitor.visit_i64(x as i64))
+        self.grab_integer(i64::MIN as f64, i64::MAX as f64)
+            .and_then(|x| visitor.visit_i64(x as i64))
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> DeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.grab_number().and_then(|x| visitor.visit_u8(x as u8))
+        self.grab_integer(u8::MIN as f64, u8::MAX as f64)
+            .and_then(|x| visitor.visit_u8(x as u8))
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> DeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.grab_number().and_then(|x| visitor.visit_u16(x as u16))
+        self.grab_integer(u16::MIN as f64, u16::MAX as f64)
+            .and_then(|x| visitor.visit_u16(x as u16))
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> DeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.grab_number().and_then(|x| visitor.visit_u32(x as u32))
+        self.grab_integer(u32::MIN as f64, u32::MAX as f64)
+            .and_then(|x| visitor.visit_u32(x as u32))
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> DeResult<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.grab_number().and_then(|x| visitor.visit_u64(x as u64))
+        self.grab_integer(u64::MIN as f64, u64::MAX as f64)
+            .and_then(|x| visitor.visit_u64(x as u64))
     }
 
     fn deserialize_f32<V>(self, visitor: V) -> DeResult<V::Value>
@@ -287,15 +357,80 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_seq(SeqSeparated::new(&mut self, len))
     }
 
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> DeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        // Tuple visitors stop asking for elements once they have enough, so the sequence state
+        // is unwound here instead of in `SeqSeparated`
+        let depth = self.depth.len();
+        match self.current()?.clone() {
+            DeType::Item(_key, ref v) => {
+                if v.len() != len {
+                    return Err(de::Error::invalid_length(v.len(), &visitor));
+                }
+
+                let res = visitor.visit_seq(SeqSeparated::new(&mut *self, len));
+                self.depth.truncate(depth);
+                res
+            }
+
+            // A tuple within a sequence (eg: `Server "a" 80` and `Server "b" 81` deserialized as
+            // `Vec<(String, u16)>`) takes the next `len` values of the sequence.
+            DeType::Seq(ref values, ind) => {
+                if ind + len > values.len() {
+                    return Err(de::Error::invalid_length(values.len() - ind, &visitor));
+                }
+
+                let tuple = values[ind..ind + len].to_vec();
+                self.depth.push(DeType::Item("", tuple));
+                let res = visitor.visit_seq(SeqSeparated::new(&mut *self, len));
+                self.depth.truncate(depth);
+                self.consumed = len.saturating_sub(1);
+                res
+            }
+            _ => Err(de::Error::custom(
+                "expected an item when deserializing tuple",
+            )),
+        }
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> DeResult<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
     fn deserialize_struct<V>(
         mut self,
         _name: &'static str,
-        _fields: &'static [&'static str],
+        fields: &'static [&'static str],
         visitor: V,
     ) -> DeResult<V::Value>
     where
         V: Visitor<'de>,
     {
+        if self.strict {
+            let unknown = match *self.current()? {
+                DeType::Struct(ref values, _ind) => unknown_key(values, fields),
+                DeType::Seq(ref values, ind) => match values[ind] {
+                    DeConfig::Object(ref obj) => unknown_key(obj, fields),
+                    _ => None,
+                },
+                _ => None,
+            };
+
+            if let Some(key) = unknown {
+                return Err(Error(DeError::UnknownKey(String::from(key))));
+            }
+        }
+
         // A small hack to remember if we dive into a sequence's children. Since we're going down
         // two levels, we need to remember to pop back up when were down the children.
         let mut to_pop = false;
@@ -375,11 +510,17 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
     forward_to_deserialize_any! {
         bytes
-        byte_buf unit unit_struct tuple
-        tuple_struct map
+        byte_buf unit unit_struct map
     }
 }
 
+fn unknown_key<'a>(values: &[(&'a str, Vec<DeConfig<'a>>)], fields: &[&str]) -> Option<&'a str> {
+    values
+        .iter()
+        .map(|(key, _)| *key)
+        .find(|key| !fields.contains(key))
+}
+
 struct UnitVariantAccess<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
 }
@@ -507,8 +648,10 @@ impl<'de, 'a> SeqAccess<'de> for SeqSeparated<'a, 'de> {
         }
 
         self.de.push_seq(self.item_pos);
-        self.item_pos += 1;
-        seed.deserialize(&mut *self.de).map(Some)
+        let res = seed.deserialize(&mut *self.de).map(Some);
+        self.item_pos += 1 + self.de.consumed;
+        self.de.consumed = 0;
+        res
     }
 }
 
@@ -516,7 +659,7 @@ impl<'de, 'a> SeqAccess<'de> for SeqSeparated<'a, 'de> {
 mod tests {
     use super::super::ConfigValue;
     use super::*;
-    use crate::api::LogLevel;
+    use crate::api::{HostNaming, LogLevel, SanitizePreset, TlsConfig};
     use serde::Deserialize;
 
     #[test]
@@ -811,6 +954,93 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_serde_host_naming() {
+        #[derive(Deserialize, PartialEq, Eq, Debug)]
+        struct MyStruct {
+            host_naming: HostNaming,
+        }
+
+        let items = vec![ConfigItem {
+            key: "host_naming",
+            values: vec![ConfigValue::String("Auto")],
+            children: vec![],
+        }];
+
+        let actual = from_collectd(&items).unwrap();
+        assert_eq!(
+            MyStruct {
+                host_naming: HostNaming::Auto
+            },
+            actual
+        );
+
+        let items = vec![ConfigItem {
+            key: "host_naming",
+            values: vec![ConfigValue::String("vm")],
+            children: vec![],
+        }];
+        assert!(from_collectd::<MyStruct>(&items).is_err());
+    }
+
+    #[test]
+    fn test_serde_sanitize_preset() {
+        #[derive(Deserialize, PartialEq, Eq, Debug)]
+        struct MyStruct {
+            sanitize: SanitizePreset,
+        }
+
+        let items = vec![ConfigItem {
+            key: "sanitize",
+            values: vec![ConfigValue::String("prometheus")],
+            children: vec![],
+        }];
+
+        let actual = from_collectd(&items).unwrap();
+        assert_eq!(
+            MyStruct {
+                sanitize: SanitizePreset::PrometheusLabel
+            },
+            actual
+        );
+
+        let items = vec![ConfigItem {
+            key: "sanitize",
+            values: vec![ConfigValue::String("opentsdb")],
+            children: vec![],
+        }];
+        assert!(from_collectd::<MyStruct>(&items).is_err());
+    }
+
+    #[test]
+    fn test_serde_tls_config() {
+        let items = vec![
+            ConfigItem {
+                key: "URL",
+                values: vec![ConfigValue::String("https://localhost")],
+                children: vec![],
+            },
+            ConfigItem {
+                key: "CACert",
+                values: vec![ConfigValue::String("/etc/ssl/ca.pem")],
+                children: vec![],
+            },
+            ConfigItem {
+                key: "VerifyHost",
+                values: vec![ConfigValue::Boolean(false)],
+                children: vec![],
+            },
+        ];
+
+        let actual: TlsConfig = from_collectd(&items).unwrap();
+        let expected = TlsConfig {
+            ca_cert: Some(std::path::PathBuf::from("/etc/ssl/ca.pem")),
+            verify_host: false,
+            ..Default::default()
+        };
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn test_serde_char() {
         #[derive(Deserialize, PartialEq, Eq, Debug)]
@@ -852,6 +1082,65 @@ mod tests {
         assert_eq!(MyStruct { my_char: '/' }, actual);
     }
 
+    #[test]
+    fn test_serde_tuple_vec_sep() {
+        #[derive(Deserialize, PartialEq, Eq, Debug)]
+        struct MyStruct {
+            server: Vec<(String, u16)>,
+            primary: (String, u16),
+        }
+
+        let items = vec![
+            ConfigItem {
+                key: "server",
+                values: vec![ConfigValue::String("a"), ConfigValue::Number(80.0)],
+                children: vec![],
+            },
+            ConfigItem {
+                key: "primary",
+                values: vec![ConfigValue::String("c"), ConfigValue::Number(82.0)],
+                children: vec![],
+            },
+            ConfigItem {
+                key: "server",
+                values: vec![ConfigValue::String("b"), ConfigValue::Number(81.0)],
+                children: vec![],
+            },
+        ];
+
+        let actual = from_collectd(&items).unwrap();
+        assert_eq!(
+            MyStruct {
+                server: vec![(String::from("a"), 80), (String::from("b"), 81)],
+                primary: (String::from("c"), 82),
+            },
+            actual
+        );
+    }
+
+    #[test]
+    fn test_serde_tuple_missing_value() {
+        #[derive(Deserialize, PartialEq, Eq, Debug)]
+        struct MyStruct {
+            server: Vec<(String, u16)>,
+        }
+
+        let items = vec![
+            ConfigItem {
+                key: "server",
+                values: vec![ConfigValue::String("a"), ConfigValue::Number(80.0)],
+                children: vec![],
+            },
+            ConfigItem {
+                key: "server",
+                values: vec![ConfigValue::String("b")],
+                children: vec![],
+            },
+        ];
+
+        assert!(from_collectd::<MyStruct>(&items).is_err());
+    }
+
     #[test]
     fn test_serde_nested() {
         #[derive(Deserialize, PartialEq, Eq, Debug)]
@@ -1070,4 +1359,51 @@ mod tests {
             actual
         );
     }
+
+    #[test]
+    fn test_serde_strict() {
+        #[derive(Deserialize, PartialEq, Eq, Debug)]
+        struct Server {
+            port: u16,
+        }
+
+        #[derive(Deserialize, PartialEq, Eq, Debug)]
+        struct MyStruct {
+            server: Vec<Server>,
+        }
+
+        let items = |key, port| {
+            vec![ConfigItem {
+                key: "server",
+                values: vec![],
+                children: vec![ConfigItem {
+                    key,
+                    values: vec![ConfigValue::Number(port)],
+                    children: vec![],
+                }],
+            }]
+        };
+
+        let good = items("port", 8080.0);
+        let expected = MyStruct {
+            server: vec![Server { port: 8080 }],
+        };
+        assert_eq!(expected, from_collectd_strict(&good).unwrap());
+
+        let typo = items("prot", 8080.0);
+        assert!(from_collectd::<MyStruct>(&typo).is_err());
+        match from_collectd_strict::<MyStruct>(&typo) {
+            Err(Error(DeError::UnknownKey(ref key))) => assert_eq!("prot", key),
+            x => panic!("expected unknown key: {:?}", x),
+        }
+
+        for port in &[70000.0, 80.5, -1.0] {
+            let lossy = items("port", *port);
+            assert!(from_collectd::<MyStruct>(&lossy).is_ok());
+            match from_collectd_strict::<MyStruct>(&lossy) {
+                Err(Error(DeError::InexactNumber(x))) => assert_eq!(*port, x),
+                x => panic!("expected inexact number: {:?}", x),
+            }
+        }
+    }
 }
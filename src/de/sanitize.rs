@@ -0,0 +1,38 @@
+use crate::api::SanitizePreset;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use std::fmt;
+
+struct SanitizePresetVisitor;
+
+impl<'de> Visitor<'de> for SanitizePresetVisitor {
+    type Value = SanitizePreset;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("GRAPHITE | PROMETHEUS | INFLUX")
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<SanitizePreset, E>
+    where
+        E: de::Error,
+    {
+        let upper = s.to_ascii_uppercase();
+        match upper.as_str() {
+            "GRAPHITE" => Ok(SanitizePreset::Graphite),
+            "PROMETHEUS" => Ok(SanitizePreset::PrometheusLabel),
+            "INFLUX" => Ok(SanitizePreset::Influx),
+            x => Err(E::custom(format!(
+                "Did not expect sanitize preset of: {}",
+                x
+            ))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SanitizePreset {
+    fn deserialize<D>(deserializer: D) -> Result<SanitizePreset, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(SanitizePresetVisitor)
+    }
+}
@@ -0,0 +1,29 @@
+use crate::api::Schedule;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use std::fmt;
+
+struct ScheduleVisitor;
+
+impl<'de> Visitor<'de> for ScheduleVisitor {
+    type Value = Schedule;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a cron-like schedule (eg: \"5 * * * *\")")
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Schedule, E>
+    where
+        E: de::Error,
+    {
+        s.parse().map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D>(deserializer: D) -> Result<Schedule, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(ScheduleVisitor)
+    }
+}
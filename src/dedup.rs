@@ -0,0 +1,214 @@
+use crate::api::{
+    CdTime, ConfigItem, Duration, Identifier, IntoDuration, LogLevel, Notification, Schedule,
+    ValueList,
+};
+use crate::errors::DurationError;
+use crate::plugins::{Plugin, PluginCapabilities, ReadStatus};
+use std::collections::{BTreeSet, HashMap};
+use std::error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Wraps a write plugin so that value lists whose identifier and time have already been written
+/// within a window are dropped. Protects backends from duplicates when collectd re-dispatches
+/// values from its cache or when multiple collectd instances forward overlapping data.
+///
+/// The window is in terms of the time of the values: a value list is a duplicate if a value list
+/// with the same identifier and time has been written, and that time is no older than the window
+/// before the newest time written for the identifier. Identifiers that are no longer written (eg:
+/// a container that has stopped) are forgotten once their newest time is older than the window
+/// before the newest time written for any identifier.
+///
+/// ```
+/// use collectd_plugin::{DedupWriter, Plugin, PluginCapabilities, ValueList};
+/// use std::error;
+///
+/// struct MyWriter;
+///
+/// impl Plugin for MyWriter {
+///     fn capabilities(&self) -> PluginCapabilities {
+///         PluginCapabilities::WRITE
+///     }
+///
+///     fn write_values(&self, list: ValueList<'_>) -> Result<(), Box<dyn error::Error>> {
+///         Ok(())
+///     }
+/// }
+///
+/// let plugin = DedupWriter::new(MyWriter, "5m").unwrap();
+/// ```
+pub struct DedupWriter<P> {
+    plugin: P,
+    window: CdTime,
+    seen: Mutex<Seen>,
+    dropped: AtomicUsize,
+}
+
+#[derive(Default)]
+struct Seen {
+    times: HashMap<Identifier, BTreeSet<u64>>,
+
+    /// The newest time written for any identifier
+    newest: u64,
+
+    /// The newest time written when the identifiers were last swept
+    swept: u64,
+}
+
+impl<P: Plugin> DedupWriter<P> {
+    /// Wraps the plugin, remembering the times written for each identifier for the window
+    pub fn new<T: IntoDuration>(plugin: P, window: T) -> Result<Self, DurationError> {
+        Ok(DedupWriter {
+            plugin,
+            window: CdTime::from(window.into_duration()?),
+            seen: Mutex::new(Seen::default()),
+            dropped: AtomicUsize::new(0),
+        })
+    }
+
+    /// The number of value lists that have been dropped as duplicates
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Records that the identifier has values at the time, returning false if it had already
+    /// been recorded
+    fn first_seen(&self, id: Identifier, time: CdTime) -> bool {
+        let mut guard = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let seen = &mut *guard;
+        let times = seen.times.entry(id).or_default();
+        if !times.insert(time.0) {
+            return false;
+        }
+
+        let newest = times.iter().next_back().cloned().unwrap_or(time.0);
+        let cutoff = newest.saturating_sub(self.window.0);
+        *times = times.split_off(&cutoff);
+
+        // Forget the identifiers whose newest time has fallen out of the window. Sweeping every
+        // identifier is costly, so it happens at most once per window.
+        seen.newest = seen.newest.max(time.0);
+        let cutoff = seen.newest.saturating_sub(self.window.0);
+        if seen.swept < cutoff {
+            seen.times
+                .retain(|_, times| times.range(cutoff..).next().is_some());
+            seen.swept = seen.newest;
+        }
+        true
+    }
+}
+
+impl<P: Plugin> Plugin for DedupWriter<P> {
+    fn initialize(&self) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.initialize()
+    }
+
+    fn reconfigure(&self, config: &[ConfigItem<'_>]) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.reconfigure(config)
+    }
+
+    fn capabilities(&self) -> PluginCapabilities {
+        self.plugin.capabilities()
+    }
+
+    fn log(&self, lvl: LogLevel, msg: &str) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.log(lvl, msg)
+    }
+
+    fn read_values(&self) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.read_values()
+    }
+
+    fn read_status(&self) -> ReadStatus {
+        self.plugin.read_status()
+    }
+
+    fn read_interval(&self) -> Option<Duration> {
+        self.plugin.read_interval()
+    }
+
+    fn read_group(&self) -> Option<&str> {
+        self.plugin.read_group()
+    }
+
+    fn interval_changed(&self, interval: Duration) {
+        self.plugin.interval_changed(interval)
+    }
+
+    fn schedule(&self) -> Option<Schedule> {
+        self.plugin.schedule()
+    }
+
+    fn write_values(&self, list: ValueList<'_>) -> Result<(), Box<dyn error::Error>> {
+        if self.first_seen(Identifier::from(&list), CdTime::from(list.time)) {
+            self.plugin.write_values(list)
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    fn flush(
+        &self,
+        timeout: Option<Duration>,
+        identifier: Option<&str>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.flush(timeout, identifier)
+    }
+
+    fn notification(&self, notification: Notification<'_>) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.notification(notification)
+    }
+
+    fn missing(&self, list: ValueList<'_>) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.missing(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoopPlugin;
+
+    impl Plugin for NoopPlugin {}
+
+    fn ident(type_instance: &str) -> Identifier {
+        "localhost/memory/memory-used"
+            .replace("used", type_instance)
+            .parse()
+            .unwrap()
+    }
+
+    fn secs(x: u64) -> CdTime {
+        CdTime(x << 30)
+    }
+
+    #[test]
+    fn test_dedup_first_seen() {
+        let dedup = DedupWriter::new(NoopPlugin, "60s").unwrap();
+        assert!(dedup.first_seen(ident("used"), secs(100)));
+        assert!(!dedup.first_seen(ident("used"), secs(100)));
+        assert!(dedup.first_seen(ident("free"), secs(100)));
+        assert!(dedup.first_seen(ident("used"), secs(110)));
+
+        // Times that are older than the window are forgotten
+        assert!(dedup.first_seen(ident("used"), secs(200)));
+        assert!(dedup.first_seen(ident("used"), secs(100)));
+        assert!(!dedup.first_seen(ident("used"), secs(200)));
+    }
+
+    #[test]
+    fn test_dedup_forgets_identifiers() {
+        let dedup = DedupWriter::new(NoopPlugin, "60s").unwrap();
+        assert!(dedup.first_seen(ident("used"), secs(100)));
+        assert!(dedup.first_seen(ident("free"), secs(100)));
+        assert!(dedup.first_seen(ident("used"), secs(150)));
+        assert_eq!(2, dedup.seen.lock().unwrap().times.len());
+
+        // Once no longer written, the identifier is forgotten after the window
+        assert!(dedup.first_seen(ident("used"), secs(200)));
+        assert_eq!(1, dedup.seen.lock().unwrap().times.len());
+        assert!(dedup.first_seen(ident("free"), secs(100)));
+    }
+}
@@ -0,0 +1,319 @@
+use crate::api::{CdTime, Duration, Identifier, Timestamp, Value, ValueList};
+use std::fmt::Write as FmtWrite;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{self, Instant};
+
+/// How value lists are written to a debug dump
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DumpFormat {
+    /// One JSON object per line
+    Json,
+
+    /// Collectd's plain text protocol (eg: `PUTVAL "host/load/load" interval=10.000 N:1:2:3`)
+    Putval,
+}
+
+/// When a debug dump moves on to a new file
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Rotation {
+    /// Once the file has grown to the number of bytes
+    Size(u64),
+
+    /// Once the file has been written to for the duration
+    Every(time::Duration),
+}
+
+struct DumpFile {
+    file: File,
+    written: u64,
+    opened: Instant,
+}
+
+/// Writes value lists to rotated files so that discrepancies between what collectd delivered and
+/// what a backend stored can be diagnosed. The dump starts disabled and can be toggled at runtime
+/// (eg: from a config reload or a control command). When rotated, the file at the path is renamed
+/// with a `.1` suffix, the previous `.1` becomes `.2`, and so on, up to the number of files kept.
+///
+/// ```
+/// use collectd_plugin::{DebugDump, DumpFormat, Rotation, ValueList};
+///
+/// fn write(dump: &DebugDump, list: &ValueList<'_>) {
+///     if let Err(e) = dump.record(list) {
+///         // log the error
+///     }
+/// }
+///
+/// let dump = DebugDump::new("/tmp/myplugin.dump", DumpFormat::Json, Rotation::Size(1 << 20));
+/// dump.set_enabled(true);
+/// ```
+pub struct DebugDump {
+    path: PathBuf,
+    format: DumpFormat,
+    rotation: Rotation,
+    keep: usize,
+    enabled: AtomicBool,
+    file: Mutex<Option<DumpFile>>,
+}
+
+impl DebugDump {
+    /// Creates a disabled dump that keeps five rotated files
+    pub fn new<P: AsRef<Path>>(path: P, format: DumpFormat, rotation: Rotation) -> Self {
+        DebugDump {
+            path: path.as_ref().to_path_buf(),
+            format,
+            rotation,
+            keep: 5,
+            enabled: AtomicBool::new(false),
+            file: Mutex::new(None),
+        }
+    }
+
+    /// The number of rotated files that are kept in addition to the one being written
+    pub fn keep(mut self, keep: usize) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    /// Starts or stops writing to the dump. The file is closed when the dump is disabled.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+        if !enabled {
+            *self.file.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Writes a value list received by a write plugin to the dump, if enabled
+    pub fn record(&self, list: &ValueList<'_>) -> io::Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let values: Vec<Value> = list.values.iter().map(|x| x.value).collect();
+        self.record_values(&Identifier::from(list), list.time, list.interval, &values)
+    }
+
+    /// Writes values (eg: those about to be submitted by a read plugin) to the dump, if enabled
+    pub fn record_values(
+        &self,
+        id: &Identifier,
+        time: Timestamp,
+        interval: Duration,
+        values: &[Value],
+    ) -> io::Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let time = seconds(CdTime::from(time));
+        let interval = seconds(CdTime::from(interval));
+        let line = match self.format {
+            DumpFormat::Json => format_json(id, time, interval, values),
+            DumpFormat::Putval => format_putval(id, time, interval, values),
+        };
+
+        let mut guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let rotate = match *guard {
+            Some(ref dump) => match self.rotation {
+                Rotation::Size(size) => dump.written > 0 && dump.written + line.len() as u64 > size,
+                Rotation::Every(every) => dump.opened.elapsed() >= every,
+            },
+            None => false,
+        };
+
+        if rotate {
+            *guard = None;
+            self.rotate()?;
+        }
+
+        if guard.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            let written = file.metadata()?.len();
+            *guard = Some(DumpFile {
+                file,
+                written,
+                opened: Instant::now(),
+            });
+        }
+
+        if let Some(ref mut dump) = *guard {
+            dump.file.write_all(line.as_bytes())?;
+            dump.written += line.len() as u64;
+        }
+
+        Ok(())
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if self.keep == 0 {
+            return fs::remove_file(&self.path);
+        }
+
+        for n in (1..self.keep).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                fs::rename(&from, self.rotated(n + 1))?;
+            }
+        }
+
+        fs::rename(&self.path, self.rotated(1))
+    }
+}
+
+fn seconds(time: CdTime) -> f64 {
+    time::Duration::from(time).as_secs_f64()
+}
+
+fn format_json(id: &Identifier, time: f64, interval: f64, values: &[Value]) -> String {
+    let mut line = String::from("{");
+    let _ = write!(line, "\"host\":{}", json_string(&id.host));
+    let _ = write!(line, ",\"plugin\":{}", json_string(&id.plugin));
+    let plugin_instance = id.plugin_instance.as_deref();
+    let _ = write!(
+        line,
+        ",\"plugin_instance\":{}",
+        json_string(plugin_instance.unwrap_or(""))
+    );
+    let _ = write!(line, ",\"type\":{}", json_string(&id.type_));
+    let type_instance = id.type_instance.as_deref();
+    let _ = write!(
+        line,
+        ",\"type_instance\":{}",
+        json_string(type_instance.unwrap_or(""))
+    );
+    let _ = write!(
+        line,
+        ",\"time\":{:.3},\"interval\":{:.3},\"values\":[",
+        time, interval
+    );
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+
+        match *value {
+            Value::Gauge(x) if !x.is_finite() => line.push_str("null"),
+            x => {
+                let _ = write!(line, "{}", x);
+            }
+        }
+    }
+
+    line.push_str("]}\n");
+    line
+}
+
+fn json_string(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(res, "\\u{:04x}", c as u32);
+            }
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+fn format_putval(id: &Identifier, time: f64, interval: f64, values: &[Value]) -> String {
+    let mut line = format!("PUTVAL \"{}\" interval={:.3} {:.3}", id, interval, time);
+    for value in values {
+        match *value {
+            Value::Gauge(x) if x.is_nan() => line.push_str(":U"),
+            x => {
+                let _ = write!(line, ":{}", x);
+            }
+        }
+    }
+
+    line.push('\n');
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn ident() -> Identifier {
+        "localhost/load/load".parse().unwrap()
+    }
+
+    #[test]
+    fn test_format_json() {
+        let id: Identifier = "local\"host/cpu-0/cpu-idle".parse().unwrap();
+        let values = [Value::Gauge(1.5), Value::Gauge(f64::NAN), Value::Derive(-2)];
+        assert_eq!(
+            "{\"host\":\"local\\\"host\",\"plugin\":\"cpu\",\"plugin_instance\":\"0\",\
+             \"type\":\"cpu\",\"type_instance\":\"idle\",\"time\":1500000000.250,\
+             \"interval\":10.000,\"values\":[1.5,null,-2]}\n",
+            format_json(&id, 1_500_000_000.25, 10.0, &values)
+        );
+    }
+
+    #[test]
+    fn test_format_putval() {
+        let values = [Value::Gauge(0.5), Value::Gauge(f64::NAN), Value::Counter(3)];
+        assert_eq!(
+            "PUTVAL \"localhost/load/load\" interval=10.000 1500000000.000:0.5:U:3\n",
+            format_putval(&ident(), 1_500_000_000.0, 10.0, &values)
+        );
+    }
+
+    #[test]
+    fn test_debug_dump_rotates() {
+        let dir = env::temp_dir().join(format!("collectd-dump-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dump");
+        let dump = DebugDump::new(&path, DumpFormat::Putval, Rotation::Size(100)).keep(1);
+        let record = || {
+            let time = CdTime(1_500_000_000 << 30).into();
+            let interval = CdTime(10 << 30).into();
+            dump.record_values(&ident(), time, interval, &[Value::Gauge(1.0)])
+                .unwrap();
+        };
+
+        // Nothing is written while disabled
+        record();
+        assert!(!path.exists());
+
+        dump.set_enabled(true);
+        for _ in 0..5 {
+            record();
+        }
+
+        // Only one line fits in a file
+        let len = format_putval(&ident(), 1_500_000_000.0, 10.0, &[Value::Gauge(1.0)]).len();
+        assert_eq!(len as u64, fs::metadata(&path).unwrap().len());
+        assert_eq!(len as u64, fs::metadata(dump.rotated(1)).unwrap().len());
+        assert!(!dump.rotated(2).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
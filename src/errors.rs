@@ -1,3 +1,4 @@
+use crate::api::Value;
 use std::error;
 use std::fmt;
 use std::panic::PanicInfo;
@@ -11,6 +12,12 @@ pub enum ConfigError {
 
     /// The config string contains invalid UTF-8 characters
     StringDecode(Utf8Error),
+
+    /// The config key was expected to have a single value but had the given number of values
+    ValueCount(String, usize),
+
+    /// The config key's value could not be converted into the requested type
+    ValueType(String),
 }
 
 impl fmt::Display for ConfigError {
@@ -22,6 +29,14 @@ impl fmt::Display for ConfigError {
             ConfigError::StringDecode(ref _e) => {
                 write!(f, "unable to convert config string to utf8")
             }
+            ConfigError::ValueCount(ref key, count) => write!(
+                f,
+                "expected a single value for config key {} but found {}",
+                key, count
+            ),
+            ConfigError::ValueType(ref key) => {
+                write!(f, "unexpected value type for config key: {}", key)
+            }
         }
     }
 }
@@ -34,7 +49,9 @@ impl error::Error for ConfigError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
             ConfigError::StringDecode(ref e) => Some(e),
-            ConfigError::UnknownType(_) => None,
+            ConfigError::UnknownType(_)
+            | ConfigError::ValueCount(_, _)
+            | ConfigError::ValueType(_) => None,
         }
     }
 }
@@ -72,6 +89,12 @@ impl error::Error for ArrayError {
 pub enum ReceiveError {
     /// A plugin submitted a field that contained invalid UTF-8 characters
     Utf8(String, &'static str, Utf8Error),
+
+    /// A data source of the plugin's values has a type that isn't known
+    DataSourceType(String, UnknownConstant),
+
+    /// A notification from the plugin has a severity that isn't known
+    Severity(String, UnknownConstant),
 }
 
 impl fmt::Display for ReceiveError {
@@ -80,22 +103,57 @@ impl fmt::Display for ReceiveError {
             ReceiveError::Utf8(ref plugin, ref field, ref _err) => {
                 write!(f, "plugin: {} submitted bad field: {}", plugin, field)
             }
+            ReceiveError::DataSourceType(ref plugin, ref err) => {
+                write!(f, "plugin: {} submitted values with an {}", plugin, err)
+            }
+            ReceiveError::Severity(ref plugin, ref err) => {
+                write!(
+                    f,
+                    "plugin: {} submitted a notification with an {}",
+                    plugin, err
+                )
+            }
         }
     }
 }
 
 impl error::Error for ReceiveError {
     fn description(&self) -> &str {
-        "error generating a value list"
+        "error receiving data from collectd"
     }
 
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
             ReceiveError::Utf8(ref _plugin, ref _field, ref err) => Some(err),
+            ReceiveError::DataSourceType(ref _plugin, ref err) => Some(err),
+            ReceiveError::Severity(ref _plugin, ref err) => Some(err),
         }
     }
 }
 
+/// A constant received from collectd (eg: a log level or data source type) that does not
+/// correspond to any variant of the crate's enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownConstant {
+    /// The kind of constant (eg: "log level")
+    pub kind: &'static str,
+
+    /// The value received from collectd
+    pub value: u32,
+}
+
+impl fmt::Display for UnknownConstant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown {}: {}", self.kind, self.value)
+    }
+}
+
+impl error::Error for UnknownConstant {
+    fn description(&self) -> &str {
+        "unknown collectd constant"
+    }
+}
+
 /// Errors that occur when submitting values to collectd
 #[derive(Debug, Clone)]
 pub enum SubmitError {
@@ -103,6 +161,40 @@ pub enum SubmitError {
     Dispatch(i32),
 
     Field(&'static str, ArrayError),
+
+    /// The interval given to the value list could not be converted into a duration
+    Interval(DurationError),
+
+    /// Collectd ran out of memory while queueing the values for the write plugins. This is not a
+    /// sign of a backlog: values dropped because of `WriteQueueLimitHigh` / `WriteQueueLimitLow`
+    /// are discarded by collectd without reporting a failure, so use `write_queue_length` to tell
+    /// whether the write plugins are keeping up.
+    OutOfMemory,
+
+    /// Collectd could not attach the meta data entry with the given key
+    Meta(String),
+
+    /// The value lies outside of the bounds of the named data source and was submitted with
+    /// `BoundsPolicy::Reject`
+    OutOfBounds(String, Value),
+
+    /// The values can't be attributed to the host given to the value list
+    Host(HostError),
+}
+
+/// Status returned by collectd when it can't allocate the values to queue (same value across unix
+/// platforms)
+pub(crate) const ENOMEM: i32 = 12;
+
+impl SubmitError {
+    /// The return code from `plugin_dispatch_values` if collectd rejected the submission
+    pub fn dispatch_code(&self) -> Option<i32> {
+        match *self {
+            SubmitError::Dispatch(code) => Some(code),
+            SubmitError::OutOfMemory => Some(ENOMEM),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for SubmitError {
@@ -112,6 +204,15 @@ impl fmt::Display for SubmitError {
                 write!(f, "plugin_dispatch_values returned an error: {}", code)
             }
             SubmitError::Field(ref field, ref _err) => write!(f, "error submitting {}", field),
+            SubmitError::Interval(ref _err) => write!(f, "error submitting interval"),
+            SubmitError::OutOfMemory => write!(f, "collectd ran out of memory queueing the values"),
+            SubmitError::Meta(ref key) => write!(f, "error attaching meta data: {}", key),
+            SubmitError::Host(ref _err) => write!(f, "error submitting host"),
+            SubmitError::OutOfBounds(ref name, ref value) => write!(
+                f,
+                "value {} is outside the bounds of data source {}",
+                value, name
+            ),
         }
     }
 }
@@ -123,8 +224,45 @@ impl error::Error for SubmitError {
 
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
-            SubmitError::Dispatch(_code) => None,
+            SubmitError::Dispatch(_)
+            | SubmitError::OutOfMemory
+            | SubmitError::Meta(_)
+            | SubmitError::OutOfBounds(..) => None,
             SubmitError::Field(_field, ref err) => Some(err),
+            SubmitError::Interval(ref err) => Some(err),
+            SubmitError::Host(ref err) => Some(err),
+        }
+    }
+}
+
+/// Error that occurred when converting a value into a duration
+#[derive(Debug, Clone, PartialEq)]
+pub enum DurationError {
+    /// The string could not be parsed as a duration
+    Parse(String, humantime::DurationError),
+
+    /// The duration is negative or too large to be represented
+    OutOfRange,
+}
+
+impl fmt::Display for DurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            DurationError::Parse(ref s, ref _err) => write!(f, "unable to parse duration: {}", s),
+            DurationError::OutOfRange => write!(f, "duration is out of range"),
+        }
+    }
+}
+
+impl error::Error for DurationError {
+    fn description(&self) -> &str {
+        "error converting into a duration"
+    }
+
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            DurationError::Parse(ref _s, ref err) => Some(err),
+            DurationError::OutOfRange => None,
         }
     }
 }
@@ -165,6 +303,218 @@ impl error::Error for CacheRateError {
     }
 }
 
+/// Error that occurred while validating the instance names of a `PluginRegistration::Multiple`.
+/// Collectd would otherwise silently replace the callbacks of an instance with those of another
+/// instance of the same name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegistrationError {
+    /// The names that were given to more than one instance
+    Duplicates(Vec<String>),
+
+    /// The instance name is empty or contains characters (`/`, whitespace, or control characters)
+    /// that collectd can't distinguish in an identifier
+    InvalidName(String),
+
+    /// The instance name, once prefixed with the plugin manager's name, exceeds the given limit
+    TooLong(String, usize),
+
+    /// Collectd rejected a callback of the instance with the given status (eg: a read callback is
+    /// already registered under the name)
+    Rejected(String, &'static str, i32),
+}
+
+impl fmt::Display for RegistrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RegistrationError::Duplicates(ref names) => write!(
+                f,
+                "plugin instance names registered more than once: {}",
+                names.join(", ")
+            ),
+            RegistrationError::InvalidName(ref name) => write!(
+                f,
+                "plugin instance name must be non-empty and not contain slashes, whitespace, \
+                 or control characters: {:?}",
+                name
+            ),
+            RegistrationError::TooLong(ref name, limit) => write!(
+                f,
+                "plugin instance name {} is longer than {} bytes",
+                name, limit
+            ),
+            RegistrationError::Rejected(ref name, callback, status) => write!(
+                f,
+                "collectd rejected the {} callback of plugin instance {} with status {}",
+                callback, name, status
+            ),
+        }
+    }
+}
+
+impl error::Error for RegistrationError {
+    fn description(&self) -> &str {
+        "error registering plugin instances"
+    }
+}
+
+/// The targets of a multi-target read that failed, as aggregated by a `TargetReport`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetsError {
+    /// The targets that failed along with their errors
+    pub failures: Vec<(String, String)>,
+
+    /// The number of targets that were read
+    pub total: usize,
+}
+
+impl fmt::Display for TargetsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} targets failed: ",
+            self.failures.len(),
+            self.total
+        )?;
+        for (i, (target, err)) in self.failures.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "; " };
+            write!(f, "{}{}: {}", sep, target, err)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for TargetsError {
+    fn description(&self) -> &str {
+        "error reading targets"
+    }
+}
+
+/// Error that occurred when parsing an identifier that isn't in the format of
+/// `host/plugin[-plugin_instance]/type[-type_instance]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseIdentifierError(pub String);
+
+impl fmt::Display for ParseIdentifierError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid identifier: {}", self.0)
+    }
+}
+
+impl error::Error for ParseIdentifierError {
+    fn description(&self) -> &str {
+        "error parsing identifier"
+    }
+}
+
+/// A host that values can't be attributed to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostError {
+    /// The host is empty
+    Empty,
+
+    /// The host is too long to fit into collectd's fields
+    TooLong(usize),
+
+    /// The host contains a character that isn't allowed
+    Character(char),
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            HostError::Empty => write!(f, "host is empty"),
+            HostError::TooLong(len) => write!(f, "host length of {} is too long", len),
+            HostError::Character(c) => write!(f, "host contains the character {:?}", c),
+        }
+    }
+}
+
+impl error::Error for HostError {
+    fn description(&self) -> &str {
+        "invalid host"
+    }
+}
+
+/// Error that occurred when parsing a cron-like read schedule
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseScheduleError(pub String);
+
+impl fmt::Display for ParseScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid schedule: {}", self.0)
+    }
+}
+
+impl error::Error for ParseScheduleError {
+    fn description(&self) -> &str {
+        "error parsing schedule"
+    }
+}
+
+/// Error that occurred when parsing text in collectd's config syntax, along with the line it
+/// occurred on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseConfigError(pub usize, pub String);
+
+impl fmt::Display for ParseConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid config on line {}: {}", self.0, self.1)
+    }
+}
+
+impl error::Error for ParseConfigError {
+    fn description(&self) -> &str {
+        "error parsing config"
+    }
+}
+
+/// Error for TLS settings that are incomplete or reference files that can't be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsConfigError(pub String);
+
+impl fmt::Display for TlsConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid TLS config: {}", self.0)
+    }
+}
+
+impl error::Error for TlsConfigError {
+    fn description(&self) -> &str {
+        "error validating TLS config"
+    }
+}
+
+/// Returned from a callback by an instance that hit an error it can't recover from (eg: the
+/// device it monitors was removed or its credentials were revoked). Instead of failing forever
+/// and triggering collectd's backoff, the instance's callbacks are unregistered from collectd.
+/// Sibling instances are unaffected.
+///
+/// ```
+/// use collectd_plugin::Unregister;
+/// use std::error;
+///
+/// fn read_values(present: bool) -> Result<(), Box<dyn error::Error>> {
+///     if !present {
+///         return Err(Unregister(String::from("device was removed")).into());
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unregister(pub String);
+
+impl fmt::Display for Unregister {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "permanent error: {}", self.0)
+    }
+}
+
+impl error::Error for Unregister {
+    fn description(&self) -> &str {
+        "permanent error"
+    }
+}
+
 /// Errors that occur on the boundary between collectd and a plugin
 #[derive(Debug)]
 pub enum FfiError<'a> {
@@ -190,6 +540,9 @@ pub enum FfiError<'a> {
 
     /// Collectd gave us field that contains invalid UTF-8 characters
     Utf8(&'static str, Utf8Error),
+
+    /// Collectd gave us values of a type that isn't in its types database
+    UnknownType(String),
 }
 
 impl<'a> fmt::Display for FfiError<'a> {
@@ -215,6 +568,7 @@ impl<'a> fmt::Display for FfiError<'a> {
             }
             FfiError::Plugin(_) => write!(f, "plugin encountered an error"),
             FfiError::Utf8(field, ref _e) => write!(f, "UTF-8 error for field: {}", field),
+            FfiError::UnknownType(ref type_) => write!(f, "no data set for type: {}", type_),
         }
     }
 }
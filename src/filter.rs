@@ -0,0 +1,466 @@
+use crate::api::{log_err, to_array_res, ConfigItem, Value, ValueList, ValueType};
+use crate::bindings::{
+    data_set_t, fc_register_match, fc_register_target, match_proc_t, notification_meta_t,
+    oconfig_item_t, target_proc_t, value_list_t, ARR_LENGTH, FC_MATCH_MATCHES, FC_MATCH_NO_MATCH,
+    FC_TARGET_CONTINUE, FC_TARGET_RETURN, FC_TARGET_STOP,
+};
+use crate::errors::{FfiError, ReceiveError, SubmitError};
+use std::convert::TryFrom;
+use std::error;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int, c_void};
+use std::panic::{catch_unwind, AssertUnwindSafe, RefUnwindSafe, UnwindSafe};
+use std::ptr;
+use std::slice;
+
+/// A match that can be used in collectd's filter chains (eg: matching identifiers against a
+/// regular expression), so that a target is only invoked for the values that the match selects.
+///
+/// ```plain
+/// <Chain "PreCache">
+///   <Rule "ignore_loopback">
+///     <Match "myregex">
+///       Interface "^lo$"
+///     </Match>
+///     Target "stop"
+///   </Rule>
+/// </Chain>
+/// ```
+pub trait Match: Sized + Send + Sync + UnwindSafe + RefUnwindSafe + 'static {
+    /// The name that rules refer to the match by
+    fn name() -> &'static str;
+
+    /// Creates the match from the options of a `<Match>` block. Each rule that uses the match
+    /// creates its own. An error fails collectd's config of the chain.
+    fn create(config: &[ConfigItem<'_>]) -> Result<Self, Box<dyn error::Error>>;
+
+    /// Returns true if the values match. An error is logged and the rule is not applied.
+    fn matches(&self, list: &ValueList<'_>) -> Result<bool, Box<dyn error::Error>>;
+}
+
+/// What collectd does with a value list once a target has been invoked
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TargetAction {
+    /// Invoke the rule's remaining targets and continue with the chain
+    Continue,
+
+    /// Stop processing the value list, so that it isn't cached or written (like the builtin
+    /// `stop` target)
+    Stop,
+
+    /// Return from the chain to the chain that jumped to it (like the builtin `return` target)
+    Return,
+}
+
+/// A target that can be used in collectd's filter chains to modify or drop the value lists that
+/// a rule's matches select (eg: rewriting the host like the `set` target).
+///
+/// ```plain
+/// <Chain "PreCache">
+///   <Rule "rename_host">
+///     <Target "myrename">
+///       Host "web"
+///     </Target>
+///   </Rule>
+/// </Chain>
+/// ```
+pub trait Target: Sized + Send + Sync + UnwindSafe + RefUnwindSafe + 'static {
+    /// The name that rules refer to the target by
+    fn name() -> &'static str;
+
+    /// Creates the target from the options of a `<Target>` block. Each rule that uses the target
+    /// creates its own. An error fails collectd's config of the chain.
+    fn create(config: &[ConfigItem<'_>]) -> Result<Self, Box<dyn error::Error>>;
+
+    /// Modifies the value list. An error is logged and the chain continues.
+    fn invoke(&self, list: &mut ValueListMut<'_>) -> Result<TargetAction, Box<dyn error::Error>>;
+}
+
+/// Mutable access to a value list passing through a filter chain. The type and number of values
+/// are fixed by the data set, so only the identifier's other fields and the values themselves can
+/// be modified.
+pub struct ValueListMut<'a> {
+    set: &'a data_set_t,
+    list: &'a mut value_list_t,
+}
+
+impl<'a> ValueListMut<'a> {
+    /// A view of the value list as it currently is
+    pub fn list(&self) -> Result<ValueList<'_>, ReceiveError> {
+        ValueList::from(self.set, self.list)
+    }
+
+    pub fn set_host(&mut self, host: &str) -> Result<(), SubmitError> {
+        set_field(&mut self.list.host, "host", host)
+    }
+
+    pub fn set_plugin(&mut self, plugin: &str) -> Result<(), SubmitError> {
+        set_field(&mut self.list.plugin, "plugin", plugin)
+    }
+
+    pub fn set_plugin_instance(
+        &mut self,
+        plugin_instance: Option<&str>,
+    ) -> Result<(), SubmitError> {
+        let value = plugin_instance.unwrap_or("");
+        set_field(&mut self.list.plugin_instance, "plugin_instance", value)
+    }
+
+    pub fn set_type_instance(&mut self, type_instance: Option<&str>) -> Result<(), SubmitError> {
+        let value = type_instance.unwrap_or("");
+        set_field(&mut self.list.type_instance, "type_instance", value)
+    }
+
+    /// Replaces the value at the index. Returns false if there is no value at the index or if the
+    /// value's type differs from the data source's.
+    pub fn set_value(&mut self, index: usize, value: Value) -> bool {
+        let len = self.len();
+        if index >= len {
+            return false;
+        }
+
+        let sources = unsafe { slice::from_raw_parts(self.set.ds, len) };
+        match ValueType::try_from(sources[index].type_ as u32) {
+            Ok(x) if x == value.value_type() => {}
+            _ => return false,
+        }
+
+        let values = unsafe { slice::from_raw_parts_mut(self.list.values, len) };
+        values[index] = value.into();
+        true
+    }
+
+    /// The number of values in the list
+    pub fn len(&self) -> usize {
+        if self.list.values.is_null() || self.set.ds.is_null() {
+            return 0;
+        }
+
+        let values = self.list.values_len as usize;
+        values.min(self.set.ds_num as usize)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn set_field(
+    field: &mut [c_char; ARR_LENGTH],
+    name: &'static str,
+    value: &str,
+) -> Result<(), SubmitError> {
+    *field = to_array_res(value).map_err(|e| SubmitError::Field(name, e))?;
+    Ok(())
+}
+
+/// Registers a match with collectd's filter chains. Since collectd reads `<Chain>` blocks as it
+/// encounters them in the config, matches should be registered from
+/// `PluginManager::register_filters`, which is called when the plugin is loaded. Returns true if
+/// collectd accepted the match.
+pub fn register_match<M: Match>() -> bool {
+    let name = CString::new(M::name()).expect("Match name to not contain nulls");
+    let proc_ = match_proc_t {
+        create: Some(match_create::<M>),
+        destroy: Some(destroy::<M>),
+        match_: Some(match_values::<M>),
+    };
+
+    unsafe { fc_register_match(name.as_ptr(), proc_) == 0 }
+}
+
+/// Registers a target with collectd's filter chains. Like matches, targets should be registered
+/// from `PluginManager::register_filters`. Returns true if collectd accepted the target.
+pub fn register_target<T: Target>() -> bool {
+    let name = CString::new(T::name()).expect("Target name to not contain nulls");
+    let proc_ = target_proc_t {
+        create: Some(target_create::<T>),
+        destroy: Some(destroy::<T>),
+        invoke: Some(target_invoke::<T>),
+    };
+
+    unsafe { fc_register_target(name.as_ptr(), proc_) == 0 }
+}
+
+type Create<T> = fn(&[ConfigItem<'_>]) -> Result<T, Box<dyn error::Error>>;
+
+/// Creates a match or target from its config block and hands ownership to collectd
+unsafe fn create<T>(
+    desc: &str,
+    ci: *const oconfig_item_t,
+    user_data: *mut *mut c_void,
+    create: Create<T>,
+) -> c_int {
+    let res = ConfigItem::from(&*ci)
+        .map_err(|e| FfiError::Collectd(Box::new(e)))
+        .and_then(|config| {
+            catch_unwind(|| create(&config.children))
+                .map_err(|_| FfiError::Panic)
+                .and_then(|x| x.map_err(FfiError::Plugin))
+        });
+
+    match res {
+        Ok(x) => {
+            *user_data = Box::into_raw(Box::new(x)) as *mut c_void;
+            0
+        }
+        Err(ref e) => {
+            log_err(desc, e);
+            -1
+        }
+    }
+}
+
+unsafe extern "C" fn match_create<M: Match>(
+    ci: *const oconfig_item_t,
+    user_data: *mut *mut c_void,
+) -> c_int {
+    create("creating match", ci, user_data, M::create)
+}
+
+unsafe extern "C" fn target_create<T: Target>(
+    ci: *const oconfig_item_t,
+    user_data: *mut *mut c_void,
+) -> c_int {
+    create("creating target", ci, user_data, T::create)
+}
+
+unsafe extern "C" fn destroy<T>(user_data: *mut *mut c_void) -> c_int {
+    if !user_data.is_null() && !(*user_data).is_null() {
+        drop(Box::from_raw(*user_data as *mut T));
+        *user_data = ptr::null_mut();
+    }
+
+    0
+}
+
+unsafe extern "C" fn match_values<M: Match>(
+    ds: *const data_set_t,
+    vl: *const value_list_t,
+    _meta: *mut *mut notification_meta_t,
+    user_data: *mut *mut c_void,
+) -> c_int {
+    let m = &*(*user_data as *const M);
+    let res = ValueList::from(&*ds, &*vl)
+        .map_err(|e| FfiError::Collectd(Box::new(e)))
+        .and_then(|list| {
+            catch_unwind(|| m.matches(&list))
+                .map_err(|_| FfiError::Panic)
+                .and_then(|x| x.map_err(FfiError::Plugin))
+        });
+
+    match res {
+        Ok(true) => FC_MATCH_MATCHES,
+        Ok(false) => FC_MATCH_NO_MATCH,
+        Err(ref e) => {
+            log_err("matching", e);
+            -1
+        }
+    }
+}
+
+unsafe extern "C" fn target_invoke<T: Target>(
+    ds: *const data_set_t,
+    vl: *mut value_list_t,
+    _meta: *mut *mut notification_meta_t,
+    user_data: *mut *mut c_void,
+) -> c_int {
+    let target = &*(*user_data as *const T);
+    let mut list = ValueListMut {
+        set: &*ds,
+        list: &mut *vl,
+    };
+
+    // A target that panics may leave the value list partially modified, which is no worse than
+    // the target returning an error after modifying it
+    let res = catch_unwind(AssertUnwindSafe(|| target.invoke(&mut list)))
+        .map_err(|_| FfiError::Panic)
+        .and_then(|x| x.map_err(FfiError::Plugin));
+
+    match res {
+        Ok(TargetAction::Continue) => FC_TARGET_CONTINUE,
+        Ok(TargetAction::Stop) => FC_TARGET_STOP,
+        Ok(TargetAction::Return) => FC_TARGET_RETURN,
+        Err(ref e) => {
+            log_err("invoking target", e);
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::{data_source_t, value_t, DS_TYPE_GAUGE};
+
+    struct LoadMatch;
+
+    impl Match for LoadMatch {
+        fn name() -> &'static str {
+            "load"
+        }
+
+        fn create(config: &[ConfigItem<'_>]) -> Result<Self, Box<dyn error::Error>> {
+            if config.is_empty() {
+                Ok(LoadMatch)
+            } else {
+                Err("unexpected options".into())
+            }
+        }
+
+        fn matches(&self, list: &ValueList<'_>) -> Result<bool, Box<dyn error::Error>> {
+            Ok(list.plugin == "load")
+        }
+    }
+
+    struct DoubleTarget;
+
+    impl Target for DoubleTarget {
+        fn name() -> &'static str {
+            "double"
+        }
+
+        fn create(_config: &[ConfigItem<'_>]) -> Result<Self, Box<dyn error::Error>> {
+            Ok(DoubleTarget)
+        }
+
+        fn invoke(
+            &self,
+            list: &mut ValueListMut<'_>,
+        ) -> Result<TargetAction, Box<dyn error::Error>> {
+            let value = match list.list()?.values[0].value {
+                Value::Gauge(x) => Value::Gauge(x * 2.0),
+                _ => return Ok(TargetAction::Stop),
+            };
+
+            assert!(!list.set_value(1, value));
+            assert!(!list.set_value(0, Value::Derive(1)));
+            assert!(list.set_value(0, value));
+            list.set_host("web")?;
+            list.set_type_instance(Some("doubled"))?;
+            Ok(TargetAction::Continue)
+        }
+    }
+
+    fn name(s: &str) -> [c_char; ARR_LENGTH] {
+        let mut res = [0; ARR_LENGTH];
+        for (i, c) in s.bytes().enumerate() {
+            res[i] = c as c_char;
+        }
+        res
+    }
+
+    #[test]
+    fn test_match_callbacks() {
+        assert!(register_match::<LoadMatch>());
+
+        let mut key = CString::new("Match").unwrap().into_bytes_with_nul();
+        let ci = oconfig_item_t {
+            key: key.as_mut_ptr() as *mut c_char,
+            values: ptr::null_mut(),
+            values_num: 0,
+            parent: ptr::null_mut(),
+            children: ptr::null_mut(),
+            children_num: 0,
+        };
+
+        let mut user_data = ptr::null_mut();
+        assert_eq!(0, unsafe { match_create::<LoadMatch>(&ci, &mut user_data) });
+        assert!(!user_data.is_null());
+
+        let mut sources = [data_source_t {
+            name: name("value"),
+            type_: DS_TYPE_GAUGE as i32,
+            min: 0.0,
+            max: f64::NAN,
+        }];
+        let ds = data_set_t {
+            type_: name("load"),
+            ds_num: 1,
+            ds: sources.as_mut_ptr(),
+        };
+
+        let mut values = [value_t { gauge: 1.0 }];
+        let mut vl = value_list_t {
+            values: values.as_mut_ptr(),
+            values_len: 1,
+            time: 1 << 30,
+            interval: 10 << 30,
+            host: name("localhost"),
+            plugin: name("load"),
+            plugin_instance: name(""),
+            type_: name("load"),
+            type_instance: name(""),
+            meta: ptr::null_mut(),
+        };
+
+        let mut meta = ptr::null_mut();
+        let res = unsafe { match_values::<LoadMatch>(&ds, &vl, &mut meta, &mut user_data) };
+        assert_eq!(FC_MATCH_MATCHES, res);
+
+        vl.plugin = name("cpu");
+        let res = unsafe { match_values::<LoadMatch>(&ds, &vl, &mut meta, &mut user_data) };
+        assert_eq!(FC_MATCH_NO_MATCH, res);
+
+        assert_eq!(0, unsafe { destroy::<LoadMatch>(&mut user_data) });
+        assert!(user_data.is_null());
+    }
+
+    #[test]
+    fn test_target_callbacks() {
+        assert!(register_target::<DoubleTarget>());
+
+        let mut key = CString::new("Target").unwrap().into_bytes_with_nul();
+        let ci = oconfig_item_t {
+            key: key.as_mut_ptr() as *mut c_char,
+            values: ptr::null_mut(),
+            values_num: 0,
+            parent: ptr::null_mut(),
+            children: ptr::null_mut(),
+            children_num: 0,
+        };
+
+        let mut user_data = ptr::null_mut();
+        assert_eq!(0, unsafe {
+            target_create::<DoubleTarget>(&ci, &mut user_data)
+        });
+
+        let mut sources = [data_source_t {
+            name: name("value"),
+            type_: DS_TYPE_GAUGE as i32,
+            min: 0.0,
+            max: f64::NAN,
+        }];
+        let ds = data_set_t {
+            type_: name("load"),
+            ds_num: 1,
+            ds: sources.as_mut_ptr(),
+        };
+
+        let mut values = [value_t { gauge: 1.5 }];
+        let mut vl = value_list_t {
+            values: values.as_mut_ptr(),
+            values_len: 1,
+            time: 1 << 30,
+            interval: 10 << 30,
+            host: name("localhost"),
+            plugin: name("load"),
+            plugin_instance: name(""),
+            type_: name("load"),
+            type_instance: name(""),
+            meta: ptr::null_mut(),
+        };
+
+        let mut meta = ptr::null_mut();
+        let res = unsafe { target_invoke::<DoubleTarget>(&ds, &mut vl, &mut meta, &mut user_data) };
+        assert_eq!(FC_TARGET_CONTINUE, res);
+
+        let list = ValueList::from(&ds, &vl).unwrap();
+        assert_eq!("web", list.host);
+        assert_eq!(Some("doubled"), list.type_instance);
+        assert_eq!(Value::Gauge(3.0), list.values[0].value);
+
+        assert_eq!(0, unsafe { destroy::<DoubleTarget>(&mut user_data) });
+        assert!(user_data.is_null());
+    }
+}
@@ -1,39 +1,134 @@
 //! Module used exclusively to setup the `collectd_plugin!` macro. No public functions from here
 //! should be used.
 use crate::api::{
-    empty_to_none, get_default_interval, log_err, CdTime, ConfigItem, LogLevel, ValueList,
+    collectd_log, cstr_from_array, empty_to_none, get_default_interval, log_err,
+    take_shutdown_hooks, with_instance, CdTime, ConfigItem, ConfigValue, LogLevel, Notification,
+    OwnedConfigItem, ReadInterval, ValueList,
 };
+use crate::bindings::ARR_LENGTH;
 use crate::bindings::{
-    cdtime_t, data_set_t, oconfig_item_t, plugin_register_complex_read, plugin_register_flush,
-    plugin_register_log, plugin_register_write, user_data_t, value_list_t,
+    cdtime_t, data_set_t, notification_t, oconfig_item_t, plugin_get_ds, plugin_get_interval,
+    plugin_register_complex_read, plugin_register_flush, plugin_register_log,
+    plugin_register_missing, plugin_register_notification, plugin_register_write,
+    plugin_unregister_flush, plugin_unregister_log, plugin_unregister_missing,
+    plugin_unregister_notification, plugin_unregister_read, plugin_unregister_write, user_data_t,
+    value_list_t,
 };
-use crate::errors::FfiError;
-use crate::plugins::{Plugin, PluginManager, PluginManagerCapabilities, PluginRegistration};
+use crate::errors::{FfiError, RegistrationError};
+use crate::plugins::{
+    Plugin, PluginCapabilities, PluginManager, PluginManagerCapabilities, PluginRegistration,
+    ReadStatus,
+};
+use crate::registry::{self, InstanceStats, PluginInstance};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::ffi::{CStr, CString};
+use std::fmt::{self, Write};
 use std::ops::Deref;
 use std::os::raw::{c_char, c_int, c_void};
-use std::panic::{self, catch_unwind};
+use std::panic::{self, catch_unwind, AssertUnwindSafe};
 use std::ptr;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+lazy_static! {
+    // Config sections of a plugin manager, kept until it is initialized. Sections of a manager with
+    // aliases can appear under any of the names, so they are merged and registered once collectd
+    // has read all of its configuration.
+    static ref MANAGER_CONFIG: Mutex<HashMap<&'static str, Vec<OwnedConfigItem>>> =
+        Mutex::new(HashMap::new());
+
+    // Serializes the instances registered at runtime so that concurrent registrations of the same
+    // name are caught
+    static ref RUNTIME_REGISTRATION: Mutex<()> = Mutex::new(());
+}
+
+/// Number of plugin managers in the shared object that have yet to shut down
+static ACTIVE_MANAGERS: AtomicUsize = AtomicUsize::new(0);
+
+/// How often the read callback of a plugin that reads on a schedule is invoked
+const SCHEDULE_HEARTBEAT: Duration = Duration::from_secs(1);
 
 extern "C" fn plugin_read(dt: *mut user_data_t) -> c_int {
-    let plugin = unsafe { &mut *((*dt).data as *mut Box<dyn Plugin>) };
-    let res = catch_unwind(|| plugin.read_values())
-        .map_err(|_| FfiError::Panic)
-        .and_then(|x| x.map_err(FfiError::Plugin));
+    let instance = unsafe { &*((*dt).data as *const Arc<PluginInstance>) };
+    read_instance(instance)
+}
+
+/// Reads the instances of a partition one after another. Collectd backs off from a failing read
+/// callback, so the partition only reports a failure when every instance failed.
+extern "C" fn plugin_read_partition(dt: *mut user_data_t) -> c_int {
+    let partition = unsafe { &*((*dt).data as *const Vec<Arc<PluginInstance>>) };
+    let mut failures = 0;
+    let mut active = 0;
+    for instance in partition.iter().filter(|x| x.is_active()) {
+        active += 1;
+        if read_instance(instance) != 0 {
+            failures += 1;
+        }
+    }
+
+    if active > 0 && failures == active {
+        -1
+    } else {
+        0
+    }
+}
+
+fn read_instance(instance: &PluginInstance) -> c_int {
+    if instance.is_quarantined() {
+        return -1;
+    }
+
+    if instance.is_disabled() || !instance.read_due(SystemTime::now()) {
+        return 0;
+    }
+
+    let changed = instance.observe_interval(CdTime::from(unsafe { plugin_get_interval() }));
+    let stats = &instance.stats;
+    InstanceStats::incr(&stats.reads);
+    let start = Instant::now();
+    let mut transient = false;
+    let res = catch_unwind(|| {
+        with_instance(&instance.name, || {
+            if let Some(interval) = changed {
+                instance.plugin.interval_changed(interval.into());
+            }
+
+            instance.plugin.read_status()
+        })
+    })
+    .map_err(|_| FfiError::Panic)
+    .and_then(|x| match x {
+        ReadStatus::Ok | ReadStatus::Skip => Ok(()),
+        ReadStatus::TransientError(e) => {
+            transient = true;
+            Err(FfiError::Plugin(e))
+        }
+        ReadStatus::PermanentError(e) => Err(FfiError::Plugin(e)),
+    });
 
     if let Err(ref e) = res {
         log_err("read", e);
     }
 
-    res.map(|_| 0).unwrap_or(-1)
+    stats.record_read(start.elapsed(), res.is_ok(), SystemTime::now());
+    instance.record_result(&stats.read_errors, &res);
+
+    // Collectd only backs off from a read callback that reports a failure
+    match res {
+        Err(_) if !transient => -1,
+        _ => 0,
+    }
 }
 
 extern "C" fn plugin_log(severity: c_int, message: *const c_char, dt: *mut user_data_t) {
-    let plugin = unsafe { &mut *((*dt).data as *mut Box<dyn Plugin>) };
+    let instance = unsafe { &*((*dt).data as *const Arc<PluginInstance>) };
+    let stats = &instance.stats;
 
     // Guard against potential null messages even if they are not supposed to happen.
-    if message.is_null() {
+    if message.is_null() || !instance.is_active() {
         return;
     }
 
@@ -41,17 +136,24 @@ extern "C" fn plugin_log(severity: c_int, message: *const c_char, dt: *mut user_
     // characters as it wouldn't be right if collectd-plugin stopped the logging of an
     // important message when a small portion of the message may be illegible.
     let msg = unsafe { CStr::from_ptr(message).to_string_lossy() };
-    let res = LogLevel::try_from(severity as u32)
-        .ok_or_else(|| FfiError::UnknownSeverity(severity))
+    InstanceStats::incr(&stats.logs);
+    let res = <LogLevel as TryFrom<u32>>::try_from(severity as u32)
+        .map_err(|_| FfiError::UnknownSeverity(severity))
         .and_then(|lvl| {
-            catch_unwind(|| plugin.log(lvl, Deref::deref(&msg)))
-                .map_err(|_| FfiError::Panic)
-                .and_then(|x| x.map_err(FfiError::Plugin))
+            catch_unwind(|| {
+                with_instance(&instance.name, || {
+                    instance.plugin.log(lvl, Deref::deref(&msg))
+                })
+            })
+            .map_err(|_| FfiError::Panic)
+            .and_then(|x| x.map_err(FfiError::Plugin))
         });
 
     if let Err(ref e) = res {
         log_err("logging", e);
     }
+
+    instance.record_result(&stats.log_errors, &res);
 }
 
 extern "C" fn plugin_write(
@@ -59,11 +161,17 @@ extern "C" fn plugin_write(
     vl: *const value_list_t,
     dt: *mut user_data_t,
 ) -> c_int {
-    let plugin = unsafe { &mut *((*dt).data as *mut Box<dyn Plugin>) };
+    let instance = unsafe { &*((*dt).data as *const Arc<PluginInstance>) };
+    if !instance.is_active() {
+        return 0;
+    }
+
+    let stats = &instance.stats;
+    InstanceStats::incr(&stats.writes);
     let res = unsafe { ValueList::from(&*ds, &*vl) }
         .map_err(|e| FfiError::Collectd(Box::new(e)))
         .and_then(|list| {
-            catch_unwind(|| plugin.write_values(list))
+            catch_unwind(|| with_instance(&instance.name, || instance.plugin.write_values(list)))
                 .map_err(|_| FfiError::Panic)
                 .and_then(|x| x.map_err(FfiError::Plugin))
         });
@@ -72,6 +180,7 @@ extern "C" fn plugin_write(
         log_err("writing", e);
     }
 
+    instance.record_result(&stats.write_errors, &res);
     res.map(|_| 0).unwrap_or(-1)
 }
 
@@ -80,8 +189,15 @@ extern "C" fn plugin_flush(
     identifier: *const c_char,
     dt: *mut user_data_t,
 ) -> c_int {
-    let plugin = unsafe { &mut *((*dt).data as *mut Box<dyn crate::Plugin>) };
+    let instance = unsafe { &*((*dt).data as *const Arc<PluginInstance>) };
+    if !instance.is_active() {
+        return 0;
+    }
+
+    let stats = &instance.stats;
+    InstanceStats::incr(&stats.flushes);
 
+    // A zero timeout is collectd's way of requesting that everything is flushed
     let dur = if timeout == 0 {
         None
     } else {
@@ -98,7 +214,7 @@ extern "C" fn plugin_flush(
     };
 
     let res = ident.and_then(|id| {
-        catch_unwind(|| plugin.flush(dur, id))
+        catch_unwind(|| with_instance(&instance.name, || instance.plugin.flush(dur, id)))
             .map_err(|_| FfiError::Panic)
             .and_then(|x| x.map_err(FfiError::Plugin))
     });
@@ -107,24 +223,345 @@ extern "C" fn plugin_flush(
         log_err("flush", e);
     }
 
+    instance.record_result(&stats.flush_errors, &res);
+    res.map(|_| 0).unwrap_or(-1)
+}
+
+extern "C" fn plugin_notification(notif: *const notification_t, dt: *mut user_data_t) -> c_int {
+    let instance = unsafe { &*((*dt).data as *const Arc<PluginInstance>) };
+    if !instance.is_active() || notif.is_null() {
+        return 0;
+    }
+
+    let stats = &instance.stats;
+    InstanceStats::incr(&stats.notifications);
+    let res = Notification::from(unsafe { &*notif })
+        .map_err(|e| FfiError::Collectd(Box::new(e)))
+        .and_then(|n| {
+            catch_unwind(|| with_instance(&instance.name, || instance.plugin.notification(n)))
+                .map_err(|_| FfiError::Panic)
+                .and_then(|x| x.map_err(FfiError::Plugin))
+        });
+
+    if let Err(ref e) = res {
+        log_err("notification", e);
+    }
+
+    instance.record_result(&stats.notification_errors, &res);
+    res.map(|_| 0).unwrap_or(-1)
+}
+
+extern "C" fn plugin_missing(vl: *const value_list_t, dt: *mut user_data_t) -> c_int {
+    let instance = unsafe { &*((*dt).data as *const Arc<PluginInstance>) };
+    if !instance.is_active() || vl.is_null() {
+        return 0;
+    }
+
+    let stats = &instance.stats;
+    InstanceStats::incr(&stats.missing);
+    let list = unsafe { &*vl };
+
+    // Collectd doesn't hand the data set of the missing values to the callback
+    let ds = unsafe { plugin_get_ds(list.type_.as_ptr()).as_ref() }.ok_or_else(|| {
+        FfiError::UnknownType(cstr_from_array(&list.type_).to_string_lossy().into())
+    });
+
+    let res = ds.and_then(|ds| {
+        ValueList::from(ds, list)
+            .map_err(|e| FfiError::Collectd(Box::new(e)))
+            .and_then(|list| {
+                catch_unwind(|| with_instance(&instance.name, || instance.plugin.missing(list)))
+                    .map_err(|_| FfiError::Panic)
+                    .and_then(|x| x.map_err(FfiError::Plugin))
+            })
+    });
+
+    if let Err(ref e) = res {
+        log_err("missing", e);
+    }
+
+    instance.record_result(&stats.missing_errors, &res);
     res.map(|_| 0).unwrap_or(-1)
 }
 
 unsafe extern "C" fn plugin_free_user_data(raw: *mut c_void) {
-    let ptr = raw as *mut Box<dyn Plugin>;
+    let ptr = raw as *mut Arc<PluginInstance>;
+    drop(Box::from_raw(ptr));
+}
+
+/// Each callback is given its own reference to the instance, which collectd releases when the
+/// callback is unregistered
+fn user_data(instance: &Arc<PluginInstance>) -> user_data_t {
+    let data: Box<Arc<PluginInstance>> = Box::new(Arc::clone(instance));
+    user_data_t {
+        data: Box::into_raw(data) as *mut c_void,
+        free_func: Some(plugin_free_user_data),
+    }
+}
+
+/// Collectd frees the user data of most callbacks that it rejects, but the user data of a rejected
+/// read callback is left with the caller, so it is released here
+unsafe fn release_rejected(status: c_int, d: &user_data_t) -> c_int {
+    if status != 0 {
+        if let Some(free_func) = d.free_func {
+            free_func(d.data);
+        }
+    }
+
+    status
+}
+
+unsafe extern "C" fn plugin_free_partition(raw: *mut c_void) {
+    let ptr = raw as *mut Vec<Arc<PluginInstance>>;
     drop(Box::from_raw(ptr));
 }
 
-fn plugin_registration(name: &str, plugin: Box<dyn Plugin>) {
-    let pl: Box<Box<dyn Plugin>> = Box::new(plugin);
+/// The instances that read at the default interval, spread round-robin across a fixed number of
+/// read callbacks
+struct ReadPartitions {
+    partitions: Vec<Vec<Arc<PluginInstance>>>,
+    next: usize,
+}
+
+impl ReadPartitions {
+    fn new(count: usize) -> Self {
+        ReadPartitions {
+            partitions: (0..count).map(|_| Vec::new()).collect(),
+            next: 0,
+        }
+    }
+
+    /// Adds the instance to the next partition and returns the partition's index
+    fn assign(&mut self, instance: Arc<PluginInstance>) -> usize {
+        let ind = self.next % self.partitions.len();
+        self.partitions[ind].push(instance);
+        self.next += 1;
+        ind
+    }
+}
+
+/// When collectd invokes the read callback of an instance
+#[derive(Debug, PartialEq)]
+enum ReadTiming {
+    /// The interval of the `LoadPlugin` block or the global interval
+    Default,
+    Interval(Duration),
+    Schedule(String),
+
+    /// Read at the default interval by the read callback of the partition
+    Partition(usize),
+}
+
+impl fmt::Display for ReadTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ReadTiming::Default => write!(f, "interval: default"),
+            ReadTiming::Interval(d) => write!(f, "interval: {}", humantime::format_duration(d)),
+            ReadTiming::Schedule(ref schedule) => write!(f, "schedule: {}", schedule),
+            ReadTiming::Partition(ind) => write!(f, "interval: default, partition: {}", ind),
+        }
+    }
+}
+
+fn read_group<T: PluginManager>() -> Option<CString> {
+    T::read_group().map(|x| CString::new(x).expect("Group to not contain nulls"))
+}
+
+/// A callback that an instance registers with collectd
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Callback {
+    Read,
+    Write,
+    Log,
+    Flush,
+    Notification,
+    Missing,
+}
+
+impl Callback {
+    /// The callbacks that an instance registered under its own name. Instances read by a
+    /// partition don't have a read callback of their own.
+    fn registered(capabilities: PluginCapabilities, timing: &ReadTiming) -> Vec<Callback> {
+        let mut res = Vec::new();
+        if capabilities.has_read() && !matches!(timing, ReadTiming::Partition(_)) {
+            res.push(Callback::Read);
+        }
+
+        let others = [
+            (capabilities.has_write(), Callback::Write),
+            (capabilities.has_log(), Callback::Log),
+            (capabilities.has_flush(), Callback::Flush),
+            (capabilities.has_notification(), Callback::Notification),
+            (capabilities.has_missing(), Callback::Missing),
+        ];
+
+        res.extend(others.iter().filter(|(has, _)| *has).map(|(_, x)| *x));
+        res
+    }
+
+    /// The callbacks that an instance registered besides its read callback. These are the ones
+    /// that collectd still holds when the shutdown callbacks run: collectd has already destroyed
+    /// the read callbacks along with their user data, while the flush callbacks are only destroyed
+    /// after the shutdown callbacks.
+    fn besides_read(capabilities: PluginCapabilities) -> Vec<Callback> {
+        let all = [
+            (capabilities.has_write(), Callback::Write),
+            (capabilities.has_log(), Callback::Log),
+            (capabilities.has_flush(), Callback::Flush),
+            (capabilities.has_notification(), Callback::Notification),
+            (capabilities.has_missing(), Callback::Missing),
+        ];
+
+        all.iter()
+            .filter(|(has, _)| *has)
+            .map(|(_, x)| *x)
+            .collect()
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Callback::Read => "read",
+            Callback::Write => "write",
+            Callback::Log => "log",
+            Callback::Flush => "flush",
+            Callback::Notification => "notification",
+            Callback::Missing => "missing",
+        }
+    }
+
+    /// Collectd frees the user data of an unregistered callback, which releases the instance
+    fn unregister(self, name: &CStr) {
+        let name = name.as_ptr();
+        unsafe {
+            match self {
+                Callback::Read => plugin_unregister_read(name),
+                Callback::Write => plugin_unregister_write(name),
+                Callback::Log => plugin_unregister_log(name),
+                Callback::Flush => plugin_unregister_flush(name),
+                Callback::Notification => plugin_unregister_notification(name),
+                Callback::Missing => plugin_unregister_missing(name),
+            };
+        }
+    }
+}
+
+/// The callbacks of an instance that has been taken out of service, which are unregistered from a
+/// read callback that runs once
+struct Unregistration {
+    name: CString,
+    callbacks: Vec<Callback>,
+}
+
+impl Unregistration {
+    fn read_name(&self) -> CString {
+        let name = format!("{}/unregister", self.name.to_string_lossy());
+        CString::new(name).expect("Plugin name to not contain nulls")
+    }
+}
+
+extern "C" fn plugin_unregister_callbacks(dt: *mut user_data_t) -> c_int {
+    let unregistration = unsafe { &*((*dt).data as *const Unregistration) };
+    for callback in &unregistration.callbacks {
+        callback.unregister(&unregistration.name);
+    }
+
+    // Collectd defers the removal of a read callback that unregisters itself until it returns
+    Callback::Read.unregister(&unregistration.read_name());
+    0
+}
+
+unsafe extern "C" fn plugin_free_unregistration(raw: *mut c_void) {
+    drop(Box::from_raw(raw as *mut Unregistration));
+}
+
+/// Unregisters every callback of an instance that has been taken out of service. Collectd defers
+/// the removal of a running read callback, so the read callback is unregistered right away. The
+/// other callbacks are freed the moment they are unregistered, even while collectd is invoking
+/// them (which is when instances are taken out of service), so they are unregistered from a read
+/// callback that runs once.
+pub(crate) fn unregister_instance(name: &str, capabilities: PluginCapabilities) {
+    let s = CString::new(name).expect("Plugin name to not contain nulls");
+    if capabilities.has_read() {
+        Callback::Read.unregister(&s);
+    }
+
+    let callbacks = Callback::besides_read(capabilities);
+    if callbacks.is_empty() {
+        return;
+    }
+
+    let unregistration = Box::new(Unregistration { name: s, callbacks });
+    let read_name = unregistration.read_name();
+    let d = &mut user_data_t {
+        data: Box::into_raw(unregistration) as *mut c_void,
+        free_func: Some(plugin_free_unregistration),
+    };
+
+    let status = unsafe {
+        let status = plugin_register_complex_read(
+            ptr::null(),
+            read_name.as_ptr(),
+            Some(plugin_unregister_callbacks),
+            get_default_interval(),
+            d,
+        );
+        release_rejected(status, d)
+    };
+
+    if status != 0 {
+        let err = RegistrationError::Rejected(
+            read_name.to_string_lossy().into_owned(),
+            Callback::Read.as_str(),
+            status,
+        );
+        let msg = format!("{}: unable to unregister its callbacks: {}", name, err);
+        collectd_log(LogLevel::Error, &msg);
+    }
+}
+
+/// Unregisters the callbacks of instances that were registered before a later instance failed to
+/// register, so that a plugin manager is registered in full or not at all
+fn roll_back(manager: &str, registered: &[(String, PluginCapabilities, ReadTiming)]) {
+    for (name, capabilities, timing) in registered {
+        let s = CString::new(name.as_str()).expect("Plugin name to not contain nulls");
+        for callback in Callback::registered(*capabilities, timing) {
+            callback.unregister(&s);
+        }
 
-    // Grab all the properties we need until `into_raw` away
-    let should_read = pl.capabilities().has_read();
-    let should_log = pl.capabilities().has_log();
-    let should_write = pl.capabilities().has_write();
-    let should_flush = pl.capabilities().has_flush();
+        registry::remove(name);
+    }
 
+    if !registered.is_empty() {
+        let names: Vec<&str> = registered
+            .iter()
+            .map(|(name, _, _)| name.as_str())
+            .collect();
+        let msg = format!(
+            "{}: unregistered {} plugin instance(s) after a failed registration: {}",
+            manager,
+            registered.len(),
+            names.join(", ")
+        );
+        collectd_log(LogLevel::Warning, &msg);
+    }
+}
+
+fn plugin_registration<T: PluginManager>(
+    name: &str,
+    plugin: Box<dyn Plugin>,
+    partitions: Option<&mut ReadPartitions>,
+) -> Result<(PluginCapabilities, ReadTiming), RegistrationError> {
+    // The instance is shared between collectd's user data and the registry so that it can be
+    // reached from outside of the callbacks (eg: the control socket)
+    let mut instance = PluginInstance::new(T::name(), name, plugin);
+    instance.panic_threshold = T::panic_threshold();
+    let instance = Arc::new(instance);
+    registry::insert(Arc::clone(&instance));
+
+    let capabilities = instance.capabilities;
     let s = CString::new(name).expect("Plugin name to not contain nulls");
+    let mut timing = ReadTiming::Default;
+    let mut statuses = Vec::new();
 
     // Plugin registration differs only a tiny bit between collectd-57 and older
     // versions. The one difference is that user_data_t went from mutable to not
@@ -132,64 +569,217 @@ fn plugin_registration(name: &str, plugin: Box<dyn Plugin>) {
     // encapsulated in a single crate instead of many others.
     #[cfg_attr(feature = "cargo-clippy", allow(clippy::unnecessary_mut_passed))]
     unsafe {
-        let plugin_ptr = Box::into_raw(pl) as *mut c_void;
-
         // The user data that is passed to read, writes, logs, etc. It is not passed to
         // config or init. Since user_data_t implements copy, we don't need to forget about
         // it. See clippy suggestion (forget_copy)
-        let mut data = user_data_t {
-            data: plugin_ptr,
-            free_func: Some(plugin_free_user_data),
-        };
+        if capabilities.has_read() {
+            // Scheduled reads are checked every second to see if they are due
+            timing = match instance.schedule {
+                Some(ref schedule) => ReadTiming::Schedule(schedule.to_string()),
+                None => instance
+                    .plugin
+                    .read_interval()
+                    .map(|x| ReadTiming::Interval(Duration::from(CdTime::from(x))))
+                    .unwrap_or(ReadTiming::Default),
+            };
+
+            let custom = match timing {
+                ReadTiming::Default | ReadTiming::Partition(_) => None,
+                ReadTiming::Interval(d) => Some(ReadInterval::new(d)),
+                ReadTiming::Schedule(_) => Some(ReadInterval::new(SCHEDULE_HEARTBEAT)),
+            };
+
+            let own_group = instance
+                .plugin
+                .read_group()
+                .map(|x| CString::new(x).expect("Group to not contain nulls"));
+
+            match partitions {
+                Some(partitions) if custom.is_none() && own_group.is_none() => {
+                    timing = ReadTiming::Partition(partitions.assign(Arc::clone(&instance)));
+                }
+                _ => {
+                    let interval = custom
+                        .as_ref()
+                        .map(|x| x.as_arg())
+                        .unwrap_or_else(get_default_interval);
+
+                    let group = own_group.or_else(read_group::<T>);
+                    let group_ptr = group.as_ref().map(|x| x.as_ptr()).unwrap_or_else(ptr::null);
+
+                    let d = &mut user_data(&instance);
+                    let name = s.as_ptr();
+                    let status = plugin_register_complex_read(
+                        group_ptr,
+                        name,
+                        Some(plugin_read),
+                        interval,
+                        d,
+                    );
+                    let status = release_rejected(status, d);
+                    statuses.push((Callback::Read, status));
+                }
+            }
+        }
+
+        if capabilities.has_write() {
+            let d = &mut user_data(&instance);
+            let status = plugin_register_write(s.as_ptr(), Some(plugin_write), d);
+            statuses.push((Callback::Write, status));
+        }
+
+        if capabilities.has_log() {
+            let d = &mut user_data(&instance);
+            let status = plugin_register_log(s.as_ptr(), Some(plugin_log), d);
+            statuses.push((Callback::Log, status));
+        }
+
+        if capabilities.has_flush() {
+            let d = &mut user_data(&instance);
+            let status = plugin_register_flush(s.as_ptr(), Some(plugin_flush), d);
+            statuses.push((Callback::Flush, status));
+        }
+
+        if capabilities.has_notification() {
+            let d = &mut user_data(&instance);
+            let status = plugin_register_notification(s.as_ptr(), Some(plugin_notification), d);
+            statuses.push((Callback::Notification, status));
+        }
+
+        if capabilities.has_missing() {
+            let d = &mut user_data(&instance);
+            let status = plugin_register_missing(s.as_ptr(), Some(plugin_missing), d);
+            statuses.push((Callback::Missing, status));
+        }
+    }
+
+    // Collectd rejects a callback when, for instance, a read callback is already registered under
+    // the name. Rather than leave the instance partially registered, its other callbacks are
+    // unregistered.
+    if let Some(&(callback, status)) = statuses.iter().find(|(_, status)| *status != 0) {
+        for &(registered, _) in statuses.iter().filter(|(_, status)| *status == 0) {
+            registered.unregister(&s);
+        }
+
+        registry::remove(name);
+        return Err(RegistrationError::Rejected(
+            String::from(name),
+            callback.as_str(),
+            status,
+        ));
+    }
+
+    Ok((capabilities, timing))
+}
 
-        // If a plugin registers more than one callback, we make sure to deregister the
-        // free function to avoid data being freed twice:
-        // https://collectd.org/wiki/index.php/User_data_t
-        let mut no_free_data = user_data_t {
-            data: plugin_ptr,
-            free_func: None,
+/// Registers a read callback for each partition that has instances. Should collectd reject one of
+/// them, the partitions registered before it are unregistered.
+fn register_partitions<T: PluginManager>(
+    partitions: ReadPartitions,
+) -> Result<(), RegistrationError> {
+    let group = read_group::<T>();
+    let group_ptr = group.as_ref().map(|x| x.as_ptr()).unwrap_or_else(ptr::null);
+    let members = partitions.partitions.into_iter().enumerate();
+    let mut registered: Vec<CString> = Vec::new();
+    for (ind, partition) in members.filter(|(_, x)| !x.is_empty()) {
+        let name = format!("{}/partition-{}", T::name(), ind);
+        let s = CString::new(name.as_str()).expect("Plugin name to not contain nulls");
+        let data: Box<Vec<Arc<PluginInstance>>> = Box::new(partition);
+        let d = &mut user_data_t {
+            data: Box::into_raw(data) as *mut c_void,
+            free_func: Some(plugin_free_partition),
         };
 
-        if should_read {
-            plugin_register_complex_read(
-                ptr::null(),
+        let status = unsafe {
+            let status = plugin_register_complex_read(
+                group_ptr,
                 s.as_ptr(),
-                Some(plugin_read),
+                Some(plugin_read_partition),
                 get_default_interval(),
-                &mut data,
+                d,
             );
-        }
+            release_rejected(status, d)
+        };
 
-        if should_write {
-            let d = if !should_read {
-                &mut data
-            } else {
-                &mut no_free_data
-            };
+        if status != 0 {
+            for earlier in &registered {
+                Callback::Read.unregister(earlier);
+            }
 
-            plugin_register_write(s.as_ptr(), Some(plugin_write), d);
+            return Err(RegistrationError::Rejected(
+                name,
+                Callback::Read.as_str(),
+                status,
+            ));
         }
 
-        if should_log {
-            let d = if !should_read && !should_write {
-                &mut data
-            } else {
-                &mut no_free_data
-            };
+        registered.push(s);
+    }
+
+    Ok(())
+}
+
+/// Summarizes what a plugin manager registered so that operators can confirm from the collectd
+/// logs what a plugin set up.
+fn registration_report(
+    manager: &str,
+    registered: &[(String, PluginCapabilities, ReadTiming)],
+) -> String {
+    let mut msg = format!(
+        "{}: registered {} plugin instance(s)",
+        manager,
+        registered.len()
+    );
+
+    for (i, (name, capabilities, timing)) in registered.iter().enumerate() {
+        let sep = if i == 0 { ": " } else { ", " };
+        let _ = write!(
+            msg,
+            "{}{} (capabilities: {:?}, {})",
+            sep, name, capabilities, timing
+        );
+    }
 
-            plugin_register_log(s.as_ptr(), Some(plugin_log), d);
+    msg
+}
+
+/// Ensures that each instance of a `PluginRegistration::Multiple` can be registered under a
+/// distinct name. The names are checked once prefixed with the plugin manager's name, as that is
+/// what the callbacks are registered under.
+fn validate_instance_names<'a, I>(manager: &str, ids: I) -> Result<(), RegistrationError>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut seen = Vec::new();
+    let mut duplicates = Vec::new();
+    for id in ids {
+        if id.is_empty()
+            || id
+                .chars()
+                .any(|c| c == '/' || c.is_whitespace() || c.is_control())
+        {
+            return Err(RegistrationError::InvalidName(String::from(id)));
         }
 
-        if should_flush {
-            let d = if !should_read && !should_write && !should_log {
-                &mut data
-            } else {
-                &mut no_free_data
-            };
+        let name = format!("{}/{}", manager, id);
+        if name.len() >= ARR_LENGTH {
+            return Err(RegistrationError::TooLong(name, ARR_LENGTH - 1));
+        }
 
-            plugin_register_flush(s.as_ptr(), Some(plugin_flush), d);
+        if seen.contains(&id) {
+            if !duplicates.contains(&name) {
+                duplicates.push(name);
+            }
+        } else {
+            seen.push(id);
         }
     }
+
+    if duplicates.is_empty() {
+        Ok(())
+    } else {
+        Err(RegistrationError::Duplicates(duplicates))
+    }
 }
 
 fn register_all_plugins<T: PluginManager>(config: Option<&[ConfigItem<'_>]>) -> c_int {
@@ -197,19 +787,51 @@ fn register_all_plugins<T: PluginManager>(config: Option<&[ConfigItem<'_>]>) ->
         .map_err(|_| FfiError::Panic)
         .and_then(|reged| reged.map_err(FfiError::Plugin))
         .and_then(|registration| {
+            let mut registered = Vec::new();
+            let mut partitions = T::read_partitions()
+                .filter(|x| *x > 0)
+                .map(ReadPartitions::new);
+
             match registration {
                 PluginRegistration::Single(pl) => {
-                    plugin_registration(T::name(), pl);
+                    let (capabilities, timing) =
+                        plugin_registration::<T>(T::name(), pl, partitions.as_mut())
+                            .map_err(|e| FfiError::Plugin(Box::new(e)))?;
+                    registered.push((String::from(T::name()), capabilities, timing));
                 }
                 PluginRegistration::Multiple(v) => {
+                    validate_instance_names(T::name(), v.iter().map(|(id, _)| id.as_str()))
+                        .map_err(|e| FfiError::Plugin(Box::new(e)))?;
+
                     for (id, pl) in v {
                         let name = format!("{}/{}", T::name(), id);
 
-                        plugin_registration(name.as_str(), pl);
+                        match plugin_registration::<T>(name.as_str(), pl, partitions.as_mut()) {
+                            Ok((capabilities, timing)) => {
+                                registered.push((name, capabilities, timing))
+                            }
+                            Err(e) => {
+                                roll_back(T::name(), &registered);
+                                return Err(FfiError::Plugin(Box::new(e)));
+                            }
+                        }
                     }
                 }
+                PluginRegistration::Empty(reason) => {
+                    let msg = format!("{}: registered no plugin instances: {}", T::name(), reason);
+                    collectd_log(LogLevel::Info, &msg);
+                    return Ok(());
+                }
+            }
+
+            if let Some(partitions) = partitions {
+                if let Err(e) = register_partitions::<T>(partitions) {
+                    roll_back(T::name(), &registered);
+                    return Err(FfiError::Plugin(Box::new(e)));
+                }
             }
 
+            collectd_log(LogLevel::Info, &registration_report(T::name(), &registered));
             Ok(())
         });
 
@@ -219,12 +841,51 @@ fn register_all_plugins<T: PluginManager>(config: Option<&[ConfigItem<'_>]>) ->
     res.map(|_| 0).unwrap_or(-1)
 }
 
+/// Registers an instance discovered after collectd was initialized. See `register_instance`.
+pub fn register_instance<T: PluginManager>(
+    id: &str,
+    plugin: Box<dyn Plugin>,
+) -> Result<(), RegistrationError> {
+    validate_instance_names(T::name(), std::iter::once(id))?;
+
+    let name = format!("{}/{}", T::name(), id);
+    let (capabilities, timing) = {
+        let _guard = RUNTIME_REGISTRATION
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+
+        if registry::instances().iter().any(|x| x.name == name) {
+            return Err(RegistrationError::Duplicates(vec![name]));
+        }
+
+        plugin_registration::<T>(name.as_str(), plugin, None)?
+    };
+
+    let registered = [(name, capabilities, timing)];
+    collectd_log(LogLevel::Info, &registration_report(T::name(), &registered));
+
+    // The lock is released beforehand so that the plugin can register instances of its own
+    let instances = registry::instances();
+    if let Some(instance) = instances.iter().find(|x| x.name == registered[0].0) {
+        instance.initialize();
+    }
+
+    Ok(())
+}
+
 pub fn plugin_init<T: PluginManager>(config_seen: &AtomicBool) -> c_int {
     let mut result = 0;
+    let stashed = MANAGER_CONFIG
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(T::name());
+    let config: Option<Vec<ConfigItem<'_>>> = stashed
+        .as_ref()
+        .map(|children| children.iter().map(|x| x.as_item()).collect());
 
     let capabilities = T::capabilities();
     if capabilities.intersects(PluginManagerCapabilities::INIT) {
-        let res = catch_unwind(T::initialize)
+        let res = catch_unwind(|| T::initialize_with_config(config.as_deref()))
             .map_err(|_e| FfiError::Panic)
             .and_then(|init| init.map_err(FfiError::Plugin));
 
@@ -235,7 +896,43 @@ pub fn plugin_init<T: PluginManager>(config_seen: &AtomicBool) -> c_int {
     }
 
     if result == 0 && !config_seen.swap(true, Ordering::Relaxed) {
-        result = register_all_plugins::<T>(None);
+        result = register_all_plugins::<T>(config.as_deref());
+    }
+
+    if result == 0 {
+        let instances = registry::instances();
+        for instance in instances.iter().filter(|x| x.manager == T::name()) {
+            instance.initialize();
+        }
+    }
+
+    result
+}
+
+/// Takes the instances of the plugin manager out of the registry and drops them. Returns false if
+/// dropping an instance panicked.
+fn release_instances(manager: &str) -> bool {
+    let mut result = true;
+    let instances = registry::remove_manager(manager);
+    let (lvl, msg) = registry::shutdown_report(manager, &instances);
+    collectd_log(lvl, &msg);
+
+    // Unregistering the remaining callbacks has collectd free their user data, so that ours are
+    // the last references to the instances and the plugins are dropped here (closing their files,
+    // sockets, and threads) instead of whenever collectd gets around to it
+    for instance in &instances {
+        let s = CString::new(instance.name.as_str()).expect("Plugin name to not contain nulls");
+        for callback in Callback::besides_read(instance.capabilities) {
+            callback.unregister(&s);
+        }
+    }
+
+    for instance in instances {
+        let name = instance.name.clone();
+        if catch_unwind(AssertUnwindSafe(|| drop(instance))).is_err() {
+            result = false;
+            log_err(&format!("{} drop", name), &FfiError::Panic);
+        }
     }
 
     result
@@ -244,6 +941,35 @@ pub fn plugin_init<T: PluginManager>(config_seen: &AtomicBool) -> c_int {
 pub fn plugin_shutdown<T: PluginManager>() -> c_int {
     let mut result = 0;
 
+    if !release_instances(T::name()) {
+        result = -1;
+    }
+
+    // Shutdown hooks are shared by all the plugin managers of the shared object
+    let remaining = ACTIVE_MANAGERS
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| {
+            Some(x.saturating_sub(1))
+        })
+        .map(|x| x.saturating_sub(1))
+        .unwrap_or(0);
+
+    let hooks = if remaining == 0 {
+        take_shutdown_hooks()
+    } else {
+        Vec::new()
+    };
+
+    for hook in hooks {
+        let res = catch_unwind(AssertUnwindSafe(hook))
+            .map_err(|_e| FfiError::Panic)
+            .and_then(|r| r.map_err(FfiError::Plugin));
+
+        if let Err(ref e) = res {
+            result = -1;
+            log_err("shutdown hook", e);
+        }
+    }
+
     let capabilities = T::capabilities();
     if capabilities.intersects(PluginManagerCapabilities::INIT) {
         let res = catch_unwind(T::shutdown)
@@ -263,23 +989,91 @@ pub unsafe fn plugin_complex_config<T: PluginManager>(
     config_seen: &AtomicBool,
     config: *mut oconfig_item_t,
 ) -> c_int {
-    // If we've already seen the config, let's error out as one shouldn't use multiple
-    // sections of configuration (group them under nodes like write_graphite)
+    let config = match ConfigItem::from(&*config) {
+        Ok(config) => config,
+        Err(e) => {
+            log_err(
+                "collectd config conversion",
+                &FfiError::Collectd(Box::new(e)),
+            );
+            return -1;
+        }
+    };
+
+    // The sections of a plugin manager with aliases are merged until it is initialized
+    if !T::aliases().is_empty() && !config_seen.load(Ordering::Relaxed) {
+        stash_config::<T>(&config);
+        return 0;
+    }
+
+    // A section seen after the instances were registered is forwarded to them, as collectd can
+    // hand us several sections (group them under nodes like write_graphite to avoid this)
     if config_seen.swap(true, Ordering::Relaxed) {
+        return reconfigure_instances::<T>(&config.children);
+    }
+
+    stash_config::<T>(&config);
+    register_all_plugins::<T>(Some(&config.children))
+}
+
+/// Hands a later config section to the instances of the plugin manager that can be reconfigured.
+/// The section is a duplicate if none of them can.
+fn reconfigure_instances<T: PluginManager>(config: &[ConfigItem<'_>]) -> c_int {
+    let instances: Vec<_> = registry::instances()
+        .into_iter()
+        .filter(|x| x.manager == T::name() && x.capabilities.has_reconfigure())
+        .collect();
+
+    if instances.is_empty() {
         log_err("config", &FfiError::MultipleConfig);
         return -1;
     }
 
-    match ConfigItem::from(&*config) {
-        Ok(config) => register_all_plugins::<T>(Some(&config.children)),
-        Err(e) => {
-            log_err(
-                "collectd config conversion",
-                &FfiError::Collectd(Box::new(e)),
+    let mut result = 0;
+    for instance in instances.iter().filter(|x| x.is_active()) {
+        let res =
+            catch_unwind(|| with_instance(&instance.name, || instance.plugin.reconfigure(config)))
+                .map_err(|_| FfiError::Panic)
+                .and_then(|x| x.map_err(FfiError::Plugin));
+
+        if let Err(ref e) = res {
+            result = -1;
+            log_err("reconfigure", e);
+        }
+    }
+
+    result
+}
+
+/// Stashes the children of a config section that was read under the plugin manager's name or one
+/// of its aliases, so that they can be handed to `PluginManager::initialize_with_config`. The
+/// plugins of a manager with aliases are registered with the merged config on init.
+fn stash_config<T: PluginManager>(config: &ConfigItem<'_>) {
+    // The section for `<Plugin "name">` has the key of "Plugin" and the name as the value
+    if let Some(&ConfigValue::String(name)) = config.values.first() {
+        if !name.eq_ignore_ascii_case(T::name()) {
+            collectd_log(
+                LogLevel::Notice,
+                &format!(
+                    "{}: reading config from section of alias `{}`",
+                    T::name(),
+                    name
+                ),
             );
-            -1
         }
     }
+
+    MANAGER_CONFIG
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(T::name())
+        .or_default()
+        .extend(config.children.iter().map(OwnedConfigItem::from));
+}
+
+/// Records that a plugin manager was registered when collectd loaded the shared object
+pub fn register_manager() {
+    ACTIVE_MANAGERS.fetch_add(1, Ordering::SeqCst);
 }
 
 pub fn register_panic_handler() {
@@ -287,3 +1081,658 @@ pub fn register_panic_handler() {
         log_err("panic hook", &FfiError::PanicHook(info));
     }));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::overrides::reject_callback;
+    use crate::errors::{SubmitError, Unregister};
+    use std::error::Error;
+
+    #[test]
+    fn test_validate_instance_names() {
+        assert_eq!(Ok(()), validate_instance_names("myplugin", vec!["a", "b"]));
+        assert_eq!(
+            Err(RegistrationError::Duplicates(vec![
+                String::from("myplugin/a"),
+                String::from("myplugin/b")
+            ])),
+            validate_instance_names("myplugin", vec!["a", "b", "a", "b", "a", "c"])
+        );
+        assert_eq!(
+            Err(RegistrationError::InvalidName(String::from("a/b"))),
+            validate_instance_names("myplugin", vec!["a/b"])
+        );
+        assert_eq!(
+            Err(RegistrationError::InvalidName(String::from(""))),
+            validate_instance_names("myplugin", vec![""])
+        );
+
+        let long = "x".repeat(ARR_LENGTH - "myplugin/".len());
+        match validate_instance_names("myplugin", vec![long.as_str()]) {
+            Err(RegistrationError::TooLong(_, limit)) => assert_eq!(ARR_LENGTH - 1, limit),
+            x => panic!("unexpected result: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn test_registration_report() {
+        let registered = vec![
+            (
+                String::from("myplugin/a"),
+                PluginCapabilities::READ | PluginCapabilities::WRITE,
+                ReadTiming::Default,
+            ),
+            (
+                String::from("myplugin/b"),
+                PluginCapabilities::LOG,
+                ReadTiming::Default,
+            ),
+            (
+                String::from("myplugin/c"),
+                PluginCapabilities::READ,
+                ReadTiming::Interval(Duration::from_secs(300)),
+            ),
+            (
+                String::from("myplugin/d"),
+                PluginCapabilities::READ,
+                ReadTiming::Schedule(String::from("5 * * * *")),
+            ),
+            (
+                String::from("myplugin/e"),
+                PluginCapabilities::READ,
+                ReadTiming::Partition(1),
+            ),
+        ];
+
+        assert_eq!(
+            registration_report("myplugin", &registered),
+            "myplugin: registered 5 plugin instance(s): \
+             myplugin/a (capabilities: READ | WRITE, interval: default), \
+             myplugin/b (capabilities: LOG, interval: default), \
+             myplugin/c (capabilities: READ, interval: 5m), \
+             myplugin/d (capabilities: READ, schedule: 5 * * * *), \
+             myplugin/e (capabilities: READ, interval: default, partition: 1)"
+        );
+    }
+
+    #[test]
+    fn test_read_partitions_round_robin() {
+        struct NoopPlugin;
+        impl Plugin for NoopPlugin {}
+
+        let mut partitions = ReadPartitions::new(2);
+        let assigned: Vec<usize> = (0..5)
+            .map(|i| {
+                let name = format!("myplugin/{}", i);
+                let instance = PluginInstance::new("myplugin", &name, Box::new(NoopPlugin));
+                partitions.assign(Arc::new(instance))
+            })
+            .collect();
+
+        assert_eq!(vec![0, 1, 0, 1, 0], assigned);
+        assert_eq!(3, partitions.partitions[0].len());
+        assert_eq!(2, partitions.partitions[1].len());
+    }
+
+    #[test]
+    fn test_registration_report_empty() {
+        assert_eq!(
+            registration_report("myplugin", &[]),
+            "myplugin: registered 0 plugin instance(s)"
+        );
+    }
+
+    #[test]
+    fn test_register_instance() {
+        struct RuntimeManager;
+        impl PluginManager for RuntimeManager {
+            fn name() -> &'static str {
+                "runtime"
+            }
+
+            fn plugins(
+                _config: Option<&[ConfigItem<'_>]>,
+            ) -> Result<PluginRegistration, Box<dyn Error>> {
+                Ok(PluginRegistration::Empty(String::from("discovered later")))
+            }
+        }
+
+        struct NoopPlugin;
+        impl Plugin for NoopPlugin {}
+
+        register_instance::<RuntimeManager>("sda", Box::new(NoopPlugin)).unwrap();
+        assert!(registry::instances()
+            .iter()
+            .any(|x| x.name == "runtime/sda"));
+
+        let dup = register_instance::<RuntimeManager>("sda", Box::new(NoopPlugin));
+        assert_eq!(
+            Err(RegistrationError::Duplicates(vec![String::from(
+                "runtime/sda"
+            )])),
+            dup
+        );
+
+        let invalid = register_instance::<RuntimeManager>("sd a", Box::new(NoopPlugin));
+        assert!(matches!(invalid, Err(RegistrationError::InvalidName(_))));
+
+        registry::remove_manager("runtime");
+    }
+
+    #[test]
+    fn test_log_reaches_log_plugins() {
+        use std::sync::Mutex;
+
+        lazy_static! {
+            static ref RECEIVED: Mutex<Vec<(LogLevel, String)>> = Mutex::new(Vec::new());
+        }
+
+        struct LogManager;
+        impl PluginManager for LogManager {
+            fn name() -> &'static str {
+                "logsink"
+            }
+
+            fn plugins(
+                _config: Option<&[ConfigItem<'_>]>,
+            ) -> Result<PluginRegistration, Box<dyn Error>> {
+                Ok(PluginRegistration::Empty(String::from(
+                    "registered in test",
+                )))
+            }
+        }
+
+        struct LogPlugin;
+        impl Plugin for LogPlugin {
+            fn capabilities(&self) -> PluginCapabilities {
+                PluginCapabilities::LOG
+            }
+
+            fn log(&self, lvl: LogLevel, msg: &str) -> Result<(), Box<dyn Error>> {
+                if msg.starts_with("logsink test") {
+                    // Logging from within a log callback is not delivered again
+                    collectd_log(LogLevel::Info, "logsink test: nested");
+                    RECEIVED.lock().unwrap().push((lvl, String::from(msg)));
+                }
+                Ok(())
+            }
+        }
+
+        register_instance::<LogManager>("a", Box::new(LogPlugin)).unwrap();
+        collectd_log(LogLevel::Warning, "logsink test: hello");
+        assert_eq!(
+            vec![(LogLevel::Warning, String::from("logsink test: hello"))],
+            *RECEIVED.lock().unwrap()
+        );
+
+        // Once unregistered, the instance no longer receives messages
+        assert!(release_instances("logsink"));
+        collectd_log(LogLevel::Warning, "logsink test: goodbye");
+        assert_eq!(1, RECEIVED.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_registration_rolls_back() {
+        struct RollbackManager;
+        impl PluginManager for RollbackManager {
+            fn name() -> &'static str {
+                "rollback"
+            }
+
+            fn plugins(
+                _config: Option<&[ConfigItem<'_>]>,
+            ) -> Result<PluginRegistration, Box<dyn Error>> {
+                let ids = ["a", "b", "c"];
+                let plugins = ids
+                    .iter()
+                    .map(|x| (String::from(*x), Box::new(ReadPlugin) as Box<dyn Plugin>))
+                    .collect();
+                Ok(PluginRegistration::Multiple(plugins))
+            }
+        }
+
+        struct ReadPlugin;
+        impl Plugin for ReadPlugin {
+            fn capabilities(&self) -> PluginCapabilities {
+                PluginCapabilities::READ | PluginCapabilities::WRITE
+            }
+        }
+
+        let register_read = |name: &str| {
+            let s = CString::new(name).unwrap();
+            unsafe {
+                plugin_register_complex_read(
+                    ptr::null(),
+                    s.as_ptr(),
+                    None,
+                    get_default_interval(),
+                    ptr::null_mut(),
+                )
+            }
+        };
+
+        // Another plugin already has a read callback under the name of the second instance
+        assert_eq!(0, register_read("rollback/b"));
+        assert_eq!(-1, register_all_plugins::<RollbackManager>(None));
+        assert!(!registry::instances()
+            .iter()
+            .any(|x| x.manager == "rollback"));
+
+        // The first instance's read callback was unregistered
+        assert_eq!(0, register_read("rollback/a"));
+
+        let res = register_instance::<RollbackManager>("b", Box::new(ReadPlugin));
+        assert_eq!(
+            Err(RegistrationError::Rejected(
+                String::from("rollback/b"),
+                "read",
+                -1
+            )),
+            res
+        );
+        assert!(!registry::instances()
+            .iter()
+            .any(|x| x.manager == "rollback"));
+    }
+
+    #[test]
+    fn test_rejected_registration_frees_user_data() {
+        struct LeakManager;
+        impl PluginManager for LeakManager {
+            fn name() -> &'static str {
+                "leak"
+            }
+
+            fn plugins(
+                _config: Option<&[ConfigItem<'_>]>,
+            ) -> Result<PluginRegistration, Box<dyn Error>> {
+                Ok(PluginRegistration::Empty(String::from(
+                    "registered at runtime",
+                )))
+            }
+        }
+
+        struct ReadPlugin {
+            _alive: Arc<()>,
+        }
+        impl Plugin for ReadPlugin {
+            fn capabilities(&self) -> PluginCapabilities {
+                PluginCapabilities::READ
+            }
+        }
+
+        let s = CString::new("leak/a").unwrap();
+        let status = unsafe {
+            plugin_register_complex_read(
+                ptr::null(),
+                s.as_ptr(),
+                None,
+                get_default_interval(),
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(0, status);
+
+        // Once collectd rejects the read callback, nothing should hold onto the instance
+        let alive = Arc::new(());
+        let res = register_instance::<LeakManager>(
+            "a",
+            Box::new(ReadPlugin {
+                _alive: Arc::clone(&alive),
+            }),
+        );
+        assert!(res.is_err());
+        assert_eq!(1, Arc::strong_count(&alive));
+    }
+
+    #[test]
+    fn test_rejected_write_frees_user_data_once() {
+        struct RejectManager;
+        impl PluginManager for RejectManager {
+            fn name() -> &'static str {
+                "rejectwrite"
+            }
+
+            fn plugins(
+                _config: Option<&[ConfigItem<'_>]>,
+            ) -> Result<PluginRegistration, Box<dyn Error>> {
+                Ok(PluginRegistration::Empty(String::from(
+                    "registered at runtime",
+                )))
+            }
+        }
+
+        struct WritePlugin {
+            _alive: Arc<()>,
+        }
+        impl Plugin for WritePlugin {
+            fn capabilities(&self) -> PluginCapabilities {
+                PluginCapabilities::WRITE
+            }
+        }
+
+        // Collectd frees the user data of the rejected write callback itself
+        reject_callback("rejectwrite/a");
+        let alive = Arc::new(());
+        let res = register_instance::<RejectManager>(
+            "a",
+            Box::new(WritePlugin {
+                _alive: Arc::clone(&alive),
+            }),
+        );
+        assert!(res.is_err());
+        assert_eq!(1, Arc::strong_count(&alive));
+    }
+
+    #[test]
+    fn test_unregister_instance() {
+        struct UnregisterManager;
+        impl PluginManager for UnregisterManager {
+            fn name() -> &'static str {
+                "unregister"
+            }
+
+            fn plugins(
+                _config: Option<&[ConfigItem<'_>]>,
+            ) -> Result<PluginRegistration, Box<dyn Error>> {
+                Ok(PluginRegistration::Empty(String::from(
+                    "registered at runtime",
+                )))
+            }
+        }
+
+        struct LogPlugin;
+        impl Plugin for LogPlugin {
+            fn capabilities(&self) -> PluginCapabilities {
+                PluginCapabilities::LOG
+            }
+
+            fn log(&self, _lvl: LogLevel, _msg: &str) -> Result<(), Box<dyn Error>> {
+                Ok(())
+            }
+        }
+
+        register_instance::<UnregisterManager>("a", Box::new(LogPlugin)).unwrap();
+        let instance = registry::instances()
+            .into_iter()
+            .find(|x| x.name == "unregister/a")
+            .unwrap();
+        let references = Arc::strong_count(&instance);
+
+        let err = Unregister(String::from("device was removed"));
+        let res: Result<(), FfiError<'_>> = Err(FfiError::Plugin(Box::new(err)));
+        instance.record_result(&instance.stats.log_errors, &res);
+
+        // The log callback is unregistered from a read callback that runs once
+        let read_name = CString::new("unregister/a/unregister").unwrap();
+        let register_read = || unsafe {
+            plugin_register_complex_read(
+                ptr::null(),
+                read_name.as_ptr(),
+                None,
+                get_default_interval(),
+                ptr::null_mut(),
+            )
+        };
+        assert_ne!(0, register_read());
+
+        let unregistration = Box::new(Unregistration {
+            name: CString::new("unregister/a").unwrap(),
+            callbacks: vec![Callback::Log],
+        });
+        let mut d = user_data_t {
+            data: Box::into_raw(unregistration) as *mut c_void,
+            free_func: Some(plugin_free_unregistration),
+        };
+
+        assert_eq!(0, plugin_unregister_callbacks(&mut d));
+        assert_eq!(references - 1, Arc::strong_count(&instance));
+        assert_eq!(0, register_read());
+
+        unsafe {
+            plugin_free_unregistration(d.data);
+            plugin_unregister_read(read_name.as_ptr());
+        }
+        release_instances("unregister");
+    }
+
+    #[test]
+    fn test_read_status_backoff() {
+        struct StatusPlugin(AtomicUsize);
+        impl Plugin for StatusPlugin {
+            fn read_status(&self) -> ReadStatus {
+                let err = || Box::new(SubmitError::Dispatch(-1)) as Box<dyn Error>;
+                match self.0.fetch_add(1, Ordering::Relaxed) {
+                    0 => ReadStatus::Ok,
+                    1 => ReadStatus::Skip,
+                    2 => ReadStatus::TransientError(err()),
+                    _ => ReadStatus::PermanentError(err()),
+                }
+            }
+        }
+
+        let plugin = Box::new(StatusPlugin(AtomicUsize::new(0)));
+        let instance = PluginInstance::new("status", "status", plugin);
+        let statuses: Vec<_> = (0..4).map(|_| read_instance(&instance)).collect();
+        assert_eq!(vec![0, 0, 0, -1], statuses);
+        assert_eq!(2, instance.stats.read_errors.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_reconfigure_instances() {
+        struct ReloadManager;
+        impl PluginManager for ReloadManager {
+            fn name() -> &'static str {
+                "reload"
+            }
+
+            fn plugins(
+                _config: Option<&[ConfigItem<'_>]>,
+            ) -> Result<PluginRegistration, Box<dyn Error>> {
+                Ok(PluginRegistration::Empty(String::from(
+                    "registered in test",
+                )))
+            }
+        }
+
+        static RELOADED: AtomicUsize = AtomicUsize::new(0);
+
+        struct ReloadPlugin;
+        impl Plugin for ReloadPlugin {
+            fn capabilities(&self) -> PluginCapabilities {
+                PluginCapabilities::RECONFIGURE
+            }
+
+            fn reconfigure(&self, config: &[ConfigItem<'_>]) -> Result<(), Box<dyn Error>> {
+                RELOADED.fetch_add(config.len(), Ordering::Relaxed);
+                Ok(())
+            }
+        }
+
+        struct NoopPlugin;
+        impl Plugin for NoopPlugin {}
+
+        let config = vec![ConfigItem {
+            key: "Interval",
+            values: vec![ConfigValue::Number(5.0)],
+            children: vec![],
+        }];
+
+        // Without an instance that can be reconfigured, the section is a duplicate
+        register_instance::<ReloadManager>("a", Box::new(NoopPlugin)).unwrap();
+        assert_eq!(-1, reconfigure_instances::<ReloadManager>(&config));
+
+        register_instance::<ReloadManager>("b", Box::new(ReloadPlugin)).unwrap();
+        assert_eq!(0, reconfigure_instances::<ReloadManager>(&config));
+        assert_eq!(1, RELOADED.load(Ordering::Relaxed));
+
+        registry::remove_manager("reload");
+    }
+
+    #[test]
+    fn test_register_instance_from_initialize() {
+        static INITIALIZED: AtomicUsize = AtomicUsize::new(0);
+
+        struct NestedManager;
+        impl PluginManager for NestedManager {
+            fn name() -> &'static str {
+                "nested"
+            }
+
+            fn plugins(
+                _config: Option<&[ConfigItem<'_>]>,
+            ) -> Result<PluginRegistration, Box<dyn Error>> {
+                Ok(PluginRegistration::Empty(String::from(
+                    "registered at runtime",
+                )))
+            }
+        }
+
+        struct ChildPlugin;
+        impl Plugin for ChildPlugin {
+            fn initialize(&self) -> Result<(), Box<dyn Error>> {
+                INITIALIZED.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+        }
+
+        // Discovers its children when initialized
+        struct ParentPlugin;
+        impl Plugin for ParentPlugin {
+            fn initialize(&self) -> Result<(), Box<dyn Error>> {
+                INITIALIZED.fetch_add(1, Ordering::Relaxed);
+                register_instance::<NestedManager>("child", Box::new(ChildPlugin))?;
+                Ok(())
+            }
+        }
+
+        register_instance::<NestedManager>("parent", Box::new(ParentPlugin)).unwrap();
+        assert_eq!(2, INITIALIZED.load(Ordering::Relaxed));
+
+        // Initializing all of the manager's instances leaves those that were already initialized
+        for instance in registry::instances()
+            .iter()
+            .filter(|x| x.manager == "nested")
+        {
+            assert!(instance.initialize());
+        }
+        assert_eq!(2, INITIALIZED.load(Ordering::Relaxed));
+
+        registry::remove_manager("nested");
+    }
+
+    #[test]
+    fn test_callbacks_at_shutdown() {
+        let capabilities = PluginCapabilities::READ | PluginCapabilities::FLUSH;
+        assert_eq!(vec![Callback::Flush], Callback::besides_read(capabilities));
+    }
+
+    #[test]
+    fn test_release_instances_drops_plugins() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        struct DropPlugin;
+        impl Plugin for DropPlugin {
+            fn capabilities(&self) -> PluginCapabilities {
+                PluginCapabilities::WRITE | PluginCapabilities::LOG
+            }
+        }
+
+        impl Drop for DropPlugin {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let instance = Arc::new(PluginInstance::new(
+            "teardown",
+            "teardown/a",
+            Box::new(DropPlugin),
+        ));
+        registry::insert(Arc::clone(&instance));
+        registry::insert(Arc::new(PluginInstance::new(
+            "teardown",
+            "teardown/b",
+            Box::new(DropPlugin),
+        )));
+
+        // The other references are released before the instance is dropped
+        assert!(release_instances("teardown"));
+        assert_eq!(1, DROPPED.load(Ordering::Relaxed));
+        assert!(!registry::instances()
+            .iter()
+            .any(|x| x.manager == "teardown"));
+
+        drop(instance);
+        assert_eq!(2, DROPPED.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_user_data_free_func_releases_instance() {
+        struct NoopPlugin;
+        impl Plugin for NoopPlugin {}
+
+        let instance = Arc::new(PluginInstance::new("free", "free", Box::new(NoopPlugin)));
+        let data = user_data(&instance);
+        assert_eq!(2, Arc::strong_count(&instance));
+
+        // What collectd does when the callback is unregistered
+        unsafe { data.free_func.unwrap()(data.data) };
+        assert_eq!(1, Arc::strong_count(&instance));
+    }
+
+    #[test]
+    fn test_initialize_receives_config() {
+        static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+        struct PoolManager;
+        impl PluginManager for PoolManager {
+            fn name() -> &'static str {
+                "pool"
+            }
+
+            fn plugins(
+                _config: Option<&[ConfigItem<'_>]>,
+            ) -> Result<PluginRegistration, Box<dyn Error>> {
+                Ok(PluginRegistration::Empty(String::from(
+                    "registered in test",
+                )))
+            }
+
+            fn initialize_with_config(
+                config: Option<&[ConfigItem<'_>]>,
+            ) -> Result<(), Box<dyn Error>> {
+                let threads = config
+                    .unwrap_or_default()
+                    .iter()
+                    .find(|x| x.key == "Threads")
+                    .and_then(|x| match x.values.first() {
+                        Some(&ConfigValue::Number(n)) => Some(n as usize),
+                        _ => None,
+                    });
+                THREADS.store(threads.unwrap_or(1), Ordering::Relaxed);
+                Ok(())
+            }
+        }
+
+        let config = ConfigItem {
+            key: "Plugin",
+            values: vec![ConfigValue::String("pool")],
+            children: vec![ConfigItem {
+                key: "Threads",
+                values: vec![ConfigValue::Number(4.0)],
+                children: vec![],
+            }],
+        };
+
+        // The plugins were registered when the config was read
+        stash_config::<PoolManager>(&config);
+        assert_eq!(0, plugin_init::<PoolManager>(&AtomicBool::new(true)));
+        assert_eq!(4, THREADS.load(Ordering::Relaxed));
+
+        // The config is released once the manager is initialized
+        assert_eq!(0, plugin_init::<PoolManager>(&AtomicBool::new(true)));
+        assert_eq!(1, THREADS.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,128 @@
+use crate::api::{
+    CdTime, ConfigItem, Duration, IntoDuration, LogLevel, Notification, Schedule, ValueList,
+};
+use crate::errors::DurationError;
+use crate::plugins::{Plugin, PluginCapabilities, ReadStatus};
+use std::error;
+
+/// Wraps a plugin so that collectd reads its values at the given interval instead of the interval
+/// of the `LoadPlugin` block or the global `Interval`. This allows the instances of a
+/// `PluginRegistration` to each read at their own interval without implementing
+/// `Plugin::read_interval`.
+///
+/// ```
+/// use collectd_plugin::{Plugin, PluginRegistration, ReadEvery};
+/// use std::error;
+///
+/// struct Poller;
+/// impl Plugin for Poller {}
+///
+/// fn registration() -> Result<PluginRegistration, Box<dyn error::Error>> {
+///     Ok(PluginRegistration::Multiple(vec![
+///         (String::from("fast"), Box::new(ReadEvery::new(Poller, "5s")?)),
+///         (String::from("slow"), Box::new(ReadEvery::new(Poller, "5m")?)),
+///     ]))
+/// }
+/// ```
+pub struct ReadEvery<P> {
+    plugin: P,
+    interval: Duration,
+}
+
+impl<P: Plugin> ReadEvery<P> {
+    pub fn new<T: IntoDuration>(plugin: P, interval: T) -> Result<Self, DurationError> {
+        Ok(ReadEvery {
+            plugin,
+            interval: CdTime::from(interval.into_duration()?).into(),
+        })
+    }
+}
+
+impl<P: Plugin> Plugin for ReadEvery<P> {
+    fn initialize(&self) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.initialize()
+    }
+
+    fn reconfigure(&self, config: &[ConfigItem<'_>]) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.reconfigure(config)
+    }
+
+    fn capabilities(&self) -> PluginCapabilities {
+        self.plugin.capabilities()
+    }
+
+    fn log(&self, lvl: LogLevel, msg: &str) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.log(lvl, msg)
+    }
+
+    fn read_values(&self) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.read_values()
+    }
+
+    fn read_status(&self) -> ReadStatus {
+        self.plugin.read_status()
+    }
+
+    fn read_interval(&self) -> Option<Duration> {
+        Some(self.interval)
+    }
+
+    fn read_group(&self) -> Option<&str> {
+        self.plugin.read_group()
+    }
+
+    fn interval_changed(&self, interval: Duration) {
+        self.plugin.interval_changed(interval)
+    }
+
+    fn schedule(&self) -> Option<Schedule> {
+        self.plugin.schedule()
+    }
+
+    fn write_values(&self, list: ValueList<'_>) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.write_values(list)
+    }
+
+    fn flush(
+        &self,
+        timeout: Option<Duration>,
+        identifier: Option<&str>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.flush(timeout, identifier)
+    }
+
+    fn notification(&self, notification: Notification<'_>) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.notification(notification)
+    }
+
+    fn missing(&self, list: ValueList<'_>) -> Result<(), Box<dyn error::Error>> {
+        self.plugin.missing(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time;
+
+    struct Reader;
+
+    impl Plugin for Reader {
+        fn capabilities(&self) -> PluginCapabilities {
+            PluginCapabilities::READ
+        }
+
+        fn read_interval(&self) -> Option<Duration> {
+            Some(CdTime::from(time::Duration::from_secs(10)).into())
+        }
+    }
+
+    #[test]
+    fn test_read_every_overrides_interval() {
+        let plugin = ReadEvery::new(Reader, "5s").unwrap();
+        let interval = plugin.read_interval().map(CdTime::from);
+        assert_eq!(Some(CdTime::from(time::Duration::from_secs(5))), interval);
+        assert_eq!(PluginCapabilities::READ, plugin.capabilities());
+        assert!(ReadEvery::new(Reader, "soon").is_err());
+    }
+}
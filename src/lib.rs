@@ -97,22 +97,65 @@ pub mod de;
 #[cfg(feature = "serde")]
 pub mod ser;
 
+#[cfg(unix)]
+mod activation;
 pub mod bindings;
 pub mod internal;
 #[macro_use]
 mod api;
+#[cfg(unix)]
+mod control;
+mod dedup;
+mod dump;
 mod errors;
+mod filter;
+mod interval;
 #[macro_use]
 mod plugins;
+mod read_fn;
+mod registry;
+mod roles;
+mod sidecar;
+mod stats;
 
+#[cfg(unix)]
+pub use crate::activation::{listen_fd, listen_fds, ListenFd};
 pub use crate::api::{
-    collectd_log, CdTime, CollectdLoggerBuilder, ConfigItem, ConfigValue, LogLevel, Value,
-    ValueList, ValueListBuilder, ValueReport,
+    capabilities, collectd_log, data_sources, dispatch_stats, get_interval, hostname, on_shutdown,
+    request_flush, set_significant_digits, validate_host, write_queue_length, Admission,
+    BoundsPolicy, BudgetPolicy, CacheEntry, CachedRead, CdTime, CollectdLoggerBuilder, ConfigItem,
+    ConfigValue, ContainerInfo, ContextGuard, DaemonCapabilities, DataSource, DispatchStats,
+    Duration, Escalator, FlushPlan, FromConfigValue, HostNaming, Identifier, IdentifierPattern,
+    InternedIdentifier, IntoDuration, LazyInit, LocalCache, LogLevel, MemoryBudget, MetaValue,
+    Notification, NotificationBuilder, NotificationDeduper, NotificationLevel, OwnedConfigItem,
+    PluginContext, RateState, SanitizePreset, Schedule, SchemaRegistry, SeriesPoints, SeriesWindow,
+    SharedConfig, ShutdownHook, SyslogFormat, TargetReport, Timestamp, TlsConfig, TlsMaterial,
+    Unit, Value, ValueList, ValueListBuilder, ValueReport, ValueType,
 };
-pub use crate::errors::{CacheRateError, ConfigError, ReceiveError, SubmitError};
+#[cfg(unix)]
+pub use crate::control::ControlSocket;
+pub use crate::dedup::DedupWriter;
+pub use crate::dump::{DebugDump, DumpFormat, Rotation};
+pub use crate::errors::{
+    CacheRateError, ConfigError, DurationError, HostError, ParseConfigError, ParseIdentifierError,
+    ParseScheduleError, ReceiveError, RegistrationError, SubmitError, TargetsError, TlsConfigError,
+    UnknownConstant, Unregister,
+};
+pub use crate::filter::{
+    register_match, register_target, Match, Target, TargetAction, ValueListMut,
+};
+pub use crate::interval::ReadEvery;
 pub use crate::plugins::{
-    Plugin, PluginCapabilities, PluginManager, PluginManagerCapabilities, PluginRegistration,
+    register_instance, unregister_read_group, Plugin, PluginCapabilities, PluginManager,
+    PluginManagerCapabilities, PluginRegistration, ReadStatus,
 };
+pub use crate::read_fn::ReadFn;
+pub use crate::roles::{LogOnly, LogPlugin, ReadOnly, ReadPlugin, WriteOnly, WritePlugin};
+pub use crate::sidecar::{Sidecar, SidecarBuilder, SidecarOutput};
+pub use crate::stats::{read_health, ReadHealth, StatsReporter};
+
+#[doc(hidden)]
+pub use log as __log;
 
 #[cfg(doctest)]
 doc_comment::doctest!("../README.md");
@@ -1,8 +1,11 @@
-use crate::api::{ConfigItem, LogLevel, ValueList};
-use crate::errors::NotImplemented;
+use crate::api::{
+    ConfigItem, Duration, IdentifierPattern, LogLevel, Notification, Schedule, ValueList,
+};
+use crate::bindings::plugin_unregister_read_group;
+use crate::errors::{NotImplemented, RegistrationError};
 use bitflags::bitflags;
-use chrono::Duration;
 use std::error;
+use std::ffi::CString;
 use std::panic::{RefUnwindSafe, UnwindSafe};
 
 bitflags! {
@@ -13,6 +16,9 @@ bitflags! {
         const LOG =    0b0000_0010;
         const WRITE =  0b0000_0100;
         const FLUSH =  0b0000_1000;
+        const NOTIFICATION = 0b0001_0000;
+        const MISSING = 0b0010_0000;
+        const RECONFIGURE = 0b0100_0000;
     }
 }
 
@@ -21,6 +27,39 @@ bitflags! {
     #[derive(Default)]
     pub struct PluginManagerCapabilities: u32 {
         const INIT = 0b0000_0001;
+
+        /// The plugin manager takes no configuration. No config callback is registered with
+        /// collectd, so a config section for the plugin is an error, and the plugins are
+        /// registered without configuration on initialization.
+        const NO_CONFIG = 0b0000_0010;
+    }
+}
+
+/// The outcome of a read, which determines whether collectd backs off from reading the plugin.
+/// See `Plugin::read_status`.
+#[derive(Debug)]
+pub enum ReadStatus {
+    /// Values were read
+    Ok,
+
+    /// Nothing was due to be read (eg: the source has no new data). Not an error.
+    Skip,
+
+    /// The read failed, but the next one is expected to succeed (eg: a network blip). The error
+    /// is logged and counted, but collectd keeps reading at the interval.
+    TransientError(Box<dyn error::Error>),
+
+    /// The read failed and retrying at the interval is not expected to help (eg: a
+    /// misconfiguration), so collectd backs off exponentially as it does for any failed read.
+    PermanentError(Box<dyn error::Error>),
+}
+
+impl From<Result<(), Box<dyn error::Error>>> for ReadStatus {
+    fn from(res: Result<(), Box<dyn error::Error>>) -> Self {
+        match res {
+            Ok(()) => ReadStatus::Ok,
+            Err(e) => ReadStatus::PermanentError(e),
+        }
     }
 }
 
@@ -31,6 +70,12 @@ pub enum PluginRegistration {
 
     /// Our module registers several modules. The String in the tuple must be unique identifier
     Multiple(Vec<(String, Box<dyn Plugin>)>),
+
+    /// Our module registers nothing, but collectd should keep running (eg: the hardware that the
+    /// plugin monitors isn't present on this host, or the module only validates configuration,
+    /// spawns helper threads, or registers other hooks). No dummy `Plugin` is needed. The reason
+    /// is logged.
+    Empty(String),
 }
 
 impl PluginCapabilities {
@@ -49,6 +94,18 @@ impl PluginCapabilities {
     pub fn has_flush(self) -> bool {
         self.intersects(PluginCapabilities::FLUSH)
     }
+
+    pub fn has_notification(self) -> bool {
+        self.intersects(PluginCapabilities::NOTIFICATION)
+    }
+
+    pub fn has_missing(self) -> bool {
+        self.intersects(PluginCapabilities::MISSING)
+    }
+
+    pub fn has_reconfigure(self) -> bool {
+        self.intersects(PluginCapabilities::RECONFIGURE)
+    }
 }
 
 /// Defines the entry point for a collectd plugin. Based on collectd's configuration, a
@@ -57,31 +114,110 @@ pub trait PluginManager {
     /// Name of the plugin. Must not contain null characters or panic.
     fn name() -> &'static str;
 
-    /// Defines the capabilities of the plugin manager. Must not panic.
+    /// Additional config section names that the plugin manager accepts (eg: the name of a plugin
+    /// before it was renamed). The sections of the name and all aliases are merged into one config
+    /// that is given to `plugins` once collectd has read all of its configuration. Must not
+    /// contain null characters or panic.
+    fn aliases() -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Number of consecutive panics after which a plugin instance is quarantined: its read
+    /// callback is unregistered, its other callbacks are no longer invoked, and a failure
+    /// notification is dispatched. Sibling instances keep running. By default instances are never
+    /// quarantined. Must not panic.
+    fn panic_threshold() -> Option<usize> {
+        None
+    }
+
+    /// The group that the read callbacks of the plugin instances are registered under, which lets
+    /// collectd address all of the instances at once. Defaults to the plugin manager's name. Must
+    /// not contain null characters or panic.
+    fn read_group() -> Option<&'static str> {
+        Some(Self::name())
+    }
+
+    /// Spreads the instances that read at the default interval across this many read callbacks
+    /// (named `<manager>/partition-<n>`), each of which reads its instances one after another.
+    /// Managers that register hundreds of instances can then make use of collectd's
+    /// `ReadThreads` without registering a read callback per instance. By default, each instance
+    /// has its own read callback. Must not panic.
+    fn read_partitions() -> Option<usize> {
+        None
+    }
+
+    /// Defines the capabilities of the plugin manager. Managers that take no configuration can
+    /// add `NO_CONFIG`. Must not panic.
     fn capabilities() -> PluginManagerCapabilities {
         PluginManagerCapabilities::INIT
     }
 
     /// Returns one or many instances of a plugin that is configured from collectd's configuration
     /// file. If parameter is `None`, a configuration section for the plugin was not found, so
-    /// default values should be used.
+    /// default values should be used. An error is reported to collectd as a failure to load the
+    /// plugin (which can keep collectd from starting), so return `PluginRegistration::Empty` when
+    /// the plugin merely has nothing to do on this host.
     fn plugins(
         _config: Option<&[ConfigItem<'_>]>,
     ) -> Result<PluginRegistration, Box<dyn error::Error>>;
 
+    /// Called when collectd loads the plugin, before any config is read, so that filter chain
+    /// matches and targets can be registered with `register_match` and `register_target`
+    fn register_filters() {}
+
     /// Initialize any socket, files, event loops, or any other resources that will be shared
     /// between multiple plugin instances.
-    fn initialize() -> Result<(), Box<dyn error::Error>>;
+    fn initialize() -> Result<(), Box<dyn error::Error>> {
+        Ok(())
+    }
+
+    /// Like `initialize`, but receives the same config that was given to `plugins`, so that the
+    /// shared resources can be sized from it (eg: the number of threads in a pool). Invoked
+    /// instead of `initialize`, which it calls by default.
+    fn initialize_with_config(
+        _config: Option<&[ConfigItem<'_>]>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        Self::initialize()
+    }
 
     /// Cleanup any resources or glodal data, allocated during initialize()
-    fn shutdown() -> Result<(), Box<dyn error::Error>>;
+    /// The plugin instances have been dropped by the time this is called, so their `Drop`
+    /// implementations can rely on the shared resources still being open.
+    fn shutdown() -> Result<(), Box<dyn error::Error>> {
+        Ok(())
+    }
 }
 
 /// An individual plugin that is capable of reporting values to collectd, receiving values from
 /// other plugins, or logging messages. A plugin must implement `Sync + Send` as collectd could be sending
 /// values to be written or logged concurrently. The Rust compiler will ensure that everything
 /// not thread safe is wrapped in a Mutex (or another compatible datastructure)
+///
+/// A registered plugin is owned by the crate and shared by the callbacks it is registered for: the
+/// user data of each callback holds a reference that collectd releases (through the user data's
+/// `free_func`) when the callback is unregistered, be it by quarantine, `Unregister`, a failed
+/// registration, or shutdown. The plugin is dropped once the last of its callbacks is released,
+/// which at the latest happens when its plugin manager shuts down. A plugin can't borrow
+/// non-static data (`Box<dyn Plugin>` is `'static`), so anything it shares with other plugins
+/// needs to be reference counted.
 pub trait Plugin: Send + Sync + UnwindSafe + RefUnwindSafe {
+    /// Opens the resources of the instance (eg: its own sockets or files). Invoked once all the
+    /// instances of the plugin manager have been registered, during collectd's initialization and
+    /// before values are first read. An instance that fails to initialize is quarantined and its
+    /// error is logged, while the other instances are unaffected.
+    fn initialize(&self) -> Result<(), Box<dyn error::Error>> {
+        Ok(())
+    }
+
+    /// Collectd handed the plugin manager another config section after its instances were
+    /// registered (eg: the section is split across files included into collectd's config). The
+    /// children of the new section are forwarded to every instance of the manager, which may have
+    /// yet to be initialized. Only invoked for plugins with the `RECONFIGURE` capability. If no
+    /// instance has the capability, the section is rejected as a duplicate.
+    fn reconfigure(&self, _config: &[ConfigItem<'_>]) -> Result<(), Box<dyn error::Error>> {
+        Err(NotImplemented)?
+    }
+
     /// A plugin's capabilities. By default a plugin does nothing, but can advertise that it can
     /// configure itself and / or report values.
     fn capabilities(&self) -> PluginCapabilities {
@@ -98,11 +234,50 @@ pub trait Plugin: Send + Sync + UnwindSafe + RefUnwindSafe {
     /// at the `Interval` defined in the global config (but can be overridden). Implementations
     /// that expect to report values need to have at least have a capability of `READ`. An error in
     /// reporting values will cause collectd to backoff exponentially until a delay of a day is
-    /// reached.
+    /// reached (implement `read_status` for errors that shouldn't back off). An instance that can
+    /// never report values again can return `Unregister` to unregister itself from collectd
+    /// instead.
     fn read_values(&self) -> Result<(), Box<dyn error::Error>> {
         Err(NotImplemented)?
     }
 
+    /// An alternative to `read_values` for plugins that need to tell collectd whether a failed
+    /// read is transient, so that a network blip doesn't cause the same backoff as a fatal
+    /// misconfiguration. By default, the result of `read_values` is used where an error is
+    /// permanent. Implementing this still requires advertising `PluginCapabilities::READ`.
+    fn read_status(&self) -> ReadStatus {
+        ReadStatus::from(self.read_values())
+    }
+
+    /// The interval at which collectd reads values from the plugin. Defaults to the interval of the
+    /// plugin's `LoadPlugin` block or else the global `Interval`. Retrieved once when the plugin is
+    /// registered and ignored when the plugin has a `schedule`.
+    fn read_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// The group that the instance's read callback is registered under, in place of the plugin
+    /// manager's `read_group`. Instances of a group can be unregistered together with
+    /// `unregister_read_group`. Retrieved once when the plugin is registered. An instance with its
+    /// own group is not part of the plugin manager's read partitions.
+    fn read_group(&self) -> Option<&str> {
+        None
+    }
+
+    /// Invoked before values are read when the effective interval of the plugin differs from that
+    /// of the previous read (eg: the `Interval` was changed and collectd reloaded), so that rate
+    /// calculations and anything else derived from the interval can adjust instead of reporting
+    /// a garbage sample. Not invoked for the first read.
+    fn interval_changed(&self, _interval: Duration) {}
+
+    /// A cron-like schedule for when values are read, for reads that need to happen at specific
+    /// times (eg: hourly at five minutes past the hour) rather than at a fixed interval. The
+    /// schedule is retrieved once when the plugin is registered. By default, there is no
+    /// schedule and values are read at the interval.
+    fn schedule(&self) -> Option<Schedule> {
+        None
+    }
+
     /// Collectd is giving you reported values, do with them as you please. If writing values is
     /// expensive, prefer to buffer them in some way and register a `flush` callback to write.
     fn write_values(&self, _list: ValueList<'_>) -> Result<(), Box<dyn error::Error>> {
@@ -110,26 +285,194 @@ pub trait Plugin: Send + Sync + UnwindSafe + RefUnwindSafe {
     }
 
     /// Flush values to be written that are older than given duration. If an identifier is given,
-    /// then only those buffered values should be flushed.
+    /// then only those buffered values should be flushed, where empty components of the identifier
+    /// match any value. A timeout of `None` means that all buffered values are to be flushed (eg:
+    /// collectd is shutting down). `FlushPlan` can be used to select the buffered values that are
+    /// to be flushed. By default, the identifier is parsed and handed to `flush_matching`.
     fn flush(
+        &self,
+        timeout: Option<Duration>,
+        identifier: Option<&str>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        match identifier.map(str::parse::<IdentifierPattern>) {
+            None => self.flush_matching(timeout, None),
+            Some(Ok(pattern)) => self.flush_matching(timeout, Some(&pattern)),
+
+            // Flushing more than was requested is always safe. The error is only reported once
+            // flush_matching is known to be implemented, so that a plugin that implements
+            // neither method fails with NotImplemented whatever the identifier.
+            Some(Err(e)) => {
+                self.flush_matching(timeout, None)?;
+                Err(Box::new(e))
+            }
+        }
+    }
+
+    /// An alternative to `flush` that receives the identifier already parsed, so that buffered
+    /// values can be selected with `IdentifierPattern::matches` (a flush of `/cpu-/` matches all
+    /// the values of the cpu plugin). An identifier that can't be parsed flushes all buffered
+    /// values, and then fails the flush. Implementing this still requires advertising
+    /// `PluginCapabilities::FLUSH`.
+    fn flush_matching(
         &self,
         _timeout: Option<Duration>,
-        _identifier: Option<&str>,
+        _identifier: Option<&IdentifierPattern>,
     ) -> Result<(), Box<dyn error::Error>> {
         Err(NotImplemented)?
     }
+
+    /// Collectd is delivering a notification (eg: a threshold was crossed) raised by this or
+    /// another plugin. Only invoked for plugins with the `NOTIFICATION` capability.
+    fn notification(&self, _notification: Notification<'_>) -> Result<(), Box<dyn error::Error>> {
+        Err(NotImplemented)?
+    }
+
+    /// Collectd hasn't received values for the identifier of the value list within the timeout
+    /// (`Timeout` multiplied by the interval), so the series has stopped arriving (eg: a write
+    /// plugin can mark the series as stale downstream). The value list contains no values. Only
+    /// invoked for plugins with the `MISSING` capability.
+    fn missing(&self, _list: ValueList<'_>) -> Result<(), Box<dyn error::Error>> {
+        Err(NotImplemented)?
+    }
+}
+
+/// Registers an instance of the plugin manager after collectd has been initialized, so that a
+/// plugin can start monitoring entities that are discovered at runtime (eg: a hot-plugged disk or
+/// a newly started container). It can be called from `Plugin::initialize` or while values are read.
+///
+/// Like instances of a `PluginRegistration::Multiple`, the instance is named `<manager>/<id>`,
+/// and an error is returned if the name is invalid or already registered. The instance is given
+/// its own read callback (read partitions only apply to the instances registered at config time)
+/// and is initialized before it is first read.
+pub fn register_instance<T: PluginManager>(
+    id: &str,
+    plugin: Box<dyn Plugin>,
+) -> Result<(), RegistrationError> {
+    crate::internal::register_instance::<T>(id, plugin)
+}
+
+/// Unregisters the read callbacks of every instance in the group (eg: the instances of a plugin
+/// manager, or those given the group with `Plugin::read_group`). The instances stay registered
+/// otherwise. Like any read callback, a group can be unregistered from within its own reads.
+/// Returns true if collectd knew of the group.
+pub fn unregister_read_group(group: &str) -> bool {
+    match CString::new(group) {
+        Ok(s) => unsafe { plugin_unregister_read_group(s.as_ptr()) == 0 },
+        Err(_) => false,
+    }
+}
+
+/// Implements `Plugin` with capabilities derived from the methods that are implemented, so that a
+/// plugin can't advertise `READ` while leaving `read_values` unimplemented, or implement
+/// `write_values` but forget to advertise `WRITE`. The methods map to capabilities as:
+/// `read_values` and `read_status` to `READ`, `write_values` to `WRITE`, `log` to `LOG`, `flush`
+/// and `flush_matching` to `FLUSH`, `notification` to `NOTIFICATION`, and `missing` to `MISSING`.
+/// Implementing `capabilities` as well fails to compile.
+///
+/// ```
+/// use collectd_plugin::{impl_plugin, Plugin, PluginCapabilities, ValueList};
+/// use std::error;
+///
+/// struct MyPlugin;
+///
+/// impl_plugin! {
+///     impl Plugin for MyPlugin {
+///         fn read_values(&self) -> Result<(), Box<dyn error::Error>> {
+///             Ok(())
+///         }
+///
+///         fn write_values(&self, _list: ValueList<'_>) -> Result<(), Box<dyn error::Error>> {
+///             Ok(())
+///         }
+///     }
+/// }
+///
+/// let expected = PluginCapabilities::READ | PluginCapabilities::WRITE;
+/// assert_eq!(expected, MyPlugin.capabilities());
+/// ```
+#[macro_export]
+macro_rules! impl_plugin {
+    (impl Plugin for $type:ty { $($body:tt)* }) => {
+        impl $crate::Plugin for $type {
+            $($body)*
+
+            fn capabilities(&self) -> $crate::PluginCapabilities {
+                $crate::__plugin_capabilities!($($body)*)
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __plugin_capabilities {
+    () => {
+        $crate::PluginCapabilities::empty()
+    };
+    (# $attr:tt $($rest:tt)*) => {
+        $crate::__plugin_capabilities!($($rest)*)
+    };
+    (fn read_values $($rest:tt)*) => {
+        $crate::PluginCapabilities::READ | $crate::__plugin_capabilities!(@skip $($rest)*)
+    };
+    (fn read_status $($rest:tt)*) => {
+        $crate::PluginCapabilities::READ | $crate::__plugin_capabilities!(@skip $($rest)*)
+    };
+    (fn write_values $($rest:tt)*) => {
+        $crate::PluginCapabilities::WRITE | $crate::__plugin_capabilities!(@skip $($rest)*)
+    };
+    (fn log $($rest:tt)*) => {
+        $crate::PluginCapabilities::LOG | $crate::__plugin_capabilities!(@skip $($rest)*)
+    };
+    (fn flush $($rest:tt)*) => {
+        $crate::PluginCapabilities::FLUSH | $crate::__plugin_capabilities!(@skip $($rest)*)
+    };
+    (fn flush_matching $($rest:tt)*) => {
+        $crate::PluginCapabilities::FLUSH | $crate::__plugin_capabilities!(@skip $($rest)*)
+    };
+    (fn notification $($rest:tt)*) => {
+        $crate::PluginCapabilities::NOTIFICATION | $crate::__plugin_capabilities!(@skip $($rest)*)
+    };
+    (fn missing $($rest:tt)*) => {
+        $crate::PluginCapabilities::MISSING | $crate::__plugin_capabilities!(@skip $($rest)*)
+    };
+    (fn reconfigure $($rest:tt)*) => {
+        $crate::PluginCapabilities::RECONFIGURE | $crate::__plugin_capabilities!(@skip $($rest)*)
+    };
+    (fn $name:ident $($rest:tt)*) => {
+        $crate::__plugin_capabilities!(@skip $($rest)*)
+    };
+
+    // Skips the remainder of a method (its arguments and return type) up to and including its body
+    (@skip { $($body:tt)* } $($rest:tt)*) => {
+        $crate::__plugin_capabilities!($($rest)*)
+    };
+    (@skip $skipped:tt $($rest:tt)*) => {
+        $crate::__plugin_capabilities!(@skip $($rest)*)
+    };
 }
 
 /// Sets up all the ffi entry points that collectd expects when given a `PluginManager`.
 #[macro_export]
 macro_rules! collectd_plugin {
     ($type:ty) => {
-        // Let's us know if we've seen our config section before
-        static CONFIG_SEEN: ::std::sync::atomic::AtomicBool =
-            ::std::sync::atomic::AtomicBool::new(false);
+        $crate::collectd_plugins!($type);
+    };
+}
 
-        // This is the main entry point that collectd looks for. Our plugin manager will register
-        // callbacks for configuration related to our name. It also registers a callback for
+/// Sets up the ffi entry points for several `PluginManager`s, so that a single shared object can
+/// hold logically distinct plugins. Each plugin manager is registered under its own name with its
+/// own config section, initialization, and shutdown, as if each was in its own shared object.
+/// Shutdown hooks are shared by the plugin managers, so they run once the last of them shuts down.
+///
+/// ```ignore
+/// collectd_plugins!(CpuManager, DiskManager);
+/// ```
+#[macro_export]
+macro_rules! collectd_plugins {
+    ($($type:ty),+ $(,)?) => {
+        // This is the main entry point that collectd looks for. Each plugin manager will register
+        // callbacks for configuration related to its name. It also registers a callback for
         // initialization for when configuration is absent or a single plugin wants to hold global
         // data
         #[no_mangle]
@@ -141,30 +484,54 @@ macro_rules! collectd_plugin {
 
             $crate::internal::register_panic_handler();
 
-            let s = CString::new(<$type as $crate::PluginManager>::name())
-                .expect("Plugin name to not contain nulls");
+            $({
+                // Let's us know if we've seen our config section before
+                static CONFIG_SEEN: ::std::sync::atomic::AtomicBool =
+                    ::std::sync::atomic::AtomicBool::new(false);
 
-            unsafe {
-                plugin_register_complex_config(s.as_ptr(), Some(collectd_plugin_complex_config));
+                extern "C" fn collectd_plugin_init() -> ::std::os::raw::c_int {
+                    $crate::internal::plugin_init::<$type>(&CONFIG_SEEN)
+                }
 
-                plugin_register_init(s.as_ptr(), Some(collectd_plugin_init));
+                extern "C" fn collectd_plugin_shutdown() -> ::std::os::raw::c_int {
+                    $crate::internal::plugin_shutdown::<$type>()
+                }
 
-                plugin_register_shutdown(s.as_ptr(), Some(collectd_plugin_shutdown));
-            }
-        }
+                unsafe extern "C" fn collectd_plugin_complex_config(
+                    config: *mut $crate::bindings::oconfig_item_t,
+                ) -> ::std::os::raw::c_int {
+                    $crate::internal::plugin_complex_config::<$type>(&CONFIG_SEEN, config)
+                }
 
-        extern "C" fn collectd_plugin_init() -> ::std::os::raw::c_int {
-            $crate::internal::plugin_init::<$type>(&CONFIG_SEEN)
-        }
+                $crate::internal::register_manager();
+                <$type as $crate::PluginManager>::register_filters();
 
-        extern "C" fn collectd_plugin_shutdown() -> ::std::os::raw::c_int {
-            $crate::internal::plugin_shutdown::<$type>()
-        }
+                let s = CString::new(<$type as $crate::PluginManager>::name())
+                    .expect("Plugin name to not contain nulls");
 
-        unsafe extern "C" fn collectd_plugin_complex_config(
-            config: *mut $crate::bindings::oconfig_item_t,
-        ) -> ::std::os::raw::c_int {
-            $crate::internal::plugin_complex_config::<$type>(&CONFIG_SEEN, config)
+                let capabilities = <$type as $crate::PluginManager>::capabilities();
+                unsafe {
+                    if !capabilities.intersects($crate::PluginManagerCapabilities::NO_CONFIG) {
+                        plugin_register_complex_config(
+                            s.as_ptr(),
+                            Some(collectd_plugin_complex_config),
+                        );
+
+                        for alias in <$type as $crate::PluginManager>::aliases() {
+                            let a =
+                                CString::new(*alias).expect("Plugin alias to not contain nulls");
+                            plugin_register_complex_config(
+                                a.as_ptr(),
+                                Some(collectd_plugin_complex_config),
+                            );
+                        }
+                    }
+
+                    plugin_register_init(s.as_ptr(), Some(collectd_plugin_init));
+
+                    plugin_register_shutdown(s.as_ptr(), Some(collectd_plugin_shutdown));
+                }
+            })+
         }
     };
 }
@@ -183,4 +550,74 @@ mod tests {
         assert_eq!(capabilities.has_read(), true);
         assert_eq!(capabilities.has_write(), false);
     }
+
+    #[test]
+    fn test_impl_plugin_capabilities() {
+        struct Nothing;
+        impl_plugin! {
+            impl Plugin for Nothing {}
+        }
+
+        struct LogFlush;
+        impl_plugin! {
+            impl Plugin for LogFlush {
+                /// Not a capability
+                fn read_interval(&self) -> Option<Duration> {
+                    None
+                }
+
+                fn log(&self, _lvl: LogLevel, _msg: &str) -> Result<(), Box<dyn error::Error>> {
+                    Ok(())
+                }
+
+                #[allow(unused_variables)]
+                fn flush(
+                    &self,
+                    timeout: Option<Duration>,
+                    identifier: Option<&str>,
+                ) -> Result<(), Box<dyn error::Error>> {
+                    if timeout.is_some() {
+                        return Ok(());
+                    }
+                    Ok(())
+                }
+            }
+        }
+
+        struct Matching;
+        impl_plugin! {
+            impl Plugin for Matching {
+                fn flush_matching(
+                    &self,
+                    _timeout: Option<Duration>,
+                    identifier: Option<&IdentifierPattern>,
+                ) -> Result<(), Box<dyn error::Error>> {
+                    assert!(identifier.map(|x| x.is_wildcard()).unwrap_or(true));
+                    Ok(())
+                }
+            }
+        }
+
+        assert_eq!(PluginCapabilities::empty(), Nothing.capabilities());
+        assert_eq!(PluginCapabilities::FLUSH, Matching.capabilities());
+        assert!(Matching.flush(None, None).is_ok());
+        assert!(Matching.flush(None, Some("//")).is_ok());
+        assert!(Matching.flush(None, Some("cpu")).is_err());
+
+        // A plugin that implements neither flush method doesn't flush, whatever the identifier
+        for identifier in [None, Some("//"), Some("cpu")].iter() {
+            let err = Nothing.flush(None, *identifier).unwrap_err();
+            assert!(err.downcast_ref::<NotImplemented>().is_some());
+        }
+        assert_eq!(
+            PluginCapabilities::LOG | PluginCapabilities::FLUSH,
+            LogFlush.capabilities()
+        );
+    }
+
+    #[test]
+    fn test_unregister_read_group() {
+        assert!(unregister_read_group("myplugin"));
+        assert!(!unregister_read_group("my\0plugin"));
+    }
 }
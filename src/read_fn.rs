@@ -0,0 +1,120 @@
+use crate::api::{CdTime, Duration, IntoDuration};
+use crate::errors::DurationError;
+use crate::plugins::{Plugin, PluginCapabilities, PluginRegistration};
+use std::error;
+use std::panic::{RefUnwindSafe, UnwindSafe};
+
+/// A plugin that only reads values, with the reading done by a closure. Tiny plugins that submit a
+/// value or two can skip declaring a struct, implementing `Plugin`, and advertising capabilities.
+///
+/// ```
+/// use collectd_plugin::{PluginRegistration, ReadFn, Value, ValueListBuilder};
+/// use std::error;
+///
+/// fn registration() -> Result<PluginRegistration, Box<dyn error::Error>> {
+///     let read = ReadFn::every("30s", || {
+///         ValueListBuilder::new("myplugin", "gauge")
+///             .values(&[Value::Gauge(42.0)])
+///             .submit()?;
+///         Ok(())
+///     })?;
+///
+///     Ok(PluginRegistration::Single(Box::new(read)))
+/// }
+/// ```
+pub struct ReadFn<F> {
+    read: F,
+    interval: Option<Duration>,
+}
+
+impl<F> ReadFn<F>
+where
+    F: Fn() -> Result<(), Box<dyn error::Error>> + Send + Sync + UnwindSafe + RefUnwindSafe,
+{
+    /// Reads values with the closure at the interval of the `LoadPlugin` block or the global
+    /// `Interval`
+    pub fn new(read: F) -> Self {
+        ReadFn {
+            read,
+            interval: None,
+        }
+    }
+
+    /// Reads values with the closure at the given interval
+    pub fn every<T: IntoDuration>(interval: T, read: F) -> Result<Self, DurationError> {
+        Ok(ReadFn {
+            read,
+            interval: Some(CdTime::from(interval.into_duration()?).into()),
+        })
+    }
+}
+
+impl<F> Plugin for ReadFn<F>
+where
+    F: Fn() -> Result<(), Box<dyn error::Error>> + Send + Sync + UnwindSafe + RefUnwindSafe,
+{
+    fn capabilities(&self) -> PluginCapabilities {
+        PluginCapabilities::READ
+    }
+
+    fn read_values(&self) -> Result<(), Box<dyn error::Error>> {
+        (self.read)()
+    }
+
+    fn read_interval(&self) -> Option<Duration> {
+        self.interval
+    }
+}
+
+impl PluginRegistration {
+    /// Registers a single plugin that reads values with the closure, see `ReadFn`
+    pub fn read_fn<F>(read: F) -> PluginRegistration
+    where
+        F: Fn() -> Result<(), Box<dyn error::Error>>
+            + Send
+            + Sync
+            + UnwindSafe
+            + RefUnwindSafe
+            + 'static,
+    {
+        PluginRegistration::Single(Box::new(ReadFn::new(read)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_read_fn() {
+        let reads = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&reads);
+        let plugin = ReadFn::every("5s", move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(PluginCapabilities::READ, plugin.capabilities());
+        assert!(plugin.read_values().is_ok());
+        assert!(plugin.read_values().is_ok());
+        assert_eq!(2, reads.load(Ordering::Relaxed));
+
+        let five = CdTime::from(std::time::Duration::from_secs(5));
+        assert_eq!(Some(five), plugin.read_interval().map(CdTime::from));
+        assert!(ReadFn::every("soon", || Ok(())).is_err());
+    }
+
+    #[test]
+    fn test_registration_read_fn() {
+        match PluginRegistration::read_fn(|| Ok(())) {
+            PluginRegistration::Single(plugin) => {
+                assert_eq!(PluginCapabilities::READ, plugin.capabilities());
+                assert_eq!(None, plugin.read_interval());
+            }
+            _ => panic!("expected a single plugin"),
+        }
+    }
+}
@@ -0,0 +1,664 @@
+//! Bookkeeping of the plugin instances that have been registered with collectd. Collectd owns the
+//! user data that is handed to each callback, but subsystems outside of the callbacks (like the
+//! control socket) need to be able to enumerate and reach the instances too.
+use crate::api::{
+    collectd_log, dispatch_notification, log_err, CdTime, LogLevel, NotificationLevel, Schedule,
+};
+use crate::errors::{FfiError, Unregister};
+use crate::internal;
+use crate::plugins::{Plugin, PluginCapabilities};
+use lazy_static::lazy_static;
+use std::convert::TryFrom;
+use std::error;
+use std::fmt::{self, Write};
+use std::panic::catch_unwind;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+lazy_static! {
+    static ref INSTANCES: RwLock<Vec<Arc<PluginInstance>>> = RwLock::new(Vec::new());
+}
+
+/// Running tally of the callbacks that collectd has invoked on an instance
+#[derive(Debug, Default)]
+pub struct InstanceStats {
+    pub reads: AtomicUsize,
+    pub read_errors: AtomicUsize,
+    pub writes: AtomicUsize,
+    pub write_errors: AtomicUsize,
+    pub logs: AtomicUsize,
+    pub log_errors: AtomicUsize,
+    pub flushes: AtomicUsize,
+    pub flush_errors: AtomicUsize,
+    pub notifications: AtomicUsize,
+    pub notification_errors: AtomicUsize,
+    pub missing: AtomicUsize,
+    pub missing_errors: AtomicUsize,
+    pub panics: AtomicUsize,
+
+    /// Number of reads in a row that failed. Reset by a successful read
+    pub consecutive_read_failures: AtomicUsize,
+
+    /// Total, most recent, and longest time spent reading in nanoseconds
+    pub read_nanos: AtomicU64,
+    pub last_read_nanos: AtomicU64,
+    pub max_read_nanos: AtomicU64,
+
+    /// When the most recent successful read finished as epoch nanoseconds. Zero until then.
+    pub last_read_success: AtomicU64,
+}
+
+impl InstanceStats {
+    /// Increments the given counter
+    pub fn incr(counter: &AtomicUsize) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Tracks the latency and outcome of a read
+    pub fn record_read(&self, elapsed: Duration, success: bool, now: SystemTime) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.read_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.last_read_nanos.store(nanos, Ordering::Relaxed);
+        self.max_read_nanos.fetch_max(nanos, Ordering::Relaxed);
+
+        if success {
+            let CdTime(now) = CdTime::from(now);
+            self.consecutive_read_failures.store(0, Ordering::Relaxed);
+            self.last_read_success.store(now, Ordering::Relaxed);
+        } else {
+            InstanceStats::incr(&self.consecutive_read_failures);
+        }
+    }
+}
+
+impl fmt::Display for InstanceStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "reads={} read_errors={} writes={} write_errors={} logs={} log_errors={} \
+             flushes={} flush_errors={} notifications={} notification_errors={} missing={} missing_errors={} panics={}",
+            self.reads.load(Ordering::Relaxed),
+            self.read_errors.load(Ordering::Relaxed),
+            self.writes.load(Ordering::Relaxed),
+            self.write_errors.load(Ordering::Relaxed),
+            self.logs.load(Ordering::Relaxed),
+            self.log_errors.load(Ordering::Relaxed),
+            self.flushes.load(Ordering::Relaxed),
+            self.flush_errors.load(Ordering::Relaxed),
+            self.notifications.load(Ordering::Relaxed),
+            self.notification_errors.load(Ordering::Relaxed),
+            self.missing.load(Ordering::Relaxed),
+            self.missing_errors.load(Ordering::Relaxed),
+            self.panics.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// A registered plugin and the metadata that accompanies it
+pub struct PluginInstance {
+    /// Name of the plugin manager that registered this instance
+    pub manager: &'static str,
+
+    /// The name that the instance was registered with collectd (eg: `myplugin/instance`)
+    pub name: String,
+
+    pub capabilities: PluginCapabilities,
+    pub plugin: Box<dyn Plugin>,
+    pub stats: InstanceStats,
+
+    /// Number of panics in a row. Reset when a callback completes without panicking
+    pub consecutive_panics: AtomicUsize,
+
+    /// Number of consecutive panics after which the instance is quarantined
+    pub panic_threshold: Option<usize>,
+
+    /// A quarantined instance no longer has its callbacks invoked
+    pub quarantined: AtomicBool,
+
+    /// A disabled instance stays registered with collectd, but its callbacks return immediately
+    /// until it is enabled again (eg: while its target is under maintenance)
+    disabled: AtomicBool,
+
+    /// Set once the plugin is initialized, as instances registered while collectd initializes
+    /// would otherwise be initialized again along with the rest
+    initialized: AtomicBool,
+
+    /// The most recent error returned from one of the instance's callbacks
+    pub last_error: Mutex<Option<String>>,
+
+    /// When values are to be read if the plugin reads on a schedule instead of an interval
+    pub schedule: Option<Schedule>,
+
+    /// The next time that the schedule is due
+    next_read: Mutex<Option<SystemTime>>,
+
+    /// The interval (in collectd's time format) seen by the most recent read. Zero until the
+    /// first read.
+    interval: AtomicU64,
+}
+
+impl PluginInstance {
+    pub fn new(manager: &'static str, name: &str, plugin: Box<dyn Plugin>) -> Self {
+        let schedule = plugin.schedule();
+        let next_read = schedule
+            .as_ref()
+            .and_then(|x| x.next_after(SystemTime::now()));
+
+        PluginInstance {
+            manager,
+            name: String::from(name),
+            capabilities: plugin.capabilities(),
+            schedule,
+            next_read: Mutex::new(next_read),
+            plugin,
+            stats: InstanceStats::default(),
+            consecutive_panics: AtomicUsize::new(0),
+            panic_threshold: None,
+            quarantined: AtomicBool::new(false),
+            disabled: AtomicBool::new(false),
+            initialized: AtomicBool::new(false),
+            last_error: Mutex::new(None),
+            interval: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_quarantined(&self) -> bool {
+        self.quarantined.load(Ordering::Relaxed)
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled.load(Ordering::Relaxed)
+    }
+
+    /// Disables or re-enables the instance. Returns true if the state of the instance changed.
+    pub fn set_disabled(&self, disabled: bool) -> bool {
+        self.disabled.swap(disabled, Ordering::SeqCst) != disabled
+    }
+
+    /// Returns true if the instance's callbacks are to call into the plugin
+    pub fn is_active(&self) -> bool {
+        !self.is_quarantined() && !self.is_disabled()
+    }
+
+    /// Returns true if values should be read at the given time. Instances without a schedule are
+    /// always due, as collectd invokes them at their interval. A scheduled read that was missed
+    /// (eg: collectd was busy) is performed late rather than skipped.
+    pub fn read_due(&self, now: SystemTime) -> bool {
+        let schedule = match self.schedule {
+            Some(ref schedule) => schedule,
+            None => return true,
+        };
+
+        let mut next_read = self.next_read.lock().unwrap_or_else(|e| e.into_inner());
+        match *next_read {
+            Some(next) if next <= now => {
+                *next_read = schedule.next_after(now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Records the effective interval of a read and returns the interval if it differs from that of
+    /// the previous read. The first read establishes the interval and isn't considered a change.
+    pub fn observe_interval(&self, interval: CdTime) -> Option<CdTime> {
+        let previous = self.interval.swap(interval.0, Ordering::Relaxed);
+        if previous != 0 && previous != interval.0 {
+            Some(interval)
+        } else {
+            None
+        }
+    }
+
+    /// Records the panic and returns true if it is the panic that pushes the instance over its
+    /// threshold, in which case the instance is now quarantined
+    pub fn record_panic(&self) -> bool {
+        InstanceStats::incr(&self.stats.panics);
+        let panics = self.consecutive_panics.fetch_add(1, Ordering::Relaxed) + 1;
+        match self.panic_threshold {
+            Some(threshold) if panics >= threshold => {
+                !self.quarantined.swap(true, Ordering::SeqCst)
+            }
+            _ => false,
+        }
+    }
+
+    /// Records that a callback completed without panicking
+    pub fn record_no_panic(&self) {
+        self.consecutive_panics.store(0, Ordering::Relaxed);
+    }
+
+    /// Bumps the error counter for a failed callback and keeps track of panics so that an
+    /// instance that keeps on panicking is quarantined. An instance that fails with `Unregister`
+    /// is taken out of service immediately.
+    pub fn record_result<T>(&self, counter: &AtomicUsize, res: &Result<T, FfiError<'_>>) {
+        match *res {
+            Err(ref e) => {
+                InstanceStats::incr(counter);
+                *self.last_error.lock().unwrap_or_else(|e| e.into_inner()) = Some(error_message(e));
+                if let FfiError::Panic = *e {
+                    if self.record_panic() {
+                        self.quarantine();
+                    }
+                } else {
+                    self.record_no_panic();
+                }
+
+                if let FfiError::Plugin(ref err) = *e {
+                    if let Some(reason) = err.downcast_ref::<Unregister>() {
+                        self.unregister(reason);
+                    }
+                }
+            }
+            Ok(_) => self.record_no_panic(),
+        }
+    }
+
+    /// Stops the instance from being invoked after it has panicked too many times in a row.
+    /// Sibling instances are unaffected.
+    ///
+    /// The instance's callbacks are unregistered from collectd. Until they are, they return
+    /// immediately without calling into the plugin.
+    fn quarantine(&self) {
+        let msg = format!(
+            "{}: quarantined after panicking {} times in a row",
+            self.name,
+            self.consecutive_panics.load(Ordering::Relaxed)
+        );
+        self.take_out_of_service(&msg);
+    }
+
+    /// Takes the instance out of service at its own request, see `Unregister`
+    fn unregister(&self, reason: &Unregister) {
+        if !self.quarantined.swap(true, Ordering::SeqCst) {
+            let msg = format!("{}: unregistered after a {}", self.name, reason);
+            self.take_out_of_service(&msg);
+        }
+    }
+
+    /// Calls the instance's `Plugin::initialize`. An instance that fails to initialize is
+    /// quarantined so that it is never read from or written to, while sibling instances are
+    /// unaffected. The plugin is only initialized once, later calls report the outcome of the
+    /// first.
+    pub fn initialize(&self) -> bool {
+        if self.initialized.swap(true, Ordering::SeqCst) {
+            return !self.is_quarantined();
+        }
+
+        let res = catch_unwind(|| self.plugin.initialize())
+            .map_err(|_| FfiError::Panic)
+            .and_then(|x| x.map_err(FfiError::Plugin));
+
+        match res {
+            Ok(()) => true,
+            Err(ref e) => {
+                log_err("initialize", e);
+                *self.last_error.lock().unwrap_or_else(|e| e.into_inner()) = Some(error_message(e));
+                if !self.quarantined.swap(true, Ordering::SeqCst) {
+                    self.take_out_of_service(&format!(
+                        "{}: quarantined after failing to initialize",
+                        self.name
+                    ));
+                }
+                false
+            }
+        }
+    }
+
+    fn take_out_of_service(&self, msg: &str) {
+        internal::unregister_instance(&self.name, self.capabilities);
+
+        collectd_log(LogLevel::Error, msg);
+
+        // Instances registered as part of `PluginRegistration::Multiple` are named `manager/id`
+        let plugin_instance = self
+            .name
+            .find('/')
+            .map(|ind| &self.name[ind + 1..])
+            .filter(|x| !x.is_empty());
+
+        let res = dispatch_notification(
+            NotificationLevel::Failure,
+            self.manager,
+            plugin_instance,
+            msg,
+        );
+
+        if let Err(e) = res {
+            log_err("quarantine notification", &FfiError::Collectd(Box::new(e)));
+        }
+    }
+
+    /// A single line summary of the instance's state
+    pub fn diagnostics(&self) -> String {
+        let mut msg = format!(
+            "{} (capabilities: {:?}, quarantined: {}, disabled: {}, {}",
+            self.name,
+            self.capabilities,
+            self.is_quarantined(),
+            self.is_disabled(),
+            self.stats
+        );
+
+        let last_error = self.last_error.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(ref e) = *last_error {
+            let _ = write!(msg, ", last error: {}", e);
+        }
+
+        msg.push(')');
+        msg
+    }
+}
+
+/// The last error to remember for an instance. For the errors that a plugin returned, that is the
+/// error itself along with its sources rather than the fact that the plugin failed.
+fn error_message(err: &FfiError<'_>) -> String {
+    let err: &dyn error::Error = match *err {
+        FfiError::Plugin(ref e) => e.as_ref(),
+        _ => return err.to_string(),
+    };
+
+    let mut msg = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        let _ = write!(msg, "; {}", cause);
+        source = cause.source();
+    }
+
+    msg
+}
+
+/// Describes the internal state of the crate: the registered instances with their callbacks,
+/// counters, and the last error each encountered. Plugins that want to expose the state of their
+/// own buffers can do so from their flush callback.
+pub fn diagnostics(instances: &[Arc<PluginInstance>]) -> Vec<String> {
+    let mut lines = vec![format!(
+        "collectd-plugin {} diagnostics: {} plugin instance(s), log level: {}",
+        env!("CARGO_PKG_VERSION"),
+        instances.len(),
+        log::max_level()
+    )];
+
+    lines.extend(instances.iter().map(|x| x.diagnostics()));
+    lines
+}
+
+/// Summarizes the instances of a plugin manager that is shutting down as a single line, so that
+/// operators can tell from collectd's log whether values were lost. A write error is a value list
+/// that the plugin failed to write and a flush error is a buffer that the plugin failed to flush,
+/// so the summary is logged as a warning if either occurred or an instance was quarantined.
+pub fn shutdown_report(manager: &str, instances: &[Arc<PluginInstance>]) -> (LogLevel, String) {
+    let sum = |f: fn(&InstanceStats) -> &AtomicUsize| -> usize {
+        instances
+            .iter()
+            .map(|x| f(&x.stats).load(Ordering::Relaxed))
+            .sum()
+    };
+
+    let quarantined = instances.iter().filter(|x| x.is_quarantined()).count();
+    let write_errors = sum(|x| &x.write_errors);
+    let flush_errors = sum(|x| &x.flush_errors);
+
+    let msg = format!(
+        "{}: shutdown: instances={} quarantined={} reads={} read_errors={} writes={} \
+         write_errors={} flushes={} flush_errors={} panics={}",
+        manager,
+        instances.len(),
+        quarantined,
+        sum(|x| &x.reads),
+        sum(|x| &x.read_errors),
+        sum(|x| &x.writes),
+        write_errors,
+        sum(|x| &x.flushes),
+        flush_errors,
+        sum(|x| &x.panics),
+    );
+
+    let lvl = if write_errors > 0 || flush_errors > 0 || quarantined > 0 {
+        LogLevel::Warning
+    } else {
+        LogLevel::Info
+    };
+
+    (lvl, msg)
+}
+
+/// Adds the instance to the registry
+pub fn insert(instance: Arc<PluginInstance>) {
+    let mut instances = INSTANCES.write().unwrap_or_else(|e| e.into_inner());
+    instances.push(instance);
+}
+
+/// Returns a snapshot of all the registered instances
+pub fn instances() -> Vec<Arc<PluginInstance>> {
+    let instances = INSTANCES.read().unwrap_or_else(|e| e.into_inner());
+    instances.clone()
+}
+
+/// Removes the instance with the name
+pub fn remove(name: &str) {
+    let mut instances = INSTANCES.write().unwrap_or_else(|e| e.into_inner());
+    instances.retain(|x| x.name != name);
+}
+
+/// Removes all instances that belong to the plugin manager and returns them, so that the caller
+/// decides when they are dropped (and not while the registry is locked)
+pub fn remove_manager(manager: &str) -> Vec<Arc<PluginInstance>> {
+    let mut instances = INSTANCES.write().unwrap_or_else(|e| e.into_inner());
+    let (removed, kept) = instances.drain(..).partition(|x| x.manager == manager);
+    *instances = kept;
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error;
+    use std::io;
+
+    struct NoopPlugin;
+    impl Plugin for NoopPlugin {}
+
+    #[test]
+    fn test_record_panic_quarantines_at_threshold() {
+        let mut instance = PluginInstance::new("myplugin", "myplugin/a", Box::new(NoopPlugin));
+        instance.panic_threshold = Some(2);
+
+        assert!(!instance.record_panic());
+        instance.record_no_panic();
+        assert!(!instance.record_panic());
+        assert!(!instance.is_quarantined());
+        assert!(instance.record_panic());
+        assert!(instance.is_quarantined());
+
+        // Only the panic crossing the threshold triggers the quarantine
+        assert!(!instance.record_panic());
+        assert_eq!(4, instance.stats.panics.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let instance = Arc::new(PluginInstance::new(
+            "myplugin",
+            "myplugin/a",
+            Box::new(NoopPlugin),
+        ));
+        let res: Result<(), FfiError<'_>> = Err(FfiError::MultipleConfig);
+        instance.record_result(&instance.stats.read_errors, &res);
+
+        let lines = diagnostics(&[instance]);
+        assert_eq!(2, lines.len());
+        assert!(lines[0].contains("diagnostics: 1 plugin instance(s)"));
+        assert_eq!(
+            lines[1],
+            "myplugin/a (capabilities: (empty), quarantined: false, disabled: false, reads=0 read_errors=1 \
+             writes=0 write_errors=0 logs=0 log_errors=0 flushes=0 flush_errors=0 \
+             notifications=0 notification_errors=0 missing=0 missing_errors=0 panics=0, \
+             last error: duplicate config section)"
+        );
+    }
+
+    #[test]
+    fn test_initialize_failure_quarantines() {
+        struct FailingPlugin;
+        impl Plugin for FailingPlugin {
+            fn initialize(&self) -> Result<(), Box<dyn error::Error>> {
+                Err(Box::new(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "no socket",
+                )))
+            }
+        }
+
+        let ok = PluginInstance::new("myplugin", "myplugin/a", Box::new(NoopPlugin));
+        assert!(ok.initialize());
+        assert!(!ok.is_quarantined());
+
+        let failing = PluginInstance::new("myplugin", "myplugin/b", Box::new(FailingPlugin));
+        assert!(!failing.initialize());
+        assert!(failing.is_quarantined());
+        assert_eq!(
+            Some(String::from("no socket")),
+            *failing.last_error.lock().unwrap()
+        );
+        assert!(!failing.initialize());
+    }
+
+    #[test]
+    fn test_unregister_error() {
+        let instance = PluginInstance::new("myplugin", "myplugin/a", Box::new(NoopPlugin));
+        let err = Unregister(String::from("device was removed"));
+        let res: Result<(), FfiError<'_>> = Err(FfiError::Plugin(Box::new(err)));
+        instance.record_result(&instance.stats.read_errors, &res);
+
+        assert!(instance.is_quarantined());
+        assert_eq!(1, instance.stats.read_errors.load(Ordering::Relaxed));
+        assert_eq!(
+            Some(String::from("permanent error: device was removed")),
+            *instance.last_error.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_last_error_sources() {
+        #[derive(Debug)]
+        struct ConnectError(io::Error);
+        impl fmt::Display for ConnectError {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "unable to connect to the database")
+            }
+        }
+
+        impl error::Error for ConnectError {
+            fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        let instance = PluginInstance::new("myplugin", "myplugin/a", Box::new(NoopPlugin));
+        let err = ConnectError(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            "connection refused",
+        ));
+        let res: Result<(), FfiError<'_>> = Err(FfiError::Plugin(Box::new(err)));
+        instance.record_result(&instance.stats.read_errors, &res);
+        assert_eq!(
+            Some(String::from(
+                "unable to connect to the database; connection refused"
+            )),
+            *instance.last_error.lock().unwrap()
+        );
+
+        let res: Result<(), FfiError<'_>> = Err(FfiError::Panic);
+        instance.record_result(&instance.stats.read_errors, &res);
+        assert_eq!(
+            Some(String::from("plugin panicked")),
+            *instance.last_error.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_set_disabled() {
+        let instance = PluginInstance::new("myplugin", "myplugin", Box::new(NoopPlugin));
+        assert!(instance.is_active());
+        assert!(instance.set_disabled(true));
+        assert!(!instance.set_disabled(true));
+        assert!(!instance.is_active());
+        assert!(instance.set_disabled(false));
+        assert!(instance.is_active());
+    }
+
+    #[test]
+    fn test_record_panic_without_threshold() {
+        let instance = PluginInstance::new("myplugin", "myplugin", Box::new(NoopPlugin));
+        for _ in 0..10 {
+            assert!(!instance.record_panic());
+        }
+        assert!(!instance.is_quarantined());
+    }
+
+    #[test]
+    fn test_record_read() {
+        let stats = InstanceStats::default();
+        let now = SystemTime::now();
+        stats.record_read(Duration::from_millis(30), true, now);
+        stats.record_read(Duration::from_millis(10), false, now);
+        stats.record_read(Duration::from_millis(20), false, now);
+
+        assert_eq!(2, stats.consecutive_read_failures.load(Ordering::Relaxed));
+        assert_eq!(60_000_000, stats.read_nanos.load(Ordering::Relaxed));
+        assert_eq!(20_000_000, stats.last_read_nanos.load(Ordering::Relaxed));
+        assert_eq!(30_000_000, stats.max_read_nanos.load(Ordering::Relaxed));
+        assert_eq!(
+            CdTime::from(now).0,
+            stats.last_read_success.load(Ordering::Relaxed)
+        );
+
+        stats.record_read(Duration::from_millis(10), true, now);
+        assert_eq!(0, stats.consecutive_read_failures.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_observe_interval() {
+        let instance = PluginInstance::new("myplugin", "myplugin", Box::new(NoopPlugin));
+        let ten = CdTime::from(Duration::from_secs(10));
+        let thirty = CdTime::from(Duration::from_secs(30));
+
+        assert_eq!(None, instance.observe_interval(ten));
+        assert_eq!(None, instance.observe_interval(ten));
+        assert_eq!(Some(thirty), instance.observe_interval(thirty));
+        assert_eq!(None, instance.observe_interval(thirty));
+    }
+
+    #[test]
+    fn test_shutdown_report() {
+        let a = Arc::new(PluginInstance::new(
+            "myplugin",
+            "myplugin/a",
+            Box::new(NoopPlugin),
+        ));
+        let b = Arc::new(PluginInstance::new(
+            "myplugin",
+            "myplugin/b",
+            Box::new(NoopPlugin),
+        ));
+        InstanceStats::incr(&a.stats.writes);
+        InstanceStats::incr(&b.stats.writes);
+        InstanceStats::incr(&b.stats.flushes);
+
+        let (lvl, msg) = shutdown_report("myplugin", &[a.clone(), b.clone()]);
+        assert_eq!(LogLevel::Info, lvl);
+        assert_eq!(
+            msg,
+            "myplugin: shutdown: instances=2 quarantined=0 reads=0 read_errors=0 writes=2 \
+             write_errors=0 flushes=1 flush_errors=0 panics=0"
+        );
+
+        let res: Result<(), FfiError<'_>> = Err(FfiError::MultipleConfig);
+        b.record_result(&b.stats.write_errors, &res);
+        let (lvl, msg) = shutdown_report("myplugin", &[a, b]);
+        assert_eq!(LogLevel::Warning, lvl);
+        assert!(msg.contains(" write_errors=1 "));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::api::{Duration, IdentifierPattern, LogLevel, ValueList};
+use crate::plugins::{Plugin, PluginCapabilities, PluginRegistration};
+use std::error;
+use std::panic::{RefUnwindSafe, UnwindSafe};
+
+/// A plugin that only reports values. Wrap it in `ReadOnly` to register it, which advertises the
+/// `READ` capability.
+///
+/// ```
+/// use collectd_plugin::{PluginRegistration, ReadOnly, ReadPlugin, Value, ValueListBuilder};
+/// use std::error;
+///
+/// struct Uptime;
+/// impl ReadPlugin for Uptime {
+///     fn read_values(&self) -> Result<(), Box<dyn error::Error>> {
+///         ValueListBuilder::new("uptime", "uptime")
+///             .values(&[Value::Gauge(3600.0)])
+///             .submit()?;
+///         Ok(())
+///     }
+/// }
+///
+/// fn registration() -> PluginRegistration {
+///     PluginRegistration::Single(Box::new(ReadOnly(Uptime)))
+/// }
+/// ```
+pub trait ReadPlugin: Send + Sync + UnwindSafe + RefUnwindSafe {
+    /// See `Plugin::read_values`
+    fn read_values(&self) -> Result<(), Box<dyn error::Error>>;
+
+    /// See `Plugin::read_interval`
+    fn read_interval(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// A plugin that only writes the values reported to collectd. Wrap it in `WriteOnly` to register
+/// it, which advertises the `WRITE` and `FLUSH` capabilities.
+pub trait WritePlugin: Send + Sync + UnwindSafe + RefUnwindSafe {
+    /// See `Plugin::write_values`
+    fn write_values(&self, list: ValueList<'_>) -> Result<(), Box<dyn error::Error>>;
+
+    /// See `Plugin::flush_matching`. Nothing is buffered by default, so there is nothing to flush.
+    fn flush_matching(
+        &self,
+        _timeout: Option<Duration>,
+        _identifier: Option<&IdentifierPattern>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        Ok(())
+    }
+}
+
+/// A plugin that only receives collectd's log messages. Wrap it in `LogOnly` to register it,
+/// which advertises the `LOG` capability.
+pub trait LogPlugin: Send + Sync + UnwindSafe + RefUnwindSafe {
+    /// See `Plugin::log`
+    fn log(&self, lvl: LogLevel, msg: &str) -> Result<(), Box<dyn error::Error>>;
+}
+
+// Closures are plugins of the role their signature matches, so that the wrappers accept them too
+
+impl<F> ReadPlugin for F
+where
+    F: Fn() -> Result<(), Box<dyn error::Error>> + Send + Sync + UnwindSafe + RefUnwindSafe,
+{
+    fn read_values(&self) -> Result<(), Box<dyn error::Error>> {
+        self()
+    }
+}
+
+impl<F> WritePlugin for F
+where
+    F: Fn(ValueList<'_>) -> Result<(), Box<dyn error::Error>>
+        + Send
+        + Sync
+        + UnwindSafe
+        + RefUnwindSafe,
+{
+    fn write_values(&self, list: ValueList<'_>) -> Result<(), Box<dyn error::Error>> {
+        self(list)
+    }
+}
+
+impl<F> LogPlugin for F
+where
+    F: Fn(LogLevel, &str) -> Result<(), Box<dyn error::Error>>
+        + Send
+        + Sync
+        + UnwindSafe
+        + RefUnwindSafe,
+{
+    fn log(&self, lvl: LogLevel, msg: &str) -> Result<(), Box<dyn error::Error>> {
+        self(lvl, msg)
+    }
+}
+
+/// Registers a `ReadPlugin` as a `Plugin`
+pub struct ReadOnly<P>(pub P);
+
+impl<P: ReadPlugin> Plugin for ReadOnly<P> {
+    fn capabilities(&self) -> PluginCapabilities {
+        PluginCapabilities::READ
+    }
+
+    fn read_values(&self) -> Result<(), Box<dyn error::Error>> {
+        self.0.read_values()
+    }
+
+    fn read_interval(&self) -> Option<Duration> {
+        self.0.read_interval()
+    }
+}
+
+/// Registers a `WritePlugin` as a `Plugin`
+pub struct WriteOnly<P>(pub P);
+
+impl<P: WritePlugin> Plugin for WriteOnly<P> {
+    fn capabilities(&self) -> PluginCapabilities {
+        PluginCapabilities::WRITE | PluginCapabilities::FLUSH
+    }
+
+    fn write_values(&self, list: ValueList<'_>) -> Result<(), Box<dyn error::Error>> {
+        self.0.write_values(list)
+    }
+
+    fn flush_matching(
+        &self,
+        timeout: Option<Duration>,
+        identifier: Option<&IdentifierPattern>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        self.0.flush_matching(timeout, identifier)
+    }
+}
+
+/// Registers a `LogPlugin` as a `Plugin`
+pub struct LogOnly<P>(pub P);
+
+impl<P: LogPlugin> Plugin for LogOnly<P> {
+    fn capabilities(&self) -> PluginCapabilities {
+        PluginCapabilities::LOG
+    }
+
+    fn log(&self, lvl: LogLevel, msg: &str) -> Result<(), Box<dyn error::Error>> {
+        self.0.log(lvl, msg)
+    }
+}
+
+impl PluginRegistration {
+    /// Registers a single plugin that writes values with the closure, see `WritePlugin`
+    ///
+    /// ```
+    /// use collectd_plugin::PluginRegistration;
+    ///
+    /// let registration = PluginRegistration::write_fn(|list| {
+    ///     println!("{} {}: {:?}", list.plugin, list.type_, list.values);
+    ///     Ok(())
+    /// });
+    /// ```
+    pub fn write_fn<F>(write: F) -> PluginRegistration
+    where
+        F: Fn(ValueList<'_>) -> Result<(), Box<dyn error::Error>>
+            + Send
+            + Sync
+            + UnwindSafe
+            + RefUnwindSafe
+            + 'static,
+    {
+        PluginRegistration::Single(Box::new(WriteOnly(write)))
+    }
+
+    /// Registers a single plugin that receives collectd's log messages with the closure, see
+    /// `LogPlugin`
+    pub fn log_fn<F>(log: F) -> PluginRegistration
+    where
+        F: Fn(LogLevel, &str) -> Result<(), Box<dyn error::Error>>
+            + Send
+            + Sync
+            + UnwindSafe
+            + RefUnwindSafe
+            + 'static,
+    {
+        PluginRegistration::Single(Box::new(LogOnly(log)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_read_only() {
+        struct Reader;
+        impl ReadPlugin for Reader {
+            fn read_values(&self) -> Result<(), Box<dyn error::Error>> {
+                Ok(())
+            }
+        }
+
+        let plugin = ReadOnly(Reader);
+        assert_eq!(PluginCapabilities::READ, plugin.capabilities());
+        assert!(plugin.read_values().is_ok());
+        assert!(plugin.flush(None, None).is_err());
+    }
+
+    #[test]
+    fn test_write_only() {
+        struct Writer;
+        impl WritePlugin for Writer {
+            fn write_values(&self, _list: ValueList<'_>) -> Result<(), Box<dyn error::Error>> {
+                Ok(())
+            }
+        }
+
+        let plugin = WriteOnly(Writer);
+        let expected = PluginCapabilities::WRITE | PluginCapabilities::FLUSH;
+        assert_eq!(expected, plugin.capabilities());
+        assert!(plugin.flush(None, Some("/cpu-/")).is_ok());
+        assert!(plugin.read_values().is_err());
+    }
+
+    #[test]
+    fn test_log_only() {
+        struct Recorder(Mutex<Vec<String>>);
+        impl LogPlugin for Recorder {
+            fn log(&self, _lvl: LogLevel, msg: &str) -> Result<(), Box<dyn error::Error>> {
+                self.0.lock().unwrap().push(String::from(msg));
+                Ok(())
+            }
+        }
+
+        let plugin = LogOnly(Recorder(Mutex::new(Vec::new())));
+        assert_eq!(PluginCapabilities::LOG, plugin.capabilities());
+        assert!(plugin.log(LogLevel::Info, "hello").is_ok());
+        assert_eq!(vec![String::from("hello")], *(plugin.0).0.lock().unwrap());
+    }
+
+    #[test]
+    fn test_closures() {
+        let plugin = ReadOnly(|| Ok(()));
+        assert_eq!(PluginCapabilities::READ, plugin.capabilities());
+        assert!(plugin.read_values().is_ok());
+
+        let plugin = LogOnly(|_lvl: LogLevel, msg: &str| {
+            assert_eq!("hello", msg);
+            Ok(())
+        });
+        assert!(plugin.log(LogLevel::Info, "hello").is_ok());
+
+        match PluginRegistration::write_fn(|_list| Ok(())) {
+            PluginRegistration::Single(plugin) => {
+                let expected = PluginCapabilities::WRITE | PluginCapabilities::FLUSH;
+                assert_eq!(expected, plugin.capabilities());
+            }
+            _ => panic!("expected a single plugin"),
+        }
+
+        match PluginRegistration::log_fn(|_lvl, _msg| Ok(())) {
+            PluginRegistration::Single(plugin) => {
+                assert_eq!(PluginCapabilities::LOG, plugin.capabilities());
+            }
+            _ => panic!("expected a single plugin"),
+        }
+    }
+}
@@ -0,0 +1,481 @@
+use crate::api::MetaValue;
+use serde::ser::{self, Serialize};
+use std::error;
+use std::fmt::{self, Display};
+
+/// Error that occurred while flattening a value into meta data
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error(pub String);
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "error serializing meta data"
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error serializing meta data: {}", self.0)
+    }
+}
+
+/// Flattens a struct (or map) into meta data entries that can be attached to a value list. Nested
+/// fields are joined with a period (eg: `disk.model`) and elements of a sequence are keyed by their
+/// index (eg: `tags.0`). Fields that are `None` are left out. Use `de::from_meta` for the reverse.
+///
+/// ```
+/// use collectd_plugin::MetaValue;
+/// use collectd_plugin::ser::to_meta;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Disk {
+///     model: &'static str,
+///     rotational: bool,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Context {
+///     disk: Disk,
+///     queue_depth: u32,
+/// }
+///
+/// let context = Context {
+///     disk: Disk { model: "ssd-1", rotational: false },
+///     queue_depth: 32,
+/// };
+///
+/// assert_eq!(
+///     to_meta(&context).unwrap(),
+///     vec![
+///         (String::from("disk.model"), MetaValue::String(String::from("ssd-1"))),
+///         (String::from("disk.rotational"), MetaValue::Boolean(false)),
+///         (String::from("queue_depth"), MetaValue::UnsignedInt(32)),
+///     ]
+/// );
+/// ```
+pub fn to_meta<T: Serialize + ?Sized>(value: &T) -> Result<Vec<(String, MetaValue)>, Error> {
+    let mut out = Vec::new();
+    value.serialize(MetaSerializer {
+        key: String::new(),
+        out: &mut out,
+    })?;
+    Ok(out)
+}
+
+struct MetaSerializer<'a> {
+    key: String,
+    out: &'a mut Vec<(String, MetaValue)>,
+}
+
+fn child(key: &str, name: &str) -> String {
+    if key.is_empty() {
+        String::from(name)
+    } else {
+        format!("{}.{}", key, name)
+    }
+}
+
+impl<'a> MetaSerializer<'a> {
+    fn leaf(self, value: MetaValue) -> Result<(), Error> {
+        if self.key.is_empty() {
+            return Err(Error(String::from(
+                "only structs and maps can be meta data",
+            )));
+        }
+
+        self.out.push((self.key, value));
+        Ok(())
+    }
+
+    /// Fields of the compound are nested under the key (and variant for enums)
+    fn compound(self, variant: Option<&str>) -> Compound<'a> {
+        let key = match variant {
+            Some(variant) => child(&self.key, variant),
+            None => self.key,
+        };
+
+        Compound {
+            key,
+            out: self.out,
+            index: 0,
+            map_key: None,
+        }
+    }
+}
+
+impl<'a> ser::Serializer for MetaSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.leaf(MetaValue::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.leaf(MetaValue::SignedInt(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.leaf(MetaValue::UnsignedInt(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.serialize_f64(f64::from(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.leaf(MetaValue::Double(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.leaf(MetaValue::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.leaf(MetaValue::String(String::from(v)))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), Error> {
+        Err(Error(String::from("bytes are not supported in meta data")))
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let key = child(&self.key, variant);
+        value.serialize(MetaSerializer { key, out: self.out })
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        Ok(self.compound(Some(variant)))
+    }
+}
+
+struct Compound<'a> {
+    key: String,
+    out: &'a mut Vec<(String, MetaValue)>,
+    index: usize,
+    map_key: Option<String>,
+}
+
+impl<'a> Compound<'a> {
+    fn field<T: Serialize + ?Sized>(&mut self, name: &str, value: &T) -> Result<(), Error> {
+        value.serialize(MetaSerializer {
+            key: child(&self.key, name),
+            out: &mut *self.out,
+        })
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let name = self.index.to_string();
+        self.index += 1;
+        self.field(&name, value)
+    }
+}
+
+impl<'a> ser::SerializeSeq for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTuple for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTupleVariant for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeMap for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        // Keys are serialized as if they were a field so that strings, numbers, and unit enum
+        // variants are all accepted
+        let mut out = Vec::new();
+        key.serialize(MetaSerializer {
+            key: String::from("key"),
+            out: &mut out,
+        })?;
+
+        let key = match out.pop() {
+            Some((_, MetaValue::String(s))) => s,
+            Some((_, MetaValue::SignedInt(x))) => x.to_string(),
+            Some((_, MetaValue::UnsignedInt(x))) => x.to_string(),
+            Some((_, MetaValue::Boolean(x))) => x.to_string(),
+            _ => return Err(Error(String::from("map keys must be strings or integers"))),
+        };
+
+        self.map_key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .map_key
+            .take()
+            .ok_or_else(|| Error(String::from("map value serialized before its key")))?;
+        self.field(&key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeStruct for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize)]
+    enum Media {
+        Ssd,
+        Hdd { rpm: u32 },
+    }
+
+    #[derive(Serialize)]
+    struct Disk {
+        media: Media,
+        serial: Option<String>,
+        partitions: Vec<i8>,
+        labels: BTreeMap<u8, char>,
+        ratio: (f32, bool),
+    }
+
+    fn entry(key: &str, value: MetaValue) -> (String, MetaValue) {
+        (String::from(key), value)
+    }
+
+    #[test]
+    fn test_to_meta_flattens() {
+        let mut labels = BTreeMap::new();
+        labels.insert(1, 'a');
+
+        let disk = Disk {
+            media: Media::Hdd { rpm: 7200 },
+            serial: None,
+            partitions: vec![-1, 2],
+            labels,
+            ratio: (0.5, true),
+        };
+
+        assert_eq!(
+            to_meta(&disk).unwrap(),
+            vec![
+                entry("media.Hdd.rpm", MetaValue::UnsignedInt(7200)),
+                entry("partitions.0", MetaValue::SignedInt(-1)),
+                entry("partitions.1", MetaValue::SignedInt(2)),
+                entry("labels.1", MetaValue::String(String::from("a"))),
+                entry("ratio.0", MetaValue::Double(0.5)),
+                entry("ratio.1", MetaValue::Boolean(true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_meta_unit_variant() {
+        let mut map = BTreeMap::new();
+        map.insert("media", Media::Ssd);
+        assert_eq!(
+            to_meta(&map).unwrap(),
+            vec![entry("media", MetaValue::String(String::from("Ssd")))]
+        );
+    }
+
+    #[test]
+    fn test_to_meta_requires_struct() {
+        assert!(to_meta(&10).is_err());
+        assert!(to_meta("disk").is_err());
+    }
+}
@@ -1,3 +1,6 @@
+mod meta;
+pub use self::meta::{to_meta, Error};
+
 use super::Value;
 use serde::{Serialize, Serializer};
 
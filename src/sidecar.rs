@@ -0,0 +1,484 @@
+//! Supervision of helper processes that a plugin runs alongside collectd
+use crate::api::{
+    collectd_log, length, CdTime, Identifier, IntoDuration, LogLevel, Value, ValueListBuilder,
+    ValueType,
+};
+use crate::bindings::plugin_get_ds;
+use crate::errors::DurationError;
+use std::convert::TryFrom;
+use std::ffi::{CString, OsStr, OsString};
+use std::io::{self, BufRead, BufReader, Read};
+use std::process::{Child, Command, Stdio};
+use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How the lines that a sidecar writes to stdout are interpreted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SidecarOutput {
+    /// Lines are `PUTVAL` commands, as with collectd's exec plugin (eg:
+    /// `PUTVAL "myhost/myplugin/gauge-temp" interval=10 N:21.5`), whose values are dispatched
+    Putval,
+
+    /// Lines are written to the collectd log at the given level
+    Log(LogLevel),
+}
+
+/// Configures a helper process that is started, restarted whenever it exits, and killed when the
+/// returned `Sidecar` is dropped. Anything the process writes to stderr is logged as a warning.
+///
+/// ```
+/// use collectd_plugin::{SidecarBuilder, SidecarOutput};
+///
+/// fn start() -> std::io::Result<()> {
+///     let sidecar = SidecarBuilder::new("smart", "/usr/local/bin/smart-collector")
+///         .arg("--interval=10")
+///         .output(SidecarOutput::Putval)
+///         .restart_delay("5s")
+///         .unwrap()
+///         .spawn()?;
+///
+///     // store the sidecar with the plugin so that the process is killed on shutdown
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SidecarBuilder {
+    name: String,
+    program: OsString,
+    args: Vec<OsString>,
+    output: SidecarOutput,
+    restart_delay: Duration,
+}
+
+impl SidecarBuilder {
+    /// Creates a sidecar of the given name (used in log messages) that runs the program
+    pub fn new<S: AsRef<OsStr>>(name: &str, program: S) -> Self {
+        SidecarBuilder {
+            name: String::from(name),
+            program: program.as_ref().to_os_string(),
+            args: Vec::new(),
+            output: SidecarOutput::Log(LogLevel::Info),
+            restart_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Adds an argument to pass to the program
+    pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
+        self.args.push(arg.as_ref().to_os_string());
+        self
+    }
+
+    /// Adds arguments to pass to the program
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|x| x.as_ref().to_os_string()));
+        self
+    }
+
+    /// How the process' stdout is interpreted. Defaults to logging each line at the info level
+    pub fn output(mut self, output: SidecarOutput) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// How long to wait before restarting the process after it exits or fails to start. Defaults
+    /// to a second.
+    pub fn restart_delay<T: IntoDuration>(mut self, delay: T) -> Result<Self, DurationError> {
+        self.restart_delay = delay.into_duration()?;
+        Ok(self)
+    }
+
+    /// Starts the process and the thread that supervises it. An error is returned if the
+    /// process can't be started the first time, later failures are logged and retried.
+    pub fn spawn(self) -> io::Result<Sidecar> {
+        let child = self.start()?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let current = Arc::new(Mutex::new(None));
+        let supervisor = Supervisor {
+            config: self,
+            shutdown: Arc::clone(&shutdown),
+            child: Arc::clone(&current),
+        };
+
+        let handle = thread::Builder::new()
+            .name(format!("collectd-sidecar-{}", supervisor.config.name))
+            .spawn(move || supervisor.run(child))?;
+
+        Ok(Sidecar {
+            shutdown,
+            child: current,
+            handle: Mutex::new(Some(handle)),
+        })
+    }
+
+    fn start(&self) -> io::Result<Child> {
+        Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+    }
+}
+
+/// A running helper process. Dropping the sidecar kills the process and stops restarting it.
+pub struct Sidecar {
+    shutdown: Arc<AtomicBool>,
+    child: Arc<Mutex<Option<Child>>>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Sidecar {
+    /// The process id of the currently running process, if any
+    pub fn id(&self) -> Option<u32> {
+        let child = self.child.lock().unwrap_or_else(|e| e.into_inner());
+        child.as_ref().map(|x| x.id())
+    }
+}
+
+impl Drop for Sidecar {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        {
+            let mut child = self.child.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(ref mut child) = *child {
+                let _ = child.kill();
+            }
+        }
+
+        let handle = self
+            .handle
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some(handle) = handle {
+            let _ = handle.join();
+        }
+    }
+}
+
+struct Supervisor {
+    config: SidecarBuilder,
+    shutdown: Arc<AtomicBool>,
+    child: Arc<Mutex<Option<Child>>>,
+}
+
+impl Supervisor {
+    fn log(&self, level: LogLevel, msg: &str) {
+        collectd_log(level, &format!("sidecar {}: {}", self.config.name, msg));
+    }
+
+    fn run(&self, first: Child) {
+        let mut next = Some(first);
+        while !self.shutdown.load(Ordering::SeqCst) {
+            let child = match next.take().map(Ok).unwrap_or_else(|| self.config.start()) {
+                Ok(child) => child,
+                Err(e) => {
+                    self.log(LogLevel::Error, &format!("unable to start: {}", e));
+                    self.pause();
+                    continue;
+                }
+            };
+
+            let started = Instant::now();
+            let status = self.supervise(child);
+            if self.shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+
+            let msg = match status {
+                Ok(status) => format!(
+                    "exited ({}) after {:?}, restarting",
+                    status,
+                    started.elapsed()
+                ),
+                Err(e) => format!("lost track of process ({}), restarting", e),
+            };
+            self.log(LogLevel::Warning, &msg);
+            self.pause();
+        }
+
+        let mut child = self.child.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(mut child) = child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    /// Handles the output of the process until it exits
+    fn supervise(&self, mut child: Child) -> io::Result<std::process::ExitStatus> {
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        *self.child.lock().unwrap_or_else(|e| e.into_inner()) = Some(child);
+
+        // The process may have been started while the sidecar was being dropped
+        if self.shutdown.load(Ordering::SeqCst) {
+            if let Some(ref mut child) = *self.child.lock().unwrap_or_else(|e| e.into_inner()) {
+                let _ = child.kill();
+            }
+        }
+
+        let name = self.config.name.clone();
+        let errors = stderr.map(|stderr| {
+            thread::spawn(move || {
+                for_each_line(stderr, |line| {
+                    collectd_log(LogLevel::Warning, &format!("sidecar {}: {}", name, line))
+                })
+            })
+        });
+
+        if let Some(stdout) = stdout {
+            for_each_line(stdout, |line| self.handle_line(line));
+        }
+
+        if let Some(errors) = errors {
+            let _ = errors.join();
+        }
+
+        let child = self.child.lock().unwrap_or_else(|e| e.into_inner()).take();
+        match child {
+            Some(mut child) => child.wait(),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "process missing")),
+        }
+    }
+
+    fn handle_line(&self, line: &str) {
+        match self.config.output {
+            SidecarOutput::Log(level) => self.log(level, line),
+            SidecarOutput::Putval => {
+                if let Err(e) = dispatch_putval(line) {
+                    self.log(LogLevel::Warning, &format!("{}: {}", e, line));
+                }
+            }
+        }
+    }
+
+    /// Waits out the restart delay, unless the sidecar is shutting down
+    fn pause(&self) {
+        let deadline = Instant::now() + self.config.restart_delay;
+        while !self.shutdown.load(Ordering::SeqCst) {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            thread::sleep((deadline - now).min(Duration::from_millis(100)));
+        }
+    }
+}
+
+fn for_each_line<R: Read, F: FnMut(&str)>(reader: R, mut f: F) {
+    for line in BufReader::new(reader).lines() {
+        match line {
+            Ok(ref line) if line.trim().is_empty() => {}
+            Ok(ref line) => f(line.trim()),
+            Err(_) => break,
+        }
+    }
+}
+
+/// A parsed `PUTVAL` command of collectd's plain text protocol
+#[derive(Debug, PartialEq)]
+struct Putval<'a> {
+    identifier: Identifier,
+    interval: Option<f64>,
+    time: Option<f64>,
+    values: Vec<&'a str>,
+}
+
+fn parse_putval(line: &str) -> Result<Putval<'_>, String> {
+    let rest = line
+        .strip_prefix("PUTVAL")
+        .filter(|x| x.starts_with(char::is_whitespace))
+        .ok_or_else(|| String::from("not a PUTVAL command"))?
+        .trim_start();
+
+    let (identifier, rest) = if let Some(quoted) = rest.strip_prefix('"') {
+        let end = quoted
+            .find('"')
+            .ok_or_else(|| String::from("unterminated identifier"))?;
+        (&quoted[..end], &quoted[end + 1..])
+    } else {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        (&rest[..end], &rest[end..])
+    };
+
+    let identifier = identifier
+        .parse::<Identifier>()
+        .map_err(|e| e.to_string())?;
+    let mut interval = None;
+    let mut values = None;
+    for arg in rest.split_whitespace() {
+        if let Some(secs) = arg.strip_prefix("interval=") {
+            interval = Some(
+                secs.parse::<f64>()
+                    .map_err(|_| format!("invalid interval: {}", secs))?,
+            );
+        } else if values.is_none() {
+            values = Some(arg);
+        } else {
+            return Err(format!("unexpected argument: {}", arg));
+        }
+    }
+
+    let mut fields = values
+        .ok_or_else(|| String::from("missing values"))?
+        .split(':');
+    let time = match fields.next() {
+        Some("N") => None,
+        Some(t) => Some(
+            t.parse::<f64>()
+                .map_err(|_| format!("invalid time: {}", t))?,
+        ),
+        None => None,
+    };
+
+    Ok(Putval {
+        identifier,
+        interval,
+        time,
+        values: fields.collect(),
+    })
+}
+
+/// Parses a value according to the type of its data source. `U` denotes an unknown gauge.
+fn parse_value(ds_type: ValueType, s: &str) -> Option<Value> {
+    match ds_type {
+        ValueType::Gauge if s == "U" => Some(Value::Gauge(f64::NAN)),
+        ValueType::Gauge => s.parse().ok().map(Value::Gauge),
+        ValueType::Counter => s.parse().ok().map(Value::Counter),
+        ValueType::Derive => s.parse().ok().map(Value::Derive),
+        ValueType::Absolute => s.parse().ok().map(Value::Absolute),
+    }
+}
+
+fn secs_to_cdtime(secs: f64) -> CdTime {
+    CdTime((secs * 1e9) as u64)
+}
+
+fn dispatch_putval(line: &str) -> Result<(), String> {
+    let putval = parse_putval(line)?;
+    let id = &putval.identifier;
+    let type_ = CString::new(id.type_.as_str()).map_err(|e| e.to_string())?;
+    let ds = unsafe { plugin_get_ds(type_.as_ptr()).as_ref() }
+        .ok_or_else(|| format!("unknown type: {}", id.type_))?;
+    let sources = unsafe { slice::from_raw_parts(ds.ds, length(ds.ds_num)) };
+
+    if sources.len() != putval.values.len() {
+        return Err(format!(
+            "expected {} values for type {} but found {}",
+            sources.len(),
+            id.type_,
+            putval.values.len()
+        ));
+    }
+
+    let values = sources
+        .iter()
+        .zip(putval.values.iter())
+        .map(|(source, s)| {
+            ValueType::try_from(source.type_ as u32)
+                .ok()
+                .and_then(|ds_type| parse_value(ds_type, s))
+                .ok_or_else(|| format!("invalid value: {}", s))
+        })
+        .collect::<Result<Vec<Value>, String>>()?;
+
+    let mut builder = ValueListBuilder::new(id.plugin.as_str(), id.type_.as_str())
+        .values(&values)
+        .host(id.host.as_str());
+
+    if let Some(ref instance) = id.plugin_instance {
+        builder = builder.plugin_instance(instance.as_str());
+    }
+
+    if let Some(ref instance) = id.type_instance {
+        builder = builder.type_instance(instance.as_str());
+    }
+
+    if let Some(time) = putval.time {
+        builder = builder.time(secs_to_cdtime(time).into());
+    }
+
+    if let Some(interval) = putval.interval {
+        builder = builder.interval(Duration::from(secs_to_cdtime(interval)));
+    }
+
+    builder.submit().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_putval() {
+        let putval =
+            parse_putval(r#"PUTVAL "myhost/exec-disk/gauge-temp" interval=10 1500000000:21.5:U"#)
+                .unwrap();
+        assert_eq!("myhost/exec-disk/gauge-temp", putval.identifier.to_string());
+        assert_eq!(Some(10.0), putval.interval);
+        assert_eq!(Some(1_500_000_000.0), putval.time);
+        assert_eq!(vec!["21.5", "U"], putval.values);
+
+        let putval = parse_putval("PUTVAL myhost/load/load N:1:2:3").unwrap();
+        assert_eq!(None, putval.interval);
+        assert_eq!(None, putval.time);
+        assert_eq!(vec!["1", "2", "3"], putval.values);
+    }
+
+    #[test]
+    fn test_parse_putval_errors() {
+        assert!(parse_putval("PUTVALUE myhost/load/load N:1").is_err());
+        assert!(parse_putval("PUTVAL myhost/load N:1").is_err());
+        assert!(parse_putval(r#"PUTVAL "myhost/load/load N:1"#).is_err());
+        assert!(parse_putval("PUTVAL myhost/load/load interval=x N:1").is_err());
+        assert!(parse_putval("PUTVAL myhost/load/load").is_err());
+        assert!(parse_putval("PUTVAL myhost/load/load N:1 N:2").is_err());
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(
+            Some(Value::Gauge(1.5)),
+            parse_value(ValueType::Gauge, "1.5")
+        );
+        assert!(parse_value(ValueType::Gauge, "U").unwrap().is_nan());
+        assert_eq!(
+            Some(Value::Derive(-2)),
+            parse_value(ValueType::Derive, "-2")
+        );
+        assert_eq!(None, parse_value(ValueType::Counter, "-2"));
+        assert_eq!(None, parse_value(ValueType::Absolute, "U"));
+    }
+
+    #[test]
+    fn test_sidecar_restarts_and_stops() {
+        let sidecar = SidecarBuilder::new("test", "sh")
+            .args(["-c", "sleep 0.05"])
+            .restart_delay("10ms")
+            .unwrap()
+            .spawn()
+            .unwrap();
+
+        let first = sidecar.id();
+        let mut restarted = false;
+        for _ in 0..100 {
+            thread::sleep(Duration::from_millis(20));
+            let id = sidecar.id();
+            if id.is_some() && id != first {
+                restarted = true;
+                break;
+            }
+        }
+
+        assert!(restarted);
+        drop(sidecar);
+    }
+}
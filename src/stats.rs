@@ -0,0 +1,292 @@
+use crate::api::{
+    dispatch_stats, CdTime, Duration, MemoryBudget, Timestamp, Value, ValueListBuilder,
+};
+use crate::errors::SubmitError;
+use crate::plugins::{Plugin, PluginCapabilities};
+use crate::registry::{self, PluginInstance};
+use std::error;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// Periodically dispatches the crate's internal statistics about the instances of a plugin
+/// manager, the same way that collectd reports its own statistics (`CollectInternalStats`), so
+/// that a fleet can monitor its Rust plugins uniformly. The reporter is opted into by registering
+/// it as one of the manager's instances, and the statistics are dispatched every time it is read.
+///
+/// Statistics are dispatched under the `collectd` plugin with the manager's name as the plugin
+/// instance (eg: `localhost/collectd-myplugin/derive-a-reads` for the reads of the instance
+/// registered as `a`):
+///
+/// - `derive-[<id>-]<counter>`: the callbacks invoked on each instance and how many failed
+/// - `gauge-[<id>-]quarantined`: 1 if the instance has been quarantined
+/// - `derive-dispatched`, `derive-dispatch_out_of_memory`, `derive-dispatch_failed`: the outcome of
+///   submitting values
+/// - `bytes-budget_used`, `derive-budget_dropped`: the state of a `MemoryBudget`, if given
+///
+/// ```
+/// use collectd_plugin::{Plugin, PluginRegistration, StatsReporter};
+///
+/// fn registration(plugin: Box<dyn Plugin>) -> PluginRegistration {
+///     PluginRegistration::Multiple(vec![
+///         (String::from("main"), plugin),
+///         (String::from("stats"), Box::new(StatsReporter::new("myplugin"))),
+///     ])
+/// }
+/// ```
+pub struct StatsReporter {
+    manager: &'static str,
+    budget: Option<Arc<MemoryBudget>>,
+}
+
+impl StatsReporter {
+    /// Creates a reporter for the instances of the plugin manager
+    pub fn new(manager: &'static str) -> Self {
+        StatsReporter {
+            manager,
+            budget: None,
+        }
+    }
+
+    /// Additionally reports the state of the memory budget
+    pub fn budget(mut self, budget: Arc<MemoryBudget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    fn submit(&self, type_: &str, type_instance: &str, value: Value) -> Result<(), SubmitError> {
+        ValueListBuilder::new("collectd", type_)
+            .plugin_instance(self.manager)
+            .type_instance(type_instance)
+            .values(&[value])
+            .submit()
+    }
+}
+
+/// The statistics of an instance as (type, type instance, value)
+fn instance_stats(manager: &str, instance: &PluginInstance) -> Vec<(&'static str, String, Value)> {
+    // Instances of a `PluginRegistration::Multiple` are named `manager/id`
+    let prefix = instance
+        .name
+        .strip_prefix(manager)
+        .and_then(|x| x.strip_prefix('/'))
+        .map(|id| format!("{}-", id))
+        .unwrap_or_default();
+
+    let stats = &instance.stats;
+    let counters = [
+        ("reads", &stats.reads),
+        ("read_errors", &stats.read_errors),
+        ("writes", &stats.writes),
+        ("write_errors", &stats.write_errors),
+        ("logs", &stats.logs),
+        ("log_errors", &stats.log_errors),
+        ("flushes", &stats.flushes),
+        ("flush_errors", &stats.flush_errors),
+        ("notifications", &stats.notifications),
+        ("notification_errors", &stats.notification_errors),
+        ("missing", &stats.missing),
+        ("missing_errors", &stats.missing_errors),
+        ("panics", &stats.panics),
+    ];
+
+    let mut res: Vec<_> = counters
+        .iter()
+        .map(|(name, counter)| {
+            let value = Value::Derive(counter.load(Ordering::Relaxed) as i64);
+            ("derive", format!("{}{}", prefix, name), value)
+        })
+        .collect();
+
+    let quarantined = if instance.is_quarantined() { 1.0 } else { 0.0 };
+    res.push((
+        "gauge",
+        format!("{}quarantined", prefix),
+        Value::Gauge(quarantined),
+    ));
+    res
+}
+
+/// The health of the reads of a plugin instance, as tracked by the crate
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadHealth {
+    /// The name that the instance was registered with collectd (eg: `myplugin/instance`)
+    pub name: String,
+
+    /// Number of times that values were read
+    pub reads: usize,
+
+    /// Number of reads that failed
+    pub read_errors: usize,
+
+    /// Number of the most recent reads that failed in a row
+    pub consecutive_failures: usize,
+
+    /// Time spent on the most recent read, if there has been one
+    pub last_duration: Option<Duration>,
+
+    /// Average time spent on a read, if there has been one
+    pub mean_duration: Option<Duration>,
+
+    /// Longest time spent on a read, if there has been one
+    pub max_duration: Option<Duration>,
+
+    /// When the most recent successful read finished
+    pub last_success: Option<Timestamp>,
+
+    /// The instance was taken out of service and is no longer read
+    pub quarantined: bool,
+}
+
+impl ReadHealth {
+    fn new(instance: &PluginInstance) -> Self {
+        let stats = &instance.stats;
+        let reads = stats.reads.load(Ordering::Relaxed);
+        let nanos = |x: u64| Some(CdTime(x).into()).filter(|_| reads > 0);
+        let last_success = stats.last_read_success.load(Ordering::Relaxed);
+        ReadHealth {
+            name: instance.name.clone(),
+            reads,
+            read_errors: stats.read_errors.load(Ordering::Relaxed),
+            consecutive_failures: stats.consecutive_read_failures.load(Ordering::Relaxed),
+            last_duration: nanos(stats.last_read_nanos.load(Ordering::Relaxed)),
+            mean_duration: nanos(stats.read_nanos.load(Ordering::Relaxed) / reads.max(1) as u64),
+            max_duration: nanos(stats.max_read_nanos.load(Ordering::Relaxed)),
+            last_success: Some(last_success)
+                .filter(|&x| x > 0)
+                .map(|x| CdTime(x).into()),
+            quarantined: instance.is_quarantined(),
+        }
+    }
+}
+
+/// Reports the health of the reads of every instance that reads values, so that a plugin can
+/// alert on the others (eg: an instance that has failed to read several times in a row). Collectd
+/// keeps no statistics of its read callbacks that plugins can query, so only the instances
+/// registered through this crate within the same shared object are known.
+///
+/// ```
+/// use collectd_plugin::read_health;
+///
+/// for health in read_health() {
+///     if health.consecutive_failures >= 3 {
+///         // raise an alert for health.name
+///     }
+/// }
+/// ```
+pub fn read_health() -> Vec<ReadHealth> {
+    registry::instances()
+        .iter()
+        .filter(|x| x.capabilities.has_read())
+        .map(|x| ReadHealth::new(x))
+        .collect()
+}
+
+fn derive(counter: usize) -> Value {
+    Value::Derive(counter as i64)
+}
+
+impl Plugin for StatsReporter {
+    fn capabilities(&self) -> PluginCapabilities {
+        PluginCapabilities::READ
+    }
+
+    fn read_values(&self) -> Result<(), Box<dyn error::Error>> {
+        let instances = registry::instances();
+        let managed = instances.iter().filter(|x| x.manager == self.manager);
+        for instance in managed {
+            for (type_, type_instance, value) in instance_stats(self.manager, instance) {
+                self.submit(type_, &type_instance, value)?;
+            }
+        }
+
+        let dispatch = dispatch_stats();
+        self.submit("derive", "dispatched", derive(dispatch.dispatched))?;
+        self.submit(
+            "derive",
+            "dispatch_out_of_memory",
+            derive(dispatch.out_of_memory),
+        )?;
+        self.submit("derive", "dispatch_failed", derive(dispatch.failed))?;
+
+        if let Some(ref budget) = self.budget {
+            self.submit("bytes", "budget_used", Value::Gauge(budget.used() as f64))?;
+            self.submit("derive", "budget_dropped", derive(budget.dropped()))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::BudgetPolicy;
+    use crate::registry::InstanceStats;
+
+    struct NoopPlugin;
+    impl Plugin for NoopPlugin {}
+
+    #[test]
+    fn test_instance_stats() {
+        let instance = PluginInstance::new("myplugin", "myplugin/a", Box::new(NoopPlugin));
+        InstanceStats::incr(&instance.stats.reads);
+        InstanceStats::incr(&instance.stats.reads);
+
+        let stats = instance_stats("myplugin", &instance);
+        assert_eq!(14, stats.len());
+        assert_eq!(
+            ("derive", String::from("a-reads"), Value::Derive(2)),
+            stats[0]
+        );
+        assert_eq!(
+            ("gauge", String::from("a-quarantined"), Value::Gauge(0.0)),
+            stats[13]
+        );
+
+        let single = PluginInstance::new("myplugin", "myplugin", Box::new(NoopPlugin));
+        let stats = instance_stats("myplugin", &single);
+        assert_eq!(
+            ("derive", String::from("reads"), Value::Derive(0)),
+            stats[0]
+        );
+    }
+
+    #[test]
+    fn test_read_health() {
+        let instance = PluginInstance::new("myplugin", "myplugin/a", Box::new(NoopPlugin));
+        let health = ReadHealth::new(&instance);
+        assert_eq!(0, health.reads);
+        assert_eq!(None, health.last_duration);
+        assert_eq!(None, health.mean_duration);
+        assert_eq!(None, health.last_success);
+
+        let stats = &instance.stats;
+        let now = std::time::SystemTime::now();
+        for (millis, success) in &[(10, true), (30, false)] {
+            InstanceStats::incr(&stats.reads);
+            let elapsed = std::time::Duration::from_millis(*millis);
+            stats.record_read(elapsed, *success, now);
+        }
+        InstanceStats::incr(&stats.read_errors);
+
+        let health = ReadHealth::new(&instance);
+        assert_eq!(String::from("myplugin/a"), health.name);
+        assert_eq!(2, health.reads);
+        assert_eq!(1, health.read_errors);
+        assert_eq!(1, health.consecutive_failures);
+        assert_eq!(Some(CdTime::from(now).into()), health.last_success);
+
+        let millis = |x: Option<Duration>| x.map(|x| CdTime::from(x).0 / 1_000_000);
+        assert_eq!(Some(30), millis(health.last_duration));
+        assert_eq!(Some(20), millis(health.mean_duration));
+        assert_eq!(Some(30), millis(health.max_duration));
+        assert!(!health.quarantined);
+    }
+
+    #[test]
+    fn test_stats_reporter_read() {
+        let budget = Arc::new(MemoryBudget::new(10, BudgetPolicy::DropNewest));
+        let reporter = StatsReporter::new("myplugin").budget(budget);
+        assert!(reporter.read_values().is_ok());
+    }
+}
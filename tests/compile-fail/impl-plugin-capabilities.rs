@@ -0,0 +1,18 @@
+use collectd_plugin::{impl_plugin, PluginCapabilities};
+use std::error;
+
+struct MyPlugin;
+
+impl_plugin! {
+    impl Plugin for MyPlugin {
+        fn capabilities(&self) -> PluginCapabilities {
+            PluginCapabilities::READ
+        }
+
+        fn log(&self, _lvl: collectd_plugin::LogLevel, _msg: &str) -> Result<(), Box<dyn error::Error>> {
+            Ok(())
+        }
+    }
+}
+
+fn main() {}
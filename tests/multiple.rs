@@ -0,0 +1,44 @@
+use collectd_plugin::collectd_plugins;
+
+mod tt {
+    use collectd_plugin::*;
+    use std::error;
+
+    pub struct FirstPlugin;
+
+    impl PluginManager for FirstPlugin {
+        fn name() -> &'static str {
+            "first"
+        }
+
+        fn plugins(
+            _config: Option<&[ConfigItem<'_>]>,
+        ) -> Result<PluginRegistration, Box<dyn error::Error>> {
+            Ok(PluginRegistration::Empty(String::from("nothing to read")))
+        }
+    }
+
+    pub struct SecondPlugin;
+
+    impl PluginManager for SecondPlugin {
+        fn name() -> &'static str {
+            "second"
+        }
+
+        fn aliases() -> &'static [&'static str] {
+            &["second_alias"]
+        }
+
+        fn plugins(
+            _config: Option<&[ConfigItem<'_>]>,
+        ) -> Result<PluginRegistration, Box<dyn error::Error>> {
+            Ok(PluginRegistration::Multiple(vec![]))
+        }
+    }
+}
+
+collectd_plugins!(tt::FirstPlugin, tt::SecondPlugin);
+
+// Compiling is the test, as collectd isn't around to load the plugins
+#[test]
+fn can_generate_multiple_plugins() {}